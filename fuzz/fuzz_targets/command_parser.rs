@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use shell::command::Command;
+use shell::tokenizer::{quote, tokenize};
+
+fuzz_target!(|data: &[u8]| {
+    let line = String::from_utf8_lossy(data);
+
+    if let Ok(words) = tokenize(&line) {
+        // Quoting what we tokenized must give back the same words
+        let requoted = words.iter().map(|word| quote(word)).collect::<Vec<_>>().join(" ");
+        assert_eq!(tokenize(&requoted).unwrap(), words);
+    }
+
+    let _ = Command::try_from(line.as_ref());
+});
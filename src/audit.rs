@@ -0,0 +1,337 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use anyhow::anyhow;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::config;
+use crate::dirs;
+use crate::encryption;
+use crate::errors::CrateResult;
+use crate::i18n::tr;
+
+const AUDIT_FILE: &str = "history";
+
+// Lines kept out of the log when `history_ignore` isn't set: those typed with
+// a leading space, as with bash's ignorespace
+pub const DEFAULT_HISTORY_IGNORE: [&str; 1] = ["^ "];
+
+// How shells running at the same time share the log
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryMode {
+    // Each line is written as it runs, and other shells see it at their
+    // next prompt
+    #[default]
+    Shared,
+    // A session's lines are kept to itself and written when it exits
+    Session,
+}
+
+// What an interactive session knows of the log
+struct Session {
+    mode: HistoryMode,
+    // How far into the log the session has read; later lines are new to it
+    read_to: u64,
+    // Lines from other shells found while writing, for the next `merge`
+    others: Vec<String>,
+    // In session mode, the session's entries waiting for it to exit
+    pending: Vec<String>,
+}
+
+static SESSION: Mutex<Option<Session>> = Mutex::new(None);
+
+fn session() -> MutexGuard<'static, Option<Session>> {
+    SESSION.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[derive(Clone, Debug)]
+pub struct AuditEntry {
+    pub timestamp: i64,
+    pub success: bool,
+    pub command: String,
+}
+
+impl AuditEntry {
+    // The builtin (or program) name, used to group entries in `stats`
+    pub fn name(&self) -> &str {
+        self.command.split_whitespace().next().unwrap_or("")
+    }
+}
+
+pub fn audit_path() -> PathBuf {
+    dirs::state_dir().join(AUDIT_FILE)
+}
+
+// The log holds every line typed, secrets and all, so it and the files
+// written to replace it are readable by this user only
+fn write_private(path: &Path, contents: &str) -> io::Result<()> {
+    OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(path)?.write_all(contents.as_bytes())
+}
+
+// Whether `line`, as typed, may be written down. Lines matching one of the
+// `history_ignore` patterns may hold secrets and stay out of the audit log,
+// the log file and crash reports.
+pub fn should_record(line: &str) -> bool {
+    // The pattern given to scrub is likely the secret itself
+    if line.trim_start().starts_with("history scrub") {
+        return false;
+    }
+    let config = config::get();
+    let patterns = match &config.history_ignore {
+        Some(patterns) => patterns.iter().map(String::as_str).collect(),
+        None => DEFAULT_HISTORY_IGNORE.to_vec(),
+    };
+    // Patterns were checked when the config was loaded
+    !patterns.into_iter().filter_map(|pattern| Regex::new(pattern).ok()).any(|pattern| pattern.is_match(line))
+}
+
+pub fn record(command: &str, success: bool) -> CrateResult<()> {
+    // One entry per line: timestamp, status, command line
+    let entry = format!(
+        "{}\t{}\t{}",
+        chrono::Utc::now().timestamp(),
+        if success { "ok" } else { "err" },
+        command.replace(['\n', '\t'], " ")
+    );
+    // With encryption on, a locked session writes nothing rather than plain text
+    let line = match encryption::enabled() {
+        true => match encryption::seal(&entry) {
+            Some(sealed) => sealed,
+            None => return Ok(()),
+        },
+        false => entry,
+    };
+
+    let mut session = session();
+    if let Some(session) = session.as_mut().filter(|session| session.mode == HistoryMode::Session) {
+        session.pending.push(line);
+        return Ok(());
+    }
+
+    let _lock = lock()?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .mode(0o600)
+        .open(audit_path())?;
+    // What others wrote since this shell last looked is taken in first, so
+    // its own line isn't read back as theirs
+    if let Some(session) = session.as_mut() {
+        session.others.extend(read_new(&mut file, &mut session.read_to)?);
+    }
+    writeln!(file, "{}", line)?;
+    if let Some(session) = session.as_mut() {
+        session.read_to = file.metadata()?.len();
+    }
+
+    Ok(())
+}
+
+// Starts keeping track of the log for an interactive session, in the mode
+// the config asks for, and returns the command lines in it so far
+pub fn begin_session() -> Vec<String> {
+    let mut file = match File::open(audit_path()) {
+        Ok(file) => Some(file),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => {
+            tracing::warn!(error = %e, "could not read the history");
+            None
+        }
+    };
+    // Logs from before it was made private are put right
+    if let Some(metadata) = file.as_ref().and_then(|file| file.metadata().ok()).filter(|metadata| metadata.permissions().mode() & 0o077 != 0) {
+        let mut permissions = metadata.permissions();
+        permissions.set_mode(0o600);
+        if let Err(e) = fs::set_permissions(audit_path(), permissions) {
+            tracing::warn!(error = %e, "could not make the history private");
+        }
+    }
+    let mut read_to = 0;
+    let commands = file.as_mut().and_then(|file| read_new(file, &mut read_to).ok()).unwrap_or_default();
+    *session() = Some(Session { mode: config::get().history_mode, read_to, others: Vec::new(), pending: Vec::new() });
+    commands
+}
+
+// In shared mode, the command lines other shells wrote to the log since the
+// last call
+pub fn merge() -> Vec<String> {
+    let mut session = session();
+    let Some(session) = session.as_mut().filter(|session| session.mode == HistoryMode::Shared) else {
+        return Vec::new();
+    };
+    if let Ok(mut file) = File::open(audit_path()) {
+        match read_new(&mut file, &mut session.read_to) {
+            Ok(commands) => session.others.extend(commands),
+            Err(e) => tracing::warn!(error = %e, "could not read the history"),
+        }
+    }
+    std::mem::take(&mut session.others)
+}
+
+// The command lines in whole entries after `read_to`, which moves past them.
+// A log shorter than that was rewritten by `history scrub` somewhere, and
+// only lines added from then on are new.
+fn read_new(file: &mut File, read_to: &mut u64) -> CrateResult<Vec<String>> {
+    let length = file.metadata()?.len();
+    if length < *read_to {
+        *read_to = length;
+        return Ok(Vec::new());
+    }
+    file.seek(SeekFrom::Start(*read_to))?;
+    let mut contents = Vec::new();
+    file.take(length - *read_to).read_to_end(&mut contents)?;
+    // A line still being written is left for next time
+    let complete = contents.iter().rposition(|byte| *byte == b'\n').map_or(0, |newline| newline + 1);
+    *read_to += complete as u64;
+    let contents = String::from_utf8_lossy(&contents[..complete]);
+    Ok(parse(&contents).into_iter().map(|entry| entry.command).collect())
+}
+
+// Takes the log for writing. Shells sharing it wait their turn, so lines
+// don't interleave and none are lost to a scrub rewriting the file.
+fn lock() -> CrateResult<File> {
+    let file = OpenOptions::new().create(true).truncate(false).write(true).open(audit_path().with_extension("lock"))?;
+    while unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        let e = io::Error::last_os_error();
+        if e.kind() != io::ErrorKind::Interrupted {
+            return Err(e.into());
+        }
+    }
+    Ok(file)
+}
+
+// Makes sure what was recorded is on disk, for when the shell exits. In
+// session mode that is when the session's lines are added to the log.
+pub fn sync() -> CrateResult<()> {
+    let pending = session().as_mut().map(|session| std::mem::take(&mut session.pending)).unwrap_or_default();
+    if !pending.is_empty() {
+        let _lock = lock()?;
+        let mut file = OpenOptions::new().create(true).append(true).mode(0o600).open(audit_path())?;
+        file.write_all(pending.iter().map(|line| format!("{}\n", line)).collect::<String>().as_bytes())?;
+    }
+    match OpenOptions::new().append(true).open(audit_path()) {
+        Ok(file) => Ok(file.sync_all()?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Every entry, with a session's own ones not yet written last
+pub fn load() -> CrateResult<Vec<AuditEntry>> {
+    let path = audit_path();
+    let contents = match path.exists() {
+        true => fs::read_to_string(path)?,
+        false => String::new(),
+    };
+    let mut entries = parse(&contents);
+    if let Some(session) = session().as_ref() {
+        entries.extend(parse(&session.pending.join("\n")));
+    }
+    Ok(entries)
+}
+
+fn parse(contents: &str) -> Vec<AuditEntry> {
+    let mut entries = Vec::new();
+
+    // Encrypted entries that can't be read this session are left out
+    for line in contents.lines().filter_map(encryption::open) {
+        let mut fields = line.splitn(3, '\t');
+        let (Some(timestamp), Some(status), Some(command)) = (fields.next(), fields.next(), fields.next()) else {
+            // Skip malformed lines rather than refusing to load the whole log
+            continue;
+        };
+
+        if let Ok(timestamp) = timestamp.parse() {
+            entries.push(AuditEntry {
+                timestamp,
+                success: status == "ok",
+                command: command.to_string(),
+            });
+        }
+    }
+
+    entries
+}
+
+// Drops every entry whose command matches `pattern` and returns how many went.
+// The log is rewritten next to itself and renamed over, so a crash midway
+// leaves the old one.
+pub fn scrub(pattern: &Regex) -> CrateResult<usize> {
+    let matches = |line: &str| {
+        encryption::open(line).is_some_and(|entry| pattern.is_match(entry.splitn(3, '\t').nth(2).unwrap_or(&entry)))
+    };
+    let mut session = session();
+    let mut removed = 0;
+    if let Some(session) = session.as_mut() {
+        let before = session.pending.len();
+        session.pending.retain(|line| !matches(line));
+        removed += before - session.pending.len();
+    }
+
+    let path = audit_path();
+    if !path.exists() {
+        return Ok(removed);
+    }
+
+    let _lock = lock()?;
+    let contents = fs::read_to_string(&path)?;
+    let mut kept = String::new();
+    let mut removed_here = 0;
+    for line in contents.lines() {
+        if matches(line) {
+            removed_here += 1;
+        } else {
+            kept.push_str(line);
+            kept.push('\n');
+        }
+    }
+
+    if removed_here > 0 {
+        let temporary = path.with_extension("scrub");
+        write_private(&temporary, &kept)?;
+        fs::rename(&temporary, &path)?;
+        // Everything left has been seen
+        if let Some(session) = session.as_mut() {
+            session.read_to = kept.len() as u64;
+        }
+    }
+    Ok(removed + removed_here)
+}
+
+// Encrypts the entries written before encryption was turned on, once the key
+// is unlocked, and returns how many there were
+pub fn encrypt_plain_entries() -> CrateResult<usize> {
+    let path = audit_path();
+    if !path.exists() || !encryption::unlocked() {
+        return Ok(0);
+    }
+
+    let _lock = lock()?;
+    let contents = fs::read_to_string(&path)?;
+    let mut sealed = String::new();
+    let mut count = 0;
+    for line in contents.lines() {
+        match encryption::is_sealed(line) {
+            true => sealed.push_str(line),
+            false => {
+                sealed.push_str(&encryption::seal(line).ok_or_else(|| anyhow!(tr("encryption failed")))?);
+                count += 1;
+            }
+        }
+        sealed.push('\n');
+    }
+
+    if count > 0 {
+        let temporary = path.with_extension("encrypt");
+        write_private(&temporary, &sealed)?;
+        fs::rename(&temporary, &path)?;
+    }
+    Ok(count)
+}
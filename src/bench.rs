@@ -0,0 +1,200 @@
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use colored::*;
+
+use crate::command::Command;
+use crate::errors::CrateResult;
+use crate::helpers;
+use crate::i18n::{tr, trf};
+use crate::search::{FindFilters, Matcher};
+use crate::state::ShellState;
+
+pub const DEFAULT_RUNS: usize = 10;
+
+struct Timing {
+    label: String,
+    samples: Vec<Duration>,
+}
+
+impl Timing {
+    fn mean(&self) -> Duration {
+        self.samples.iter().sum::<Duration>() / self.samples.len().max(1) as u32
+    }
+
+    fn min(&self) -> Duration {
+        self.samples.iter().min().copied().unwrap_or_default()
+    }
+
+    fn max(&self) -> Duration {
+        self.samples.iter().max().copied().unwrap_or_default()
+    }
+}
+
+// The system command equivalent to a builtin invocation
+fn system_equivalent(command: &Command) -> CrateResult<Vec<String>> {
+    let args = match command {
+        // Filters could delete things, so only plain searches are measured
+        Command::Find(dir, pattern, options, filters) if *filters == FindFilters::default() => {
+            let test = if Matcher::new(pattern, options.case()).ignores_case() { "-iname" } else { "-name" };
+            vec!["find".into(), dir.clone(), test.into(), format!("*{}*", pattern)]
+        }
+        Command::Grep(file, pattern, options) => {
+            let mut args = vec!["grep".into(), "-n".into(), "-F".into()];
+            if Matcher::new(pattern, options.case()).ignores_case() {
+                args.push("-i".into());
+            }
+            [args, vec![pattern.clone(), file.clone()]].concat()
+        }
+        Command::Cat(paths, options) => {
+            let flags = [
+                (options.show_nonprinting, "-v"),
+                (options.show_tabs, "-T"),
+                (options.show_ends, "-E"),
+                (options.squeeze_blank, "-s"),
+            ];
+            let flags = flags.iter().filter(|(set, _)| *set).map(|(_, flag)| flag.to_string());
+            [vec!["cat".into()], flags.collect(), paths.clone()].concat()
+        }
+        Command::Stat(paths) => [vec!["stat".into()], paths.clone()].concat(),
+        Command::Cp(src, dest) => vec!["cp".into(), src.clone(), dest.clone()],
+        Command::CpR(src, dest, options) => {
+            let flags = options.one_file_system.then(|| "-x".to_string());
+            [vec!["cp".into(), "-r".into()], flags.into_iter().collect(), vec![src.clone(), dest.clone()]].concat()
+        }
+        _ => return Err(anyhow!(tr("bench supports find, grep, cat, stat, cp and cp -r"))),
+    };
+    Ok(args)
+}
+
+fn run_builtin(state: &ShellState, command: &Command) -> CrateResult<()> {
+    match command {
+        Command::Find(dir, pattern, options, filters) => {
+            let mut count = 0;
+            helpers::find(&state.resolve(dir), &Matcher::new(pattern, options.case()), filters, &mut |_| {
+                count += 1;
+                options.wants_more(count)
+            })?;
+        }
+        Command::Grep(file, pattern, options) => {
+            let mut count = 0;
+            helpers::grep(&state.resolve(file), &Matcher::new(pattern, options.case()), &mut |_, _| {
+                count += 1;
+                options.wants_more(count)
+            })?;
+        }
+        Command::Cat(paths, options) => {
+            for path in paths {
+                helpers::cat(&state.resolve(path), options)?;
+            }
+        }
+        Command::Stat(paths) => {
+            for path in paths {
+                helpers::stat(&state.resolve(path))?;
+            }
+        }
+        Command::Cp(src, dest) => helpers::cp(&state.resolve(src), &state.resolve(dest))?,
+        Command::CpR(src, dest, options) => helpers::cp_r(&state.resolve(src), &state.resolve(dest), options)?,
+        _ => unreachable!("system_equivalent rejects unsupported commands"),
+    }
+    Ok(())
+}
+
+fn run_system(state: &ShellState, args: &[String]) -> CrateResult<()> {
+    let status = state
+        .command(&args[0])
+        .args(&args[1..])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+
+    // grep exits with 1 when nothing matched, which is not a failure here
+    let no_match = args[0] == "grep" && status.code() == Some(1);
+    if !status.success() && !no_match {
+        return Err(anyhow!(trf("'{}' failed with {}", &[&args.join(" "), &status])));
+    }
+    Ok(())
+}
+
+// Copies leave a destination behind; remove it so every run does the same work
+fn cleanup(state: &ShellState, command: &Command) -> CrateResult<()> {
+    match command {
+        Command::Cp(_, dest) if state.resolve(dest).is_file() => std::fs::remove_file(state.resolve(dest))?,
+        Command::CpR(_, dest, _) if state.resolve(dest).is_dir() => std::fs::remove_dir_all(state.resolve(dest))?,
+        _ => {}
+    }
+    Ok(())
+}
+
+fn measure(
+    state: &ShellState,
+    label: String,
+    runs: usize,
+    command: &Command,
+    mut run: impl FnMut() -> CrateResult<()>,
+) -> CrateResult<Timing> {
+    let mut samples = Vec::with_capacity(runs);
+    for _ in 0..runs {
+        let start = Instant::now();
+        run()?;
+        samples.push(start.elapsed());
+        cleanup(state, command)?;
+    }
+    Ok(Timing { label, samples })
+}
+
+pub fn bench(state: &ShellState, runs: usize, line: &str) -> CrateResult<String> {
+    let command = Command::try_from(line)?;
+    let system_args = system_equivalent(&command)?;
+
+    if let Command::Cp(_, dest) | Command::CpR(_, dest, _) = &command {
+        if state.resolve(dest).exists() {
+            return Err(anyhow!(trf("'{}' already exists; bench needs a fresh destination", &[dest])));
+        }
+    }
+
+    let builtin = measure(state, tr("builtin").to_string(), runs, &command, || run_builtin(state, &command))?;
+    let system = measure(state, system_args.join(" "), runs, &command, || run_system(state, &system_args))?;
+
+    let label_width = builtin.label.len().max(system.label.len()).max(14);
+    let mut result = format!(
+        "{:label_width$}  {:>10}  {:>10}  {:>10}\n",
+        tr("Implementation").cyan().bold(),
+        tr("mean").cyan().bold(),
+        tr("min").cyan().bold(),
+        tr("max").cyan().bold(),
+    );
+    result.push_str(&format!("{}\n", "─".repeat(label_width + 36).bright_black()));
+
+    for timing in [&builtin, &system] {
+        result.push_str(&format!(
+            "{:label_width$}  {:>10}  {:>10}  {:>10}\n",
+            timing.label.green(),
+            format_duration(timing.mean()),
+            format_duration(timing.min()),
+            format_duration(timing.max()),
+        ));
+    }
+
+    let ratio = system.mean().as_secs_f64() / builtin.mean().as_secs_f64().max(f64::EPSILON);
+    let verdict = if ratio >= 1.0 {
+        trf("builtin is {}× faster over {} runs", &[&format!("{:.2}", ratio), &runs]).bright_green()
+    } else {
+        trf("builtin is {}× slower over {} runs", &[&format!("{:.2}", 1.0 / ratio), &runs]).yellow()
+    };
+    result.push_str(&format!("\n{}\n", verdict));
+
+    Ok(result)
+}
+
+fn format_duration(duration: Duration) -> String {
+    let micros = duration.as_secs_f64() * 1_000_000.0;
+    if micros < 1000.0 {
+        format!("{:.1} µs", micros)
+    } else if micros < 1_000_000.0 {
+        format!("{:.2} ms", micros / 1000.0)
+    } else {
+        format!("{:.2} s", micros / 1_000_000.0)
+    }
+}
@@ -0,0 +1,87 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::errors::CrateResult;
+
+static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+// An empty file only this user can read, for output handed from one command
+// to another; the caller removes it
+pub fn temporary_file() -> CrateResult<(PathBuf, File)> {
+    loop {
+        let name = format!("shellbasics-{}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed));
+        let path = std::env::temp_dir().join(name);
+        match OpenOptions::new().write(true).create_new(true).mode(0o600).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            // Left over from an earlier shell with the same process id
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+// `fd` pointed at `file` until dropped, then back where it was
+struct Redirect {
+    fd: libc::c_int,
+    saved: libc::c_int,
+}
+
+impl Redirect {
+    fn start(fd: libc::c_int, file: &File) -> CrateResult<Redirect> {
+        let saved = unsafe { libc::dup(fd) };
+        if saved < 0 || unsafe { libc::dup2(file.as_raw_fd(), fd) } < 0 {
+            let e = std::io::Error::last_os_error();
+            if saved >= 0 {
+                unsafe { libc::close(saved) };
+            }
+            return Err(e.into());
+        }
+        Ok(Redirect { fd, saved })
+    }
+}
+
+impl Drop for Redirect {
+    fn drop(&mut self) {
+        unsafe {
+            libc::dup2(self.saved, self.fd);
+            libc::close(self.saved);
+        }
+    }
+}
+
+// Sends what builtins print to `file` until dropped. Colors are turned off
+// meanwhile, so the file holds plain text.
+pub struct Capture {
+    _redirect: Redirect,
+    colors: bool,
+}
+
+impl Capture {
+    pub fn start(file: &File) -> CrateResult<Capture> {
+        std::io::stdout().flush()?;
+        Capture::on(libc::STDOUT_FILENO, file)
+    }
+
+    // The same for the errors builtins print
+    pub fn errors(file: &File) -> CrateResult<Capture> {
+        Capture::on(libc::STDERR_FILENO, file)
+    }
+
+    fn on(fd: libc::c_int, file: &File) -> CrateResult<Capture> {
+        let redirect = Redirect::start(fd, file)?;
+        let colors = colored::control::SHOULD_COLORIZE.should_colorize();
+        colored::control::set_override(false);
+        Ok(Capture { _redirect: redirect, colors })
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        let _ = std::io::stdout().flush();
+        colored::control::set_override(self.colors);
+    }
+}
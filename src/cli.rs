@@ -0,0 +1,85 @@
+use std::io;
+use std::path::PathBuf;
+
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+
+use crate::config::ColorMode;
+use crate::logging::LogLevel;
+
+#[derive(Debug, Parser)]
+#[command(name = "shell", version, about = "A colorful, beginner-friendly shell")]
+pub struct Cli {
+    /// When to use colors in the output
+    #[arg(long, value_enum, value_name = "WHEN", global = true)]
+    pub color: Option<ColorMode>,
+
+    /// Read settings from this file instead of ~/.config/shellbasics/config.toml
+    #[arg(long, value_name = "PATH", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Color theme: default, ocean, forest or mono
+    #[arg(long, value_name = "NAME", global = true)]
+    pub theme: Option<String>,
+
+    /// Verbosity of the log file in ~/.local/state/shellbasics/logs
+    #[arg(long, value_enum, value_name = "LEVEL", global = true)]
+    pub log_level: Option<LogLevel>,
+
+    /// Replay a script of commands in presenter mode (same as `run --demo`)
+    #[arg(long, value_name = "SCRIPT")]
+    pub demo: Option<String>,
+
+    /// Run a single command line and exit (same as `exec -c`)
+    #[arg(short = 'c', long = "command", value_name = "LINE", conflicts_with = "demo")]
+    pub command: Option<String>,
+
+    /// Run one stage of a pipeline, started by the shell itself
+    #[arg(long, value_name = "LINE", hide = true, conflicts_with_all = ["demo", "command"])]
+    pub stage: Option<String>,
+
+    #[command(subcommand)]
+    pub subcommand: Option<CliCommand>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CliCommand {
+    /// Start the interactive shell (the default)
+    Run {
+        /// Replay a script of commands in presenter mode
+        #[arg(long, value_name = "SCRIPT")]
+        demo: Option<String>,
+    },
+    /// Run a single command line and exit with its status
+    Exec {
+        #[arg(short = 'c', long = "command", value_name = "LINE")]
+        command: String,
+    },
+    /// Run every command of a script file without pausing
+    Replay {
+        script: String,
+
+        /// Stop at the first command that fails
+        #[arg(long)]
+        stop_on_error: bool,
+    },
+    /// Connect to a host from ~/.ssh/config or known_hosts with the system ssh
+    Ssh {
+        host: String,
+
+        /// Run this command on the host instead of a login shell
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+    /// Print shell completion definitions for this binary's flags
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+pub fn print_completions(shell: Shell) {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, &mut io::stdout());
+}
@@ -1,147 +1,1809 @@
 use anyhow::anyhow;
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, TimeZone};
+
+use crate::dedupe::Keep;
+use crate::environment;
+use crate::i18n::{tr, trf};
+use crate::limits::{Limit, Resource};
+use crate::monitor::{self, Column};
+use crate::search::{FindFilters, SearchOptions};
+use crate::signals;
+use crate::tokenizer::{quote, redirection_words, split_pipes, tokenize};
+
+// Where `choose` puts the option picked unless -v names another variable
+pub const DEFAULT_CHOICE_VARIABLE: &str = "CHOICE";
 
 #[derive(Clone, Debug)]
 pub enum Command {
     Exit,
-    Echo(String),
-    Ls,
-    LsDetailed,
+    Clear,
+    Echo(String, EchoOptions),
+    Printf(String, Vec<String>),
+    // The paths to list; none for the working directory
+    Ls(Vec<String>),
+    LsDetailed(ListOptions, Vec<String>),
     Pwd,
     Cd(String),
-    Touch(String),
-    Rm(String),
-    Cat(String),
-    Mkdir(String),
-    MkdirP(String),
-    Rmdir(String),
-    RmdirR(String),
+    Touch(Vec<String>, TouchOptions),
+    Rm(Vec<String>, RemoveOptions),
+    Cat(Vec<String>, CatOptions),
+    Mkdir(Vec<String>),
+    MkdirP(Vec<String>),
+    // Named pipes to create, and their permission bits
+    Mkfifo(Vec<String>, u32),
+    Rmdir(Vec<String>),
     Cp(String, String),
-    CpR(String, String),
-    Mv(String, String),
-    Stat(String),
-    Find(String, String),
-    Grep(String, String),
-    Ln(String, String),
+    CpR(String, String, CopyOptions),
+    Mv(String, String, MoveOptions),
+    Stat(Vec<String>),
+    Find(String, String, SearchOptions, FindFilters),
+    Grep(String, String, SearchOptions),
+    Ln(String, String, LinkOptions),
+    Dedupe(String, DedupeOptions),
+    // File, prefix, bytes per piece
+    Split(String, String, u64),
+    Merge(Vec<String>, String),
+    Truncate(Vec<String>, TruncateOptions),
+    Du(Vec<String>, DiskUsageOptions),
+    Usage(String),
+    // Expression, files, --dry-run
+    Rename(String, Vec<String>, bool),
+    Hosts,
+    // ssh or scp and its arguments, run by the system program
+    Remote(String, Vec<String>),
+    // A program that isn't a builtin and its arguments, found on PATH
+    Program(String, Vec<String>),
+    // A pipeline that ran, by its stages; only a line with `|` makes one
+    Pipeline(Vec<String>),
+    // Local file, host:path
+    Push(String, String, TransferOptions),
+    // host:path, local file or directory
+    Pull(String, String, TransferOptions),
+    // Directory, port
+    Serve(String, u16),
+    Jobs,
+    // The command line to run as its own process that outlives the shell
+    Nohup(String),
+    // Job numbers
+    Disown(Vec<usize>),
+    // A suspended program's job number; the latest one if not given
+    Fg(Option<usize>),
+    Bg(Option<usize>),
+    // Signal, %job numbers and process ids
+    Kill(i32, Vec<String>),
+    // kill -l
+    KillList,
+    // Signal, process name
+    Killall(i32, String),
+    // Pid to start from
+    Pstree(Option<u32>),
+    // Template and project name
+    New(String, String),
+    NewList,
+    // None lists the tasks
+    Task(Option<String>),
+    GitStatus,
+    GitLog(usize, Option<String>),
+    GitDiff(bool, Option<String>),
+    // Refresh interval, sort column
+    Monitor(std::time::Duration, Column),
+    // --kill PORT
+    Ports(Option<u16>),
+    Resolve(String, ResolveOptions),
+    Download(String, DownloadOptions),
+    // Names to print; all variables when empty
+    Printenv(Vec<String>),
+    // Names to look up as abbreviations, builtins and programs on PATH
+    Which(Vec<String>),
+    Hash(HashAction),
+    Rehash,
+    // Read the settings file again
+    Reload,
+    Doctor,
+    Env(EnvOptions),
+    // NAME=value pairs
+    Export(Vec<(String, String)>),
+    Unset(Vec<String>),
+    Abbr(AbbrAction),
+    // `NAME=value command`: the pairs are set only while the rest of the line runs
+    WithEnv(Vec<(String, String)>, String),
+    Ulimit(UlimitAction),
+    // Limits for one command line, and the line
+    Limit(Vec<(Resource, Limit)>, String),
+    // Increment and the command line to run with it
+    Nice(i32, String),
+    // Priority and %jobs or process ids
+    Renice(i32, Vec<String>),
+    Stats,
+    History,
+    // Regex for the entries to drop
+    HistoryScrub(String),
+    // The history number of the line to edit and run again, counting back
+    // from the end when negative; without one, the last line
+    Fc(Option<i64>),
+    // The options and the variable that receives the one chosen
+    Choose(Vec<String>, String),
+    // The expression, without the closing `]` of `[ ... ]`
+    Test(Vec<String>),
+    SpinnerStart(String),
+    SpinnerStop,
+    // Current step, total and the label shown before the bar
+    Progress(u64, u64, Option<String>),
+    // Keyring entry names
+    SecretGet(String),
+    SecretSet(String),
+    Undo(usize),
+    Tutorial(String),
+    Practice(String, Option<String>),
+    Bench(usize, String),
+}
+
+// Flags accepted by `ln`, as in coreutils
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LinkOptions {
+    // -s: make a symbolic link instead of a hard link
+    pub symbolic: bool,
+    // -f: replace an existing file at the link name
+    pub force: bool,
+    // -r: store the target relative to the link's directory
+    pub relative: bool,
+}
+
+// Flags accepted by `dedupe`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DedupeOptions {
+    // --auto: resolve every group without asking
+    pub auto: bool,
+    // --keep newest|oldest: the copy --auto keeps
+    pub keep: Keep,
+    // --link: replace copies with hard links instead of deleting them
+    pub link: bool,
+}
+
+// Flags accepted by `push` and `pull`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TransferOptions {
+    // -c/--continue: finish a partial copy left by an earlier attempt
+    pub resume: bool,
+}
+
+// Flags accepted by `resolve`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ResolveOptions {
+    // --server ADDR: ask this name server instead of the system's
+    pub server: Option<String>,
+    // -t TYPE: one record type instead of all of dns::RECORD_TYPES
+    pub kind: Option<String>,
+}
+
+// Flags accepted by `download`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DownloadOptions {
+    // -o FILE: where to save it instead of the URL's file name
+    pub output: Option<String>,
+    // --sha256 HEX: the checksum the download must have
+    pub sha256: Option<String>,
+    // -b/--background: run as a job instead of waiting with a progress bar
+    pub background: bool,
+}
+
+// Flags accepted by `env`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EnvOptions {
+    // --diff: only what changed since the shell started
+    pub diff: bool,
+    // --sorted: by name instead of in inherited order
+    pub sorted: bool,
+    // --grep PATTERN: variables whose name or value matches
+    pub grep: Option<String>,
+}
+
+// What `nice` adds without -n, as in coreutils
+pub const DEFAULT_NICENESS: i32 = 10;
+
+// Commits `glog` shows without -n
+pub const DEFAULT_LOG_LENGTH: usize = 20;
+
+// What `ulimit` was asked to do
+#[derive(Clone, Debug, PartialEq)]
+pub enum UlimitAction {
+    // -a, or no arguments
+    All,
+    Show(Resource),
+    Set(Resource, Limit),
+}
+
+// What `abbr` was asked to do
+#[derive(Clone, Debug, PartialEq)]
+pub enum AbbrAction {
+    // No arguments, or -l
+    List,
+    // The abbreviation and what it expands to
+    Add(String, String),
+    Erase(Vec<String>),
+}
+
+// What `hash` was asked to do
+#[derive(Clone, Debug, PartialEq)]
+pub enum HashAction {
+    // No arguments
+    List,
+    // Programs to look up and remember without running them
+    Add(Vec<String>),
+    // -d
+    Forget(Vec<String>),
+    // -r
+    Clear,
+}
+
+// What `truncate -s` does to a file's size
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SizeChange {
+    Set(u64),
+    // -s +SIZE / -s -SIZE
+    Grow(u64),
+    Shrink(u64),
+}
+
+impl SizeChange {
+    pub fn apply(self, current: u64) -> u64 {
+        match self {
+            SizeChange::Set(size) => size,
+            SizeChange::Grow(size) => current.saturating_add(size),
+            SizeChange::Shrink(size) => current.saturating_sub(size),
+        }
+    }
+}
+
+// Flags accepted by `truncate`
+#[derive(Clone, Debug, PartialEq)]
+pub struct TruncateOptions {
+    pub size: SizeChange,
+    // -c: don't create missing files
+    pub no_create: bool,
+}
+
+// Flags accepted by `ls -l`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ListOptions {
+    // -n: show owner and group as numbers
+    pub numeric_ids: bool,
+}
+
+// Flags accepted by `du`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DiskUsageOptions {
+    // -s/--summarize: one line per argument instead of one per directory
+    pub summarize: bool,
+    // --one-file-system: skip directories on other filesystems
+    pub one_file_system: bool,
+}
+
+// Flags accepted by `echo`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EchoOptions {
+    // -n: no trailing newline
+    pub no_newline: bool,
+    // -e: interpret backslash escapes (-E turns them back off)
+    pub escapes: bool,
+}
+
+// Flags accepted by `rm`; `rmdir -r` is the same as `rm -r`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RemoveOptions {
+    // -r/-R: remove directories and their contents
+    pub recursive: bool,
+    // -f: ignore missing paths and don't ask before removing large trees
+    pub force: bool,
+    // --no-preserve-root: allow removing / or a directory we're inside
+    pub no_preserve_root: bool,
+    // --one-file-system: leave directories on other filesystems alone
+    pub one_file_system: bool,
+    // --git (or grm): stage the removal of tracked files with git rm
+    pub git: bool,
+}
+
+// Flags accepted by `mv`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MoveOptions {
+    // --git (or gmv): move tracked files with git mv so the rename is staged
+    pub git: bool,
+}
+
+// Flags accepted by `cp`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CopyOptions {
+    // --one-file-system: don't descend into directories on other filesystems
+    pub one_file_system: bool,
+}
+
+// Flags accepted by `cat`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CatOptions {
+    // -v: show control characters as ^X and high bytes as M-x
+    pub show_nonprinting: bool,
+    // -T: show tabs as ^I
+    pub show_tabs: bool,
+    // -E: mark line ends with $
+    pub show_ends: bool,
+    // -s: collapse runs of blank lines into one
+    pub squeeze_blank: bool,
+    // -H: print `==> name <==` before each file
+    pub headers: bool,
+}
+
+impl CatOptions {
+    // Whether the contents are shown exactly as stored
+    pub fn is_plain(&self) -> bool {
+        !(self.show_nonprinting || self.show_tabs || self.show_ends || self.squeeze_blank)
+    }
+}
+
+// Flags accepted by `touch`. With neither -a nor -m both times are set.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TouchOptions {
+    // -a: change the access time
+    pub access: bool,
+    // -m: change the modification time
+    pub modification: bool,
+    // -c: don't create missing files
+    pub no_create: bool,
+    // -t/-d: use this time instead of now
+    pub time: Option<DateTime<Local>>,
+}
+
+impl TouchOptions {
+    pub fn sets_access(&self) -> bool {
+        self.access || !self.modification
+    }
+
+    pub fn sets_modification(&self) -> bool {
+        self.modification || !self.access
+    }
 }
 
 impl TryFrom<&str> for Command {
     type Error = anyhow::Error;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let split_value: Vec<&str> = value.split_whitespace().collect();
+        let tokens = tokenize(value)?;
+        let split_value: Vec<&str> = tokens.iter().map(String::as_str).collect();
         
         if split_value.is_empty() {
-            return Err(anyhow!("Empty command"));
+            return Err(anyhow!(tr("Empty command")));
+        }
+
+        // Leading NAME=value words set variables for this command only
+        let assignments = split_value
+            .iter()
+            .take_while(|word| word.split_once('=').is_some_and(|(name, _)| environment::is_name(name)))
+            .count();
+        if assignments > 0 {
+            let (pairs, rest) = split_value.split_at(assignments);
+            if rest.is_empty() {
+                return Err(anyhow!(trf("{} has no command to run; export sets a variable for the session", &[&pairs.join(" ")])));
+            }
+            let pairs = pairs.iter().filter_map(|pair| pair.split_once('=')).map(|(name, value)| (name.to_string(), value.to_string())).collect();
+            // Re-quoted so the command parses to the same words
+            return Ok(Command::WithEnv(pairs, rest.iter().map(|word| quote(word)).collect::<Vec<_>>().join(" ")));
         }
 
         match split_value[0] {
             "exit" => Ok(Command::Exit),
+            "clear" => Ok(Command::Clear),
             "ls" => {
-                if split_value.len() > 1 && split_value[1] == "-l" {
-                    Ok(Command::LsDetailed)
+                let mut detailed = false;
+                let mut options = ListOptions::default();
+                let (flags, operands): (Vec<&str>, Vec<&str>) = split_value[1..].iter().partition(|arg| arg.len() > 1 && arg.starts_with('-'));
+                for flags in flags.iter().filter_map(|arg| arg.strip_prefix('-')) {
+                    for flag in flags.chars() {
+                        match flag {
+                            'l' => detailed = true,
+                            // -n implies -l, as in coreutils
+                            'n' => {
+                                detailed = true;
+                                options.numeric_ids = true;
+                            }
+                            _ => return Err(anyhow!(trf("ls: unknown option -{}", &[&flag]))),
+                        }
+                    }
+                }
+                if detailed {
+                    Ok(Command::LsDetailed(options, paths(&operands)))
                 } else {
-                    Ok(Command::Ls)
+                    Ok(Command::Ls(paths(&operands)))
                 }
             },
             "echo" => {
                 if split_value.len() < 2 {
-                    Err(anyhow!("echo command requires an argument"))
+                    return Err(anyhow!(tr("echo command requires an argument")));
+                }
+
+                // As in bash, a word with anything but n/e/E after the dash is text
+                let mut options = EchoOptions::default();
+                let is_flag = |word: &str| {
+                    word.strip_prefix('-').is_some_and(|flags| !flags.is_empty() && flags.chars().all(|c| "neE".contains(c)))
+                };
+                let flags = split_value[1..].iter().take_while(|word| is_flag(word)).count();
+                for flag in split_value[1..=flags].iter().flat_map(|word| word[1..].chars()) {
+                    match flag {
+                        'n' => options.no_newline = true,
+                        'e' => options.escapes = true,
+                        _ => options.escapes = false,
+                    }
+                }
+                Ok(Command::Echo(split_value[1 + flags..].join(" "), options))
+            }
+            "printf" => {
+                if split_value.len() < 2 {
+                    Err(anyhow!(tr("printf requires a format")))
                 } else {
-                    Ok(Command::Echo(split_value[1..].join(" ")))
+                    Ok(Command::Printf(split_value[1].to_string(), paths(&split_value[2..])))
                 }
             }
             "pwd" => Ok(Command::Pwd),
             "cd" => {
                 if split_value.len() < 2 {
-                    Err(anyhow!("cd command requires an argument"))
+                    // A bare `cd` goes home, as in other shells
+                    Ok(Command::Cd("~".to_string()))
                 } else {
                     Ok(Command::Cd(split_value[1..].join(" ")))
                 }
             }
             "touch" => {
-                if split_value.len() < 2 {
-                    Err(anyhow!("touch command requires an argument"))
+                let mut options = TouchOptions::default();
+                let mut operands = Vec::new();
+                let mut args = split_value[1..].iter();
+                while let Some(arg) = args.next() {
+                    match arg.strip_prefix('-') {
+                        Some(flags) if !flags.is_empty() => {
+                            for flag in flags.chars() {
+                                match flag {
+                                    'a' => options.access = true,
+                                    'm' => options.modification = true,
+                                    'c' => options.no_create = true,
+                                    't' | 'd' => {
+                                        let Some(value) = args.next() else {
+                                            return Err(anyhow!(trf("touch -{} requires a time", &[&flag])));
+                                        };
+                                        let time = if flag == 't' { parse_stamp(value) } else { parse_date(value) };
+                                        options.time = Some(time.ok_or_else(|| anyhow!(trf("touch: invalid time '{}'", &[value])))?);
+                                    }
+                                    _ => return Err(anyhow!(trf("touch: unknown option -{}", &[&flag]))),
+                                }
+                            }
+                        }
+                        _ => operands.push(*arg),
+                    }
+                }
+
+                if operands.is_empty() {
+                    Err(anyhow!(tr("touch command requires an argument")))
                 } else {
-                    Ok(Command::Touch(split_value[1..].join(" ")))
+                    Ok(Command::Touch(paths(&operands), options))
                 }
             }
-            "rm" => {
-                if split_value.len() < 2 {
-                    Err(anyhow!("rm command requires an argument"))
+            "rm" | "grm" => {
+                let mut options = RemoveOptions { git: split_value[0] == "grm", ..Default::default() };
+                let mut operands = Vec::new();
+                for arg in &split_value[1..] {
+                    match arg.strip_prefix('-') {
+                        Some("-no-preserve-root") => options.no_preserve_root = true,
+                        Some("-git") => options.git = true,
+                        Some("-one-file-system") => options.one_file_system = true,
+                        Some(flags) if !flags.is_empty() => {
+                            for flag in flags.chars() {
+                                match flag {
+                                    'r' | 'R' => options.recursive = true,
+                                    'f' => options.force = true,
+                                    _ => return Err(anyhow!(trf("rm: unknown option -{}", &[&flag]))),
+                                }
+                            }
+                        }
+                        _ => operands.push(*arg),
+                    }
+                }
+
+                if operands.is_empty() {
+                    Err(anyhow!(tr("rm command requires an argument")))
                 } else {
-                    Ok(Command::Rm(split_value[1..].join(" ")))
+                    Ok(Command::Rm(paths(&operands), options))
                 }
             }
             "cat" => {
-                if split_value.len() < 2 {
-                    Err(anyhow!("cat command requires an argument"))
-                } else {
-                    Ok(Command::Cat(split_value[1..].join(" ")))
+                let mut options = CatOptions::default();
+                let mut operands = Vec::new();
+                for arg in &split_value[1..] {
+                    match arg.strip_prefix('-') {
+                        Some(flags) if !flags.is_empty() => {
+                            for flag in flags.chars() {
+                                match flag {
+                                    'A' => {
+                                        options.show_nonprinting = true;
+                                        options.show_tabs = true;
+                                        options.show_ends = true;
+                                    }
+                                    'v' => options.show_nonprinting = true,
+                                    'T' => options.show_tabs = true,
+                                    'E' => options.show_ends = true,
+                                    's' => options.squeeze_blank = true,
+                                    'H' => options.headers = true,
+                                    _ => return Err(anyhow!(trf("cat: unknown option -{}", &[&flag]))),
+                                }
+                            }
+                        }
+                        _ => operands.push(*arg),
+                    }
                 }
+
+                // Without a file, cat reads standard input
+                if operands.is_empty() {
+                    operands.push("-");
+                }
+                Ok(Command::Cat(paths(&operands), options))
             }
             "mkdir" => {
                 if split_value.len() < 2 {
-                    Err(anyhow!("mkdir command requires an argument"))
+                    Err(anyhow!(tr("mkdir command requires an argument")))
                 } else if split_value.len() > 2 && split_value[1] == "-p" {
-                    Ok(Command::MkdirP(split_value[2..].join(" ")))
+                    Ok(Command::MkdirP(paths(&split_value[2..])))
                 } else {
-                    Ok(Command::Mkdir(split_value[1..].join(" ")))
+                    Ok(Command::Mkdir(paths(&split_value[1..])))
+                }
+            }
+            "mkfifo" => {
+                let (mode, names) = match split_value[1..] {
+                    ["-m", mode, ref names @ ..] => match u32::from_str_radix(mode, 8) {
+                        Ok(mode) if mode <= 0o7777 => (mode, names),
+                        _ => return Err(anyhow!(trf("mkfifo: invalid mode '{}'", &[&mode]))),
+                    },
+                    ref names => (crate::fifo::DEFAULT_MODE, names),
+                };
+                if names.is_empty() {
+                    Err(anyhow!(tr("mkfifo requires a name")))
+                } else {
+                    Ok(Command::Mkfifo(paths(names), mode))
                 }
             }
             "rmdir" => {
                 if split_value.len() < 2 {
-                    Err(anyhow!("rmdir command requires an argument"))
+                    Err(anyhow!(tr("rmdir command requires an argument")))
                 } else if split_value.len() > 2 && split_value[1] == "-r" {
-                    Ok(Command::RmdirR(split_value[2..].join(" ")))
+                    Ok(Command::Rm(paths(&split_value[2..]), RemoveOptions { recursive: true, ..Default::default() }))
                 } else {
-                    Ok(Command::Rmdir(split_value[1..].join(" ")))
+                    Ok(Command::Rmdir(paths(&split_value[1..])))
                 }
             }
             "cp" => {
-                if split_value.len() < 3 {
-                    Err(anyhow!("cp command requires source and destination arguments"))
-                } else if split_value.len() > 3 && split_value[1] == "-r" {
-                    Ok(Command::CpR(split_value[2].to_string(), split_value[3].to_string()))
+                let mut recursive = false;
+                let mut options = CopyOptions::default();
+                let mut operands = Vec::new();
+                for arg in &split_value[1..] {
+                    match *arg {
+                        "-r" | "-R" => recursive = true,
+                        "--one-file-system" => options.one_file_system = true,
+                        _ => operands.push(arg.to_string()),
+                    }
+                }
+
+                match <[String; 2]>::try_from(operands) {
+                    Ok([src, dest]) if recursive => Ok(Command::CpR(src, dest, options)),
+                    Ok([src, dest]) => Ok(Command::Cp(src, dest)),
+                    Err(_) => Err(anyhow!(tr("cp command requires source and destination arguments"))),
+                }
+            }
+            "mv" | "gmv" => {
+                let options = MoveOptions { git: split_value[0] == "gmv" || split_value[1..].contains(&"--git") };
+                match split_value[1..].iter().filter(|arg| **arg != "--git").collect::<Vec<_>>()[..] {
+                    [source, destination, ..] => Ok(Command::Mv(source.to_string(), destination.to_string(), options)),
+                    _ => Err(anyhow!(tr("mv command requires source and destination arguments"))),
+                }
+            }
+            "stat" => {
+                if split_value.len() < 2 {
+                    Err(anyhow!(tr("stat command requires a file path")))
                 } else {
-                    Ok(Command::Cp(split_value[1].to_string(), split_value[2].to_string()))
+                    Ok(Command::Stat(paths(&split_value[1..])))
                 }
             }
-            "mv" => {
-                if split_value.len() < 3 {
-                    Err(anyhow!("mv command requires source and destination arguments"))
+            "find" | "grep" => {
+                let mut options = SearchOptions::default();
+                let mut filters = FindFilters::default();
+                let mut operands = Vec::new();
+                let mut args = split_value[1..].iter();
+                while let Some(arg) = args.next() {
+                    let is_find = split_value[0] == "find";
+                    match arg.strip_prefix('-') {
+                        Some("-max-results") => match args.next().map(|count| count.parse()) {
+                            Some(Ok(count)) if count > 0 => options.max_results = Some(count),
+                            _ => return Err(anyhow!(tr("--max-results expects a positive number"))),
+                        },
+                        Some(option @ ("-larger-than" | "-smaller-than")) if is_find => {
+                            let size = args.next().and_then(|size| parse_size(size));
+                            let Some(size) = size else {
+                                return Err(anyhow!(trf("-{} expects a size such as 500K, 20M or 1G", &[&option])));
+                            };
+                            if option == "-larger-than" {
+                                filters.larger_than = Some(size);
+                            } else {
+                                filters.smaller_than = Some(size);
+                            }
+                        }
+                        Some("-empty") if is_find => filters.empty = true,
+                        Some("-delete") if is_find => filters.delete = true,
+                        Some("-yes") if is_find => filters.yes = true,
+                        Some("-one-file-system") if is_find => filters.one_file_system = true,
+                        Some(option) if option.starts_with('-') => {
+                            return Err(anyhow!(trf("{}: unknown option -{}", &[&split_value[0], &option])));
+                        }
+                        Some(flags) if !flags.is_empty() => {
+                            for flag in flags.chars() {
+                                if !options.parse_flag(flag) {
+                                    return Err(anyhow!(trf("{}: unknown option -{}", &[&split_value[0], &flag])));
+                                }
+                            }
+                        }
+                        _ => operands.push(arg.to_string()),
+                    }
+                }
+
+                // find without a pattern lists everything that passes its filters
+                if split_value[0] == "find" && operands.len() == 1 {
+                    operands.push(String::new());
+                }
+                match (split_value[0], <[String; 2]>::try_from(operands)) {
+                    ("find", Ok([dir, pattern])) => Ok(Command::Find(dir, pattern, options, filters)),
+                    ("find", Err(_)) => Err(anyhow!(tr("find command requires a directory and an optional pattern"))),
+                    (_, Ok([file, pattern])) => Ok(Command::Grep(file, pattern, options)),
+                    // Without a file, grep reads standard input
+                    (_, Err(operands)) if operands.len() == 1 => Ok(Command::Grep("-".to_string(), operands[0].clone(), options)),
+                    (_, Err(_)) => Err(anyhow!(tr("grep command requires file and pattern arguments"))),
+                }
+            }
+            "ln" => {
+                let mut options = LinkOptions::default();
+                let mut operands = Vec::new();
+                for arg in &split_value[1..] {
+                    match arg.strip_prefix('-') {
+                        Some(flags) if !flags.is_empty() => {
+                            for flag in flags.chars() {
+                                match flag {
+                                    's' => options.symbolic = true,
+                                    'f' => options.force = true,
+                                    'r' => options.relative = true,
+                                    _ => return Err(anyhow!(trf("ln: unknown option -{}", &[&flag]))),
+                                }
+                            }
+                        }
+                        _ => operands.push(arg.to_string()),
+                    }
+                }
+
+                if options.relative && !options.symbolic {
+                    Err(anyhow!(tr("ln -r only makes sense with -s")))
+                } else if operands.len() != 2 {
+                    Err(anyhow!(tr("ln command requires target and link name arguments")))
                 } else {
-                    Ok(Command::Mv(split_value[1].to_string(), split_value[2].to_string()))
+                    let link_name = operands.pop().unwrap();
+                    let target = operands.pop().unwrap();
+                    Ok(Command::Ln(target, link_name, options))
                 }
             }
-            "stat" => {
+            "dedupe" => {
+                let mut options = DedupeOptions::default();
+                let mut operands = Vec::new();
+                let mut args = split_value[1..].iter();
+                while let Some(arg) = args.next() {
+                    match *arg {
+                        "--auto" => options.auto = true,
+                        "--link" => options.link = true,
+                        "--keep" => {
+                            options.keep = match args.next().copied() {
+                                Some("newest") => Keep::Newest,
+                                Some("oldest") => Keep::Oldest,
+                                _ => return Err(anyhow!(tr("--keep expects newest or oldest"))),
+                            }
+                        }
+                        option if option.starts_with('-') => {
+                            return Err(anyhow!(trf("dedupe: unknown option {}", &[&option])));
+                        }
+                        _ => operands.push(arg.to_string()),
+                    }
+                }
+
+                match <[String; 1]>::try_from(operands) {
+                    Ok([dir]) => Ok(Command::Dedupe(dir, options)),
+                    Err(_) => Err(anyhow!(tr("dedupe requires a directory"))),
+                }
+            }
+            "split" => {
+                let mut chunk = None;
+                let mut operands = Vec::new();
+                let mut args = split_value[1..].iter();
+                while let Some(arg) = args.next() {
+                    match *arg {
+                        "-b" => match args.next().and_then(|size| parse_size(size)) {
+                            Some(size) if size > 0 => chunk = Some(size),
+                            _ => return Err(anyhow!(tr("split -b expects a size such as 500K, 20M or 1G"))),
+                        },
+                        option if option.starts_with('-') && option.len() > 1 => {
+                            return Err(anyhow!(trf("split: unknown option {}", &[&option])));
+                        }
+                        _ => operands.push(*arg),
+                    }
+                }
+
+                let Some(chunk) = chunk else {
+                    return Err(anyhow!(tr("split requires a piece size, e.g. split -b 10M <file> [prefix]")));
+                };
+                match operands.as_slice() {
+                    [file] => Ok(Command::Split(file.to_string(), format!("{}.", file), chunk)),
+                    [file, prefix] => Ok(Command::Split(file.to_string(), prefix.to_string(), chunk)),
+                    _ => Err(anyhow!(tr("split requires a file and an optional prefix"))),
+                }
+            }
+            "merge" => match split_value[1..].split_last() {
+                Some((output, pieces)) if !pieces.is_empty() => Ok(Command::Merge(paths(pieces), output.to_string())),
+                _ => Err(anyhow!(tr("merge requires the pieces and an output file"))),
+            },
+            "truncate" => {
+                let mut size = None;
+                let mut no_create = false;
+                let mut operands = Vec::new();
+                let mut args = split_value[1..].iter();
+                while let Some(arg) = args.next() {
+                    match *arg {
+                        "-s" => {
+                            let value = args.next().copied().unwrap_or_default();
+                            let change = match (value.strip_prefix('+'), value.strip_prefix('-')) {
+                                (Some(grow), _) => parse_size(grow).map(SizeChange::Grow),
+                                (_, Some(shrink)) => parse_size(shrink).map(SizeChange::Shrink),
+                                _ => parse_size(value).map(SizeChange::Set),
+                            };
+                            let Some(change) = change else {
+                                return Err(anyhow!(tr("truncate -s expects a size such as 0, 1G or +10M")));
+                            };
+                            size = Some(change);
+                        }
+                        "-c" => no_create = true,
+                        option if option.starts_with('-') && option.len() > 1 => {
+                            return Err(anyhow!(trf("truncate: unknown option {}", &[&option])));
+                        }
+                        _ => operands.push(*arg),
+                    }
+                }
+
+                match size {
+                    None => Err(anyhow!(tr("truncate requires a size, e.g. truncate -s 1G <file>"))),
+                    Some(_) if operands.is_empty() => Err(anyhow!(tr("truncate requires a file"))),
+                    Some(size) => Ok(Command::Truncate(paths(&operands), TruncateOptions { size, no_create })),
+                }
+            }
+            "du" => {
+                let mut options = DiskUsageOptions::default();
+                let mut operands = Vec::new();
+                for arg in &split_value[1..] {
+                    match *arg {
+                        "-s" | "--summarize" => options.summarize = true,
+                        "--one-file-system" => options.one_file_system = true,
+                        option if option.starts_with('-') && option.len() > 1 => {
+                            return Err(anyhow!(trf("du: unknown option {}", &[&option])));
+                        }
+                        _ => operands.push(*arg),
+                    }
+                }
+
+                if operands.is_empty() {
+                    operands.push(".");
+                }
+                Ok(Command::Du(paths(&operands), options))
+            }
+            "usage" => match &split_value[1..] {
+                [] => Ok(Command::Usage(".".to_string())),
+                [option] if option.starts_with('-') && option.len() > 1 => Err(anyhow!(trf("usage: unknown option {}", &[option]))),
+                [dir] => Ok(Command::Usage(dir.to_string())),
+                _ => Err(anyhow!(tr("usage takes one directory"))),
+            },
+            "rename" => {
+                let dry_run = split_value[1..].iter().any(|arg| matches!(*arg, "-n" | "--dry-run"));
+                let operands: Vec<&str> = split_value[1..].iter().copied().filter(|arg| !matches!(*arg, "-n" | "--dry-run")).collect();
+                match operands.split_first() {
+                    Some((expression, files)) if !files.is_empty() => Ok(Command::Rename(expression.to_string(), paths(files), dry_run)),
+                    _ => Err(anyhow!(tr("rename requires an s/old/new/ expression and files"))),
+                }
+            }
+            "hosts" => Ok(Command::Hosts),
+            "ssh" | "scp" => {
                 if split_value.len() < 2 {
-                    Err(anyhow!("stat command requires a file path"))
+                    Err(anyhow!(trf("{} requires a host", &[&split_value[0]])))
                 } else {
-                    Ok(Command::Stat(split_value[1..].join(" ")))
+                    Ok(Command::Remote(split_value[0].to_string(), paths(&split_value[1..])))
+                }
+            }
+            "push" | "pull" => {
+                let mut options = TransferOptions::default();
+                let mut operands = Vec::new();
+                for arg in &split_value[1..] {
+                    match *arg {
+                        "-c" | "--continue" => options.resume = true,
+                        option if option.starts_with('-') && option.len() > 1 => {
+                            return Err(anyhow!(trf("{}: unknown option {}", &[&split_value[0], &option])));
+                        }
+                        _ => operands.push(arg.to_string()),
+                    }
+                }
+
+                match (split_value[0], <[String; 2]>::try_from(operands)) {
+                    ("push", Ok([local, remote])) => Ok(Command::Push(local, remote, options)),
+                    ("pull", Ok([remote, local])) => Ok(Command::Pull(remote, local, options)),
+                    ("push", _) => Err(anyhow!(tr("push requires a local file and a host:path"))),
+                    _ => Err(anyhow!(tr("pull requires a host:path and a local path"))),
+                }
+            }
+            "serve" => {
+                let mut port = crate::serve::DEFAULT_PORT;
+                let mut operands = Vec::new();
+                let mut args = split_value[1..].iter();
+                while let Some(arg) = args.next() {
+                    match *arg {
+                        "-p" | "--port" => {
+                            port = match args.next().map(|value| value.parse()) {
+                                Some(Ok(value)) => value,
+                                _ => return Err(anyhow!(tr("serve --port expects a port number"))),
+                            }
+                        }
+                        option if option.starts_with('-') && option.len() > 1 => {
+                            return Err(anyhow!(trf("serve: unknown option {}", &[&option])));
+                        }
+                        _ => operands.push(*arg),
+                    }
+                }
+
+                match operands.as_slice() {
+                    [] => Ok(Command::Serve(".".to_string(), port)),
+                    [dir] => Ok(Command::Serve(dir.to_string(), port)),
+                    _ => Err(anyhow!(tr("serve takes a single directory"))),
+                }
+            }
+            "jobs" => Ok(Command::Jobs),
+            "kill" if split_value[1..] == ["-l"] => Ok(Command::KillList),
+            "kill" => {
+                let (signal, targets) = parse_signal(&split_value[1..], "kill")?;
+                if targets.is_empty() {
+                    return Err(anyhow!(tr("kill requires a job (%1) or process id")));
+                }
+                for target in targets {
+                    let number = target.strip_prefix('%').unwrap_or(target);
+                    if number.parse::<u32>().is_err() {
+                        return Err(anyhow!(trf("kill: '{}' is not a job (%1) or process id", &[&target])));
+                    }
+                }
+                Ok(Command::Kill(signal, paths(targets)))
+            }
+            "nohup" => match split_value[1..] {
+                [ref words @ .., "&"] if !words.is_empty() => Ok(Command::Nohup(words.iter().map(|word| quote(word)).collect::<Vec<_>>().join(" "))),
+                [] | ["&"] => Err(anyhow!(tr("nohup requires a command"))),
+                _ => Err(anyhow!(tr("nohup: end the line with & to run the command in the background"))),
+            },
+            "disown" => {
+                let targets = &split_value[1..];
+                if targets.is_empty() {
+                    return Err(anyhow!(tr("disown requires a job (%1)")));
+                }
+                let mut ids = Vec::new();
+                for target in targets {
+                    match target.strip_prefix('%').unwrap_or(target).parse() {
+                        Ok(id) => ids.push(id),
+                        Err(_) => return Err(anyhow!(trf("disown: '{}' is not a job (%1)", &[&target]))),
+                    }
+                }
+                Ok(Command::Disown(ids))
+            }
+            "fg" | "bg" => {
+                let job = match split_value[1..] {
+                    [] => None,
+                    [target] => match target.strip_prefix('%').unwrap_or(target).parse() {
+                        Ok(id) => Some(id),
+                        Err(_) => return Err(anyhow!(trf("{}: '{}' is not a job (%1)", &[&split_value[0], &target]))),
+                    },
+                    _ => return Err(anyhow!(trf("{} takes at most one job (%1)", &[&split_value[0]]))),
+                };
+                Ok(if split_value[0] == "fg" { Command::Fg(job) } else { Command::Bg(job) })
+            }
+            "pstree" => match split_value[1..] {
+                [] => Ok(Command::Pstree(None)),
+                [pid] => pid.parse().map(|pid| Command::Pstree(Some(pid))).map_err(|_| anyhow!(trf("pstree: '{}' is not a process id", &[&pid]))),
+                _ => Err(anyhow!(tr("pstree takes at most one process id"))),
+            },
+            "monitor" => {
+                let (mut interval, mut sort) = (monitor::DEFAULT_INTERVAL, Column::Cpu);
+                let mut args = split_value[1..].iter();
+                while let Some(arg) = args.next() {
+                    match *arg {
+                        "-n" | "--interval" => {
+                            let seconds = args.next().and_then(|seconds| seconds.parse::<f64>().ok()).filter(|seconds| (0.5..=3600.0).contains(seconds));
+                            match seconds {
+                                Some(seconds) => interval = std::time::Duration::from_secs_f64(seconds),
+                                None => return Err(anyhow!(tr("monitor -n expects seconds between 0.5 and 3600"))),
+                            }
+                        }
+                        "-s" | "--sort" => match args.next().and_then(|column| Column::parse(column)) {
+                            Some(column) => sort = column,
+                            None => return Err(anyhow!(tr("monitor --sort expects cpu, mem, pid or name"))),
+                        },
+                        option => return Err(anyhow!(trf("monitor: unknown option {}", &[&option]))),
+                    }
+                }
+                Ok(Command::Monitor(interval, sort))
+            }
+            "new" => match split_value[1..] {
+                [] | ["-l" | "--list"] => Ok(Command::NewList),
+                [template, name] if !template.starts_with('-') => Ok(Command::New(template.to_string(), name.to_string())),
+                _ => Err(anyhow!(tr("new requires a template and a name, e.g. new python lab1"))),
+            },
+            "task" => match split_value[1..] {
+                [] | ["-l" | "--list"] => Ok(Command::Task(None)),
+                [option] if option.starts_with('-') => Err(anyhow!(trf("task: unknown option {}", &[&option]))),
+                [name] => Ok(Command::Task(Some(name.to_string()))),
+                _ => Err(anyhow!(tr("task takes one task name"))),
+            },
+            "gst" => match split_value[1..] {
+                [] => Ok(Command::GitStatus),
+                _ => Err(anyhow!(tr("gst takes no arguments"))),
+            },
+            "glog" => {
+                let (mut count, mut path) = (DEFAULT_LOG_LENGTH, None);
+                let mut args = split_value[1..].iter();
+                while let Some(arg) = args.next() {
+                    // -n 5, -n5 and -5 all work, as in git log
+                    let number = match *arg {
+                        "-n" => args.next().copied(),
+                        option if option.starts_with("-n") => option.get(2..),
+                        option if option.starts_with('-') && option.len() > 1 => option.get(1..),
+                        _ => {
+                            path = Some(arg.to_string());
+                            continue;
+                        }
+                    };
+                    match number.and_then(|number| number.parse().ok()).filter(|count| *count > 0) {
+                        Some(number) => count = number,
+                        None => return Err(anyhow!(trf("glog: unknown option {}", &[&arg]))),
+                    }
+                }
+                Ok(Command::GitLog(count, path))
+            }
+            "gdiff" => {
+                let staged = split_value[1..].iter().any(|arg| matches!(*arg, "--staged" | "--cached"));
+                match split_value[1..].iter().filter(|arg| !matches!(**arg, "--staged" | "--cached")).collect::<Vec<_>>()[..] {
+                    [] => Ok(Command::GitDiff(staged, None)),
+                    [option] if option.starts_with('-') && option.len() > 1 => Err(anyhow!(trf("gdiff: unknown option {}", &[&option]))),
+                    [path] => Ok(Command::GitDiff(staged, Some(path.to_string()))),
+                    _ => Err(anyhow!(tr("gdiff takes at most one path"))),
+                }
+            }
+            "killall" => match parse_signal(&split_value[1..], "killall")? {
+                (signal, [name]) => Ok(Command::Killall(signal, name.to_string())),
+                _ => Err(anyhow!(tr("killall requires a process name"))),
+            },
+            "ports" => match split_value.get(1..) {
+                Some([]) | None => Ok(Command::Ports(None)),
+                Some(["--kill", port]) => match port.parse() {
+                    Ok(port) => Ok(Command::Ports(Some(port))),
+                    Err(_) => Err(anyhow!(trf("ports: '{}' is not a port number", &[&port]))),
+                },
+                _ => Err(anyhow!(tr("ports takes no arguments, or --kill <port>"))),
+            },
+            "resolve" => {
+                let mut options = ResolveOptions::default();
+                let mut names = Vec::new();
+                let mut args = split_value[1..].iter();
+                while let Some(arg) = args.next() {
+                    match *arg {
+                        "--server" | "-s" => match args.next() {
+                            Some(server) => options.server = Some(server.to_string()),
+                            None => return Err(anyhow!(tr("resolve --server expects an address"))),
+                        },
+                        "-t" | "--type" => {
+                            let kind = args.next().map(|kind| kind.to_uppercase()).unwrap_or_default();
+                            if !crate::dns::RECORD_TYPES.contains(&kind.as_str()) {
+                                return Err(anyhow!(trf("resolve -t expects one of {}", &[&crate::dns::RECORD_TYPES.join(", ")])));
+                            }
+                            options.kind = Some(kind);
+                        }
+                        option if option.starts_with('-') && option.len() > 1 => {
+                            return Err(anyhow!(trf("resolve: unknown option {}", &[&option])));
+                        }
+                        _ => names.push(*arg),
+                    }
+                }
+
+                match names.as_slice() {
+                    [name] => Ok(Command::Resolve(name.to_string(), options)),
+                    _ => Err(anyhow!(tr("resolve requires a single name to look up"))),
+                }
+            }
+            "download" => {
+                let mut options = DownloadOptions::default();
+                let mut urls = Vec::new();
+                let mut args = split_value[1..].iter();
+                while let Some(arg) = args.next() {
+                    match *arg {
+                        "-o" | "--output" => match args.next() {
+                            Some(output) => options.output = Some(output.to_string()),
+                            None => return Err(anyhow!(tr("download -o expects a file name"))),
+                        },
+                        "--sha256" => match args.next() {
+                            Some(hash) if hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()) => {
+                                options.sha256 = Some(hash.to_lowercase())
+                            }
+                            _ => return Err(anyhow!(tr("download --sha256 expects 64 hex digits"))),
+                        },
+                        "-b" | "--background" => options.background = true,
+                        option if option.starts_with('-') && option.len() > 1 => {
+                            return Err(anyhow!(trf("download: unknown option {}", &[&option])));
+                        }
+                        _ => urls.push(*arg),
+                    }
+                }
+
+                match urls.as_slice() {
+                    [url] => Ok(Command::Download(url.to_string(), options)),
+                    _ => Err(anyhow!(tr("download requires a single URL"))),
+                }
+            }
+            "printenv" => Ok(Command::Printenv(split_value[1..].iter().map(|name| name.to_string()).collect())),
+            "hash" => match split_value[1..] {
+                [] => Ok(Command::Hash(HashAction::List)),
+                ["-r"] => Ok(Command::Hash(HashAction::Clear)),
+                ["-d", ref names @ ..] if !names.is_empty() => Ok(Command::Hash(HashAction::Forget(paths(names)))),
+                ref names if !names.iter().any(|name| name.starts_with('-')) => Ok(Command::Hash(HashAction::Add(paths(names)))),
+                _ => Err(anyhow!(tr("usage: hash [-r] [-d name...] [name...]"))),
+            },
+            "rehash" => match split_value[1..] {
+                [] => Ok(Command::Rehash),
+                _ => Err(anyhow!(tr("usage: rehash"))),
+            },
+            "reload" => match split_value[1..] {
+                [] => Ok(Command::Reload),
+                _ => Err(anyhow!(tr("usage: reload"))),
+            },
+            "doctor" => match split_value[1..] {
+                [] => Ok(Command::Doctor),
+                _ => Err(anyhow!(tr("usage: doctor"))),
+            },
+            "which" => match split_value[1..] {
+                [] => Err(anyhow!(tr("usage: which <name>..."))),
+                ref names => Ok(Command::Which(paths(names))),
+            },
+            "env" => {
+                let mut options = EnvOptions::default();
+                let mut args = split_value[1..].iter();
+                while let Some(arg) = args.next() {
+                    match *arg {
+                        "--diff" => options.diff = true,
+                        "--sorted" | "-s" => options.sorted = true,
+                        "--grep" | "-g" => match args.next() {
+                            Some(pattern) => options.grep = Some(pattern.to_string()),
+                            None => return Err(anyhow!(tr("env --grep expects a pattern"))),
+                        },
+                        option => return Err(anyhow!(trf("env: unknown option {}", &[&option]))),
+                    }
+                }
+                Ok(Command::Env(options))
+            }
+            "export" => {
+                if split_value.len() < 2 {
+                    return Err(anyhow!(tr("export requires NAME=value")));
+                }
+                let mut pairs = Vec::new();
+                for arg in &split_value[1..] {
+                    match arg.split_once('=') {
+                        Some((name, value)) if environment::is_name(name) => pairs.push((name.to_string(), value.to_string())),
+                        _ => return Err(anyhow!(trf("export: '{}' is not NAME=value", &[arg]))),
+                    }
+                }
+                Ok(Command::Export(pairs))
+            }
+            "unset" => {
+                if split_value.len() < 2 {
+                    return Err(anyhow!(tr("unset requires a variable name")));
+                }
+                Ok(Command::Unset(split_value[1..].iter().map(|name| name.to_string()).collect()))
+            }
+            "abbr" => match split_value[1..] {
+                [] | ["-l" | "--list"] => Ok(Command::Abbr(AbbrAction::List)),
+                ["-e" | "--erase"] => Err(anyhow!(tr("abbr --erase requires a name"))),
+                ["-e" | "--erase", ref names @ ..] => Ok(Command::Abbr(AbbrAction::Erase(names.iter().map(|name| name.to_string()).collect()))),
+                ["-a" | "--add", name, ref expansion @ ..] | [name, ref expansion @ ..] if !expansion.is_empty() => {
+                    if name.starts_with('-') || name.contains(char::is_whitespace) {
+                        return Err(anyhow!(trf("abbr: '{}' can't be an abbreviation", &[&name])));
+                    }
+                    Ok(Command::Abbr(AbbrAction::Add(name.to_string(), expansion.join(" "))))
+                }
+                _ => Err(anyhow!(tr("abbr requires a name and what it expands to"))),
+            },
+            "ulimit" => {
+                let resource = |flag: &str| {
+                    let mut chars = flag.chars();
+                    match (chars.next(), chars.next(), chars.next()) {
+                        (Some('-'), Some(letter), None) => Resource::from_flag(letter),
+                        _ => None,
+                    }
+                    .ok_or_else(|| anyhow!(trf("ulimit: unknown option {}", &[&flag])))
+                };
+                match split_value[1..] {
+                    [] | ["-a"] => Ok(Command::Ulimit(UlimitAction::All)),
+                    [flag] => Ok(Command::Ulimit(UlimitAction::Show(resource(flag)?))),
+                    [flag, value] => {
+                        let resource = resource(flag)?;
+                        let limit = parse_limit(resource, value).ok_or_else(|| anyhow!(trf("ulimit: bad limit '{}'", &[&value])))?;
+                        Ok(Command::Ulimit(UlimitAction::Set(resource, limit)))
+                    }
+                    _ => Err(anyhow!(tr("ulimit takes one option and an optional limit"))),
                 }
             }
-            "find" => {
-                if split_value.len() < 3 {
-                    Err(anyhow!("find command requires directory and pattern arguments"))
+            "limit" => {
+                let mut limits = Vec::new();
+                let mut rest = &split_value[1..];
+                while let [option, value, more @ ..] = rest {
+                    let resource = match *option {
+                        "--mem" => Resource::VirtualMemory,
+                        "--cpu" => Resource::CpuTime,
+                        "--files" => Resource::OpenFiles,
+                        "--procs" => Resource::Processes,
+                        option if option.starts_with("--") => return Err(anyhow!(trf("limit: unknown option {}", &[&option]))),
+                        _ => break,
+                    };
+                    let limit = parse_limit(resource, value).ok_or_else(|| anyhow!(trf("limit: bad limit '{}'", &[value])))?;
+                    limits.push((resource, limit));
+                    rest = more;
+                }
+
+                if limits.is_empty() || rest.is_empty() {
+                    Err(anyhow!(tr("limit requires at least one limit and a command")))
                 } else {
-                    Ok(Command::Find(split_value[1].to_string(), split_value[2].to_string()))
+                    Ok(Command::Limit(limits, rest.iter().map(|word| quote(word)).collect::<Vec<_>>().join(" ")))
                 }
             }
-            "grep" => {
-                if split_value.len() < 3 {
-                    Err(anyhow!("grep command requires file and pattern arguments"))
+            "nice" => {
+                let (increment, rest) = match split_value[1..] {
+                    ["-n", increment, ..] => match increment.parse() {
+                        Ok(increment) => (increment, &split_value[3..]),
+                        Err(_) => return Err(anyhow!(trf("nice: bad increment '{}'", &[&increment]))),
+                    },
+                    _ => (DEFAULT_NICENESS, &split_value[1..]),
+                };
+                if rest.is_empty() {
+                    Err(anyhow!(tr("nice requires a command")))
                 } else {
-                    Ok(Command::Grep(split_value[1].to_string(), split_value[2].to_string()))
+                    Ok(Command::Nice(increment, rest.iter().map(|word| quote(word)).collect::<Vec<_>>().join(" ")))
                 }
             }
-            "ln" => {
-                if split_value.len() < 3 {
-                    Err(anyhow!("ln command requires target and link name arguments"))
+            "renice" => {
+                let rest = match split_value.get(1) {
+                    Some(&"-n") => &split_value[2..],
+                    _ => &split_value[1..],
+                };
+                let [priority, targets @ ..] = rest else {
+                    return Err(anyhow!(tr("renice requires a priority and a job (%1) or process id")));
+                };
+                let priority = priority.parse().map_err(|_| anyhow!(trf("renice: bad priority '{}'", &[priority])))?;
+                if targets.is_empty() {
+                    return Err(anyhow!(tr("renice requires a priority and a job (%1) or process id")));
+                }
+                for target in targets {
+                    if target.strip_prefix('%').unwrap_or(target).parse::<u32>().is_err() {
+                        return Err(anyhow!(trf("renice: '{}' is not a job (%1) or process id", &[target])));
+                    }
+                }
+                Ok(Command::Renice(priority, paths(targets)))
+            }
+            "stats" => Ok(Command::Stats),
+            "history" => match split_value[1..] {
+                [] => Ok(Command::History),
+                ["scrub", pattern] => Ok(Command::HistoryScrub(pattern.to_string())),
+                ["scrub", ..] => Err(anyhow!(tr("history scrub takes one pattern"))),
+                _ => Err(anyhow!(tr("usage: history, or history scrub <pattern>"))),
+            },
+            "fc" => match split_value[1..] {
+                [] => Ok(Command::Fc(None)),
+                [number] => match number.parse() {
+                    Ok(number) if number != 0 => Ok(Command::Fc(Some(number))),
+                    _ => Err(anyhow!(trf("fc: '{}' is not a history number", &[&number]))),
+                },
+                _ => Err(anyhow!(tr("usage: fc [number]"))),
+            },
+            "choose" => {
+                let (variable, options) = match split_value[1..] {
+                    ["-v", name, ref options @ ..] if environment::is_name(name) => (name.to_string(), options),
+                    ["-v", ..] => return Err(anyhow!(tr("choose -v expects a variable name"))),
+                    ref options => (DEFAULT_CHOICE_VARIABLE.to_string(), options),
+                };
+                if options.is_empty() {
+                    Err(anyhow!(tr("choose requires at least one option")))
+                } else {
+                    Ok(Command::Choose(paths(options), variable))
+                }
+            }
+            "test" => Ok(Command::Test(paths(&split_value[1..]))),
+            "[" => match split_value[1..].split_last() {
+                Some((&"]", expression)) => Ok(Command::Test(paths(expression))),
+                _ => Err(anyhow!(tr("[: missing ']'"))),
+            },
+            "spinner" => match split_value[1..] {
+                ["start", ref message @ ..] if !message.is_empty() => Ok(Command::SpinnerStart(message.join(" "))),
+                ["stop"] => Ok(Command::SpinnerStop),
+                _ => Err(anyhow!(tr("usage: spinner start <message>, or spinner stop"))),
+            },
+            "progress" => match split_value[1..] {
+                [current, total, ref label @ ..] => {
+                    let number = |text: &str| text.parse::<u64>().map_err(|_| anyhow!(trf("progress: '{}' is not a count", &[&text])));
+                    let (current, total) = (number(current)?, number(total)?);
+                    if total == 0 {
+                        return Err(anyhow!(tr("progress: the total must be above zero")));
+                    }
+                    Ok(Command::Progress(current, total, (!label.is_empty()).then(|| label.join(" "))))
+                }
+                _ => Err(anyhow!(tr("usage: progress <current> <total> [label]"))),
+            },
+            "secret" => match split_value[1..] {
+                ["get", name] => Ok(Command::SecretGet(name.to_string())),
+                ["set", name] => Ok(Command::SecretSet(name.to_string())),
+                _ => Err(anyhow!(tr("usage: secret get <name>, or secret set <name>"))),
+            },
+            "undo" => {
+                if split_value.len() < 2 {
+                    Ok(Command::Undo(1))
+                } else {
+                    match split_value[1].parse() {
+                        Ok(count) if count > 0 => Ok(Command::Undo(count)),
+                        _ => Err(anyhow!(tr("undo expects a positive number of operations"))),
+                    }
+                }
+            }
+            "tutorial" => {
+                let action = split_value.get(1).copied().unwrap_or("start");
+                match action {
+                    "start" | "stop" | "reset" | "status" => Ok(Command::Tutorial(action.to_string())),
+                    _ => Err(anyhow!(tr("tutorial expects start, stop, reset or status"))),
+                }
+            }
+            "practice" => {
+                let action = split_value.get(1).copied().unwrap_or("task");
+                match action {
+                    "start" => match split_value.get(2) {
+                        Some(name) => Ok(Command::Practice(action.to_string(), Some(name.to_string()))),
+                        None => Err(anyhow!(tr("practice start requires a scenario name"))),
+                    },
+                    "list" | "task" | "check" => Ok(Command::Practice(action.to_string(), None)),
+                    _ => Err(anyhow!(tr("practice expects list, start <name>, task or check"))),
+                }
+            }
+            "bench" => {
+                let (runs, rest) = if split_value.len() > 2 && split_value[1] == "-n" {
+                    match split_value[2].parse() {
+                        Ok(runs) if runs > 0 => (runs, &split_value[3..]),
+                        _ => return Err(anyhow!(tr("bench -n expects a positive number of runs"))),
+                    }
                 } else {
-                    Ok(Command::Ln(split_value[1].to_string(), split_value[2].to_string()))
+                    (crate::bench::DEFAULT_RUNS, &split_value[1..])
+                };
+
+                if rest.is_empty() {
+                    Err(anyhow!(tr("bench requires a builtin command to measure")))
+                } else {
+                    // Re-quoted so the measured command parses to the same words
+                    Ok(Command::Bench(runs, rest.iter().map(|word| quote(word)).collect::<Vec<_>>().join(" ")))
+                }
+            }
+            _ => Err(anyhow!(tr("Unknown command"))),
+        }
+    }
+}
+
+// Commands joined by `|`, each reading as standard input what the one
+// before it printed. A line without a `|` is a pipeline of one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pipeline {
+    pub stages: Vec<String>,
+}
+
+impl TryFrom<&str> for Pipeline {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let stages: Vec<String> = split_pipes(value).into_iter().map(|stage| stage.trim().to_string()).collect();
+        // `a |`, `| b` and `a || b` leave a stage with no command
+        if stages.len() > 1 && stages.iter().any(String::is_empty) {
+            return Err(anyhow!(trf("syntax error near '{}': a command is missing", &[&"|"])));
+        }
+        Ok(Pipeline { stages })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stream {
+    Output,
+    Errors,
+}
+
+// Where `>` sends a command's output instead, `>>` adding to the end of the
+// file, and `2>` and `2>>` its errors
+#[derive(Clone, Debug, PartialEq)]
+pub struct Redirection {
+    pub stream: Stream,
+    pub path: String,
+    pub append: bool,
+}
+
+// A command line with its redirections taken out, which apply in order
+#[derive(Clone, Debug, PartialEq)]
+pub struct Redirected {
+    pub line: String,
+    pub redirections: Vec<Redirection>,
+}
+
+impl TryFrom<&str> for Redirected {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let words = redirection_words(value);
+        let mut line = value.to_string();
+        for word in words.iter().rev() {
+            line.replace_range(word.range.clone(), " ");
+        }
+        let mut redirections = Vec::new();
+        for word in &words {
+            let [path] = <[String; 1]>::try_from(tokenize(&word.target)?)
+                .map_err(|_| anyhow!(trf("syntax error near '{}': a file name is missing", &[&word.operator])))?;
+            let stream = if word.operator.starts_with('2') { Stream::Errors } else { Stream::Output };
+            redirections.push(Redirection { stream, path, append: word.operator.ends_with(">>") });
+        }
+        if let Some(word) = words.first().filter(|_| line.trim().is_empty()) {
+            return Err(anyhow!(trf("syntax error near '{}': a command is missing", &[&word.operator])));
+        }
+        Ok(Redirected { line: line.trim().to_string(), redirections })
+    }
+}
+
+// A byte count with an optional K, M, G or T suffix (powers of 1024), e.g. 1.5G
+fn parse_size(size: &str) -> Option<u64> {
+    let upper = size.to_uppercase();
+    let number = upper.trim_end_matches("IB").trim_end_matches('B');
+    let (number, unit) = match number.char_indices().last() {
+        Some((i, unit @ ('K' | 'M' | 'G' | 'T'))) => (&number[..i], unit),
+        _ => (number, ' '),
+    };
+    let exponent = match unit {
+        'K' => 1,
+        'M' => 2,
+        'G' => 3,
+        'T' => 4,
+        _ => 0,
+    };
+
+    let value: f64 = number.parse().ok()?;
+    (value >= 0.0).then(|| (value * 1024f64.powi(exponent)) as u64)
+}
+
+// A leading -s NAME, -NAME or -N picks the signal; SIGTERM otherwise
+fn parse_signal<'a, 'b>(args: &'a [&'b str], command: &str) -> anyhow::Result<(i32, &'a [&'b str])> {
+    let (signal, rest) = match args {
+        ["-s", signal, rest @ ..] => (*signal, rest),
+        [flag, rest @ ..] if flag.len() > 1 && flag.starts_with('-') => (&flag[1..], rest),
+        _ => return Ok((libc::SIGTERM, args)),
+    };
+    match signals::parse(signal) {
+        Some(signal) => Ok((signal, rest)),
+        None => Err(anyhow!(trf("{}: unknown signal {}", &[&command, &signal]))),
+    }
+}
+
+// A limit as typed: `unlimited`, a count or seconds, or for sizes kilobytes
+// (`512`) or a size with a suffix (`1G`)
+fn parse_limit(resource: Resource, text: &str) -> Option<Limit> {
+    if text == "unlimited" {
+        return Some(None);
+    }
+    if !resource.is_size() {
+        return text.parse().ok().map(Some);
+    }
+    match text.parse::<u64>() {
+        Ok(kilobytes) => kilobytes.checked_mul(1024).map(Some),
+        Err(_) => parse_size(text).map(Some),
+    }
+}
+
+fn paths(words: &[&str]) -> Vec<String> {
+    words.iter().map(|word| word.to_string()).collect()
+}
+
+// `touch -t` stamps: [[CC]YY]MMDDhhmm[.ss], in local time
+fn parse_stamp(stamp: &str) -> Option<DateTime<Local>> {
+    let (digits, seconds) = match stamp.split_once('.') {
+        Some((digits, seconds)) if seconds.len() == 2 => (digits, seconds),
+        Some(_) => return None,
+        None => (stamp, "00"),
+    };
+    if !digits.chars().chain(seconds.chars()).all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let year = Local::now().year().to_string();
+    let full = match digits.len() {
+        8 => format!("{}{}", year, digits),
+        // Two-digit years 69-99 are 19xx, as in POSIX
+        10 if &digits[..2] >= "69" => format!("19{}", digits),
+        10 => format!("20{}", digits),
+        12 => digits.to_string(),
+        _ => return None,
+    };
+    let naive = NaiveDateTime::parse_from_str(&format!("{}{}", full, seconds), "%Y%m%d%H%M%S").ok()?;
+    Local.from_local_datetime(&naive).earliest()
+}
+
+// `touch -d` dates: "now", "@<seconds>", "YYYY-MM-DD" or "YYYY-MM-DD HH:MM[:SS]"
+fn parse_date(date: &str) -> Option<DateTime<Local>> {
+    let date = date.trim();
+    if date == "now" {
+        return Some(Local::now());
+    }
+    if let Some(seconds) = date.strip_prefix('@') {
+        return Local.timestamp_opt(seconds.parse().ok()?, 0).single();
+    }
+
+    let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(date, format).ok())
+        .or_else(|| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0))?;
+    Local.from_local_datetime(&naive).earliest()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::CaseMode;
+    use proptest::prelude::*;
+
+    #[test]
+    fn quoted_paths_stay_whole() {
+        match Command::try_from("cp 'a b.txt' \"c d.txt\"").unwrap() {
+            Command::Cp(source, dest) => assert_eq!((source.as_str(), dest.as_str()), ("a b.txt", "c d.txt")),
+            other => panic!("parsed as {:?}", other),
+        }
+    }
+
+    #[test]
+    fn bench_keeps_quoting() {
+        match Command::try_from("bench -n 3 cat 'my notes.txt'").unwrap() {
+            Command::Bench(3, line) => assert!(matches!(Command::try_from(line.as_str()), Ok(Command::Cat(paths, _)) if paths == ["my notes.txt"])),
+            other => panic!("parsed as {:?}", other),
+        }
+    }
+
+    #[test]
+    fn abbr_joins_the_expansion() {
+        assert!(matches!(Command::try_from("abbr"), Ok(Command::Abbr(AbbrAction::List))));
+        match Command::try_from("abbr -a gco git 'checkout'").unwrap() {
+            Command::Abbr(AbbrAction::Add(name, expansion)) => assert_eq!((name.as_str(), expansion.as_str()), ("gco", "git checkout")),
+            other => panic!("parsed as {:?}", other),
+        }
+        assert!(Command::try_from("abbr gco").is_err() && Command::try_from("abbr -x y").is_err());
+    }
+
+    #[test]
+    fn ln_flags_combine() {
+        match Command::try_from("ln -sf target link").unwrap() {
+            Command::Ln(target, link, options) => {
+                assert_eq!((target.as_str(), link.as_str()), ("target", "link"));
+                assert_eq!(options, LinkOptions { symbolic: true, force: true, relative: false });
+            }
+            other => panic!("parsed as {:?}", other),
+        }
+        assert!(Command::try_from("ln -r target link").is_err());
+    }
+
+    #[test]
+    fn touch_times_parse() {
+        let expected = Local.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        for line in ["touch -d '2024-01-01 12:00' f", "touch -t 202401011200 f", "touch -t 2401011200.00 f"] {
+            match Command::try_from(line).unwrap() {
+                Command::Touch(paths, options) => {
+                    assert_eq!(paths, ["f"]);
+                    assert_eq!(options.time, Some(expected), "{}", line);
                 }
+                other => panic!("parsed as {:?}", other),
+            }
+        }
+        assert!(Command::try_from("touch -t 2024 f").is_err());
+    }
+
+    #[test]
+    fn rm_flags() {
+        match Command::try_from("rm -rf --no-preserve-root a b").unwrap() {
+            Command::Rm(paths, options) => {
+                assert_eq!(paths, ["a", "b"]);
+                assert_eq!(options, RemoveOptions { recursive: true, force: true, no_preserve_root: true, one_file_system: false, git: false });
+            }
+            other => panic!("parsed as {:?}", other),
+        }
+        assert!(matches!(Command::try_from("rmdir -r a"), Ok(Command::Rm(_, RemoveOptions { recursive: true, .. }))));
+        assert!(matches!(Command::try_from("grm -r a"), Ok(Command::Rm(_, RemoveOptions { recursive: true, git: true, .. }))));
+        assert!(matches!(Command::try_from("mv --git a b"), Ok(Command::Mv(_, _, MoveOptions { git: true }))));
+        assert!(Command::try_from("gmv a").is_err());
+    }
+
+    #[test]
+    fn echo_flags_stop_at_text() {
+        match Command::try_from("echo -ne -x a").unwrap() {
+            Command::Echo(text, options) => {
+                assert_eq!(text, "-x a");
+                assert_eq!(options, EchoOptions { no_newline: true, escapes: true });
+            }
+            other => panic!("parsed as {:?}", other),
+        }
+    }
+
+    #[test]
+    fn search_flags_anywhere() {
+        match Command::try_from("grep log.txt error -i --max-results 5").unwrap() {
+            Command::Grep(file, pattern, options) => {
+                assert_eq!((file.as_str(), pattern.as_str()), ("log.txt", "error"));
+                assert_eq!(options, SearchOptions { case: Some(CaseMode::Insensitive), max_results: Some(5) });
             }
-            _ => Err(anyhow!("Unknown command")),
+            other => panic!("parsed as {:?}", other),
+        }
+        assert!(Command::try_from("find . x --max-results 0").is_err());
+    }
+
+    #[test]
+    fn filters_read_standard_input() {
+        assert!(matches!(Command::try_from("grep -i error"), Ok(Command::Grep(file, pattern, _)) if file == "-" && pattern == "error"));
+        assert!(matches!(Command::try_from("cat -E"), Ok(Command::Cat(paths, _)) if paths == ["-"]));
+        assert!(matches!(Command::try_from("cat"), Ok(Command::Cat(paths, _)) if paths == ["-"]));
+        assert!(matches!(Command::try_from("cat a.txt - b.txt"), Ok(Command::Cat(paths, _)) if paths == ["a.txt", "-", "b.txt"]));
+    }
+
+    #[test]
+    fn one_file_system_flags() {
+        assert!(matches!(Command::try_from("find . --one-file-system"), Ok(Command::Find(_, _, _, filters)) if filters.one_file_system));
+        assert!(matches!(Command::try_from("cp -r --one-file-system a b"), Ok(Command::CpR(_, _, options)) if options.one_file_system));
+        assert!(matches!(Command::try_from("rm -r --one-file-system a"), Ok(Command::Rm(_, options)) if options.one_file_system));
+        assert!(Command::try_from("grep --one-file-system a b").is_err());
+    }
+
+    #[test]
+    fn find_filters_and_sizes() {
+        match Command::try_from("find . --larger-than 1.5G --empty --delete").unwrap() {
+            Command::Find(dir, pattern, _, filters) => {
+                assert_eq!((dir.as_str(), pattern.as_str()), (".", ""));
+                assert_eq!(filters.larger_than, Some(1_610_612_736));
+                assert!(filters.empty && filters.delete && !filters.yes);
+            }
+            other => panic!("parsed as {:?}", other),
+        }
+        assert!(matches!(Command::try_from("find . --delete --yes"), Ok(Command::Find(_, _, _, filters)) if filters.yes));
+        assert_eq!(parse_size("500k"), Some(512_000));
+        assert_eq!(parse_size("20MB"), Some(20 * 1024 * 1024));
+        assert!(Command::try_from("grep file x --empty").is_err());
+    }
+
+    #[test]
+    fn ls_n_implies_l() {
+        assert!(matches!(Command::try_from("ls -n"), Ok(Command::LsDetailed(ListOptions { numeric_ids: true }, paths)) if paths.is_empty()));
+        assert!(matches!(Command::try_from("ls -l"), Ok(Command::LsDetailed(ListOptions { numeric_ids: false }, _))));
+        assert!(matches!(Command::try_from("ls src -l notes.md"), Ok(Command::LsDetailed(_, paths)) if paths == ["src", "notes.md"]));
+        assert!(Command::try_from("ls -q").is_err());
+    }
+
+    #[test]
+    fn truncate_sizes() {
+        match Command::try_from("truncate -c -s +1M a b").unwrap() {
+            Command::Truncate(paths, options) => {
+                assert_eq!(paths, ["a", "b"]);
+                assert_eq!(options, TruncateOptions { size: SizeChange::Grow(1024 * 1024), no_create: true });
+            }
+            other => panic!("parsed as {:?}", other),
+        }
+        assert_eq!(SizeChange::Shrink(10).apply(4), 0);
+        assert!(Command::try_from("truncate a").is_err());
+        assert!(Command::try_from("truncate -s big a").is_err());
+    }
+
+    #[test]
+    fn ulimit_and_limit_values() {
+        assert_eq!(parse_limit(Resource::OpenFiles, "256"), Some(Some(256)));
+        assert_eq!(parse_limit(Resource::StackSize, "8192"), Some(Some(8 * 1024 * 1024)));
+        assert_eq!(parse_limit(Resource::VirtualMemory, "1G"), Some(Some(1 << 30)));
+        assert_eq!(parse_limit(Resource::CpuTime, "unlimited"), Some(None));
+        assert_eq!(parse_limit(Resource::OpenFiles, "1K"), None);
+        assert!(matches!(Command::try_from("ulimit -n 64"), Ok(Command::Ulimit(UlimitAction::Set(Resource::OpenFiles, Some(64))))));
+        assert!(Command::try_from("ulimit -x").is_err());
+
+        match Command::try_from("limit --mem 1G --cpu 5 ssh build 'uname -a'").unwrap() {
+            Command::Limit(limits, line) => {
+                assert_eq!(limits, [(Resource::VirtualMemory, Some(1 << 30)), (Resource::CpuTime, Some(5))]);
+                assert_eq!(line, "ssh build 'uname -a'");
+            }
+            other => panic!("parsed as {:?}", other),
+        }
+        assert!(Command::try_from("limit --mem 1G").is_err());
+    }
+
+    #[test]
+    fn kill_signals() {
+        assert!(matches!(Command::try_from("kill %1"), Ok(Command::Kill(libc::SIGTERM, targets)) if targets == ["%1"]));
+        assert!(matches!(Command::try_from("kill -KILL 42"), Ok(Command::Kill(libc::SIGKILL, _))));
+        assert!(matches!(Command::try_from("kill -s hup 42"), Ok(Command::Kill(libc::SIGHUP, _))));
+        assert!(matches!(Command::try_from("kill -9 %2"), Ok(Command::Kill(9, _))));
+        assert!(matches!(Command::try_from("kill -l"), Ok(Command::KillList)));
+        assert!(Command::try_from("kill -BOGUS 1").is_err());
+        assert!(matches!(Command::try_from("killall -INT sleep"), Ok(Command::Killall(libc::SIGINT, name)) if name == "sleep"));
+        assert!(Command::try_from("killall").is_err());
+    }
+
+    #[test]
+    fn new_from_template() {
+        assert!(matches!(Command::try_from("new"), Ok(Command::NewList)));
+        assert!(matches!(Command::try_from("new python lab1"), Ok(Command::New(template, name)) if template == "python" && name == "lab1"));
+        assert!(Command::try_from("new python").is_err());
+    }
+
+    #[test]
+    fn history() {
+        assert!(matches!(Command::try_from("history"), Ok(Command::History)));
+        assert!(matches!(Command::try_from("history scrub 'TOKEN=.*'"), Ok(Command::HistoryScrub(pattern)) if pattern == "TOKEN=.*"));
+        assert!(Command::try_from("history scrub").is_err());
+    }
+
+    #[test]
+    fn fc() {
+        assert!(matches!(Command::try_from("fc"), Ok(Command::Fc(None))));
+        assert!(matches!(Command::try_from("fc -2"), Ok(Command::Fc(Some(-2)))));
+        assert!(Command::try_from("fc 0").is_err() && Command::try_from("fc last").is_err());
+    }
+
+    #[test]
+    fn with_env() {
+        assert!(matches!(
+            Command::try_from("LANG=C GIT_PAGER= 'glog' -n 5 'a b'"),
+            Ok(Command::WithEnv(pairs, line)) if pairs == [("LANG".to_string(), "C".to_string()), ("GIT_PAGER".to_string(), String::new())] && line == "glog -n 5 'a b'"
+        ));
+        assert!(Command::try_from("LANG=C").is_err());
+        assert!(matches!(Command::try_from("echo a=b"), Ok(Command::Echo(..))));
+    }
+
+    #[test]
+    fn mkfifo() {
+        assert!(matches!(Command::try_from("mkfifo -m 600 a b"), Ok(Command::Mkfifo(names, 0o600)) if names == ["a", "b"]));
+        assert!(matches!(Command::try_from("mkfifo pipe"), Ok(Command::Mkfifo(_, 0o666))));
+        assert!(Command::try_from("mkfifo -m rw pipe").is_err());
+    }
+
+    #[test]
+    fn test_brackets() {
+        assert!(matches!(Command::try_from("[ -f notes.txt ]"), Ok(Command::Test(args)) if args == ["-f", "notes.txt"]));
+        assert!(Command::try_from("[ -f notes.txt").is_err());
+    }
+
+    #[test]
+    fn choose() {
+        assert!(matches!(Command::try_from("choose 'Option A' B"), Ok(Command::Choose(options, variable)) if options == ["Option A", "B"] && variable == "CHOICE"));
+        assert!(matches!(Command::try_from("choose -v COLOR red green"), Ok(Command::Choose(_, variable)) if variable == "COLOR"));
+        assert!(Command::try_from("choose -v 1x a").is_err() && Command::try_from("choose").is_err());
+    }
+
+    #[test]
+    fn spinner_and_progress() {
+        assert!(matches!(Command::try_from("spinner start 'Building docs'"), Ok(Command::SpinnerStart(message)) if message == "Building docs"));
+        assert!(matches!(Command::try_from("spinner stop"), Ok(Command::SpinnerStop)));
+        assert!(Command::try_from("spinner start").is_err());
+        assert!(matches!(Command::try_from("progress 3 10 Copying files"), Ok(Command::Progress(3, 10, Some(label))) if label == "Copying files"));
+        assert!(matches!(Command::try_from("progress 3 10"), Ok(Command::Progress(3, 10, None))));
+        assert!(Command::try_from("progress 3 0").is_err() && Command::try_from("progress x 10").is_err());
+    }
+
+    #[test]
+    fn secret() {
+        assert!(matches!(Command::try_from("secret get API_TOKEN"), Ok(Command::SecretGet(name)) if name == "API_TOKEN"));
+        assert!(matches!(Command::try_from("secret set API_TOKEN"), Ok(Command::SecretSet(name)) if name == "API_TOKEN"));
+        assert!(Command::try_from("secret set API_TOKEN hunter2").is_err());
+    }
+
+    #[test]
+    fn nohup_and_disown() {
+        assert!(matches!(Command::try_from("nohup serve 'my site' &"), Ok(Command::Nohup(line)) if line == "serve 'my site'"));
+        assert!(Command::try_from("nohup serve").is_err() && Command::try_from("nohup &").is_err());
+        assert!(matches!(Command::try_from("disown %1 2"), Ok(Command::Disown(ids)) if ids == [1, 2]));
+        assert!(Command::try_from("disown").is_err() && Command::try_from("disown %x").is_err());
+    }
+
+    #[test]
+    fn fg_and_bg() {
+        assert!(matches!(Command::try_from("fg"), Ok(Command::Fg(None))));
+        assert!(matches!(Command::try_from("bg %2"), Ok(Command::Bg(Some(2)))));
+        assert!(Command::try_from("fg %1 %2").is_err() && Command::try_from("bg x").is_err());
+    }
+
+    #[test]
+    fn which() {
+        assert!(matches!(Command::try_from("which cd git"), Ok(Command::Which(names)) if names == ["cd", "git"]));
+        assert!(Command::try_from("which").is_err());
+    }
+
+    #[test]
+    fn hash() {
+        assert!(matches!(Command::try_from("hash"), Ok(Command::Hash(HashAction::List))));
+        assert!(matches!(Command::try_from("hash -d git ssh"), Ok(Command::Hash(HashAction::Forget(names))) if names == ["git", "ssh"]));
+        assert!(matches!(Command::try_from("hash -r"), Ok(Command::Hash(HashAction::Clear))));
+        assert!(Command::try_from("hash -x").is_err());
+    }
+
+    #[test]
+    fn tasks() {
+        assert!(matches!(Command::try_from("task"), Ok(Command::Task(None))));
+        assert!(matches!(Command::try_from("task --list"), Ok(Command::Task(None))));
+        assert!(matches!(Command::try_from("task build"), Ok(Command::Task(Some(name))) if name == "build"));
+        assert!(Command::try_from("task a b").is_err());
+    }
+
+    #[test]
+    fn git_helpers() {
+        assert!(matches!(Command::try_from("glog"), Ok(Command::GitLog(DEFAULT_LOG_LENGTH, None))));
+        assert!(matches!(Command::try_from("glog -n 5 src"), Ok(Command::GitLog(5, Some(path))) if path == "src"));
+        assert!(matches!(Command::try_from("glog -3"), Ok(Command::GitLog(3, None))));
+        assert!(Command::try_from("glog -n0").is_err());
+        assert!(matches!(Command::try_from("gdiff --staged a.rs"), Ok(Command::GitDiff(true, Some(_)))));
+        assert!(Command::try_from("gdiff a b").is_err());
+    }
+
+    #[test]
+    fn nice_and_renice() {
+        assert!(matches!(Command::try_from("nice bench find . x"), Ok(Command::Nice(DEFAULT_NICENESS, line)) if line == "bench find . x"));
+        assert!(matches!(Command::try_from("nice -n -5 ssh build"), Ok(Command::Nice(-5, _))));
+        assert!(Command::try_from("nice -n x ssh build").is_err());
+        assert!(matches!(Command::try_from("renice -n 5 %1 42"), Ok(Command::Renice(5, targets)) if targets == ["%1", "42"]));
+        assert!(Command::try_from("renice 5").is_err());
+        assert!(Command::try_from("renice 5 me").is_err());
+    }
+
+    #[test]
+    fn pipelines() {
+        let pipeline = Pipeline::try_from("cat notes.txt | grep 'a | b' | cat -n").unwrap();
+        assert_eq!(pipeline.stages, ["cat notes.txt", "grep 'a | b'", "cat -n"]);
+        assert_eq!(Pipeline::try_from("echo hi").unwrap().stages, ["echo hi"]);
+        assert!(Pipeline::try_from("echo hi |").is_err());
+        assert!(Pipeline::try_from("echo hi || echo no").is_err());
+    }
+
+    #[test]
+    fn redirections() {
+        let redirected = Redirected::try_from("ls -l > listing.txt 2>>'my errors.log'").unwrap();
+        assert_eq!(redirected.line, "ls -l");
+        assert_eq!(
+            redirected.redirections,
+            [
+                Redirection { stream: Stream::Output, path: "listing.txt".to_string(), append: false },
+                Redirection { stream: Stream::Errors, path: "my errors.log".to_string(), append: true },
+            ]
+        );
+        assert!(Redirected::try_from("echo 'a > b'").unwrap().redirections.is_empty());
+        assert!(Redirected::try_from("echo hi >").is_err());
+        assert!(Redirected::try_from("> out.txt").is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn never_panics(line in "\\PC*") {
+            let _ = Command::try_from(line.as_str());
+        }
+
+        #[test]
+        fn never_panics_on_builtins(
+            name in "(ls|cd|cp|mv|mkdir|rmdir|find|grep|ln|undo|tutorial|practice|bench|stat)",
+            args in "[ '\"\\\\a-z0-9\u{0}é-]*",
+        ) {
+            let _ = Command::try_from(format!("{} {}", name, args).as_str());
         }
     }
 }
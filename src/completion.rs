@@ -0,0 +1,465 @@
+use std::fs;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::Path;
+
+use crate::config;
+use crate::executables;
+use crate::helpers;
+use crate::i18n::{tr, trf};
+use crate::practice;
+use crate::ssh;
+use crate::state::ShellState;
+use crate::tasks;
+use crate::templates;
+
+// What Tab knows about a builtin's arguments
+pub struct Builtin {
+    pub name: &'static str,
+    // Each flag with what it does, shown beside it in the menu
+    pub flags: &'static [(&'static str, &'static str)],
+    // Words offered for the first argument, e.g. subcommands
+    pub subcommands: &'static [&'static str],
+    // Words offered after a flag that takes a value
+    pub values: &'static [(&'static str, &'static [&'static str])],
+}
+
+const fn builtin(name: &'static str, flags: &'static [(&'static str, &'static str)]) -> Builtin {
+    Builtin { name, flags, subcommands: &[], values: &[] }
+}
+
+// Signal names for kill and killall, as -NAME and after -s
+const SIGNAL_FLAGS: &[(&str, &str)] = &[
+    ("-l", "list signal names"),
+    ("-s", "signal to send"),
+    ("-HUP", "hang up"),
+    ("-INT", "interrupt"),
+    ("-QUIT", "quit"),
+    ("-KILL", "kill, can't be caught"),
+    ("-USR1", "user signal 1"),
+    ("-SEGV", "segmentation fault"),
+    ("-USR2", "user signal 2"),
+    ("-PIPE", "broken pipe"),
+    ("-ALRM", "alarm"),
+    ("-TERM", "terminate"),
+    ("-CHLD", "child changed state"),
+    ("-CONT", "continue"),
+    ("-STOP", "stop, can't be caught"),
+    ("-TSTP", "stop from the terminal"),
+    ("-WINCH", "window size changed"),
+];
+const SIGNAL_NAMES: &[&str] = &["HUP", "INT", "QUIT", "KILL", "USR1", "SEGV", "USR2", "PIPE", "ALRM", "TERM", "CHLD", "CONT", "STOP", "TSTP", "WINCH"];
+
+const SORT_COLUMNS: &[&str] = &["cpu", "mem", "pid", "name"];
+
+pub const BUILTINS: &[Builtin] = &[
+    builtin(
+        "abbr",
+        &[
+            ("-a", "add an abbreviation"),
+            ("-e", "erase an abbreviation"),
+            ("-l", "list abbreviations"),
+            ("--add", "add an abbreviation"),
+            ("--erase", "erase an abbreviation"),
+            ("--list", "list abbreviations"),
+        ],
+    ),
+    builtin("bench", &[("-n", "number of runs")]),
+    builtin("bg", &[]),
+    builtin(
+        "cat",
+        &[
+            ("-A", "same as -vTE"),
+            ("-v", "show control characters"),
+            ("-T", "show tabs as ^I"),
+            ("-E", "mark line ends with $"),
+            ("-s", "squeeze blank lines"),
+            ("-H", "header before each file"),
+        ],
+    ),
+    builtin("cd", &[]),
+    builtin("clear", &[]),
+    builtin("choose", &[("-v", "variable to store the choice in")]),
+    builtin("cp", &[("-r", "copy directories recursively"), ("--one-file-system", "stay on one filesystem")]),
+    Builtin {
+        name: "dedupe",
+        flags: &[("--auto", "keep one copy without asking"), ("--keep", "which copy --auto keeps"), ("--link", "hard link the copies instead of deleting them")],
+        subcommands: &[],
+        values: &[("--keep", &["newest", "oldest"])],
+    },
+    builtin("disown", &[]),
+    builtin("doctor", &[]),
+    builtin(
+        "download",
+        &[
+            ("-o", "file to save to"),
+            ("--output", "file to save to"),
+            ("--sha256", "checksum to verify"),
+            ("-b", "run in the background"),
+            ("--background", "run in the background"),
+        ],
+    ),
+    builtin("du", &[("-s", "one line per path"), ("--summarize", "one line per path"), ("--one-file-system", "stay on one filesystem")]),
+    builtin("echo", &[("-n", "no newline at the end"), ("-e", "interpret backslash escapes"), ("-E", "leave backslash escapes as they are")]),
+    builtin("env", &[("--diff", "only changes since the shell started"), ("--sorted", "sort by name"), ("--grep", "names or values matching a pattern")]),
+    builtin("exit", &[]),
+    builtin("export", &[]),
+    builtin(
+        "find",
+        &[
+            ("-i", "ignore case"),
+            ("-s", "match case"),
+            ("-S", "ignore case unless the pattern has capitals"),
+            ("--max-results", "stop after this many"),
+            ("--larger-than", "only files larger than a size"),
+            ("--smaller-than", "only files smaller than a size"),
+            ("--empty", "only empty files and directories"),
+            ("--delete", "delete what is found"),
+            ("--one-file-system", "stay on one filesystem"),
+        ],
+    ),
+    builtin("fc", &[]),
+    builtin("fg", &[]),
+    builtin("gdiff", &[("--staged", "what the next commit holds")]),
+    builtin("glog", &[("-n", "number of commits")]),
+    builtin("gmv", &[]),
+    builtin(
+        "grep",
+        &[("-i", "ignore case"), ("-s", "match case"), ("-S", "ignore case unless the pattern has capitals"), ("--max-results", "stop after this many")],
+    ),
+    builtin("grm", &[("-r", "remove directories"), ("-f", "remove files with changes too"), ("--no-preserve-root", "allow removing /")]),
+    builtin("gst", &[]),
+    builtin("hash", &[("-r", "forget every program"), ("-d", "forget the programs named")]),
+    builtin("help", &[]),
+    Builtin { name: "history", flags: &[], subcommands: &["scrub"], values: &[] },
+    builtin("hosts", &[]),
+    builtin("jobs", &[]),
+    Builtin { name: "kill", flags: SIGNAL_FLAGS, subcommands: &[], values: &[("-s", SIGNAL_NAMES)] },
+    Builtin { name: "killall", flags: SIGNAL_FLAGS, subcommands: &[], values: &[("-s", SIGNAL_NAMES)] },
+    builtin("limit", &[("--mem", "memory limit"), ("--cpu", "CPU seconds"), ("--files", "open files"), ("--procs", "processes")]),
+    builtin("ln", &[("-s", "symbolic link"), ("-f", "replace an existing file"), ("-r", "relative symlink target")]),
+    builtin("ls", &[("-l", "detailed listing"), ("-n", "numeric owner and group")]),
+    builtin("merge", &[]),
+    builtin("mkdir", &[("-p", "create parents as needed")]),
+    builtin("mkfifo", &[("-m", "mode in octal")]),
+    Builtin {
+        name: "monitor",
+        flags: &[("-n", "refresh interval in seconds"), ("--interval", "refresh interval in seconds"), ("-s", "sort order"), ("--sort", "sort order")],
+        subcommands: &[],
+        values: &[("-s", SORT_COLUMNS), ("--sort", SORT_COLUMNS)],
+    },
+    builtin("mv", &[("--git", "move with git mv")]),
+    builtin("new", &[("--list", "list templates")]),
+    builtin("nice", &[("-n", "priority increment")]),
+    builtin("nohup", &[]),
+    builtin("ports", &[("--kill", "end what holds a port")]),
+    Builtin { name: "practice", flags: &[], subcommands: &["list", "start", "task", "check"], values: &[] },
+    builtin("printenv", &[]),
+    builtin("printf", &[]),
+    builtin("progress", &[]),
+    builtin("pstree", &[]),
+    builtin("pull", &[("-c", "continue an interrupted transfer"), ("--continue", "continue an interrupted transfer")]),
+    builtin("push", &[("-c", "continue an interrupted transfer"), ("--continue", "continue an interrupted transfer")]),
+    builtin("pwd", &[]),
+    builtin("rehash", &[]),
+    builtin("reload", &[]),
+    builtin("rename", &[("-n", "only show the table"), ("--dry-run", "only show the table")]),
+    Builtin {
+        name: "resolve",
+        flags: &[("-t", "record type"), ("--type", "record type"), ("-s", "DNS server to ask"), ("--server", "DNS server to ask")],
+        subcommands: &[],
+        values: &[("-t", &["A", "AAAA", "CNAME", "MX", "TXT"]), ("--type", &["A", "AAAA", "CNAME", "MX", "TXT"])],
+    },
+    builtin("renice", &[("-n", "priority")]),
+    builtin(
+        "rm",
+        &[
+            ("-r", "remove directories and their contents"),
+            ("-f", "don't ask, ignore missing paths"),
+            ("--no-preserve-root", "allow removing /"),
+            ("--one-file-system", "stay on one filesystem"),
+            ("--git", "remove with git rm"),
+        ],
+    ),
+    builtin("rmdir", &[("-r", "remove directories and their contents")]),
+    builtin("scp", &[("-r", "copy directories recursively"), ("-P", "port")]),
+    Builtin { name: "secret", flags: &[], subcommands: &["get", "set"], values: &[] },
+    builtin("serve", &[("-p", "port to listen on"), ("--port", "port to listen on")]),
+    Builtin { name: "spinner", flags: &[], subcommands: &["start", "stop"], values: &[] },
+    builtin("split", &[("-b", "size of each piece")]),
+    builtin("ssh", &[("-p", "port"), ("-l", "user to log in as")]),
+    builtin("stat", &[]),
+    builtin("stats", &[]),
+    builtin("task", &[("--list", "list tasks")]),
+    builtin(
+        "test",
+        &[
+            ("-e", "path exists"),
+            ("-f", "regular file"),
+            ("-d", "directory"),
+            ("-s", "file isn't empty"),
+            ("-r", "readable"),
+            ("-w", "writable"),
+            ("-x", "executable"),
+            ("-L", "symbolic link"),
+            ("-p", "named pipe"),
+            ("-n", "string isn't empty"),
+            ("-z", "string is empty"),
+        ],
+    ),
+    builtin(
+        "touch",
+        &[
+            ("-a", "access time only"),
+            ("-m", "modification time only"),
+            ("-c", "don't create missing files"),
+            ("-t", "time as [[CC]YY]MMDDhhmm[.ss]"),
+            ("-d", "time as YYYY-MM-DD HH:MM"),
+        ],
+    ),
+    builtin("truncate", &[("-s", "size"), ("-c", "don't create missing files")]),
+    Builtin { name: "tutorial", flags: &[], subcommands: &["start", "stop", "reset", "status"], values: &[] },
+    builtin(
+        "ulimit",
+        &[
+            ("-a", "all limits"),
+            ("-c", "core file size"),
+            ("-d", "data segment size"),
+            ("-f", "file size"),
+            ("-n", "open files"),
+            ("-s", "stack size"),
+            ("-t", "CPU seconds"),
+            ("-u", "processes"),
+            ("-v", "virtual memory"),
+        ],
+    ),
+    builtin("undo", &[]),
+    builtin("unset", &[]),
+    builtin("usage", &[]),
+    builtin("which", &[]),
+];
+
+// Whether `name` is one of the shell's own commands
+pub fn is_builtin(name: &str) -> bool {
+    BUILTINS.iter().any(|builtin| builtin.name == name)
+}
+
+// The word being completed starts at char index `start` of the line; each
+// candidate replaces it
+#[derive(Debug, PartialEq)]
+pub struct Completion {
+    pub start: usize,
+    pub candidates: Vec<Candidate>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Candidate {
+    pub word: String,
+    // Shown beside it in the menu: what a flag does, or a path's size or type
+    pub description: String,
+    // The heading it is listed under, for commands of different kinds
+    pub group: Option<&'static str>,
+}
+
+impl Candidate {
+    fn new(word: impl Into<String>) -> Candidate {
+        Candidate { word: word.into(), description: String::new(), group: None }
+    }
+
+    fn described(word: impl Into<String>, description: impl Into<String>) -> Candidate {
+        Candidate { word: word.into(), description: description.into(), group: None }
+    }
+}
+
+// Candidates for the word that ends at char index `cursor` of `line`:
+// commands first on the line, the builtin's flags for words starting
+// with -, known values after flags and subcommands, variable names for
+// printenv, unset and export, template and task names, host names for ssh,
+// scp, push and pull, and paths otherwise
+pub fn complete(line: &str, cursor: usize, state: &ShellState) -> Completion {
+    let before: Vec<char> = line.chars().take(cursor).collect();
+    let start = word_start(&before);
+    let word: String = before[start..].iter().collect();
+    let mut previous: Vec<String> = before[..start].iter().collect::<String>().split_whitespace().map(str::to_string).collect();
+    // After a `|` a new command starts
+    if let Some(pipe) = previous.iter().rposition(|word| word == "|") {
+        previous.drain(..=pipe);
+    }
+
+    let offer = |words: &mut dyn Iterator<Item = Candidate>| -> Vec<Candidate> {
+        words.filter(|candidate| candidate.word.starts_with(&word)).collect()
+    };
+    let candidates = match previous.first() {
+        None => commands(&word, state),
+        Some(name) => {
+            let builtin = BUILTINS.iter().find(|builtin| builtin.name == name);
+            let values = builtin.and_then(|builtin| {
+                let flag = previous.last()?;
+                builtin.values.iter().find(|(name, _)| name == flag).map(|(_, values)| *values)
+            });
+            match (builtin, values) {
+                (_, Some(values)) => offer(&mut values.iter().map(|value| Candidate::new(*value))),
+                (Some(builtin), _) if word.starts_with('-') => {
+                    offer(&mut builtin.flags.iter().map(|(flag, description)| Candidate::described(*flag, tr(description))))
+                }
+                (Some(builtin), _) if previous.len() == 1 && !builtin.subcommands.is_empty() => {
+                    offer(&mut builtin.subcommands.iter().map(|value| Candidate::new(*value)))
+                }
+                (Some(_), _) if previous.len() == 2 && previous[..] == ["practice", "start"] => {
+                    let scenarios = practice::scenarios().unwrap_or_default();
+                    offer(&mut scenarios.into_iter().map(|scenario| Candidate::new(scenario.name)))
+                }
+                (Some(_), _) if matches!(name.as_str(), "printenv" | "unset" | "export") => {
+                    let mut names: Vec<String> = state.env.keys().cloned().collect();
+                    names.sort();
+                    offer(&mut names.into_iter().map(Candidate::new))
+                }
+                (Some(_), _) if name == "new" && previous.len() == 1 => {
+                    offer(&mut templates::list(&templates::templates_dir()).into_iter().map(Candidate::new))
+                }
+                (Some(_), _) if name == "task" => {
+                    let names: Vec<String> = tasks::load(&state.cwd).map(|file| file.tasks.into_keys().collect()).unwrap_or_default();
+                    offer(&mut names.into_iter().map(Candidate::new))
+                }
+                (Some(_), _) if name == "ssh" => offer(&mut ssh::host_names(&state.resolve("~")).into_iter().map(Candidate::new)),
+                // scp, push and pull take local paths and host:path; what's
+                // on the host isn't known
+                (Some(_), _) if matches!(name.as_str(), "scp" | "push" | "pull") && word.contains(':') => Vec::new(),
+                (Some(_), _) if matches!(name.as_str(), "scp" | "push" | "pull") => {
+                    let hosts = ssh::host_names(&state.resolve("~")).into_iter().map(|host| Candidate::new(format!("{}:", host)));
+                    let mut candidates = paths(&word, state);
+                    candidates.extend(offer(&mut hosts.into_iter()));
+                    candidates
+                }
+                _ => paths(&word, state),
+            }
+        }
+    };
+    Completion { start, candidates }
+}
+
+// What can go first on the line, grouped as zsh does: builtins, then
+// abbreviations with what they stand for, programs on PATH, and
+// directories when autocd enters them
+fn commands(word: &str, state: &ShellState) -> Vec<Candidate> {
+    let config = config::get();
+    let mut candidates: Vec<Candidate> = BUILTINS
+        .iter()
+        .filter(|builtin| builtin.name.starts_with(word))
+        .map(|builtin| Candidate { group: Some(tr("builtins")), ..Candidate::new(builtin.name) })
+        .collect();
+    let mut abbreviations: Vec<(&String, &String)> = config.abbr.iter().filter(|(name, _)| name.starts_with(word)).collect();
+    abbreviations.sort();
+    candidates.extend(
+        abbreviations.into_iter().map(|(name, expansion)| Candidate { group: Some(tr("abbreviations")), ..Candidate::described(name, expansion) }),
+    );
+    let programs = executables::starting_with(word, state.var("PATH").unwrap_or_default());
+    candidates.extend(
+        programs
+            .into_iter()
+            // A builtin of the same name is what runs
+            .filter(|name| !is_builtin(name))
+            .map(|name| Candidate { group: Some(tr("external commands")), ..Candidate::new(name) }),
+    );
+    if config.autocd {
+        let directories = paths(word, state).into_iter().filter(|candidate| candidate.word.ends_with('/'));
+        candidates.extend(directories.map(|candidate| Candidate { group: Some(tr("directories")), ..candidate }));
+    }
+    candidates
+}
+
+// Where the word before the cursor begins; a backslash-escaped space is part of it
+fn word_start(before: &[char]) -> usize {
+    let mut start = before.len();
+    while start > 0 && !(before[start - 1].is_whitespace() && (start < 2 || before[start - 2] != '\\')) {
+        start -= 1;
+    }
+    start
+}
+
+// Files and directories whose path starts with `word`, written the way the
+// word was (~ and relative paths stay as typed), with directories ending in /
+fn paths(word: &str, state: &ShellState) -> Vec<Candidate> {
+    let typed = unescape(word);
+    if typed == "~" {
+        return vec![Candidate::described("~/", tr("directory"))];
+    }
+    let (dir, prefix) = match typed.rfind('/') {
+        Some(slash) => (&typed[..=slash], &typed[slash + 1..]),
+        None => ("", typed.as_str()),
+    };
+    let Ok(entries) = fs::read_dir(state.resolve(if dir.is_empty() { "." } else { dir })) else {
+        return Vec::new();
+    };
+
+    let mut candidates: Vec<Candidate> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            // Hidden files only when asked for
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let path = entry.path();
+            let slash = if path.is_dir() { "/" } else { "" };
+            Some(Candidate::described(format!("{}{}{}", escape(dir), escape(&name), slash), describe(&path)))
+        })
+        .collect();
+    candidates.sort_by(|a, b| a.word.cmp(&b.word));
+    candidates
+}
+
+// What a path is, for the menu: a file's size, or its type when it has none
+// worth showing
+fn describe(path: &Path) -> String {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return String::new();
+    };
+    let kind = metadata.file_type();
+    if kind.is_symlink() {
+        fs::read_link(path).map(|target| format!("→ {}", target.display())).unwrap_or_default()
+    } else if kind.is_dir() {
+        tr("directory").to_string()
+    } else if kind.is_fifo() {
+        tr("named pipe").to_string()
+    } else if kind.is_socket() {
+        tr("socket").to_string()
+    } else if kind.is_block_device() || kind.is_char_device() {
+        tr("device").to_string()
+    } else if metadata.permissions().mode() & 0o111 != 0 {
+        trf("executable, {}", &[&helpers::human_size(metadata.len())])
+    } else {
+        helpers::human_size(metadata.len())
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_whitespace() || "'\"\\*?[]$&;|<>()".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn unescape(word: &str) -> String {
+    let mut text = String::with_capacity(word.len());
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        text.push(if c == '\\' { chars.next().unwrap_or('\\') } else { c });
+    }
+    text
+}
+
+// The longest start all candidates share
+pub fn common_prefix(candidates: &[Candidate]) -> String {
+    let Some(first) = candidates.first().map(|candidate| &candidate.word) else {
+        return String::new();
+    };
+    let mut length = first.chars().count();
+    for candidate in &candidates[1..] {
+        length = first.chars().zip(candidate.word.chars()).take_while(|(a, b)| a == b).count().min(length);
+    }
+    first.chars().take(length).collect()
+}
@@ -0,0 +1,182 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+
+use crate::errors::{self, Access, CrateResult};
+use crate::i18n::{tr, trf};
+
+const UNARY: &[&str] = &["-e", "-f", "-d", "-s", "-r", "-w", "-x", "-L", "-h", "-p", "-n", "-z"];
+const BINARY: &[&str] = &["=", "==", "!=", "<", ">", "-eq", "-ne", "-lt", "-le", "-gt", "-ge", "-nt", "-ot"];
+
+// Evaluates a `test` expression: file checks (-e -f -d -s ...), string and
+// integer comparisons, `!`, `-a`, `-o` and parentheses. `resolve` turns
+// paths into absolute ones.
+pub fn evaluate(args: &[String], resolve: &dyn Fn(&str) -> PathBuf) -> CrateResult<bool> {
+    if args.is_empty() {
+        return Ok(false);
+    }
+    let mut parser = Parser { args, position: 0, resolve };
+    let result = parser.or()?;
+    match parser.args.get(parser.position) {
+        Some(extra) => Err(anyhow!(trf("test: unexpected '{}'", &[extra]))),
+        None => Ok(result),
+    }
+}
+
+struct Parser<'a> {
+    args: &'a [String],
+    position: usize,
+    resolve: &'a dyn Fn(&str) -> PathBuf,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self, offset: usize) -> Option<&str> {
+        self.args.get(self.position + offset).map(String::as_str)
+    }
+
+    fn next(&mut self) -> CrateResult<&'a str> {
+        let arg = self.args.get(self.position).ok_or_else(|| anyhow!(tr("test: expression ends too early")))?;
+        self.position += 1;
+        Ok(arg)
+    }
+
+    fn or(&mut self) -> CrateResult<bool> {
+        let mut result = self.and()?;
+        while self.peek(0) == Some("-o") {
+            self.position += 1;
+            // Both sides are parsed either way, so errors aren't hidden
+            result |= self.and()?;
+        }
+        Ok(result)
+    }
+
+    fn and(&mut self) -> CrateResult<bool> {
+        let mut result = self.not()?;
+        while self.peek(0) == Some("-a") {
+            self.position += 1;
+            result &= self.not()?;
+        }
+        Ok(result)
+    }
+
+    fn not(&mut self) -> CrateResult<bool> {
+        // A lone `!` is just a non-empty string
+        if self.peek(0) == Some("!") && self.peek(1).is_some() {
+            self.position += 1;
+            return Ok(!self.not()?);
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> CrateResult<bool> {
+        // `a = b` wins over the other readings, so `test -f = -f` compares strings
+        if let Some(operator) = self.peek(1).filter(|operator| BINARY.contains(operator)) {
+            if let Some(right) = self.peek(2) {
+                let (left, operator, right) = (self.args[self.position].clone(), operator.to_string(), right.to_string());
+                self.position += 3;
+                return self.binary(&left, &operator, &right);
+            }
+        }
+        if self.peek(0) == Some("(") && self.peek(1).is_some() {
+            self.position += 1;
+            let result = self.or()?;
+            return match self.next()? {
+                ")" => Ok(result),
+                other => Err(anyhow!(trf("test: expected ')' but found '{}'", &[&other]))),
+            };
+        }
+        if let Some(operator) = self.peek(0).filter(|operator| UNARY.contains(operator)) {
+            if let Some(operand) = self.peek(1) {
+                let (operator, operand) = (operator.to_string(), operand.to_string());
+                self.position += 2;
+                return Ok(self.unary(&operator, &operand));
+            }
+        }
+        // Any other single word is true when it isn't empty
+        Ok(!self.next()?.is_empty())
+    }
+
+    fn unary(&self, operator: &str, operand: &str) -> bool {
+        let path = || (self.resolve)(operand);
+        let metadata = || fs::metadata(path());
+        match operator {
+            "-n" => !operand.is_empty(),
+            "-z" => operand.is_empty(),
+            "-e" => metadata().is_ok(),
+            "-f" => metadata().is_ok_and(|metadata| metadata.is_file()),
+            "-d" => metadata().is_ok_and(|metadata| metadata.is_dir()),
+            "-s" => metadata().is_ok_and(|metadata| metadata.len() > 0),
+            "-p" => crate::fifo::is_fifo(&path()),
+            "-L" | "-h" => fs::symlink_metadata(path()).is_ok_and(|metadata| metadata.is_symlink()),
+            "-r" => errors::has_access(&path(), Access::Read),
+            "-w" => errors::has_access(&path(), Access::Write),
+            _ => errors::can_search(&path()),
+        }
+    }
+
+    fn binary(&self, left: &str, operator: &str, right: &str) -> CrateResult<bool> {
+        Ok(match operator {
+            "=" | "==" => left == right,
+            "!=" => left != right,
+            "<" => left < right,
+            ">" => left > right,
+            "-nt" | "-ot" => {
+                let (left, right) = (modified(&(self.resolve)(left)), modified(&(self.resolve)(right)));
+                match operator {
+                    // A missing file is older than any existing one
+                    "-nt" => left > right,
+                    _ => left < right,
+                }
+            }
+            _ => {
+                let (left, right) = (integer(left)?, integer(right)?);
+                match operator {
+                    "-eq" => left == right,
+                    "-ne" => left != right,
+                    "-lt" => left < right,
+                    "-le" => left <= right,
+                    "-gt" => left > right,
+                    _ => left >= right,
+                }
+            }
+        })
+    }
+}
+
+fn modified(path: &Path) -> Option<std::time::SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn integer(text: &str) -> CrateResult<i64> {
+    text.trim().parse().map_err(|_| anyhow!(trf("test: '{}' is not an integer", &[&text])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test(line: &str, dir: &Path) -> CrateResult<bool> {
+        let args = crate::tokenizer::tokenize(line).unwrap();
+        evaluate(&args, &|path| dir.join(path))
+    }
+
+    #[test]
+    fn files_strings_and_numbers() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        fs::write(dir.join("full"), "x").unwrap();
+        fs::write(dir.join("empty"), "").unwrap();
+
+        assert!(test("-f full -a -d . -a ! -e missing", dir).unwrap());
+        assert!(test("-s full", dir).unwrap() && !test("-s empty", dir).unwrap());
+        assert!(test("abc = abc -a 10 -gt 9 -a 3 -le 3", dir).unwrap());
+        assert!(!test("abc != abc -o 2 -eq 3", dir).unwrap());
+        assert!(test("'' = '' -a ( -z '' -o -f missing )", dir).unwrap());
+        assert!(test("-n", dir).unwrap() && !test("''", dir).unwrap() && !test("", dir).unwrap());
+        assert!(test("! -f = -f", dir).is_ok_and(|result| !result));
+        assert!(test("x -lt 3", dir).is_err());
+        assert!(test("( -f full", dir).is_err());
+        assert!(test("a b", dir).is_err());
+    }
+}
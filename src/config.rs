@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::sync::{LazyLock, OnceLock, RwLock, RwLockReadGuard};
+
+use anyhow::anyhow;
+use colored::Colorize;
+use serde::Deserialize;
+
+use crate::audit::HistoryMode;
+use crate::dirs;
+use crate::editor::Action;
+use crate::encryption::HistoryEncryption;
+use crate::errors::CrateResult;
+use crate::i18n::{tr, trf};
+use crate::logging::LogLevel;
+use crate::search::CaseMode;
+use crate::spelling::Correction;
+use crate::widgets::Widget;
+
+const RC_FILE: &str = "config.toml";
+
+static CONFIG: LazyLock<RwLock<Config>> = LazyLock::new(|| RwLock::new(Config::default()));
+// Set by --config to read settings from somewhere other than config.toml in
+// the config directory
+static RC_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+// Set by --theme and --color, which outrank the rc file, reloaded or not
+static THEME_OVERRIDE: OnceLock<String> = OnceLock::new();
+static COLOR_OVERRIDE: OnceLock<ColorMode> = OnceLock::new();
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+// Settings read from config.toml in the config directory
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub color: ColorMode,
+    // Built-in palette name; see theme::THEMES
+    pub theme: Option<String>,
+    // Message language, e.g. "es"; defaults to LC_ALL / LC_MESSAGES / LANG
+    pub locale: Option<String>,
+    // Right-aligned prompt segment, e.g. "{status} {battery} {time}"
+    pub right_prompt: Option<String>,
+    // Collapse each prompt to one line once its command has been entered
+    pub transient_prompt: bool,
+    // Show the working directory in the terminal's window/tab title (default on)
+    pub terminal_title: Option<bool>,
+    // Default letter case rule for find and grep: sensitive, insensitive or smart
+    pub search_case: Option<CaseMode>,
+    // Verbosity of the log file under the state directory; --log-level wins
+    pub log_level: Option<LogLevel>,
+    // Regexes for lines kept out of the audit log, e.g. ".*TOKEN=.*"; unset
+    // means lines typed with a leading space
+    pub history_ignore: Option<Vec<String>>,
+    // Whether shells running at once share their history as they go
+    // (shared) or each keeps its own until it exits (session)
+    pub history_mode: HistoryMode,
+    // Encrypt the audit log at rest with a key from a passphrase or the OS
+    // keyring; unset keeps it plain text
+    pub encrypt_history: Option<HistoryEncryption>,
+    // Let rm and mv stage tracked files with git rm/git mv without --git
+    pub git_aware_rm_mv: bool,
+    // Lines whose globs or history references expand are shown as they
+    // will run and wait for Enter
+    pub confirm_expansions: bool,
+    // A directory typed on its own as a command is a `cd` into it
+    pub autocd: bool,
+    // Fix mistyped builtin names and `cd` paths: off, ask or auto
+    pub correct: Correction,
+    // Ctrl+D at an empty prompt doesn't leave; `exit` does
+    pub ignoreeof: bool,
+    // Command lines run when an interactive session ends, like .bash_logout
+    pub on_exit: Vec<String>,
+    // Abbreviation → what it expands to when typed as a command, under [abbr]
+    pub abbr: HashMap<String, String>,
+    // Key name → what it does, e.g. `up = "history-substring-search-backward"`
+    // under [keymap]; see editor::KEYMAP
+    pub keymap: HashMap<String, Action>,
+    // Element name → color, e.g. `directory = "bright blue"` under [colors]
+    pub colors: HashMap<String, String>,
+    // Widget name → URL to poll, under [widgets.<name>]
+    pub widgets: HashMap<String, Widget>,
+}
+
+pub fn set_rc_path(path: PathBuf) {
+    let _ = RC_OVERRIDE.set(path);
+}
+
+pub fn rc_path() -> PathBuf {
+    if let Some(path) = RC_OVERRIDE.get() {
+        return path.clone();
+    }
+
+    dirs::config_dir().join(RC_FILE)
+}
+
+// Reads the settings into use, replacing the ones before. If the file has
+// a mistake they stay as they were.
+pub fn load() -> CrateResult<()> {
+    let mut config = read()?;
+    // A color that can't be read leaves the element its usual one
+    config.colors.retain(|element, value| {
+        let known = crate::theme::parse_color(value).is_some();
+        if !known {
+            eprintln!("{} {}", tr("Warning:").yellow(), trf("Unknown color '{}' for {} under [colors]; using the usual one", &[value, element]));
+        }
+        known
+    });
+    if let Some(theme) = THEME_OVERRIDE.get() {
+        config.theme = Some(theme.clone());
+    }
+    *CONFIG.write().unwrap() = config;
+    Ok(())
+}
+
+// The settings in the rc file, checked but not put into use
+pub fn read() -> CrateResult<Config> {
+    let path = rc_path();
+    let config: Config = if path.exists() {
+        toml::from_str(&fs::read_to_string(&path)?)
+            .map_err(|e| anyhow!(trf("Invalid config in {}: {}", &[&path.display(), &e])))?
+    } else if RC_OVERRIDE.get().is_some() {
+        return Err(anyhow!(trf("Config file {} does not exist", &[&path.display()])));
+    } else {
+        Config::default()
+    };
+
+    if let Some(theme) = &config.theme {
+        if !crate::theme::exists(theme) {
+            return Err(anyhow!(trf("Unknown theme '{}' in {}", &[theme, &path.display()])));
+        }
+    }
+
+    for pattern in config.history_ignore.iter().flatten() {
+        if let Err(e) = regex::Regex::new(pattern) {
+            return Err(anyhow!(trf("Invalid history_ignore pattern '{}' in {}: {}", &[pattern, &path.display(), &e])));
+        }
+    }
+
+    for key in config.keymap.keys() {
+        if !crate::editor::KEYMAP.iter().any(|(name, _)| name == key) {
+            return Err(anyhow!(trf("Unknown key '{}' under [keymap] in {}", &[key, &path.display()])));
+        }
+    }
+
+    Ok(config)
+}
+
+pub fn set_theme(theme: String) {
+    let _ = THEME_OVERRIDE.set(theme.clone());
+    CONFIG.write().unwrap().theme = Some(theme);
+}
+
+// The --config and --theme this shell was started with, for a copy of it
+// to have the same settings
+pub fn override_args() -> Vec<OsString> {
+    let mut args = Vec::new();
+    if let Some(path) = RC_OVERRIDE.get() {
+        args.extend(["--config".into(), path.clone().into_os_string()]);
+    }
+    if let Some(theme) = THEME_OVERRIDE.get() {
+        args.extend(["--theme".into(), theme.into()]);
+    }
+    args
+}
+
+// Adds, changes or (with None) removes an abbreviation, in the rc file as
+// well so it is there next time
+pub fn set_abbr(name: &str, expansion: Option<&str>) -> CrateResult<()> {
+    let path = rc_path();
+    let text = if path.exists() { fs::read_to_string(&path)? } else { String::new() };
+    fs::write(&path, with_abbr(&text, name, expansion))?;
+    let mut config = CONFIG.write().unwrap();
+    match expansion {
+        Some(expansion) => config.abbr.insert(name.to_string(), expansion.to_string()),
+        None => config.abbr.remove(name),
+    };
+    Ok(())
+}
+
+// `text` with `name` set in its [abbr] table, or taken out of it. Only that
+// line changes, so comments and the rest of the file stay as they were.
+fn with_abbr(text: &str, name: &str, expansion: Option<&str>) -> String {
+    let key = match name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        true => name.to_string(),
+        false => toml::Value::String(name.to_string()).to_string(),
+    };
+    let entry = expansion.map(|expansion| format!("{} = {}", key, toml::Value::String(expansion.to_string())));
+    let key_of = |line: &str| line.split_once('=').map(|(key, _)| key.trim().trim_matches('"').to_string());
+
+    let mut lines: Vec<String> = text.lines().map(String::from).collect();
+    match lines.iter().position(|line| line.trim() == "[abbr]") {
+        Some(header) => {
+            let end = lines[header + 1..].iter().position(|line| line.trim_start().starts_with('[')).map_or(lines.len(), |index| header + 1 + index);
+            let existing = (header + 1..end).find(|&index| key_of(&lines[index]).as_deref() == Some(name));
+            match (existing, entry) {
+                (Some(index), Some(entry)) => lines[index] = entry,
+                (Some(index), None) => {
+                    lines.remove(index);
+                }
+                // After the table's last entry, before any blank lines
+                (None, Some(entry)) => {
+                    let last = (header..end).rev().find(|&index| !lines[index].trim().is_empty()).unwrap_or(header);
+                    lines.insert(last + 1, entry);
+                }
+                (None, None) => {}
+            }
+        }
+        None => {
+            if let Some(entry) = entry {
+                if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                    lines.push(String::new());
+                }
+                lines.push("[abbr]".to_string());
+                lines.push(entry);
+            }
+        }
+    }
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
+
+pub fn get() -> RwLockReadGuard<'static, Config> {
+    CONFIG.read().unwrap()
+}
+
+// Decides whether to emit ANSI colors: an explicit mode wins, otherwise honor
+// NO_COLOR / CLICOLOR_FORCE / CLICOLOR and only color when stdout is a terminal.
+pub fn apply_color_mode(flag: Option<ColorMode>) {
+    if let Some(flag) = flag {
+        let _ = COLOR_OVERRIDE.set(flag);
+    }
+    let mode = COLOR_OVERRIDE.get().copied().unwrap_or(get().color);
+
+    let enabled = match mode {
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+        ColorMode::Auto => {
+            let env_set = |name: &str| std::env::var(name).map(|v| !v.is_empty()).unwrap_or(false);
+
+            if env_set("NO_COLOR") {
+                false
+            } else if std::env::var("CLICOLOR_FORCE").map(|v| v != "0" && !v.is_empty()).unwrap_or(false) {
+                true
+            } else if std::env::var("CLICOLOR").map(|v| v == "0").unwrap_or(false) {
+                false
+            } else {
+                std::io::stdout().is_terminal()
+            }
+        }
+    };
+
+    colored::control::set_override(enabled);
+}
@@ -0,0 +1,88 @@
+use std::backtrace::Backtrace;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use colored::*;
+
+use crate::errors::{self, Access, CrateResult};
+use crate::i18n::tr;
+use crate::{logging, terminal};
+
+const RECENT_LIMIT: usize = 50;
+
+// The last lines the user entered with the directory they ran in, oldest first
+static RECENT_INPUT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+pub fn record_input(cwd: &Path, line: &str) {
+    let mut recent = RECENT_INPUT.lock().unwrap_or_else(|e| e.into_inner());
+    if recent.len() == RECENT_LIMIT {
+        recent.pop_front();
+    }
+    recent.push_back(format!("{} $ {}", cwd.display(), line));
+}
+
+// Restores the terminal and writes a report before the default panic message
+pub fn install() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let raw_mode = terminal::restore();
+        let report = write_report(&info.to_string(), raw_mode);
+        default_hook(info);
+        announce(report);
+    }));
+}
+
+// For errors that end the shell without a panic
+pub fn report_fatal(error: &anyhow::Error) {
+    let raw_mode = terminal::restore();
+    announce(write_report(&format!("{:#}", error), raw_mode));
+}
+
+fn announce(report: CrateResult<PathBuf>) {
+    match report {
+        Ok(path) => eprintln!(
+            "{} {}",
+            tr("The shell crashed. Please attach this report to your bug report:").bright_red().bold(),
+            path.display()
+        ),
+        Err(e) => eprintln!("{} {}", tr("Warning: could not write crash report:").yellow(), e),
+    }
+}
+
+fn write_report(reason: &str, raw_mode: bool) -> CrateResult<PathBuf> {
+    let now = chrono::Local::now();
+    let mut report = String::new();
+
+    writeln!(report, "shell {} crash report", env!("CARGO_PKG_VERSION"))?;
+    writeln!(report, "time: {}", now.to_rfc3339())?;
+    writeln!(report, "reason: {}", reason)?;
+
+    writeln!(report, "\n[terminal]")?;
+    match terminal::window_size() {
+        Some((columns, rows)) => writeln!(report, "size: {}x{}", columns, rows)?,
+        None => writeln!(report, "size: unknown")?,
+    }
+    writeln!(report, "stdin is a tty: {}", std::io::stdin().is_terminal())?;
+    writeln!(report, "stdout is a tty: {}", std::io::stdout().is_terminal())?;
+    writeln!(report, "raw mode active: {}", raw_mode)?;
+    writeln!(report, "TERM: {}", std::env::var("TERM").unwrap_or_default())?;
+
+    writeln!(report, "\n[recent input]")?;
+    for line in RECENT_INPUT.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+        writeln!(report, "{}", line)?;
+    }
+
+    writeln!(report, "\n[backtrace]\n{}", Backtrace::force_capture())?;
+
+    let dir = logging::log_dir();
+    fs::create_dir_all(&dir).map_err(|e| errors::with_path(e, &dir, Access::Write))?;
+    let path = dir.join(format!("crash-{}.txt", now.format("%Y%m%d-%H%M%S")));
+    fs::write(&path, report).map_err(|e| errors::with_path(e, &dir, Access::Write))?;
+
+    tracing::error!(report = %path.display(), reason, "crashed");
+    Ok(path)
+}
@@ -0,0 +1,143 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::anyhow;
+use sha2::{Digest, Sha256};
+
+use crate::errors::CrateResult;
+use crate::helpers;
+use crate::i18n::trf;
+use crate::interrupt;
+use crate::search::{CaseMode, FindFilters, Matcher};
+
+// Which copy `dedupe --auto` keeps
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Keep {
+    #[default]
+    Newest,
+    Oldest,
+}
+
+// Files with identical contents
+#[derive(Debug, PartialEq)]
+pub struct Group {
+    pub size: u64,
+    // Sorted by path
+    pub paths: Vec<PathBuf>,
+}
+
+impl Group {
+    // Bytes freed by keeping a single copy
+    pub fn reclaimable(&self) -> u64 {
+        self.size * (self.paths.len() as u64 - 1)
+    }
+
+    // Index of the copy to keep; ties go to the first path
+    pub fn pick(&self, keep: Keep) -> usize {
+        let modified = |path: &PathBuf| fs::metadata(path).and_then(|m| m.modified()).unwrap_or(SystemTime::UNIX_EPOCH);
+        let mut best = 0;
+        for (i, path) in self.paths.iter().enumerate().skip(1) {
+            let better = match keep {
+                Keep::Newest => modified(path) > modified(&self.paths[best]),
+                Keep::Oldest => modified(path) < modified(&self.paths[best]),
+            };
+            if better {
+                best = i;
+            }
+        }
+        best
+    }
+}
+
+// Groups the regular files under `dir` by contents, largest savings first.
+// Only files sharing a size are hashed, and names that are already hard links
+// to each other count once. Empty files and symlinks are left alone.
+pub fn scan(dir: &Path) -> CrateResult<Vec<Group>> {
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    let mut seen = HashSet::new();
+    let everything = Matcher::new("", CaseMode::Sensitive);
+    helpers::find(dir, &everything, &FindFilters::default(), &mut |path| {
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            if metadata.is_file() && metadata.len() > 0 && seen.insert((metadata.dev(), metadata.ino())) {
+                by_size.entry(metadata.len()).or_default().push(path.to_path_buf());
+            }
+        }
+        true
+    })?;
+
+    let mut groups = Vec::new();
+    for (size, paths) in by_size.into_iter().filter(|(_, paths)| paths.len() > 1) {
+        let mut by_hash: HashMap<[u8; 32], Vec<PathBuf>> = HashMap::new();
+        for path in paths {
+            if interrupt::requested() {
+                return Err(anyhow!(trf("dedupe: interrupted while hashing {}", &[&path.display()])));
+            }
+            // A file we can't read just isn't part of any group
+            if let Ok(hash) = hash_file(&path) {
+                by_hash.entry(hash).or_default().push(path);
+            }
+        }
+        for mut paths in by_hash.into_values().filter(|paths| paths.len() > 1) {
+            paths.sort();
+            groups.push(Group { size, paths });
+        }
+    }
+
+    groups.sort_by(|a, b| b.reclaimable().cmp(&a.reclaimable()).then_with(|| a.paths.cmp(&b.paths)));
+    Ok(groups)
+}
+
+// SHA-256 of a file, read in chunks so large files don't fill memory
+fn hash_file(path: &Path) -> io::Result<[u8; 32]> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().into())
+}
+
+// Replaces `copy` with a hard link to `original`. The link is made under a
+// temporary name first so the copy is never missing if something fails.
+pub fn link(original: &Path, copy: &Path) -> CrateResult<()> {
+    let name = copy.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let temporary = copy.with_file_name(format!(".{}.dedupe", name));
+    fs::hard_link(original, &temporary)?;
+    if let Err(e) = fs::rename(&temporary, copy) {
+        let _ = fs::remove_file(&temporary);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_identical_files_once() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("a.txt"), "same").unwrap();
+        fs::write(dir.path().join("sub/b.txt"), "same").unwrap();
+        fs::write(dir.path().join("c.txt"), "diff").unwrap();
+        fs::write(dir.path().join("empty1"), "").unwrap();
+        fs::write(dir.path().join("empty2"), "").unwrap();
+
+        let groups = scan(dir.path()).unwrap();
+        assert_eq!(groups, [Group { size: 4, paths: vec![dir.path().join("a.txt"), dir.path().join("sub/b.txt")] }]);
+
+        link(&groups[0].paths[0], &groups[0].paths[1]).unwrap();
+        assert!(scan(dir.path()).unwrap().is_empty());
+        assert_eq!(fs::read_to_string(dir.path().join("sub/b.txt")).unwrap(), "same");
+    }
+}
@@ -0,0 +1,46 @@
+use std::fs;
+use std::io::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::errors::CrateResult;
+
+const BASE_DELAY_MS: u64 = 45;
+const JITTER_MS: u64 = 70;
+
+// Script lines to replay; blank lines and `#` comments are skipped
+pub fn load_script(path: &str) -> CrateResult<Vec<String>> {
+    let contents = fs::read_to_string(path)?;
+
+    let script: Vec<String> = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect();
+    tracing::debug!(path, commands = script.len(), "loaded script");
+    Ok(script)
+}
+
+// Types `line` one character at a time with a human-looking rhythm
+pub async fn type_out(line: &str) -> CrateResult<()> {
+    let mut rng = SystemTime::now().duration_since(UNIX_EPOCH)?.subsec_nanos() as u64 | 1;
+    let mut stdout = std::io::stdout();
+
+    for c in line.chars() {
+        print!("{}", c);
+        stdout.flush()?;
+
+        // xorshift is plenty for keystroke jitter
+        rng ^= rng << 13;
+        rng ^= rng >> 7;
+        rng ^= rng << 17;
+        let mut delay = BASE_DELAY_MS + rng % JITTER_MS;
+        if c == ' ' {
+            delay += BASE_DELAY_MS;
+        }
+
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+    }
+
+    Ok(())
+}
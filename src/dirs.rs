@@ -0,0 +1,91 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Where the shell keeps its files, as the XDG base directory spec has it:
+// settings and what users add to it (templates, scenarios) in the config
+// directory, and what it writes down as it runs (history, logs, progress)
+// in the state directory. macOS has its own place for both, unless the XDG
+// variables say otherwise.
+const APP: &str = "shellbasics";
+
+#[derive(Clone, Copy)]
+enum Base {
+    Config,
+    State,
+}
+
+// Files from before the XDG layout, in the home directory, and where each
+// belongs now
+const LEGACY: [(&str, Base, &str); 9] = [
+    (".shellbasicsrc", Base::Config, "config.toml"),
+    (".shellbasics/templates", Base::Config, "templates"),
+    (".shellbasics_scenarios", Base::Config, "scenarios"),
+    (".shellbasics_audit", Base::State, "history"),
+    (".shellbasics_history_key", Base::State, "history_key"),
+    (".shellbasics_journal", Base::State, "journal"),
+    (".shellbasics_logs", Base::State, "logs"),
+    (".shellbasics_practice", Base::State, "practice"),
+    (".shellbasics_tutorial", Base::State, "tutorial"),
+];
+
+pub fn config_dir() -> PathBuf {
+    base("XDG_CONFIG_HOME", ".config").join(APP)
+}
+
+pub fn state_dir() -> PathBuf {
+    base("XDG_STATE_HOME", ".local/state").join(APP)
+}
+
+fn home() -> PathBuf {
+    PathBuf::from(std::env::var("HOME").unwrap_or_else(|_| ".".to_string()))
+}
+
+// `$variable`, or the platform's place under the home directory. The spec
+// says to ignore a relative path in the variable.
+fn base(variable: &str, default: &str) -> PathBuf {
+    match std::env::var(variable) {
+        Ok(dir) if Path::new(&dir).is_absolute() => PathBuf::from(dir),
+        _ if cfg!(target_os = "macos") => home().join("Library/Application Support"),
+        _ => home().join(default),
+    }
+}
+
+fn dir(base: Base) -> PathBuf {
+    match base {
+        Base::Config => config_dir(),
+        Base::State => state_dir(),
+    }
+}
+
+// Creates the directories and moves the files older versions left in the
+// home directory into them, unless something is in their place already.
+// Returns each file found, where it was to go and how the move went.
+pub fn migrate() -> Vec<(PathBuf, PathBuf, io::Result<()>)> {
+    let moves: Vec<_> = leftovers()
+        .into_iter()
+        .map(|(from, to)| {
+            let moved = fs::create_dir_all(to.parent().unwrap_or(Path::new("."))).and_then(|_| fs::rename(&from, &to));
+            (from, to, moved)
+        })
+        .collect();
+    // ~/.shellbasics held nothing but the templates, and the history's lock
+    // file nothing at all
+    let _ = fs::remove_dir(home().join(".shellbasics"));
+    let _ = fs::remove_file(home().join(".shellbasics_audit.lock"));
+    for base in [Base::Config, Base::State] {
+        let _ = fs::create_dir_all(dir(base));
+    }
+    moves
+}
+
+// Files older versions left in the home directory with nothing in their new
+// place yet, and that place
+pub fn leftovers() -> Vec<(PathBuf, PathBuf)> {
+    let home = home();
+    LEGACY
+        .iter()
+        .map(|(old, base, new)| (home.join(old), dir(*base).join(new)))
+        .filter(|(old, new)| fs::symlink_metadata(old).is_ok() && fs::symlink_metadata(new).is_err())
+        .collect()
+}
@@ -0,0 +1,116 @@
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+
+use anyhow::anyhow;
+use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use hickory_resolver::error::ResolveErrorKind;
+use hickory_resolver::proto::rr::{RData, RecordType};
+use hickory_resolver::TokioAsyncResolver;
+use tokio::task::JoinSet;
+
+use crate::command::ResolveOptions;
+use crate::errors::CrateResult;
+use crate::i18n::trf;
+
+// What `resolve` asks for when no -t is given
+pub const RECORD_TYPES: [&str; 5] = ["A", "AAAA", "CNAME", "MX", "TXT"];
+
+// One record of an answer
+#[derive(Debug, PartialEq)]
+pub struct Answer {
+    pub kind: String,
+    pub name: String,
+    pub ttl: u32,
+    pub value: String,
+}
+
+// Looks up every requested record type at once. Records that came back for
+// more than one query (a CNAME is part of the A and AAAA answers) are listed
+// once, in the order of RECORD_TYPES.
+pub async fn lookup(name: &str, options: &ResolveOptions) -> CrateResult<Vec<Answer>> {
+    let resolver = resolver(options.server.as_deref()).await?;
+    let kinds: Vec<&str> = match &options.kind {
+        Some(kind) => vec![kind.as_str()],
+        None => RECORD_TYPES.to_vec(),
+    };
+
+    let mut queries = JoinSet::new();
+    for (order, kind) in kinds.iter().enumerate() {
+        let (resolver, name, kind) = (resolver.clone(), name.to_string(), kind.parse::<RecordType>()?);
+        queries.spawn(async move { (order, resolver.lookup(name, kind).await) });
+    }
+
+    let mut results = Vec::new();
+    while let Some(result) = queries.join_next().await {
+        results.push(result?);
+    }
+    results.sort_by_key(|(order, _)| *order);
+
+    let mut seen = HashSet::new();
+    let mut answers = Vec::new();
+    let mut failure = None;
+    for (_, result) in results {
+        let lookup = match result {
+            Ok(lookup) => lookup,
+            Err(e) if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => continue,
+            Err(e) => {
+                failure.get_or_insert(e);
+                continue;
+            }
+        };
+        for record in lookup.records() {
+            let Some(data) = record.data() else {
+                continue;
+            };
+            let answer = Answer {
+                kind: record.record_type().to_string(),
+                name: record.name().to_string(),
+                ttl: record.ttl(),
+                value: format_data(data),
+            };
+            if seen.insert((answer.kind.clone(), answer.name.clone(), answer.value.clone())) {
+                answers.push(answer);
+            }
+        }
+    }
+
+    match failure {
+        Some(e) if answers.is_empty() => Err(anyhow!(trf("resolve: {}", &[&e]))),
+        _ => Ok(answers),
+    }
+}
+
+// The system's resolvers, or just `server` (an address or host name, with
+// an optional :port)
+async fn resolver(server: Option<&str>) -> CrateResult<TokioAsyncResolver> {
+    let Some(server) = server else {
+        return Ok(TokioAsyncResolver::tokio_from_system_conf()
+            .unwrap_or_else(|_| TokioAsyncResolver::tokio(ResolverConfig::default(), ResolverOpts::default())));
+    };
+
+    let unknown = || anyhow!(trf("resolve: unknown server '{}'", &[&server]));
+    let address = if let Ok(address) = server.parse::<SocketAddr>() {
+        address
+    } else if let Ok(ip) = server.parse::<IpAddr>() {
+        SocketAddr::new(ip, 53)
+    } else {
+        let with_port = if server.contains(':') { server.to_string() } else { format!("{}:53", server) };
+        tokio::net::lookup_host(with_port).await.map_err(|_| unknown())?.next().ok_or_else(unknown)?
+    };
+    let servers = NameServerConfigGroup::from_ips_clear(&[address.ip()], address.port(), true);
+    let config = ResolverConfig::from_parts(None, Vec::new(), servers);
+    Ok(TokioAsyncResolver::tokio(config, ResolverOpts::default()))
+}
+
+fn format_data(data: &RData) -> String {
+    match data {
+        // Each string of a TXT record quoted, as dig shows them
+        RData::TXT(txt) => txt
+            .txt_data()
+            .iter()
+            .map(|part| format!("\"{}\"", String::from_utf8_lossy(part)))
+            .collect::<Vec<_>>()
+            .join(" "),
+        data => data.to_string(),
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::audit;
+use crate::config;
+use crate::dirs;
+use crate::errors::{self, Access};
+use crate::i18n::{self, tr, trf};
+use crate::logging;
+use crate::state::ShellState;
+use crate::terminal;
+use crate::tokenizer::quote;
+
+// A PATH that finds the usual programs, for when it has none
+const DEFAULT_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+
+// Something `doctor` found wrong, and what to do about it
+pub struct Problem {
+    pub what: String,
+    pub fix: String,
+}
+
+// Everything `doctor` looks at, in the order it reports it
+pub fn diagnose(state: &ShellState) -> Vec<Problem> {
+    let mut problems = directories();
+    problems.extend(rc_file());
+    problems.extend(history());
+    problems.extend(path_problems(state.var("PATH").unwrap_or_default(), |dir| Path::new(dir).is_dir(), |dir| Path::new(dir).exists()));
+    problems.extend(terminal_problems(state));
+    problems.extend(locale_problems(state));
+    problems
+}
+
+// The directories the settings, history and logs are written to, and the
+// files that belong in them
+fn directories() -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let rc = config::rc_path();
+    let config_dir = errors::parent_of(&rc);
+    if !config_dir.is_dir() {
+        problems.push(Problem {
+            what: trf("The directory for the settings, {}, doesn't exist", &[&config_dir.display()]),
+            fix: format!("mkdir -p {}", quote(&config_dir.to_string_lossy())),
+        });
+    } else if !errors::has_access(&config_dir, Access::Write) {
+        problems.push(Problem {
+            what: trf("{} can't be written to, so abbreviations and the history aren't saved", &[&config_dir.display()]),
+            fix: format!("chmod u+w {}", quote(&config_dir.to_string_lossy())),
+        });
+    }
+    // What an older version left in the home directory and the move at
+    // startup couldn't put away
+    for (old, new) in dirs::leftovers() {
+        problems.push(Problem {
+            what: trf("{} is left from an older version, so the shell doesn't use it", &[&old.display()]),
+            fix: format!("mv {} {}", quote(&old.to_string_lossy()), quote(&new.to_string_lossy())),
+        });
+    }
+    let logs = logging::log_dir();
+    if logs.exists() && !logs.is_dir() {
+        problems.push(Problem {
+            what: trf("{} is in the way of the log directory", &[&logs.display()]),
+            fix: format!("mv {} {}.old", quote(&logs.to_string_lossy()), quote(&logs.to_string_lossy())),
+        });
+    }
+    problems
+}
+
+fn rc_file() -> Vec<Problem> {
+    match config::read() {
+        Ok(_) => Vec::new(),
+        Err(e) => vec![Problem {
+            what: e.to_string(),
+            fix: trf("Correct {}, then run reload", &[&config::rc_path().display()]),
+        }],
+    }
+}
+
+fn history() -> Vec<Problem> {
+    let path = audit::audit_path();
+    let shown = quote(&path.to_string_lossy());
+    if path.is_dir() {
+        return vec![Problem { what: trf("The history file {} is a directory", &[&path.display()]), fix: format!("mv {} {}.old", shown, shown) }];
+    }
+    if path.exists() && !(errors::has_access(&path, Access::Read) && errors::has_access(&path, Access::Write)) {
+        return vec![Problem {
+            what: trf("The history file {} can't be read or written, so Up and `history` miss earlier sessions", &[&path.display()]),
+            fix: format!("chmod u+rw {}", shown),
+        }];
+    }
+    Vec::new()
+}
+
+// What is wrong with `path` as PATH: entries that are relative, missing,
+// not directories or repeated. Each fix is the PATH without any of them.
+fn path_problems(path: &str, is_dir: impl Fn(&str) -> bool, exists: impl Fn(&str) -> bool) -> Vec<Problem> {
+    if path.is_empty() {
+        return vec![Problem { what: tr("PATH is empty, so no programs can be found").to_string(), fix: format!("export PATH={}", DEFAULT_PATH) }];
+    }
+    let mut seen = HashSet::new();
+    let mut kept = Vec::new();
+    let mut found = Vec::new();
+    for dir in path.split(':') {
+        let what = if dir.is_empty() {
+            tr("PATH has an empty entry, which runs programs from whatever directory the shell is in").to_string()
+        } else if !dir.starts_with('/') {
+            trf("PATH has {}, which is relative to whatever directory the shell is in", &[&dir])
+        } else if !seen.insert(dir) {
+            trf("PATH has {} more than once", &[&dir])
+        } else if !exists(dir) {
+            trf("PATH has {}, which doesn't exist", &[&dir])
+        } else if !is_dir(dir) {
+            trf("PATH has {}, which isn't a directory", &[&dir])
+        } else {
+            kept.push(dir);
+            continue;
+        };
+        found.push(what);
+    }
+    let cleaned = if kept.is_empty() { DEFAULT_PATH.to_string() } else { kept.join(":") };
+    found.into_iter().map(|what| Problem { what, fix: format!("export PATH={}", quote(&cleaned)) }).collect()
+}
+
+fn terminal_problems(state: &ShellState) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    match state.var("TERM") {
+        None | Some("") => problems.push(Problem {
+            what: tr("TERM isn't set, so programs don't know what the terminal can do").to_string(),
+            fix: "export TERM=xterm-256color".to_string(),
+        }),
+        Some("dumb") => problems.push(Problem {
+            what: tr("TERM is dumb, so there are no colors and programs like less and vim can't draw").to_string(),
+            fix: "export TERM=xterm-256color".to_string(),
+        }),
+        Some(_) => {}
+    }
+    if terminal::is_ansi_terminal() && terminal::window_size().is_none() {
+        problems.push(Problem {
+            what: tr("The terminal doesn't tell its size, so long lines and menus wrap at 80 columns").to_string(),
+            fix: tr("Resize the window once, or run stty cols and stty rows with its size").to_string(),
+        });
+    }
+    problems
+}
+
+fn locale_problems(state: &ShellState) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"].iter().filter_map(|name| state.var(name)).find(|value| !value.is_empty());
+    match locale {
+        None => problems.push(Problem {
+            what: tr("No locale is set, so programs may garble text that isn't ASCII").to_string(),
+            fix: "export LANG=C.UTF-8".to_string(),
+        }),
+        Some(locale) if !locale.to_lowercase().replace('-', "").contains("utf8") => problems.push(Problem {
+            what: trf("The locale {} isn't UTF-8, so programs may garble text that isn't ASCII", &[&locale]),
+            fix: "export LANG=C.UTF-8".to_string(),
+        }),
+        Some(_) => {}
+    }
+    if let Some(locale) = config::get().locale.as_deref().filter(|locale| !i18n::is_supported(locale)) {
+        problems.push(Problem {
+            what: trf("There are no messages in the language of locale = \"{}\", so they are in English", &[&locale]),
+            fix: trf("Set locale = \"es\" in {}, or take the line out", &[&config::rc_path().display()]),
+        });
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_path_problems() {
+        let dirs = ["/usr/bin", "/bin"];
+        let problems = path_problems("/usr/bin::bin:/usr/bin:/gone:/bin/sh:/bin", |dir| dirs.contains(&dir), |dir| dir != "/gone");
+        let found: Vec<&str> = problems.iter().map(|problem| problem.what.as_str()).collect();
+        assert_eq!(found.len(), 5, "{:?}", found);
+        assert!(found[2].contains("more than once") && found[3].contains("/gone"));
+        assert!(problems.iter().all(|problem| problem.fix == "export PATH=/usr/bin:/bin"));
+        assert!(path_problems("/usr/bin:/bin", |_| true, |_| true).is_empty());
+        assert_eq!(path_problems("", |_| true, |_| true)[0].fix, format!("export PATH={}", DEFAULT_PATH));
+    }
+}
@@ -0,0 +1,123 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use reqwest::header::{CONTENT_RANGE, RANGE};
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::command::DownloadOptions;
+use crate::errors::CrateResult;
+use crate::helpers;
+use crate::i18n::trf;
+use crate::interrupt;
+use crate::terminal::Progress;
+
+// What a finished download did
+pub struct Downloaded {
+    pub size: u64,
+    // Bytes kept from an earlier, interrupted attempt
+    pub resumed: u64,
+}
+
+// The file a URL is saved to when -o isn't given: the last part of its path
+pub fn file_name(url: &str) -> CrateResult<String> {
+    let url = reqwest::Url::parse(url).map_err(|e| anyhow!(trf("download: bad URL '{}': {}", &[&url, &e])))?;
+    let name = url.path_segments().and_then(|segments| segments.rev().find(|segment| !segment.is_empty()));
+    Ok(name.unwrap_or("index.html").to_string())
+}
+
+// Downloads `url` to `target`. The data goes to `<target>.part` first; if
+// that file is already there from an interrupted download, only the rest is
+// asked for with a Range request, and servers that ignore it send it all again.
+pub async fn download(url: &str, target: &Path, options: &DownloadOptions, show_progress: bool) -> CrateResult<Downloaded> {
+    let partial = part_path(target);
+    let existing = tokio::fs::metadata(&partial).await.map(|metadata| metadata.len()).unwrap_or(0);
+
+    let client = reqwest::Client::new();
+    let mut request = client.get(url);
+    if existing > 0 {
+        request = request.header(RANGE, format!("bytes={}-", existing));
+    }
+    let mut response = request.send().await?;
+
+    // 416: the part file already holds everything
+    if existing > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        return finish(&partial, target, options, existing, existing).await;
+    }
+    let response_status = response.status();
+    if !response_status.is_success() {
+        return Err(anyhow!(trf("download: {} answered {}", &[&url, &response_status])));
+    }
+
+    let resumed = if response_status == StatusCode::PARTIAL_CONTENT && starts_at(&response) == Some(existing) { existing } else { 0 };
+    let size = response.content_length().map(|length| length + resumed).unwrap_or(0);
+    let mut file = tokio::fs::OpenOptions::new().create(true).write(true).append(resumed > 0).truncate(resumed == 0).open(&partial).await?;
+
+    let name = target.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let mut progress = show_progress.then(|| Progress::new(&trf("Downloading {}", &[&name]), size));
+    if let Some(progress) = &mut progress {
+        progress.skip(resumed);
+    }
+    let mut done = resumed;
+    let result = async {
+        while let Some(chunk) = response.chunk().await? {
+            if interrupt::requested() {
+                return Err(anyhow!(trf("interrupted after {}; run it again to resume", &[&helpers::human_size(done)])));
+            }
+            file.write_all(&chunk).await?;
+            done += chunk.len() as u64;
+            if let Some(progress) = &mut progress {
+                progress.advance(chunk.len() as u64);
+            }
+        }
+        file.flush().await?;
+        Ok(())
+    }
+    .await;
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+    result?;
+
+    finish(&partial, target, options, done, resumed).await
+}
+
+// Checks the checksum if one was given, then moves the part file into place
+async fn finish(partial: &Path, target: &Path, options: &DownloadOptions, size: u64, resumed: u64) -> CrateResult<Downloaded> {
+    if let Some(expected) = &options.sha256 {
+        let actual = sha256(partial).await?;
+        if !actual.eq_ignore_ascii_case(expected) {
+            tokio::fs::remove_file(partial).await?;
+            return Err(anyhow!(trf("download: checksum mismatch, expected {} but got {}; the download was removed", &[expected, &actual])));
+        }
+    }
+    tokio::fs::rename(partial, target).await?;
+    Ok(Downloaded { size, resumed })
+}
+
+fn part_path(target: &Path) -> PathBuf {
+    let mut name = target.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    target.with_file_name(name)
+}
+
+// First byte of a 206 response, from `Content-Range: bytes 100-199/200`
+fn starts_at(response: &reqwest::Response) -> Option<u64> {
+    let range = response.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+    range.strip_prefix("bytes ")?.split('-').next()?.parse().ok()
+}
+
+async fn sha256(path: &Path) -> CrateResult<String> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
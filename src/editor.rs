@@ -0,0 +1,787 @@
+use std::io::{self, BufRead, Read, Write};
+use std::ops::Range;
+use std::sync::Mutex;
+
+use colored::{ColoredString, Colorize};
+use serde::Deserialize;
+
+use crate::completion::{self, Candidate, Completion};
+use crate::config;
+use crate::helpers;
+use crate::i18n::trf;
+use crate::output::Editing;
+use crate::terminal::{self, Keys, RawMode};
+use crate::theme;
+
+// What the user did with the line
+#[derive(Debug, PartialEq)]
+pub enum Input {
+    Line(String),
+    // Ctrl+C: the line was thrown away
+    Interrupted,
+    // Ctrl+D on an empty line
+    Eof,
+}
+
+// Text deleted with Ctrl+K, Ctrl+U and Ctrl+W, newest last. As in readline
+// it lasts for the session, so a kill can be yanked into a later line.
+static KILL_RING: Mutex<Vec<String>> = Mutex::new(Vec::new());
+const KILL_RING_SIZE: usize = 10;
+
+// What a key bound under [keymap] in the settings file does
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    // The line before or after, whatever was typed
+    PreviousHistory,
+    NextHistory,
+    // Lines starting with what was typed, as readline's history-search
+    HistorySearchBackward,
+    HistorySearchForward,
+    // Lines with what was typed anywhere in them, as zsh's plugin
+    HistorySubstringSearchBackward,
+    HistorySubstringSearchForward,
+}
+
+// The keys [keymap] can bind and what they do by default
+pub const KEYMAP: &[(&str, Action)] = &[
+    ("up", Action::HistorySearchBackward),
+    ("down", Action::HistorySearchForward),
+    ("ctrl-p", Action::PreviousHistory),
+    ("ctrl-n", Action::NextHistory),
+];
+
+// Reads one line from the terminal with basic editing: the arrows, Home/End,
+// Backspace/Delete, Ctrl+A/E/U/K/W, Ctrl+Y/Alt+Y, Alt+B/F, Ctrl+L, Tab
+// completion and Up/Down through `history`, oldest first. The prompt is
+// printed here, after raw mode is on, so keys typed early aren't echoed
+// twice. Falls back to a plain read when stdin isn't a terminal.
+pub fn read_line(prompt: &str, complete: &dyn Fn(&str, usize) -> Completion, history: &[String]) -> io::Result<Input> {
+    read(prompt, "", complete, history)
+}
+
+// Like read_line, starting from `text` with the cursor at its end
+pub fn edit_line(prompt: &str, text: &str, complete: &dyn Fn(&str, usize) -> Completion) -> io::Result<Input> {
+    read(prompt, text, complete, &[])
+}
+
+fn read(prompt: &str, text: &str, complete: &dyn Fn(&str, usize) -> Completion, history: &[String]) -> io::Result<Input> {
+    let Some(_raw) = RawMode::enable_for_editing() else {
+        print!("{}", prompt);
+        io::stdout().flush()?;
+        let mut line = String::new();
+        return match io::stdin().lock().read_line(&mut line)? {
+            0 => Ok(Input::Eof),
+            _ => Ok(Input::Line(line.trim_end_matches(['\n', '\r']).to_string())),
+        };
+    };
+
+    let mut editor = Editor {
+        prompt_width: terminal::visible_width(prompt.rsplit('\n').next().unwrap_or_default()),
+        chars: text.chars().collect(),
+        cursor: text.chars().count(),
+        shown: 0,
+        last: Last::Other,
+        out: String::new(),
+        editing: Editing::start(prompt),
+    };
+    editor.out.push_str(prompt);
+    editor.out.push_str(text);
+    editor.shown_at_end();
+    editor.flush()?;
+
+    // A key that closed the completion menu, handled like any other
+    let mut pending = None;
+    loop {
+        let Some(key) = pending.take().map_or_else(|| read_key(&mut Keys), |key| Ok(Some(key)))? else {
+            return Ok(Input::Eof);
+        };
+        // Kills in a row add to one entry, and Alt+Y only follows a yank
+        let previous = std::mem::replace(&mut editor.last, Last::Other);
+        match key {
+            Key::Char(' ') => {
+                editor.expand_abbreviation();
+                editor.insert(&[' ']);
+            }
+            Key::Char(c) => editor.insert(&[c]),
+            Key::Enter => {
+                editor.move_to(editor.chars.len());
+                editor.expand_abbreviation();
+                editor.out.push('\n');
+                editor.flush()?;
+                return Ok(Input::Line(editor.chars.iter().collect()));
+            }
+            Key::Control('c') => {
+                editor.move_to(editor.chars.len());
+                editor.out.push_str("^C\n");
+                editor.flush()?;
+                return Ok(Input::Interrupted);
+            }
+            Key::Control('d') if editor.chars.is_empty() => {
+                editor.out.push('\n');
+                editor.flush()?;
+                return Ok(Input::Eof);
+            }
+            Key::Control('d') | Key::Delete => editor.delete(editor.cursor, editor.cursor + 1),
+            Key::Backspace => editor.delete(editor.cursor.saturating_sub(1), editor.cursor),
+            Key::Left => editor.move_to(editor.cursor.saturating_sub(1)),
+            Key::Right => editor.move_to((editor.cursor + 1).min(editor.chars.len())),
+            Key::Home | Key::Control('a') => editor.move_to(0),
+            Key::End | Key::Control('e') => editor.move_to(editor.chars.len()),
+            Key::Control('u') => editor.kill(0, editor.cursor, previous),
+            Key::Control('k') => editor.kill(editor.cursor, editor.chars.len(), previous),
+            Key::Control('w') => {
+                let mut start = editor.cursor;
+                while start > 0 && editor.chars[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                while start > 0 && !editor.chars[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                editor.kill(start, editor.cursor, previous);
+            }
+            Key::Control('y') => editor.yank(),
+            Key::Alt('y') => editor.yank_previous(previous),
+            Key::Alt('b') => editor.move_to(editor.word_start()),
+            Key::Alt('f') => editor.move_to(editor.word_end()),
+            Key::Control('l') => editor.clear_screen(),
+            Key::Tab => {
+                let line: String = editor.chars.iter().collect();
+                pending = editor.complete(complete(&line, editor.cursor))?;
+            }
+            key => {
+                if let Some(action) = bound(&key) {
+                    editor.browse(action, history, previous);
+                }
+            }
+        }
+        editor.flush()?;
+    }
+}
+
+struct Editor {
+    // Columns taken by the part of the prompt on the line being edited
+    prompt_width: usize,
+    chars: Vec<char>,
+    cursor: usize,
+    // Where the terminal's cursor is, in columns from the start of the
+    // prompt's last line, as `terminal::cursor_motion` counts them
+    shown: usize,
+    // What the key before did, for the kill ring and history search
+    last: Last,
+    // Output for the terminal, written once per key
+    out: String,
+    // Lets background jobs print without breaking into the line
+    editing: Editing,
+}
+
+enum Last {
+    Other,
+    Kill,
+    // Where the yanked text went
+    Yank(usize, usize),
+    History(Browse),
+}
+
+// A walk through the history with Up and Down
+struct Browse {
+    // What was typed when it began, which searches look for
+    typed: Vec<char>,
+    // The entry shown; the history's length for the typed line
+    index: usize,
+}
+
+// What the keymap does with `key`, if it is one it can bind
+fn bound(key: &Key) -> Option<Action> {
+    let name = match key {
+        Key::Up => "up",
+        Key::Down => "down",
+        Key::Control('p') => "ctrl-p",
+        Key::Control('n') => "ctrl-n",
+        _ => return None,
+    };
+    if let Some(action) = config::get().keymap.get(name) {
+        return Some(*action);
+    }
+    KEYMAP.iter().find(|(key, _)| *key == name).map(|(_, action)| *action)
+}
+
+fn kill_ring() -> std::sync::MutexGuard<'static, Vec<String>> {
+    KILL_RING.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Editor {
+    fn insert(&mut self, text: &[char]) {
+        let from = self.cursor;
+        self.chars.splice(from..from, text.iter().copied());
+        self.cursor += text.len();
+        // Typing at the end only needs the new text echoed
+        if self.cursor == self.chars.len() {
+            self.out.extend(text);
+            self.shown_at_end();
+        } else {
+            self.redraw(from);
+        }
+    }
+
+    fn delete(&mut self, from: usize, to: usize) {
+        let to = to.min(self.chars.len());
+        if from >= to {
+            return;
+        }
+        self.chars.drain(from..to);
+        self.cursor = from;
+        self.redraw(from);
+    }
+
+    // Deletes like `delete` and keeps the text for Ctrl+Y. Straight after
+    // another kill it joins that entry, in front when killing backwards.
+    fn kill(&mut self, from: usize, to: usize, previous: Last) {
+        let to = to.min(self.chars.len());
+        if from >= to {
+            self.last = previous;
+            return;
+        }
+        let text: String = self.chars[from..to].iter().collect();
+        {
+            let mut ring = kill_ring();
+            match ring.last_mut() {
+                Some(top) if matches!(previous, Last::Kill) && from < self.cursor => top.insert_str(0, &text),
+                Some(top) if matches!(previous, Last::Kill) => top.push_str(&text),
+                _ => {
+                    ring.push(text);
+                    if ring.len() > KILL_RING_SIZE {
+                        ring.remove(0);
+                    }
+                }
+            }
+        }
+        self.delete(from, to);
+        self.last = Last::Kill;
+    }
+
+    // Inserts the newest kill at the cursor
+    fn yank(&mut self) {
+        let Some(text) = kill_ring().last().cloned() else {
+            self.out.push('\x07');
+            return;
+        };
+        let start = self.cursor;
+        self.insert(&text.chars().collect::<Vec<_>>());
+        self.last = Last::Yank(start, self.cursor);
+    }
+
+    // Right after a yank, swaps the yanked text for the kill before it,
+    // going round the ring
+    fn yank_previous(&mut self, previous: Last) {
+        let Last::Yank(start, end) = previous else {
+            self.out.push('\x07');
+            return;
+        };
+        let text = {
+            let mut ring = kill_ring();
+            let newest = ring.pop().unwrap_or_default();
+            ring.insert(0, newest);
+            ring.last().cloned().unwrap_or_default()
+        };
+        self.chars.splice(start..end, text.chars());
+        self.cursor = start + text.chars().count();
+        self.redraw(start);
+        self.last = Last::Yank(start, self.cursor);
+    }
+
+    // Replaces an abbreviation just typed as the command with what it
+    // stands for, as fish does, so the line shows what will run
+    fn expand_abbreviation(&mut self) {
+        if self.chars.get(self.cursor).is_some_and(|c| !c.is_whitespace()) {
+            return;
+        }
+        let start = self.chars[..self.cursor].iter().rposition(|c| c.is_whitespace()).map_or(0, |index| index + 1);
+        if start == self.cursor || self.chars[..start].iter().any(|c| !c.is_whitespace()) {
+            return;
+        }
+        let word: String = self.chars[start..self.cursor].iter().collect();
+        let Some(expansion) = config::get().abbr.get(&word).cloned() else {
+            return;
+        };
+        self.chars.splice(start..self.cursor, expansion.chars());
+        self.cursor = start + expansion.chars().count();
+        self.redraw(start);
+    }
+
+    // Shows the next entry back or forward in the history that `action`
+    // accepts, skipping ones that look like the line already shown. Going
+    // forward past the newest brings back what was typed.
+    fn browse(&mut self, action: Action, history: &[String], previous: Last) {
+        let mut browse = match previous {
+            Last::History(browse) => browse,
+            _ => Browse { typed: self.chars.clone(), index: history.len() },
+        };
+        let query: String = browse.typed.iter().collect();
+        let shown: String = self.chars.iter().collect();
+        let accepts = |entry: &String| {
+            *entry != shown
+                && match action {
+                    Action::PreviousHistory | Action::NextHistory => true,
+                    Action::HistorySearchBackward | Action::HistorySearchForward => entry.starts_with(&query),
+                    Action::HistorySubstringSearchBackward | Action::HistorySubstringSearchForward => entry.contains(&query),
+                }
+        };
+        let backward = matches!(action, Action::PreviousHistory | Action::HistorySearchBackward | Action::HistorySubstringSearchBackward);
+        let found = match backward {
+            true => history[..browse.index].iter().rposition(accepts),
+            false => history.get(browse.index + 1..).and_then(|later| later.iter().position(accepts)).map(|index| browse.index + 1 + index),
+        };
+        match found {
+            Some(index) => {
+                browse.index = index;
+                self.chars = history[index].chars().collect();
+            }
+            None if !backward && browse.index < history.len() => {
+                browse.index = history.len();
+                self.chars = browse.typed.clone();
+            }
+            None => {
+                self.out.push('\x07');
+                self.last = Last::History(browse);
+                return;
+            }
+        }
+        self.cursor = self.chars.len();
+        self.redraw(0);
+        self.last = Last::History(browse);
+    }
+
+    // Where Alt+B goes: the start of the word before the cursor, words
+    // being letters and digits as in readline
+    fn word_start(&self) -> usize {
+        let mut position = self.cursor;
+        while position > 0 && !self.chars[position - 1].is_alphanumeric() {
+            position -= 1;
+        }
+        while position > 0 && self.chars[position - 1].is_alphanumeric() {
+            position -= 1;
+        }
+        position
+    }
+
+    // Where Alt+F goes: the end of the word after the cursor
+    fn word_end(&self) -> usize {
+        let mut position = self.cursor;
+        while position < self.chars.len() && !self.chars[position].is_alphanumeric() {
+            position += 1;
+        }
+        while position < self.chars.len() && self.chars[position].is_alphanumeric() {
+            position += 1;
+        }
+        position
+    }
+
+    fn move_to(&mut self, position: usize) {
+        self.cursor = position;
+        self.go(position);
+    }
+
+    // Moves the terminal's cursor to `position` in the text, across
+    // wrapped rows. The end of text that fills its last row is reached by
+    // writing the last character again, so the cursor waits at the margin
+    // the way typing leaves it.
+    fn go(&mut self, position: usize) {
+        let target = self.prompt_width + position;
+        match position > 0 && position == self.chars.len() && target.is_multiple_of(helpers::terminal_width().max(1)) {
+            true => {
+                self.motion_to(target - 1);
+                self.out.push(self.chars[position - 1]);
+                self.shown = target - 1;
+            }
+            false => self.motion_to(target),
+        }
+    }
+
+    fn motion_to(&mut self, column: usize) {
+        self.out.push_str(&terminal::cursor_motion(self.shown, column, helpers::terminal_width()));
+        self.shown = column;
+    }
+
+    // Rewrites the text from `from` on, clearing what was left of a longer
+    // line below it, and puts the cursor back. Rows before `from` stay as
+    // they are.
+    fn redraw(&mut self, from: usize) {
+        self.motion_to(self.prompt_width + from);
+        self.out.push_str("\x1b[J");
+        if from < self.chars.len() {
+            self.out.extend(&self.chars[from..]);
+            self.shown_at_end();
+        }
+        self.go(self.cursor);
+    }
+
+    // After the text was written out to its end
+    fn shown_at_end(&mut self) {
+        self.shown = terminal::written_to(self.prompt_width + self.chars.len(), helpers::terminal_width());
+    }
+
+    // Starts the screen afresh with the prompt and the line at the top
+    fn clear_screen(&mut self) {
+        let text: String = self.chars.iter().collect();
+        self.out.push_str(&format!("{}{}{}", terminal::CLEAR_SCREEN, self.editing.prompt(), text));
+        self.shown_at_end();
+        self.go(self.cursor);
+    }
+
+    // One candidate is filled in; several are narrowed to what they share,
+    // or offered in a menu when they share nothing more. Returns the key
+    // that closed the menu, if it still has to be handled.
+    fn complete(&mut self, completion: Completion) -> io::Result<Option<Key>> {
+        let typed = self.cursor - completion.start;
+        let replacement = match completion.candidates.as_slice() {
+            [] => {
+                self.out.push('\x07');
+                return Ok(None);
+            }
+            // A directory or host: the rest of the path comes next
+            [only] if only.word.ends_with(['/', ':']) => only.word.clone(),
+            [only] => format!("{} ", only.word),
+            candidates => {
+                let common = completion::common_prefix(candidates);
+                if common.chars().count() <= typed {
+                    return self.menu(completion.start, candidates);
+                }
+                common
+            }
+        };
+
+        let replacement: Vec<char> = replacement.chars().collect();
+        if replacement.starts_with(&self.chars[completion.start..self.cursor]) {
+            self.insert(&replacement[typed..]);
+        } else {
+            self.replace_word(completion.start, &replacement);
+        }
+        Ok(None)
+    }
+
+    // Lays `candidates` out in columns under the line, as zsh's menu
+    // completion. Tab, Shift+Tab and the arrows pick one, which goes into
+    // the line straight away; Enter keeps it, Ctrl+C or Ctrl+G brings back
+    // what was typed, and any other key keeps it and is handed back.
+    fn menu(&mut self, start: usize, candidates: &[Candidate]) -> io::Result<Option<Key>> {
+        let typed: Vec<char> = self.chars[start..self.cursor].to_vec();
+        let command = self.chars[..start].iter().all(|c| c.is_whitespace());
+        let mut menu = Menu::new(candidates, command);
+        loop {
+            self.draw_menu(&mut menu);
+            self.flush()?;
+            let key = read_key(&mut Keys)?;
+            let count = candidates.len();
+            let next = match (key, menu.selected) {
+                (Some(Key::Tab | Key::Right), selected) => selected.map_or(0, |index| (index + 1) % count),
+                (Some(Key::BackTab | Key::Left), selected) => selected.map_or(count - 1, |index| (index + count - 1) % count),
+                (Some(Key::Down), None) => 0,
+                (Some(Key::Up), None) => count - 1,
+                (Some(Key::Down), Some(index)) => menu.vertical(index, true),
+                (Some(Key::Up), Some(index)) => menu.vertical(index, false),
+                (Some(Key::Control('c' | 'g')), _) => {
+                    self.replace_word(start, &typed);
+                    self.erase_menu();
+                    return Ok(None);
+                }
+                (Some(Key::Enter) | None, _) => {
+                    self.erase_menu();
+                    return Ok(None);
+                }
+                (key, _) => {
+                    self.erase_menu();
+                    return Ok(key);
+                }
+            };
+            menu.selected = Some(next);
+            self.replace_word(start, &candidates[next].word.chars().collect::<Vec<_>>());
+        }
+    }
+
+    // Puts `word` in place of the text from `start` to the cursor
+    fn replace_word(&mut self, start: usize, word: &[char]) {
+        self.chars.splice(start..self.cursor, word.iter().copied());
+        self.cursor = start + word.len();
+        self.redraw(start);
+    }
+
+    // Draws the menu's lines below the line's last row and comes back up.
+    // Only as many lines as fit under the line are shown, scrolled to keep
+    // the pick in view, with a line saying which ones when not all fit.
+    fn draw_menu(&mut self, menu: &mut Menu) {
+        let (columns, height) = terminal::window_size().unwrap_or((helpers::terminal_width(), 24));
+        menu.lay_out(columns);
+        let rows = menu.lines.len();
+        let line_row = self.last_row(columns);
+        let room = height.saturating_sub(line_row + 3).max(1);
+        let shown = rows.min(room);
+        if let Some(row) = menu.selected.map(|index| menu.line_of(index)) {
+            if row < menu.top {
+                // With the group's heading when it is just above
+                menu.top = if row > 0 && matches!(menu.lines[row - 1], Line::Heading(_)) { row - 1 } else { row };
+            } else if row >= menu.top + shown {
+                menu.top = row + 1 - shown;
+            }
+        }
+
+        self.motion_to(line_row * columns);
+        for row in menu.top..menu.top + shown {
+            self.out.push_str("\n\x1b[2K");
+            match &menu.lines[row] {
+                Line::Heading(heading) => self.out.push_str(&heading.bold().to_string()),
+                Line::Row(indices, described) => {
+                    for index in indices.clone() {
+                        let cell = menu.cell(index, *described);
+                        self.out.push_str(&cell);
+                    }
+                }
+            }
+        }
+        let mut lines = shown;
+        if shown < rows {
+            self.out.push_str("\n\x1b[2K");
+            self.out.push_str(&trf("rows {} to {} of {}", &[&(menu.top + 1), &(menu.top + shown), &rows]).bright_black().to_string());
+            lines += 1;
+        }
+        self.out.push_str(&format!("\x1b[J\x1b[{}A\r", lines));
+        self.shown = line_row * columns;
+        self.go(self.cursor);
+    }
+
+    // Clears what is below the line's last row
+    fn erase_menu(&mut self) {
+        let columns = helpers::terminal_width().max(1);
+        self.motion_to(self.last_row(columns) * columns);
+        self.out.push_str("\x1b[B\x1b[J\x1b[A");
+        self.go(self.cursor);
+    }
+
+    // The row the text ends on, counted from the prompt's last line
+    fn last_row(&self, columns: usize) -> usize {
+        terminal::written_to(self.prompt_width + self.chars.len(), columns) / columns.max(1)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let text: String = self.chars.iter().collect();
+        self.editing.write(&std::mem::take(&mut self.out), &text, self.chars.len() - self.cursor)
+    }
+}
+
+// The completion menu on screen
+struct Menu<'a> {
+    candidates: &'a [Candidate],
+    // Candidates as listed: paths by their last component
+    names: Vec<String>,
+    // Whether they are commands, which are colored as such
+    command: bool,
+    selected: Option<usize>,
+    lines: Vec<Line>,
+    // Columns for a name, the same in every group so they line up
+    name_width: usize,
+    // The first line shown when not all fit
+    top: usize,
+}
+
+// A group's heading, or a row of candidates by index with the columns
+// their descriptions get
+enum Line {
+    Heading(&'static str),
+    Row(Range<usize>, usize),
+}
+
+impl Menu<'_> {
+    fn new(candidates: &[Candidate], command: bool) -> Menu<'_> {
+        let names = candidates
+            .iter()
+            .map(|candidate| {
+                let word = &candidate.word;
+                let trimmed = word.trim_end_matches('/');
+                let start = trimmed.rfind('/').map(|slash| slash + 1).unwrap_or(0);
+                word[start..].to_string()
+            })
+            .collect();
+        Menu { candidates, names, command, selected: None, lines: Vec::new(), name_width: 0, top: 0 }
+    }
+
+    // Fills rows as wide as `columns` with as many candidates as fit,
+    // starting a new row under a heading for each group when there are
+    // several
+    fn lay_out(&mut self, columns: usize) {
+        let usable = columns.saturating_sub(1).max(1);
+        self.name_width = self.names.iter().map(|name| name.chars().count()).max().unwrap_or(0).min(usable);
+        let grouped = self.candidates.windows(2).any(|pair| pair[0].group != pair[1].group);
+
+        self.lines.clear();
+        let mut start = 0;
+        while start < self.candidates.len() {
+            let group = self.candidates[start].group;
+            let end = start + self.candidates[start..].iter().take_while(|candidate| candidate.group == group).count();
+            if let (true, Some(heading)) = (grouped, group) {
+                self.lines.push(Line::Heading(heading));
+            }
+            let longest = self.candidates[start..end].iter().map(|candidate| candidate.description.chars().count()).max().unwrap_or(0);
+            // "  -- " goes before a description
+            let described = longest.min(usable.saturating_sub(self.name_width + 5));
+            let per_row = (usable / self.cell_width(described)).max(1);
+            for row in (start..end).step_by(per_row) {
+                self.lines.push(Line::Row(row..(row + per_row).min(end), described));
+            }
+            start = end;
+        }
+    }
+
+    // A name, its description if any, and two spaces before the next
+    fn cell_width(&self, described: usize) -> usize {
+        match described {
+            0 => self.name_width + 2,
+            width => self.name_width + 5 + width + 2,
+        }
+    }
+
+    // A candidate padded to the cell width, with its description dimmed
+    fn cell(&self, index: usize, described: usize) -> String {
+        let name: String = self.names[index].chars().take(self.name_width).collect();
+        let painted = paint(&name, self.command);
+        let painted = if self.selected == Some(index) { painted.reversed() } else { painted };
+        let mut cell = format!("{}{}", painted, " ".repeat(self.name_width - name.chars().count()));
+        let description: String = self.candidates[index].description.chars().take(described).collect();
+        if !description.is_empty() {
+            cell.push_str(&format!("  {}", format!("-- {}", description).bright_black()));
+        }
+        let used = self.name_width + if description.is_empty() { 0 } else { 5 + description.chars().count() };
+        cell.push_str(&" ".repeat(self.cell_width(described) - used));
+        cell
+    }
+
+    fn line_of(&self, index: usize) -> usize {
+        self.lines.iter().position(|line| matches!(line, Line::Row(indices, _) if indices.contains(&index))).unwrap_or(0)
+    }
+
+    // The candidate in the same column one row down or up, going round at
+    // the end, and the last of a shorter row
+    fn vertical(&self, index: usize, down: bool) -> usize {
+        let line = self.line_of(index);
+        let Line::Row(current, _) = &self.lines[line] else {
+            return index;
+        };
+        let column = index - current.start;
+        let count = self.lines.len();
+        let rows = (1..=count).map(|step| if down { (line + step) % count } else { (line + count - step) % count });
+        for row in rows {
+            if let Line::Row(indices, _) = &self.lines[row] {
+                return (indices.start + column).min(indices.end - 1);
+            }
+        }
+        index
+    }
+}
+
+// A candidate in the menu, colored by what it is the way ls colors it
+fn paint(name: &str, command: bool) -> ColoredString {
+    if name.ends_with('/') {
+        theme::paint("directory", name).bold()
+    } else if command {
+        theme::paint("executable", name)
+    } else if [".rs", ".toml", ".json", ".md"].iter().any(|extension| name.ends_with(extension)) {
+        theme::paint("source", name)
+    } else {
+        name.normal()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Key {
+    Char(char),
+    // Ctrl plus a letter
+    Control(char),
+    // Alt plus a key, which terminals send as Esc and the key
+    Alt(char),
+    Enter,
+    Tab,
+    // Shift+Tab
+    BackTab,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Unknown,
+}
+
+// Decodes the next key press; None at end of input
+pub fn read_key(input: &mut impl Read) -> io::Result<Option<Key>> {
+    let Some(byte) = read_byte(input)? else {
+        return Ok(None);
+    };
+    let key = match byte {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f | 0x08 => Key::Backspace,
+        0x1b => read_escape(input)?,
+        0x01..=0x1a => Key::Control((b'a' + byte - 1) as char),
+        0x00..=0x1f => Key::Unknown,
+        _ => {
+            // A UTF-8 sequence is as long as its first byte says
+            let length = match byte {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            let mut bytes = vec![byte];
+            for _ in 1..length {
+                bytes.extend(read_byte(input)?);
+            }
+            match std::str::from_utf8(&bytes).ok().and_then(|text| text.chars().next()) {
+                Some(c) => Key::Char(c),
+                None => Key::Unknown,
+            }
+        }
+    };
+    Ok(Some(key))
+}
+
+// ESC [ or ESC O sequences sent by the arrow and editing keys, or Esc and
+// a key for Alt
+fn read_escape(input: &mut impl Read) -> io::Result<Key> {
+    let kind = match read_byte(input)? {
+        Some(kind @ (b'[' | b'O')) => kind,
+        Some(byte) if byte.is_ascii_graphic() => return Ok(Key::Alt(byte as char)),
+        _ => return Ok(Key::Unknown),
+    };
+    let mut parameters = String::new();
+    let end = loop {
+        match read_byte(input)? {
+            Some(byte @ 0x40..=0x7e) if kind == b'O' || byte != b'[' => break byte,
+            Some(byte) => parameters.push(byte as char),
+            None => return Ok(Key::Unknown),
+        }
+    };
+    Ok(match (end, parameters.as_str()) {
+        (b'A', _) => Key::Up,
+        (b'B', _) => Key::Down,
+        (b'C', _) => Key::Right,
+        (b'D', _) => Key::Left,
+        (b'H', _) | (b'~', "1" | "7") => Key::Home,
+        (b'F', _) | (b'~', "4" | "8") => Key::End,
+        (b'~', "3") => Key::Delete,
+        (b'Z', _) => Key::BackTab,
+        _ => Key::Unknown,
+    })
+}
+
+fn read_byte(input: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut byte = [0u8; 1];
+    loop {
+        match input.read(&mut byte) {
+            Ok(0) => return Ok(None),
+            Ok(_) => return Ok(Some(byte[0])),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use std::fs;
+use std::num::NonZeroU32;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use anyhow::anyhow;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::Deserialize;
+
+use crate::config;
+use crate::dirs;
+use crate::errors::CrateResult;
+use crate::i18n::{tr, trf};
+use crate::keyring;
+use crate::terminal;
+
+// Salt and passphrase check for `encrypt_history = "passphrase"`
+const KEY_FILE: &str = "history_key";
+// The keyring entry holding the key for `encrypt_history = "keyring"`
+pub const KEYRING_ENTRY: &str = "history-key";
+// Marks an encrypted history line: the prefix, then the nonce and the
+// ciphertext in hex
+const PREFIX: &str = "enc1:";
+// Sealed into the key file so a wrong passphrase is noticed before the
+// history turns to noise
+const CHECK: &str = "shellbasics";
+const ITERATIONS: u32 = 200_000;
+const ATTEMPTS: usize = 3;
+
+// Where the key protecting the history at rest comes from
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryEncryption {
+    // Asked for when the shell starts
+    Passphrase,
+    // A random key kept in the OS keyring
+    Keyring,
+}
+
+static KEY: OnceLock<LessSafeKey> = OnceLock::new();
+
+pub fn enabled() -> bool {
+    config::get().encrypt_history.is_some()
+}
+
+// Whether history can be read and written this session
+pub fn unlocked() -> bool {
+    KEY.get().is_some()
+}
+
+fn key_path() -> PathBuf {
+    dirs::state_dir().join(KEY_FILE)
+}
+
+// Fetches or derives the key. A passphrase is only asked for when `prompt`
+// allows it; until unlocked nothing is written to the history.
+pub fn unlock(prompt: bool) -> CrateResult<()> {
+    let Some(mode) = config::get().encrypt_history else {
+        return Ok(());
+    };
+    if unlocked() {
+        return Ok(());
+    }
+    let key = match mode {
+        HistoryEncryption::Keyring => keyring_key()?,
+        HistoryEncryption::Passphrase if prompt => passphrase_key()?,
+        HistoryEncryption::Passphrase => return Err(anyhow!(tr("the history passphrase can only be entered in an interactive session"))),
+    };
+    let _ = KEY.set(key);
+    Ok(())
+}
+
+fn keyring_key() -> CrateResult<LessSafeKey> {
+    let bytes = match keyring::get(KEYRING_ENTRY)? {
+        Some(stored) => from_hex(&stored).filter(|bytes| bytes.len() == CHACHA20_POLY1305.key_len()).ok_or_else(|| anyhow!(trf("the keyring entry {} is not a history key", &[&KEYRING_ENTRY])))?,
+        None => {
+            let mut bytes = vec![0; CHACHA20_POLY1305.key_len()];
+            SystemRandom::new().fill(&mut bytes).map_err(|_| anyhow!(tr("no random numbers available")))?;
+            keyring::set(KEYRING_ENTRY, &to_hex(&bytes))?;
+            bytes
+        }
+    };
+    key(&bytes)
+}
+
+fn passphrase_key() -> CrateResult<LessSafeKey> {
+    let path = key_path();
+    let Ok(text) = fs::read_to_string(&path) else {
+        return new_passphrase();
+    };
+    let field = |name: &str| text.lines().find_map(|line| line.strip_prefix(name)?.strip_prefix(' ')).map(str::to_string);
+    let (Some(salt), Some(check)) = (field("salt").and_then(|salt| from_hex(&salt)), field("check")) else {
+        return Err(anyhow!(trf("{} is damaged; remove it and the encrypted history to start over", &[&path.display()])));
+    };
+
+    for _ in 0..ATTEMPTS {
+        let Some(passphrase) = terminal::read_hidden(tr("History passphrase: ")) else {
+            break;
+        };
+        let key = derive(&passphrase, &salt)?;
+        if open_with(&key, &check).as_deref() == Some(CHECK) {
+            return Ok(key);
+        }
+        eprintln!("{}", tr("Wrong passphrase"));
+    }
+    Err(anyhow!(tr("the history passphrase was not entered")))
+}
+
+// First use: the passphrase is asked for twice and a new salt written down
+fn new_passphrase() -> CrateResult<LessSafeKey> {
+    println!("{}", tr("Choose a passphrase for the encrypted history. It can't be recovered if forgotten."));
+    let passphrase = terminal::read_hidden(tr("New history passphrase: ")).filter(|passphrase| !passphrase.is_empty());
+    let again = terminal::read_hidden(tr("Repeat it: "));
+    let Some(passphrase) = passphrase.filter(|passphrase| Some(passphrase) == again.as_ref()) else {
+        return Err(anyhow!(tr("the passphrases were empty or didn't match")));
+    };
+
+    let mut salt = [0; 16];
+    SystemRandom::new().fill(&mut salt).map_err(|_| anyhow!(tr("no random numbers available")))?;
+    let key = derive(&passphrase, &salt)?;
+    let check = seal_with(&key, CHECK)?;
+    fs::write(key_path(), format!("salt {}\ncheck {}\n", to_hex(&salt), check))?;
+    Ok(key)
+}
+
+fn derive(passphrase: &str, salt: &[u8]) -> CrateResult<LessSafeKey> {
+    let mut bytes = [0; 32];
+    let iterations = NonZeroU32::new(ITERATIONS).expect("iterations are non-zero");
+    ring::pbkdf2::derive(ring::pbkdf2::PBKDF2_HMAC_SHA256, iterations, salt, passphrase.as_bytes(), &mut bytes);
+    key(&bytes)
+}
+
+fn key(bytes: &[u8]) -> CrateResult<LessSafeKey> {
+    let key = UnboundKey::new(&CHACHA20_POLY1305, bytes).map_err(|_| anyhow!(tr("the history key has the wrong length")))?;
+    Ok(LessSafeKey::new(key))
+}
+
+// The line as it goes to disk, or None while locked
+pub fn seal(text: &str) -> Option<String> {
+    seal_with(KEY.get()?, text).ok()
+}
+
+// A line read from disk: encrypted ones are decrypted, or None while locked
+// or if they were tampered with; plain ones come back as they are
+pub fn open(line: &str) -> Option<String> {
+    if !is_sealed(line) {
+        return Some(line.to_string());
+    }
+    open_with(KEY.get()?, line)
+}
+
+pub fn is_sealed(line: &str) -> bool {
+    line.starts_with(PREFIX)
+}
+
+fn seal_with(key: &LessSafeKey, text: &str) -> CrateResult<String> {
+    let mut nonce = [0; NONCE_LEN];
+    SystemRandom::new().fill(&mut nonce).map_err(|_| anyhow!(tr("no random numbers available")))?;
+    let mut data = text.as_bytes().to_vec();
+    key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut data)
+        .map_err(|_| anyhow!(tr("encryption failed")))?;
+    Ok(format!("{}{}{}", PREFIX, to_hex(&nonce), to_hex(&data)))
+}
+
+fn open_with(key: &LessSafeKey, line: &str) -> Option<String> {
+    let bytes = from_hex(line.strip_prefix(PREFIX)?)?;
+    if bytes.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, data) = bytes.split_at(NONCE_LEN);
+    let mut data = data.to_vec();
+    let nonce = Nonce::try_assume_unique_for_key(nonce).ok()?;
+    let text = key.open_in_place(nonce, Aad::empty(), &mut data).ok()?;
+    String::from_utf8(text.to_vec()).ok()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seals_and_opens() {
+        let key = derive("correct horse", b"0123456789abcdef").unwrap();
+        let sealed = seal_with(&key, "1700000000\tok\tcd ~/secret").unwrap();
+        assert!(is_sealed(&sealed) && !sealed.contains("secret"));
+        assert_eq!(open_with(&key, &sealed).as_deref(), Some("1700000000\tok\tcd ~/secret"));
+        // A fresh nonce each time
+        assert_ne!(seal_with(&key, "ls").unwrap(), seal_with(&key, "ls").unwrap());
+
+        let wrong = derive("battery staple", b"0123456789abcdef").unwrap();
+        assert_eq!(open_with(&wrong, &sealed), None);
+        let last = if sealed.ends_with('0') { "1" } else { "0" };
+        let tampered = format!("{}{}", &sealed[..sealed.len() - 1], last);
+        assert_eq!(open_with(&key, &tampered), None);
+        assert_eq!(from_hex(&to_hex(&[0, 15, 255])).unwrap(), [0, 15, 255]);
+    }
+}
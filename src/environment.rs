@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use crate::state::ShellState;
+
+// How a variable differs from when the shell started
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    Added(String, String),
+    Removed(String, String),
+    // Name, old value, new value
+    Changed(String, String, String),
+}
+
+impl Change {
+    pub fn name(&self) -> &str {
+        match self {
+            Change::Added(name, _) | Change::Removed(name, _) | Change::Changed(name, _, _) => name,
+        }
+    }
+}
+
+// Variables set, unset or changed since startup, sorted by name
+pub fn changes(startup: &[(String, String)], current: &HashMap<String, String>) -> Vec<Change> {
+    let before: HashMap<&str, &str> = startup.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect();
+    let mut changes: Vec<Change> = before
+        .iter()
+        .filter_map(|(name, old)| match current.get(*name) {
+            None => Some(Change::Removed(name.to_string(), old.to_string())),
+            Some(new) if new != old => Some(Change::Changed(name.to_string(), old.to_string(), new.clone())),
+            Some(_) => None,
+        })
+        .collect();
+    changes.extend(
+        current
+            .iter()
+            .filter(|(name, _)| !before.contains_key(name.as_str()))
+            .map(|(name, value)| Change::Added(name.clone(), value.clone())),
+    );
+    changes.sort_by(|a, b| a.name().cmp(b.name()));
+    changes
+}
+
+// The session's variables: the inherited ones in the order the shell got
+// them, then those added since, or all of them by name with `sorted`
+pub fn variables(state: &ShellState, sorted: bool) -> Vec<(&str, &str)> {
+    let mut variables: Vec<(&str, &str)> = state
+        .startup_env
+        .iter()
+        .filter_map(|(name, _)| state.env.get_key_value(name))
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+    let mut added: Vec<(&str, &str)> = state
+        .env
+        .iter()
+        .filter(|(name, _)| !state.startup_env.iter().any(|(startup, _)| startup == *name))
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+    added.sort();
+    variables.extend(added);
+    if sorted {
+        variables.sort();
+    }
+    variables
+}
+
+// Letters, digits and underscores, not starting with a digit
+pub fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_what_changed_since_startup() {
+        let startup = vec![("HOME".to_string(), "/home/a".to_string()), ("LANG".to_string(), "C".to_string()), ("OLD".to_string(), "1".to_string())];
+        let current = HashMap::from([
+            ("HOME".to_string(), "/home/a".to_string()),
+            ("LANG".to_string(), "es_ES.UTF-8".to_string()),
+            ("EDITOR".to_string(), "vi".to_string()),
+        ]);
+        assert_eq!(
+            changes(&startup, &current),
+            vec![
+                Change::Added("EDITOR".to_string(), "vi".to_string()),
+                Change::Changed("LANG".to_string(), "C".to_string(), "es_ES.UTF-8".to_string()),
+                Change::Removed("OLD".to_string(), "1".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_variable_names() {
+        assert!(is_name("PATH") && is_name("_x1"));
+        assert!(!is_name("") && !is_name("1X") && !is_name("A-B"));
+    }
+}
@@ -1 +1,184 @@
+use std::ffi::CString;
+use std::fmt;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use crate::i18n::{tr, trf};
+use crate::sysinfo;
+
 pub type CrateResult<T> = anyhow::Result<T>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+// Why the kernel refused an operation with EACCES/EPERM/EROFS
+#[derive(Debug)]
+pub enum PermissionCause {
+    ReadOnlyFilesystem,
+    NotOwner { owner: String },
+    MissingBit { mode: u32 },
+    Unknown,
+}
+
+#[derive(Debug)]
+pub struct PermissionError {
+    pub path: PathBuf,
+    pub access: Access,
+    pub cause: PermissionCause,
+    source: io::Error,
+}
+
+impl fmt::Display for PermissionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path.display();
+        let verb = match self.access {
+            Access::Read => tr("read"),
+            Access::Write => tr("write to"),
+        };
+
+        match &self.cause {
+            PermissionCause::ReadOnlyFilesystem => write!(
+                f,
+                "{}",
+                trf(
+                    "Cannot {} '{}': it is on a read-only filesystem. Remount it read-write or use a location on a writable disk.",
+                    &[&verb, &path]
+                )
+            ),
+            PermissionCause::NotOwner { owner } => write!(
+                f,
+                "{}",
+                trf(
+                    "Cannot {} '{}': it is owned by '{}' and not accessible to you. Ask the owner for access or run `sudo chown {} '{}'`.",
+                    &[&verb, &path, owner, &current_user(), &path]
+                )
+            ),
+            PermissionCause::MissingBit { mode } => {
+                let (bit, label) = match (self.access, Path::new(&self.path).is_dir()) {
+                    (Access::Read, true) => ("rx", tr("readable")),
+                    (Access::Read, false) => ("r", tr("readable")),
+                    (Access::Write, true) => ("wx", tr("writable")),
+                    (Access::Write, false) => ("w", tr("writable")),
+                };
+                write!(
+                    f,
+                    "{}",
+                    trf(
+                        "Cannot {} '{}': it is not {} (mode {}). Run `chmod u+{} '{}'` to fix it.",
+                        &[&verb, &path, &label, &format!("{:o}", mode & 0o777), &bit, &path]
+                    )
+                )
+            }
+            PermissionCause::Unknown => write!(f, "{}", trf("Cannot {} '{}': {}", &[&verb, &path, &self.source])),
+        }
+    }
+}
+
+impl std::error::Error for PermissionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+// A command that worked but answered no, like a false `test`: it fails the
+// line without an error message
+#[derive(Debug)]
+pub struct False;
+
+impl fmt::Display for False {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", tr("the condition is false"))
+    }
+}
+
+impl std::error::Error for False {}
+
+// Turns permission failures into an actionable PermissionError about `target`
+// (the path whose permissions matter, e.g. the parent directory when creating
+// or removing an entry). Other errors are passed through unchanged.
+pub fn with_path(err: io::Error, target: &Path, access: Access) -> anyhow::Error {
+    match err.raw_os_error() {
+        Some(libc::EACCES) | Some(libc::EPERM) | Some(libc::EROFS) => {
+            let cause = diagnose(&err, target, access);
+            PermissionError { path: target.to_path_buf(), access, cause, source: err }.into()
+        }
+        _ => err.into(),
+    }
+}
+
+// The directory whose write bit governs creating or removing `path`
+pub fn parent_of(path: impl AsRef<Path>) -> PathBuf {
+    match path.as_ref().parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+pub fn has_access(path: &Path, access: Access) -> bool {
+    let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    let mode = match access {
+        Access::Read => libc::R_OK,
+        Access::Write => libc::W_OK,
+    };
+    unsafe { libc::access(c_path.as_ptr(), mode) == 0 }
+}
+
+// Whether we may enter the directory `path` (its search bit)
+pub fn can_search(path: &Path) -> bool {
+    let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    unsafe { libc::access(c_path.as_ptr(), libc::X_OK) == 0 }
+}
+
+fn diagnose(err: &io::Error, target: &Path, access: Access) -> PermissionCause {
+    if err.raw_os_error() == Some(libc::EROFS) || (access == Access::Write && is_read_only_mount(target)) {
+        return PermissionCause::ReadOnlyFilesystem;
+    }
+
+    let Ok(metadata) = std::fs::metadata(target) else {
+        return PermissionCause::Unknown;
+    };
+
+    let mode = metadata.permissions().mode();
+    if metadata.uid() != unsafe { libc::geteuid() } {
+        return PermissionCause::NotOwner { owner: sysinfo::user_name(metadata.uid()) };
+    }
+
+    let required = match (access, metadata.is_dir()) {
+        (Access::Read, true) => 0o500,
+        (Access::Read, false) => 0o400,
+        (Access::Write, true) => 0o300,
+        (Access::Write, false) => 0o200,
+    };
+
+    if mode & required != required {
+        PermissionCause::MissingBit { mode }
+    } else {
+        PermissionCause::Unknown
+    }
+}
+
+fn is_read_only_mount(path: &Path) -> bool {
+    // statvfs needs an existing path, so walk up until we find one
+    let Some(existing) = path.ancestors().find(|p| p.exists()) else {
+        return false;
+    };
+    let Ok(c_path) = CString::new(existing.as_os_str().as_bytes()) else {
+        return false;
+    };
+
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    unsafe { libc::statvfs(c_path.as_ptr(), &mut stats) == 0 && stats.f_flag & libc::ST_RDONLY != 0 }
+}
+
+fn current_user() -> String {
+    sysinfo::user_name(unsafe { libc::geteuid() })
+}
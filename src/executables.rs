@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard};
+use std::thread;
+use std::time::Duration;
+
+// How often the index is built again, for programs installed or removed
+// meanwhile
+const REFRESH: Duration = Duration::from_secs(60);
+
+// The programs on one PATH by name. The first directory with a name wins,
+// as it does when the program runs.
+struct Index {
+    path: String,
+    programs: BTreeMap<String, PathBuf>,
+}
+
+static INDEX: RwLock<Option<Index>> = RwLock::new(None);
+
+// Programs that have run, as bash's hash remembers them: where each was
+// found and how many times it ran. It belongs to one PATH, and setting
+// another forgets it all.
+struct Hash {
+    path: String,
+    programs: BTreeMap<String, Hashed>,
+}
+
+#[derive(Clone)]
+pub struct Hashed {
+    pub path: PathBuf,
+    pub hits: usize,
+}
+
+static HASH: Mutex<Hash> = Mutex::new(Hash { path: String::new(), programs: BTreeMap::new() });
+
+fn index() -> RwLockReadGuard<'static, Option<Index>> {
+    INDEX.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn hash(path: &str) -> MutexGuard<'static, Hash> {
+    let mut hash = HASH.lock().unwrap_or_else(|e| e.into_inner());
+    if hash.path != path {
+        hash.path = path.to_string();
+        hash.programs.clear();
+    }
+    hash
+}
+
+// Indexes `path` on a thread of its own, now and every minute after, so
+// lookups at the prompt don't wait for the directories to be read
+pub fn start(path: String) {
+    thread::spawn(move || loop {
+        // The PATH looked up last, which `export` may have changed
+        let path = index().as_ref().map_or_else(|| path.clone(), |index| index.path.clone());
+        let fresh = build(path);
+        {
+            let mut index = INDEX.write().unwrap_or_else(|e| e.into_inner());
+            if index.as_ref().is_none_or(|index| index.path == fresh.path) {
+                *index = Some(fresh);
+            }
+        }
+        thread::sleep(REFRESH);
+    });
+}
+
+// Where `name` runs from with `path` as PATH
+pub fn find(name: &str, path: &str) -> Option<PathBuf> {
+    with_index(path, |programs| programs.get(name).cloned())
+}
+
+// Names of the programs on `path` that start with `prefix`, in order
+pub fn starting_with(prefix: &str, path: &str) -> Vec<String> {
+    with_index(path, |programs| {
+        programs.range(prefix.to_string()..).take_while(|(name, _)| name.starts_with(prefix)).map(|(name, _)| name.clone()).collect()
+    })
+}
+
+// Where to run the program `name` from, counted as a hit: the path it had
+// last time, or the one found now, which is remembered. None for a path
+// with a slash, which runs as it is, or a program not on `path`.
+pub fn resolve(name: &str, path: &str) -> Option<PathBuf> {
+    remember(name, path, 1)
+}
+
+// Where the shell's own use of a program, like git for the prompt, runs it
+// from: the same path as `resolve`, without counting or remembering it
+pub fn locate(name: &str, path: &str) -> Option<PathBuf> {
+    if name.contains('/') {
+        return None;
+    }
+    if let Some(hashed) = hash(path).programs.get(name).filter(|hashed| runnable(&hashed.path)) {
+        return Some(hashed.path.clone());
+    }
+    lookup(name, path)
+}
+
+// Looks `name` up, adding `hits` to its count. A program that has gone is
+// looked for again, and so is one the index doesn't have yet.
+pub fn remember(name: &str, path: &str, hits: usize) -> Option<PathBuf> {
+    if name.contains('/') {
+        return None;
+    }
+    let mut hash = hash(path);
+    if let Some(hashed) = hash.programs.get_mut(name).filter(|hashed| runnable(&hashed.path)) {
+        hashed.hits += hits;
+        return Some(hashed.path.clone());
+    }
+    hash.programs.remove(name);
+    let found = lookup(name, path)?;
+    hash.programs.insert(name.to_string(), Hashed { path: found.clone(), hits });
+    Some(found)
+}
+
+// From the index, or else from the directories on `path`, putting right
+// the one entry of the index that was missing or out of date
+fn lookup(name: &str, path: &str) -> Option<PathBuf> {
+    if let Some(found) = find(name, path).filter(|found| runnable(found)) {
+        return Some(found);
+    }
+    let found = path.split(':').filter(|dir| !dir.is_empty()).map(|dir| Path::new(dir).join(name)).find(|found| runnable(found));
+    if let Some(index) = INDEX.write().unwrap_or_else(|e| e.into_inner()).as_mut().filter(|index| index.path == path) {
+        match &found {
+            Some(found) => index.programs.insert(name.to_string(), found.clone()),
+            None => index.programs.remove(name),
+        };
+    }
+    found
+}
+
+// The programs remembered, by name, leaving out any that have gone since
+pub fn hashed(path: &str) -> Vec<(String, Hashed)> {
+    let mut hash = hash(path);
+    hash.programs.retain(|_, hashed| runnable(&hashed.path));
+    hash.programs.iter().map(|(name, hashed)| (name.clone(), hashed.clone())).collect()
+}
+
+// Forgets one program; false if it wasn't remembered
+pub fn forget(name: &str, path: &str) -> bool {
+    hash(path).programs.remove(name).is_some()
+}
+
+pub fn forget_all(path: &str) {
+    hash(path).programs.clear();
+}
+
+// Reads the directories on `path` again now rather than at the next refresh
+pub fn rebuild(path: &str) {
+    *INDEX.write().unwrap_or_else(|e| e.into_inner()) = Some(build(path.to_string()));
+}
+
+// Runs `f` on the index of `path`, built first if PATH changed since the
+// last one
+fn with_index<T>(path: &str, f: impl FnOnce(&BTreeMap<String, PathBuf>) -> T) -> T {
+    if let Some(index) = index().as_ref().filter(|index| index.path == path) {
+        return f(&index.programs);
+    }
+    let fresh = build(path.to_string());
+    let result = f(&fresh.programs);
+    *INDEX.write().unwrap_or_else(|e| e.into_inner()) = Some(fresh);
+    result
+}
+
+fn build(path: String) -> Index {
+    let mut programs = BTreeMap::new();
+    // An empty entry is the working directory, which changes too often to index
+    for dir in path.split(':').filter(|dir| !dir.is_empty()) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if runnable(&entry.path()) {
+                programs.entry(name).or_insert_with(|| entry.path());
+            }
+        }
+    }
+    Index { path, programs }
+}
+
+// An executable file, following links to what they point at
+fn runnable(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn earlier_directories_win() {
+        let dir = tempfile::tempdir().unwrap();
+        for (sub, name, mode) in [("a", "tool", 0o755), ("b", "tool", 0o755), ("b", "toolkit", 0o755), ("b", "notes", 0o644)] {
+            let path = dir.path().join(sub);
+            fs::create_dir_all(&path).unwrap();
+            fs::write(path.join(name), "").unwrap();
+            fs::set_permissions(path.join(name), fs::Permissions::from_mode(mode)).unwrap();
+        }
+        let path = format!("{}:{}", dir.path().join("a").display(), dir.path().join("b").display());
+        assert_eq!(find("tool", &path), Some(dir.path().join("a/tool")));
+        assert_eq!(find("notes", &path), None);
+        assert_eq!(starting_with("too", &path), ["tool", "toolkit"]);
+    }
+
+    #[test]
+    fn only_resolve_counts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().display().to_string();
+        assert_eq!(locate("late", &path), None);
+        // Installed after the lookup above indexed the directory
+        fs::write(dir.path().join("late"), "").unwrap();
+        fs::set_permissions(dir.path().join("late"), fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(locate("late", &path), Some(dir.path().join("late")));
+        assert!(hashed(&path).is_empty());
+        assert_eq!(resolve("late", &path), Some(dir.path().join("late")));
+        assert_eq!(hashed(&path)[0].1.hits, 1);
+    }
+}
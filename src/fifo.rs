@@ -0,0 +1,50 @@
+use std::ffi::CString;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
+use std::time::Duration;
+
+use tokio::io::AsyncReadExt;
+use tokio::net::unix::pipe;
+
+use crate::errors::{self, Access, CrateResult};
+use crate::interrupt;
+
+// Permissions for new pipes, before the umask
+pub const DEFAULT_MODE: u32 = 0o666;
+
+pub fn is_fifo(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_fifo())
+}
+
+pub fn create(path: &Path, mode: u32) -> CrateResult<()> {
+    let name = CString::new(path.as_os_str().as_bytes())?;
+    if unsafe { libc::mkfifo(name.as_ptr(), mode as libc::mode_t) } != 0 {
+        let e = std::io::Error::last_os_error();
+        return Err(errors::with_path(e, &errors::parent_of(path), Access::Write));
+    }
+    Ok(())
+}
+
+// Everything written to the pipe until its last writer closes it. Waiting for
+// a writer doesn't hold up the shell's other tasks, and Ctrl+C gives up on it
+// (None).
+pub async fn read(path: &Path) -> CrateResult<Option<Vec<u8>>> {
+    let mut receiver = pipe::OpenOptions::new().open_receiver(path).map_err(|e| errors::with_path(e, path, Access::Read))?;
+    let mut data = Vec::new();
+    let mut buffer = [0; 8192];
+    loop {
+        tokio::select! {
+            read = receiver.read(&mut buffer) => match read? {
+                0 => return Ok(Some(data)),
+                count => data.extend_from_slice(&buffer[..count]),
+            },
+            _ = tokio::time::sleep(Duration::from_millis(100)) => {
+                if interrupt::requested() {
+                    return Ok(None);
+                }
+            }
+        }
+    }
+}
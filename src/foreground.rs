@@ -0,0 +1,167 @@
+use std::io::{self, IsTerminal};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{ChildStdout, Command, ExitStatus, Stdio};
+
+use colored::Colorize;
+
+use crate::i18n::tr;
+use crate::interrupt;
+use crate::jobs;
+use crate::terminal;
+
+// Starts a program on the terminal and waits for it to end, or for Ctrl+Z
+// to stop it. With job control it gets a process group of its own, which
+// owns the terminal meanwhile, so Ctrl+C and Ctrl+Z reach it and not the shell.
+pub fn run(command: &mut Command, line: &str) -> io::Result<ExitStatus> {
+    join_group(command, 0);
+    let mut terminal = Handoff::save();
+    // A program that fails to start may have taken the terminal before its
+    // exec failed, so the shell takes it back then too
+    terminal.given = interrupt::job_control() && io::stdin().is_terminal();
+    let child = command.spawn()?;
+    terminal.give(child.id());
+    wait(child.id(), line, None)
+}
+
+// Starts the stages of a pipeline together, each reading what the one
+// before writes through a pipe, and waits for them all. They share the
+// first one's process group, so the terminal, Ctrl+C and Ctrl+Z treat them
+// as one program, and a job stopped with Ctrl+Z is kept by that first pid.
+// Returns how the last stage ended, or the stop.
+pub fn run_pipeline(commands: Vec<Command>, line: &str) -> io::Result<ExitStatus> {
+    let mut terminal = Handoff::save();
+    terminal.given = interrupt::job_control() && io::stdin().is_terminal();
+    let count = commands.len();
+    let mut pids: Vec<u32> = Vec::new();
+    let mut input: Option<ChildStdout> = None;
+    for (index, mut command) in commands.into_iter().enumerate() {
+        join_group(&mut command, pids.first().copied().unwrap_or(0) as libc::pid_t);
+        if let Some(input) = input.take() {
+            command.stdin(input);
+        }
+        if index + 1 < count {
+            command.stdout(Stdio::piped());
+        }
+        match command.spawn() {
+            Ok(mut child) => {
+                input = child.stdout.take();
+                pids.push(child.id());
+            }
+            Err(e) => {
+                // The stages already started would wait for a reader or the
+                // terminal forever
+                for pid in &pids {
+                    unsafe { libc::kill(*pid as libc::pid_t, libc::SIGTERM) };
+                    let _ = wait_for(*pid, 0);
+                }
+                return Err(e);
+            }
+        }
+        // `command` goes here, and with it the shell's end of the pipe, so a
+        // stage sees the end of its input, or a broken pipe, when its
+        // neighbour exits
+    }
+    terminal.give(pids[0]);
+    let flags = if interrupt::job_control() { libc::WUNTRACED } else { 0 };
+    let mut status = 0;
+    for pid in &pids {
+        status = wait_for(*pid, flags)?;
+        if libc::WIFSTOPPED(status) {
+            suspend(line, pids[0], None);
+            break;
+        }
+    }
+    Ok(ExitStatus::from_raw(status))
+}
+
+// With job control, puts the program in process group `group`, or one of
+// its own for 0, and has it take the terminal itself too, in case it reads
+// from it before the shell gets round to giving it
+fn join_group(command: &mut Command, group: libc::pid_t) {
+    if !interrupt::job_control() {
+        return;
+    }
+    command.process_group(group);
+    if io::stdin().is_terminal() {
+        unsafe {
+            command.pre_exec(|| {
+                libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+                libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+                libc::signal(libc::SIGTTOU, libc::SIG_DFL);
+                Ok(())
+            });
+        }
+    }
+}
+
+// Continues the suspended job `id` in the foreground, with the terminal
+// settings it had when it stopped
+pub fn resume(job: &jobs::Job, pid: u32) -> io::Result<ExitStatus> {
+    let mut terminal = Handoff::save();
+    if let Some(modes) = &job.modes {
+        terminal::set_modes(modes);
+    }
+    terminal.give(pid);
+    if unsafe { libc::kill(-(pid as libc::pid_t), libc::SIGCONT) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    wait(pid, &job.command, Some(job.id))
+}
+
+// If Ctrl+Z stops the program, it becomes a job for `fg` and `bg`, keeping
+// its number `id` if it had one, and the stopped status comes back, which
+// callers treat as a quiet failure
+fn wait(pid: u32, line: &str, id: Option<usize>) -> io::Result<ExitStatus> {
+    // Without job control Ctrl+Z stops the shell along with the program
+    let flags = if interrupt::job_control() { libc::WUNTRACED } else { 0 };
+    let status = wait_for(pid, flags)?;
+    if libc::WIFSTOPPED(status) {
+        suspend(line, pid, id);
+    }
+    Ok(ExitStatus::from_raw(status))
+}
+
+fn wait_for(pid: u32, flags: libc::c_int) -> io::Result<libc::c_int> {
+    let mut status = 0;
+    while unsafe { libc::waitpid(pid as libc::pid_t, &mut status, flags) } < 0 {
+        let e = io::Error::last_os_error();
+        if e.kind() != io::ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+    Ok(status)
+}
+
+fn suspend(line: &str, pid: u32, id: Option<usize>) {
+    let id = jobs::suspended(line, pid, id, terminal::modes());
+    println!("\n[{}]  {}  {}", id, tr("Stopped").yellow(), line);
+}
+
+// The terminal while a program runs: its process group gets it, and when
+// dropped the shell takes it back with the settings it had, whatever the
+// program left behind (raw mode, no echo, a crashed full-screen editor).
+struct Handoff {
+    modes: Option<libc::termios>,
+    given: bool,
+}
+
+impl Handoff {
+    fn save() -> Handoff {
+        Handoff { modes: terminal::modes(), given: false }
+    }
+
+    fn give(&mut self, pid: u32) {
+        self.given = interrupt::job_control() && io::stdin().is_terminal() && unsafe { libc::tcsetpgrp(libc::STDIN_FILENO, pid as libc::pid_t) } == 0;
+    }
+}
+
+impl Drop for Handoff {
+    fn drop(&mut self) {
+        if self.given {
+            unsafe { libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp()) };
+        }
+        if let Some(modes) = &self.modes {
+            terminal::set_modes(modes);
+        }
+    }
+}
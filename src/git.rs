@@ -0,0 +1,337 @@
+use std::ffi::OsStr;
+use std::io;
+use std::path::Path;
+use std::process::{Output, Stdio};
+
+use anyhow::anyhow;
+use colored::{Color, Colorize};
+
+use crate::errors::CrateResult;
+use crate::i18n::{tr, trf};
+use crate::state::ShellState;
+use crate::theme;
+
+// What `git status` reports, grouped the way gst shows it
+#[derive(Debug, Default, PartialEq)]
+pub struct Status {
+    pub branch: String,
+    pub upstream: Option<String>,
+    pub ahead: u32,
+    pub behind: u32,
+    // (status letter, path); renames read `old -> new`
+    pub staged: Vec<(char, String)>,
+    pub unstaged: Vec<(char, String)>,
+    pub untracked: Vec<String>,
+    pub conflicted: Vec<String>,
+}
+
+// Runs git in the shell's directory and environment and returns what it
+// printed; when it fails, its own message becomes the error. The helpers use
+// the git program on PATH rather than libgit2 on purpose: it is the git the
+// user already has, with their config, hooks and credential helpers.
+pub async fn git(state: &ShellState, args: &[&str]) -> CrateResult<String> {
+    let mut command = tokio::process::Command::from(state.command("git"));
+    command.args(args).stdin(Stdio::null()).kill_on_drop(true);
+    finish(command.output().await)
+}
+
+// The same for rm and mv, which run outside any async context
+pub fn git_blocking(state: &ShellState, args: &[&OsStr]) -> CrateResult<String> {
+    finish(state.command("git").args(args).stdin(Stdio::null()).output())
+}
+
+fn finish(output: io::Result<Output>) -> CrateResult<String> {
+    let output = output.map_err(not_started)?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!(message.trim().trim_start_matches("fatal: ").to_string()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn not_started(e: io::Error) -> anyhow::Error {
+    match e.kind() {
+        io::ErrorKind::NotFound => anyhow!(tr("git was not found on PATH; gst, glog, gdiff and --git need it installed")),
+        _ => e.into(),
+    }
+}
+
+// Whether git tracks `path`, or for a directory anything under it; false
+// outside a repository. Without git that's false too, unless git is
+// `required`, as with --git, when it's an error.
+pub fn tracks(state: &ShellState, path: &Path, required: bool) -> CrateResult<bool> {
+    let output = state.command("git").args(["ls-files", "--error-unmatch", "--"]).arg(path).stdin(Stdio::null()).output();
+    match output {
+        Ok(output) => Ok(output.status.success()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !required => Ok(false),
+        Err(e) => Err(not_started(e)),
+    }
+}
+
+// git rm: deletes the file and stages the deletion. Without `force` git
+// refuses to drop changes it hasn't got a copy of.
+pub fn remove(state: &ShellState, path: &Path, recursive: bool, force: bool) -> CrateResult<()> {
+    let mut args: Vec<&OsStr> = vec!["rm".as_ref(), "-q".as_ref()];
+    if recursive {
+        args.push("-r".as_ref());
+    }
+    if force {
+        args.push("-f".as_ref());
+    }
+    args.extend(["--".as_ref(), path.as_os_str()]);
+    git_blocking(state, &args).map(drop)
+}
+
+// git mv: renames in the work tree and the index together, so the history
+// follows the file
+pub fn rename(state: &ShellState, source: &Path, destination: &Path) -> CrateResult<()> {
+    git_blocking(state, &["mv".as_ref(), "--".as_ref(), source.as_os_str(), destination.as_os_str()]).map(drop)
+}
+
+pub async fn status(state: &ShellState) -> CrateResult<Status> {
+    Ok(parse_status(&git(state, &["status", "--porcelain=v1", "--branch", "-z"]).await?))
+}
+
+pub fn print_status(status: &Status) {
+    let mut heading = format!("{} {}", tr("On branch"), theme::paint("git_branch", &status.branch).bold());
+    if let Some(upstream) = &status.upstream {
+        heading = format!("{} → {}", heading, upstream);
+    }
+    let mut drift = Vec::new();
+    if status.ahead > 0 {
+        drift.push(trf("ahead {}", &[&status.ahead]));
+    }
+    if status.behind > 0 {
+        drift.push(trf("behind {}", &[&status.behind]));
+    }
+    if !drift.is_empty() {
+        heading = format!("{} ({})", heading, drift.join(", ").yellow());
+    }
+    println!("{}", heading);
+
+    if status.staged.is_empty() && status.unstaged.is_empty() && status.untracked.is_empty() && status.conflicted.is_empty() {
+        println!("{}", theme::paint("success", tr("Nothing to commit, working tree clean")));
+        return;
+    }
+    let section = |title: &str, count: usize| println!("{}", format!("{} ({})", title, count).bold());
+    if !status.conflicted.is_empty() {
+        section(tr("Conflicts"), status.conflicted.len());
+        for path in &status.conflicted {
+            println!("  {:<10}{}", tr("both").bright_red(), path.bright_red());
+        }
+    }
+    for (title, changes, color) in [(tr("Staged"), &status.staged, Color::Green), (tr("Not staged"), &status.unstaged, Color::Red)] {
+        if !changes.is_empty() {
+            section(title, changes.len());
+            for (letter, path) in changes {
+                println!("  {:<10}{}", change_name(*letter).color(color), path.color(color));
+            }
+        }
+    }
+    if !status.untracked.is_empty() {
+        section(tr("Untracked"), status.untracked.len());
+        for path in &status.untracked {
+            println!("  {}", path.bright_black());
+        }
+    }
+}
+
+fn change_name(letter: char) -> &'static str {
+    match letter {
+        'M' => tr("modified"),
+        'A' => tr("added"),
+        'D' => tr("deleted"),
+        'R' => tr("renamed"),
+        'C' => tr("copied"),
+        'T' => tr("type"),
+        _ => tr("changed"),
+    }
+}
+
+// `git status --porcelain=v1 --branch -z`: a `## branch...upstream [ahead 1]`
+// header, then `XY path` entries, X for the index and Y for the work tree.
+// A rename or copy is followed by a second entry holding the old path.
+pub fn parse_status(output: &str) -> Status {
+    let mut status = Status::default();
+    let mut entries = output.split('\0').filter(|entry| !entry.is_empty());
+    while let Some(entry) = entries.next() {
+        if let Some(header) = entry.strip_prefix("## ") {
+            parse_branch(header, &mut status);
+            continue;
+        }
+        let (Some(code), Some(path)) = (entry.get(..2), entry.get(3..)) else {
+            continue;
+        };
+        let (x, y) = (code.chars().next().unwrap_or(' '), code.chars().nth(1).unwrap_or(' '));
+        let mut shown = path.to_string();
+        if matches!(x, 'R' | 'C') {
+            if let Some(old) = entries.next() {
+                shown = format!("{} -> {}", old, path);
+            }
+        }
+        match (x, y) {
+            ('?', '?') => status.untracked.push(shown),
+            ('!', '!') => {}
+            ('U', _) | (_, 'U') | ('A', 'A') | ('D', 'D') => status.conflicted.push(shown),
+            _ => {
+                if x != ' ' {
+                    status.staged.push((x, shown.clone()));
+                }
+                if y != ' ' {
+                    status.unstaged.push((y, path.to_string()));
+                }
+            }
+        }
+    }
+    status
+}
+
+fn parse_branch(header: &str, status: &mut Status) {
+    let (names, drift) = match header.split_once(" [") {
+        Some((names, drift)) => (names, drift.trim_end_matches(']')),
+        None => (header, ""),
+    };
+    let names = names.strip_prefix("No commits yet on ").or(names.strip_prefix("Initial commit on ")).unwrap_or(names);
+    match names.split_once("...") {
+        Some((branch, upstream)) => (status.branch, status.upstream) = (branch.to_string(), Some(upstream.to_string())),
+        None => status.branch = names.to_string(),
+    }
+    for part in drift.split(", ") {
+        match part.split_once(' ') {
+            Some(("ahead", count)) => status.ahead = count.parse().unwrap_or(0),
+            Some(("behind", count)) => status.behind = count.parse().unwrap_or(0),
+            _ => {}
+        }
+    }
+}
+
+// The last `count` commits as a graph, one line each: hash, branches and
+// tags, subject, then author and age
+pub async fn log(state: &ShellState, count: usize, path: Option<&str>) -> CrateResult<Vec<String>> {
+    let count = format!("-n{}", count);
+    let mut args = vec!["log", "--graph", "--no-color", &count, "--format=%x1f%h%x1f%D%x1f%s%x1f%an%x1f%ar"];
+    if let Some(path) = path {
+        args.extend(["--", path]);
+    }
+    Ok(git(state, &args).await?.lines().map(render_log_line).collect())
+}
+
+fn render_log_line(line: &str) -> String {
+    let mut fields = line.split('\x1f');
+    let graph = fields.next().unwrap_or_default().bright_black();
+    let [Some(hash), Some(refs), Some(subject), Some(author), Some(age)] = [(); 5].map(|_| fields.next()) else {
+        // A line of the graph between commits
+        return graph.to_string();
+    };
+
+    let mut text = format!("{}{}", graph, hash.yellow());
+    if !refs.is_empty() {
+        let refs: Vec<String> = refs.split(", ").map(paint_ref).collect();
+        text = format!("{} ({})", text, refs.join(", "));
+    }
+    format!("{} {} {}", text, subject, format!("— {}, {}", author, age).bright_black())
+}
+
+fn paint_ref(name: &str) -> String {
+    if let Some(branch) = name.strip_prefix("HEAD -> ") {
+        return format!("{} {}", "HEAD →".cyan().bold(), theme::paint("git_branch", branch).bold());
+    }
+    match name {
+        "HEAD" => name.cyan().bold().to_string(),
+        tag if tag.starts_with("tag: ") => tag.yellow().bold().to_string(),
+        // Remote-tracking branches
+        remote if remote.contains('/') => remote.red().to_string(),
+        branch => theme::paint("git_branch", branch).to_string(),
+    }
+}
+
+// Changes word by word rather than line by line, with removed words in red
+// and added words in green
+pub async fn diff(state: &ShellState, staged: bool, path: Option<&str>) -> CrateResult<Vec<String>> {
+    let mut args = vec!["diff", "--no-color", "--word-diff=porcelain"];
+    if staged {
+        args.push("--staged");
+    }
+    if let Some(path) = path {
+        args.extend(["--", path]);
+    }
+    Ok(render_word_diff(&git(state, &args).await?, colored::control::SHOULD_COLORIZE.should_colorize()))
+}
+
+// `--word-diff=porcelain` puts each run of words on its own line behind a
+// ` `, `-` or `+`, and a `~` where the text itself breaks the line. Without
+// colors, changes are marked [-like this-]{+and this+} as git does.
+fn render_word_diff(output: &str, colors: bool) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut in_hunk = false;
+    for line in output.lines() {
+        if let Some(files) = line.strip_prefix("diff --git ") {
+            in_hunk = false;
+            let name = files.split_once(" b/").map_or(files, |(_, name)| name);
+            lines.push(String::new());
+            lines.push(name.bold().to_string());
+            continue;
+        }
+        if line.starts_with("@@") {
+            in_hunk = true;
+            lines.push(line.cyan().to_string());
+            continue;
+        }
+        if !in_hunk {
+            // index, ---/+++ and mode lines say nothing a reader needs,
+            // except which files are new, gone, renamed or binary
+            if ["new file", "deleted file", "rename ", "Binary files"].iter().any(|start| line.starts_with(start)) {
+                lines.push(line.bright_black().to_string());
+            }
+            continue;
+        }
+        match line.split_at(line.len().min(1)) {
+            ("~", _) => lines.push(std::mem::take(&mut current)),
+            ("-", words) if colors => current.push_str(&words.red().strikethrough().to_string()),
+            ("+", words) if colors => current.push_str(&words.green().to_string()),
+            ("-", words) => current.push_str(&format!("[-{}-]", words)),
+            ("+", words) => current.push_str(&format!("{{+{}+}}", words)),
+            (_, words) => current.push_str(words),
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    // The blank line before the first file
+    if lines.first().is_some_and(|line| line.is_empty()) {
+        lines.remove(0);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_status() {
+        let output = "## main...origin/main [ahead 2, behind 1]\0M  staged.rs\0 M changed.rs\0MM both.rs\0R  new.rs\0old.rs\0UU clash.rs\0?? notes.txt\0";
+        let status = parse_status(output);
+        assert_eq!((status.branch.as_str(), status.upstream.as_deref(), status.ahead, status.behind), ("main", Some("origin/main"), 2, 1));
+        assert_eq!(status.staged, [('M', "staged.rs".to_string()), ('M', "both.rs".to_string()), ('R', "old.rs -> new.rs".to_string())]);
+        assert_eq!(status.unstaged, [('M', "changed.rs".to_string()), ('M', "both.rs".to_string())]);
+        assert_eq!((status.conflicted, status.untracked), (vec!["clash.rs".to_string()], vec!["notes.txt".to_string()]));
+
+        let fresh = parse_status("## No commits yet on main\0");
+        assert_eq!((fresh.branch.as_str(), fresh.upstream), ("main", None));
+    }
+
+    #[test]
+    fn renders_word_diffs() {
+        let output = "diff --git a/src/a.rs b/src/a.rs\nindex 1..2 100644\n--- a/src/a.rs\n+++ b/src/a.rs\n@@ -1 +1 @@\n let x = \n-1\n+2\n ;\n~\n";
+        assert_eq!(render_word_diff(output, false), ["src/a.rs", "@@ -1 +1 @@", "let x = [-1-]{+2+};"]);
+    }
+
+    #[test]
+    fn renders_log_lines() {
+        colored::control::set_override(false);
+        assert_eq!(render_log_line("* \x1fabc1234\x1fHEAD -> main, tag: v1\x1fFix it\x1fAda\x1f2 days ago"), "* abc1234 (HEAD → main, tag: v1) Fix it — Ada, 2 days ago");
+        assert_eq!(render_log_line("|\\"), "|\\");
+    }
+}
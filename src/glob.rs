@@ -0,0 +1,213 @@
+use std::fs;
+
+use crate::errors::CrateResult;
+use crate::state::ShellState;
+use crate::tokenizer::{quote, words};
+
+// Expands unquoted *, ? and [...] in `line` against the session's files and
+// returns the line re-quoted, one word per match. A pattern that matches
+// nothing is kept as typed, like bash without nullglob.
+pub fn expand_line(line: &str, state: &ShellState) -> CrateResult<String> {
+    let words = words(line)?;
+    if words.iter().all(|word| word.pattern.is_none()) {
+        return Ok(line.to_string());
+    }
+
+    let mut expanded = Vec::new();
+    for word in words {
+        let matches = word.pattern.map(|pattern| expand(&pattern, state)).unwrap_or_default();
+        if matches.is_empty() {
+            expanded.push(quote(&word.text));
+        } else {
+            expanded.extend(matches.iter().map(|path| quote(path)));
+        }
+    }
+    Ok(expanded.join(" "))
+}
+
+// Paths matching `pattern`, sorted, written the way the pattern was (relative
+// patterns give relative paths). Hidden files only match a leading dot.
+pub fn expand(pattern: &str, state: &ShellState) -> Vec<String> {
+    let (mut found, rest) = match pattern.strip_prefix('/') {
+        Some(rest) => (vec!["/".to_string()], rest),
+        None => (vec![String::new()], pattern),
+    };
+
+    for component in rest.split('/').filter(|component| !component.is_empty()) {
+        let mut next = Vec::new();
+        for prefix in &found {
+            if !has_wildcards(component) {
+                next.push(join(prefix, &unescape(component)));
+                continue;
+            }
+
+            let dir = state.resolve(if prefix.is_empty() { "." } else { prefix });
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            next.extend(
+                entries
+                    .flatten()
+                    .filter_map(|entry| entry.file_name().into_string().ok())
+                    .filter(|name| !name.starts_with('.') || component.starts_with('.'))
+                    .filter(|name| matches(component, name))
+                    .map(|name| join(prefix, &name)),
+            );
+        }
+        found = next;
+    }
+
+    // A trailing slash only matches directories, as in other shells
+    let dirs_only = pattern.ends_with('/');
+    found.retain(|path| match fs::symlink_metadata(state.resolve(path)) {
+        Ok(_) if dirs_only => state.resolve(path).is_dir(),
+        Ok(_) => true,
+        Err(_) => false,
+    });
+    if dirs_only {
+        found.iter_mut().for_each(|path| path.push('/'));
+    }
+    found.sort();
+    found
+}
+
+// Whether `name` matches one path component of a glob pattern
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Where the last * was and how much of the name it has swallowed
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if pattern.get(p) == Some(&'*') {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some(length) = match_one(&pattern[p..], name[n]) {
+            p += length;
+            n += 1;
+        } else if let Some((star_at, swallowed)) = star {
+            // Let the * take one more character and retry
+            p = star_at + 1;
+            n = swallowed + 1;
+            star = Some((star_at, swallowed + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// If the pattern element at the start of `pattern` matches `c`, how many
+// pattern characters it spans
+fn match_one(pattern: &[char], c: char) -> Option<usize> {
+    match pattern.first()? {
+        '?' => Some(1),
+        '\\' if pattern.len() > 1 => (pattern[1] == c).then_some(2),
+        '[' => match bracket(pattern) {
+            Some((matched, length)) => matched(c).then_some(length),
+            // An unclosed [ is an ordinary character
+            None => (c == '[').then_some(1),
+        },
+        &literal => (literal == c).then_some(1),
+    }
+}
+
+// Parses a [...] class at the start of `pattern`: [abc], [a-z], [!x] or [^x]
+fn bracket(pattern: &[char]) -> Option<(impl Fn(char) -> bool, usize)> {
+    let mut i = 1;
+    let negated = matches!(pattern.get(i), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut ranges = Vec::new();
+    // A ] right after the opening bracket is part of the set
+    let first = i;
+    loop {
+        let c = *pattern.get(i)?;
+        if c == ']' && i > first {
+            break;
+        }
+        if pattern.get(i + 1) == Some(&'-') && pattern.get(i + 2).is_some_and(|&end| end != ']') {
+            ranges.push((c, pattern[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+
+    let matched = move |c: char| ranges.iter().any(|&(low, high)| low <= c && c <= high) != negated;
+    Some((matched, i + 1))
+}
+
+fn has_wildcards(component: &str) -> bool {
+    let mut chars = component.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '*' | '?' | '[' => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+fn unescape(component: &str) -> String {
+    let mut result = String::with_capacity(component.len());
+    let mut chars = component.chars();
+    while let Some(c) = chars.next() {
+        result.push(if c == '\\' { chars.next().unwrap_or('\\') } else { c });
+    }
+    result
+}
+
+fn join(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else if prefix.ends_with('/') {
+        format!("{}{}", prefix, name)
+    } else {
+        format!("{}/{}", prefix, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_wildcards_and_classes() {
+        assert!(matches("*.tmp", "a.tmp"));
+        assert!(!matches("*.tmp", "a.tmp.bak"));
+        assert!(matches("a?c", "abc"));
+        assert!(matches("[a-c]x", "bx"));
+        assert!(!matches("[!a-c]x", "bx"));
+        assert!(matches(r"\*", "*"));
+        assert!(!matches(r"\*", "a"));
+        assert!(matches("[x", "[x"));
+    }
+
+    #[test]
+    fn expands_against_the_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.tmp", "b.tmp", ".hidden.tmp", "keep.txt"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/c.tmp"), "").unwrap();
+        let mut state = ShellState::from_process().unwrap();
+        state.cwd = dir.path().to_path_buf();
+        state.env.insert("HOME".to_string(), dir.path().join("sub").display().to_string());
+
+        assert_eq!(expand("*.tmp", &state), ["a.tmp", "b.tmp"]);
+        assert_eq!(expand("*/*.tmp", &state), ["sub/c.tmp"]);
+        assert_eq!(expand("*/", &state), ["sub/"]);
+        assert_eq!(expand("~/*.tmp", &state), ["~/c.tmp"]);
+        assert_eq!(expand_line("rm *.tmp '*.txt'", &state).unwrap(), "rm a.tmp b.tmp '*.txt'");
+        assert_eq!(expand_line("rm *.none", &state).unwrap(), "rm '*.none'");
+    }
+}
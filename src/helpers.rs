@@ -1,59 +1,86 @@
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::io;
+use std::io::{self, BufRead, Write};
 use std::time::UNIX_EPOCH;
-use std::os::unix::fs::PermissionsExt;
-use chrono;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
 use filetime::FileTime;
 use colored::*;
 
-use crate::errors::CrateResult;
+use anyhow::anyhow;
 
-pub fn ls() -> CrateResult<()> {
-    let entries = fs::read_dir(".")?;
+use crate::command::{CatOptions, CopyOptions, DiskUsageOptions, LinkOptions, ListOptions, TouchOptions, TruncateOptions};
+use crate::errors::{self, Access, CrateResult};
+use crate::i18n::{tr, trf};
+use crate::interrupt;
+use crate::search::{FindFilters, Matcher};
+use crate::sysinfo;
+use crate::theme;
+
+pub fn ls(dir: &Path) -> CrateResult<()> {
+    let entries = fs::read_dir(dir)?;
 
     for entry in entries {
         let entry = entry?;
-        let metadata = entry.metadata()?;
-        let name = entry.file_name().to_string_lossy().to_string(); // Convert to an owned String
-        
-        // Colorize output based on the type
-        if metadata.is_dir() {
-            println!("{}/", name.blue().bold());
-        } else if metadata.permissions().mode() & 0o111 != 0 {
-            // Executable file
-            println!("{}", name.green());
-        } else if name.ends_with(".rs") || name.ends_with(".toml") || 
-                  name.ends_with(".json") || name.ends_with(".md") {
-            // Source code and documentation files
-            println!("{}", name.yellow());
-        } else {
-            println!("{}", name);
-        }
+        ls_entry(&entry.file_name().to_string_lossy(), &entry.metadata()?);
     }
 
     Ok(())
 }
 
-pub fn ls_detailed() -> CrateResult<()> {
-    let entries = fs::read_dir(".")?;
-    
-    println!("{} {} {} {} {}", 
+// One line of `ls`: the name colored by its type
+pub fn ls_entry(name: &str, metadata: &fs::Metadata) {
+    // Colorize output based on the type
+    if metadata.is_dir() {
+        println!("{}/", theme::paint("directory", name).bold());
+    } else if metadata.permissions().mode() & 0o111 != 0 {
+        // Executable file
+        println!("{}", theme::paint("executable", name));
+    } else if name.ends_with(".rs") || name.ends_with(".toml") || 
+              name.ends_with(".json") || name.ends_with(".md") {
+        // Source code and documentation files
+        println!("{}", theme::paint("source", name));
+    } else {
+        println!("{}", name);
+    }
+}
+
+pub fn ls_detailed(dir: &Path, options: &ListOptions) -> CrateResult<()> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        entries.push((entry.file_name().to_string_lossy().to_string(), entry.metadata()?));
+    }
+    ls_detailed_entries(&entries, options)
+}
+
+// The `ls -l` table for `entries`, names and what they are
+pub fn ls_detailed_entries(entries: &[(String, fs::Metadata)], options: &ListOptions) -> CrateResult<()> {
+
+    // Owner and group columns are as wide as the longest name in them
+    let id = |id: u32, name: fn(u32) -> String| if options.numeric_ids { id.to_string() } else { name(id) };
+    let owners: Vec<(String, String)> = entries
+        .iter()
+        .map(|(_, metadata)| (id(metadata.uid(), sysinfo::user_name), id(metadata.gid(), sysinfo::group_name)))
+        .collect();
+    let owner_width = owners.iter().map(|(owner, _)| owner.chars().count()).chain([5]).max().unwrap_or(5);
+    let group_width = owners.iter().map(|(_, group)| group.chars().count()).chain([5]).max().unwrap_or(5);
+
+    println!("{} {} {} {} {} {} {}", 
         "Type ".bright_cyan().bold(),
         "Permissions".bright_cyan().bold(),
+        format!("{:owner_width$}", "Owner").bright_cyan().bold(),
+        format!("{:group_width$}", "Group").bright_cyan().bold(),
         "Size      ".bright_cyan().bold(),
         "Modified            ".bright_cyan().bold(),
         "Name".bright_cyan().bold());
-    println!("{}", "─".repeat(80).bright_black());
+    println!("{}", "─".repeat(80 + owner_width + group_width).bright_black());
 
-    for entry in entries {
-        let entry = entry?;
-        let metadata = entry.metadata()?;
-        let name = entry.file_name().to_string_lossy().to_string(); // Convert to an owned String
-        
+    let total: u64 = entries.iter().map(|(_, metadata)| metadata.len()).sum();
+    let hidden = entries.iter().filter(|(name, _)| name.starts_with('.')).count();
+    for ((name, metadata), (owner, group)) in entries.iter().zip(owners) {
         // Format the file type with appropriate color
         let file_type = if metadata.is_dir() { 
-            "DIR ".blue().bold() 
+            theme::paint("directory", "DIR ").bold()
         } else if metadata.is_file() { 
             "FILE".normal() 
         } else { 
@@ -76,16 +103,7 @@ pub fn ls_detailed() -> CrateResult<()> {
         );
         
         // Format size with units
-        let size = metadata.len();
-        let size_str = if size < 1024 {
-            format!("{} B", size)
-        } else if size < 1024 * 1024 {
-            format!("{:.1} KB", size as f64 / 1024.0)
-        } else if size < 1024 * 1024 * 1024 {
-            format!("{:.1} MB", size as f64 / (1024.0 * 1024.0))
-        } else {
-            format!("{:.1} GB", size as f64 / (1024.0 * 1024.0 * 1024.0))
-        };
+        let size_str = human_size(metadata.len());
         
         let modified = metadata.modified()?;
         let modified_since_epoch = modified.duration_since(UNIX_EPOCH)?.as_secs();
@@ -95,160 +113,565 @@ pub fn ls_detailed() -> CrateResult<()> {
         
         // Format name with color based on type
         let colored_name = if metadata.is_dir() {
-            name.blue().bold()
+            theme::paint("directory", name).bold()
         } else if metadata.permissions().mode() & 0o111 != 0 {
             // Executable file
-            name.green()
+            theme::paint("executable", name)
         } else if name.ends_with(".rs") || name.ends_with(".toml") || 
                   name.ends_with(".json") || name.ends_with(".md") {
             // Source code files
-            name.yellow()
+            theme::paint("source", name)
         } else {
             name.normal()
         };
         
-        println!("{:4} {:9} {:10} {:20} {}", 
+        // The permissions are colored per letter, so pad them by hand to the header
+        println!("{:4} {}   {:owner_width$} {:group_width$} {:10} {:20} {}", 
             file_type, 
             permissions, 
+            owner,
+            group,
             size_str.cyan(), 
             modified_time.bright_black(),
             colored_name);
     }
 
+    println!("{}", "─".repeat(80 + owner_width + group_width).bright_black());
+    println!(
+        "{}",
+        trf("{} entries ({} hidden), {} total", &[&entries.len(), &hidden, &human_size(total)]).bright_black()
+    );
     Ok(())
 }
 
-pub fn pwd() -> CrateResult<String> {
-    let current_dir = std::env::current_dir()?;
-
-    Ok(current_dir.display().to_string())
-}
+// Returns false when -c left a missing file uncreated
+pub fn touch(path: &Path, options: &TouchOptions) -> CrateResult<bool> {
+    if !path.exists() {
+        if options.no_create {
+            return Ok(false);
+        }
+        fs::File::create(path).map_err(|e| errors::with_path(e, &errors::parent_of(path), Access::Write))?;
+        // A new file already has the current time everywhere
+        if options.time.is_none() {
+            return Ok(true);
+        }
+    }
 
-pub fn cd(path: &str) -> CrateResult<()> {
-    std::env::set_current_dir(path)?;
+    let time = match options.time {
+        Some(time) => FileTime::from_unix_time(time.timestamp(), time.timestamp_subsec_nanos()),
+        None => FileTime::now(),
+    };
+    let result = match (options.sets_access(), options.sets_modification()) {
+        (true, true) => filetime::set_file_times(path, time, time),
+        (true, false) => filetime::set_file_atime(path, time),
+        _ => filetime::set_file_mtime(path, time),
+    };
+    result.map_err(|e| errors::with_path(e, path, Access::Write))?;
 
-    Ok(())
+    Ok(true)
 }
 
-pub fn touch(path: &str) -> CrateResult<()> {
-    // Check if file exists
-    if Path::new(path).exists() {
-        // Update the access and modification times
-        let now = FileTime::now();
-        filetime::set_file_times(path, now, now)?;
-    } else {
-        // Create the file if it doesn't exist
-        fs::File::create(path)?;
-    }
+pub fn rm(path: &Path) -> CrateResult<()> {
+    fs::remove_file(path).map_err(|e| errors::with_path(e, &errors::parent_of(path), Access::Write))?;
 
     Ok(())
 }
 
-pub fn rm(path: &str) -> CrateResult<()> {
-    fs::remove_file(path)?;
-
+pub fn mkdir(path: &Path) -> CrateResult<()> {
+    fs::create_dir(path).map_err(|e| errors::with_path(e, &errors::parent_of(path), Access::Write))?;
+    
     Ok(())
 }
 
-pub fn mkdir(path: &str) -> CrateResult<()> {
-    fs::create_dir(path)?;
+pub fn mkdir_p(path: &Path) -> CrateResult<()> {
+    fs::create_dir_all(path).map_err(|e| {
+        // Blame the deepest ancestor that already exists
+        let existing = path.ancestors().find(|p| p.exists()).unwrap_or(Path::new("."));
+        errors::with_path(e, existing, Access::Write)
+    })?;
     
     Ok(())
 }
 
-pub fn mkdir_p(path: &str) -> CrateResult<()> {
-    fs::create_dir_all(path)?;
+pub fn rmdir(path: &Path) -> CrateResult<()> {
+    fs::remove_dir(path).map_err(|e| errors::with_path(e, &errors::parent_of(path), Access::Write))?;
     
     Ok(())
 }
 
-pub fn rmdir(path: &str) -> CrateResult<()> {
-    fs::remove_dir(path)?;
-    
+// Removes `path` and everything in it. With `one_file_system`, directories on
+// other filesystems are kept (and so are the directories holding them);
+// returns the ones that were kept.
+pub fn rmdir_r(path: &Path, one_file_system: bool) -> CrateResult<Vec<PathBuf>> {
+    if !one_file_system {
+        fs::remove_dir_all(path).map_err(|e| errors::with_path(e, path, Access::Write))?;
+        return Ok(Vec::new());
+    }
+
+    let device = fs::symlink_metadata(path)?.dev();
+    let mut kept = Vec::new();
+    remove_same_device(path, device, &mut kept).map_err(|e| errors::with_path(e, path, Access::Write))?;
+    Ok(kept)
+}
+
+fn remove_same_device(path: &Path, device: u64, kept: &mut Vec<PathBuf>) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return fs::remove_file(path);
+    }
+    if metadata.dev() != device {
+        kept.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(path)? {
+        remove_same_device(&entry?.path(), device, kept)?;
+    }
+    if !kept.iter().any(|kept| kept.starts_with(path)) {
+        fs::remove_dir(path)?;
+    }
     Ok(())
 }
 
-pub fn rmdir_r(path: &str) -> CrateResult<()> {
-    fs::remove_dir_all(path)?;
-    
+// Disk space used under `path`, counting allocated blocks so sparse files
+// only count what they really take, and hard-linked files once. Unless
+// --summarize is given, `report` hears each directory's total, deepest first.
+pub fn du(path: &Path, options: &DiskUsageOptions, report: &mut dyn FnMut(&Path, u64)) -> CrateResult<u64> {
+    let metadata = fs::symlink_metadata(path).map_err(|e| errors::with_path(e, path, Access::Read))?;
+    let mut seen = std::collections::HashSet::new();
+    let total = usage(path, &metadata, metadata.dev(), options, &mut seen, report);
+    // A file named on the command line gets its own line too
+    if !metadata.is_dir() && !options.summarize {
+        report(path, total);
+    }
+    Ok(total)
+}
+
+fn usage(
+    path: &Path,
+    metadata: &fs::Metadata,
+    device: u64,
+    options: &DiskUsageOptions,
+    seen: &mut std::collections::HashSet<(u64, u64)>,
+    report: &mut dyn FnMut(&Path, u64),
+) -> u64 {
+    if !seen.insert((metadata.dev(), metadata.ino())) || interrupt::requested() {
+        return 0;
+    }
+    let mut total = metadata.blocks() * 512;
+    if !metadata.is_dir() || (options.one_file_system && metadata.dev() != device) {
+        return total;
+    }
+
+    // Unreadable directories count as themselves, like summarize()
+    for entry in fs::read_dir(path).into_iter().flatten().flatten() {
+        if let Ok(child) = entry.metadata() {
+            total += usage(&entry.path(), &child, device, options, seen, report);
+        }
+    }
+    if !options.summarize {
+        report(path, total);
+    }
+    total
+}
+
+// Whether `path` is where a filesystem is mounted: its directory is on a
+// different device than its parent, or it is its own parent (/)
+pub fn is_mount_point(path: &Path) -> bool {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return false;
+    };
+    if !metadata.is_dir() {
+        return false;
+    }
+    match fs::metadata(path.join("..")) {
+        Ok(parent) => parent.dev() != metadata.dev() || parent.ino() == metadata.ino(),
+        Err(_) => false,
+    }
+}
+
+// Refuses to recursively remove `/` or a directory the session is inside
+pub fn check_removal(path: &Path, cwd: &Path) -> CrateResult<()> {
+    // A symlink is removed on its own, so where it points doesn't matter
+    if fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_symlink()) {
+        return Ok(());
+    }
+    let Ok(path) = fs::canonicalize(path) else {
+        return Ok(());
+    };
+
+    if path == Path::new("/") {
+        return Err(anyhow!(tr("Refusing to remove '/'. Pass --no-preserve-root if you really mean it.")));
+    }
+    if cwd.starts_with(&path) {
+        return Err(anyhow!(trf(
+            "Refusing to remove '{}': the current directory is inside it. Pass --no-preserve-root if you really mean it.",
+            &[&path.display()]
+        )));
+    }
     Ok(())
 }
 
-pub fn cp(source: &str, destination: &str) -> CrateResult<()> {
+#[derive(Debug, Default)]
+pub struct TreeSummary {
+    pub files: u64,
+    pub dirs: u64,
+    pub bytes: u64,
+}
+
+// Counts what removing `path` would delete, without following symlinks.
+// Unreadable directories count as one entry.
+pub fn summarize(path: &Path) -> TreeSummary {
+    let mut summary = TreeSummary::default();
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(path) = pending.pop() {
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if metadata.is_dir() {
+            summary.dirs += 1;
+            if let Ok(entries) = fs::read_dir(&path) {
+                pending.extend(entries.flatten().map(|entry| entry.path()));
+            }
+        } else {
+            summary.files += 1;
+            summary.bytes += metadata.len();
+        }
+    }
+    summary
+}
+
+pub fn human_size(size: u64) -> String {
+    if size < 1024 {
+        format!("{} B", size)
+    } else if size < 1024 * 1024 {
+        format!("{:.1} KB", size as f64 / 1024.0)
+    } else if size < 1024 * 1024 * 1024 {
+        format!("{:.1} MB", size as f64 / (1024.0 * 1024.0))
+    } else {
+        format!("{:.1} GB", size as f64 / (1024.0 * 1024.0 * 1024.0))
+    }
+}
+
+// Whole seconds, minutes and hours for how long something has run: 45s, 3m 05s, 2h 10m
+pub fn human_duration(duration: std::time::Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds < 60 {
+        format!("{}s", seconds)
+    } else if seconds < 3600 {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60)
+    }
+}
+
+pub fn cp(source: &Path, destination: &Path) -> CrateResult<()> {
     // Check if the source is a directory
-    if Path::new(source).is_dir() {
-        return Err(anyhow::anyhow!("Source is a directory. Use cp_r for recursive copy."));
+    if source.is_dir() {
+        return Err(anyhow::anyhow!(tr("Source is a directory. Use cp_r for recursive copy.")));
     }
     
-    fs::copy(source, destination)?;
+    copy_file(source, destination)?;
     
     Ok(())
 }
 
-pub fn cp_r(source: &str, destination: &str) -> CrateResult<()> {
-    copy_dir_recursive(source, destination)?;
+fn copy_file(source: &Path, destination: &Path) -> CrateResult<()> {
+    fs::copy(source, destination).map_err(|e| {
+        if !errors::has_access(source, Access::Read) {
+            errors::with_path(e, source, Access::Read)
+        } else if destination.exists() {
+            errors::with_path(e, destination, Access::Write)
+        } else {
+            errors::with_path(e, &errors::parent_of(destination), Access::Write)
+        }
+    })?;
+
+    Ok(())
+}
+
+pub fn cp_r(source: &Path, destination: &Path, options: &CopyOptions) -> CrateResult<()> {
+    // --one-file-system copies a mount point as an empty directory, like coreutils
+    let device = match options.one_file_system {
+        true => Some(fs::metadata(source).map_err(|e| errors::with_path(e, source, Access::Read))?.dev()),
+        false => None,
+    };
+    copy_dir_recursive(source, destination, device)?;
     
     Ok(())
 }
 
-fn copy_dir_recursive(source: &str, destination: &str) -> CrateResult<()> {
-    let src_path = Path::new(source);
-    let dst_path = Path::new(destination);
+fn copy_dir_recursive(src_path: &Path, dst_path: &Path, device: Option<u64>) -> CrateResult<()> {
     
     if !src_path.exists() {
-        return Err(anyhow::anyhow!("Source path doesn't exist"));
+        return Err(anyhow::anyhow!(tr("Source path doesn't exist")));
     }
     
     if !src_path.is_dir() {
         // Simple file copy
-        fs::copy(source, destination)?;
+        copy_file(src_path, dst_path)?;
         return Ok(());
     }
     
     // Create destination directory if it doesn't exist
     if !dst_path.exists() {
-        fs::create_dir_all(destination)?;
+        fs::create_dir_all(dst_path)
+            .map_err(|e| errors::with_path(e, &errors::parent_of(dst_path), Access::Write))?;
     }
     
-    for entry in fs::read_dir(source)? {
+    if device.is_some_and(|device| fs::metadata(src_path).is_ok_and(|metadata| metadata.dev() != device)) {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(src_path)? {
         let entry = entry?;
         let file_name = entry.file_name();
         let src_path = entry.path();
-        let dst_path = Path::new(destination).join(&file_name);
+        let dst_path = dst_path.join(&file_name);
         
+        // Paths stay as Path so names that aren't valid UTF-8 are copied too
         if entry.file_type()?.is_dir() {
-            copy_dir_recursive(
-                src_path.to_str().unwrap(),
-                dst_path.to_str().unwrap()
-            )?;
+            copy_dir_recursive(&src_path, &dst_path, device)?;
         } else {
-            fs::copy(&src_path, &dst_path)?;
+            copy_file(&src_path, &dst_path)?;
         }
     }
     
     Ok(())
 }
 
-pub fn mv(source: &str, destination: &str) -> CrateResult<()> {
-    fs::rename(source, destination)?;
+pub fn mv(source: &Path, destination: &Path) -> CrateResult<()> {
+    fs::rename(source, destination).map_err(|e| {
+        // Renaming needs write access to both parent directories
+        let source_parent = errors::parent_of(source);
+        let dest_parent = errors::parent_of(destination);
+        let target = if !errors::has_access(&source_parent, Access::Write) {
+            source_parent
+        } else {
+            dest_parent
+        };
+        errors::with_path(e, &target, Access::Write)
+    })?;
     
     Ok(())
 }
 
-pub fn cat(path: &str) -> CrateResult<String> {
-    let pwd = pwd()?;
+// Sets the size of `path`, creating it unless -c was given. Growing leaves a
+// hole, so even huge sizes are instant and take no disk space. Returns the new
+// size, or None for a missing file with -c.
+pub fn truncate(path: &Path, options: &TruncateOptions) -> CrateResult<Option<u64>> {
+    if options.no_create && !path.exists() {
+        return Ok(None);
+    }
+    if path.is_dir() {
+        return Err(anyhow!(trf("'{}' is a directory", &[&path.display()])));
+    }
+
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .map_err(|e| errors::with_path(e, path, Access::Write))?;
+    let size = options.size.apply(file.metadata()?.len());
+    file.set_len(size).map_err(|e| errors::with_path(e, path, Access::Write))?;
+    Ok(Some(size))
+}
+
+// Cuts `file` into pieces of at most `chunk` bytes named `prefix` plus aa, ab,
+// ... (more letters when there are over 676 pieces). `copied` hears about each
+// block written. Refuses to overwrite anything.
+pub fn split(file: &Path, prefix: &Path, chunk: u64, copied: &mut dyn FnMut(u64)) -> CrateResult<Vec<PathBuf>> {
+    let size = fs::metadata(file).map_err(|e| errors::with_path(e, file, Access::Read))?.len();
+    let count = size.div_ceil(chunk);
+    let mut letters = 2;
+    while 26u64.pow(letters) < count {
+        letters += 1;
+    }
+
+    let pieces: Vec<PathBuf> = (0..count)
+        .map(|i| {
+            let mut name = prefix.as_os_str().to_owned();
+            name.push(suffix(i, letters));
+            PathBuf::from(name)
+        })
+        .collect();
+    if let Some(existing) = pieces.iter().find(|piece| piece.exists()) {
+        return Err(anyhow!(trf("'{}' already exists", &[&existing.display()])));
+    }
+
+    let mut reader = fs::File::open(file).map_err(|e| errors::with_path(e, file, Access::Read))?;
+    for (i, piece) in pieces.iter().enumerate() {
+        let written = fs::File::create(piece)
+            .map_err(|e| errors::with_path(e, &errors::parent_of(piece), Access::Write))
+            .and_then(|mut writer| copy_stream(&mut io::Read::take(&mut reader, chunk), &mut writer, copied));
+        // Don't leave half a set of pieces behind
+        if let Err(e) = written {
+            pieces[..=i].iter().for_each(|piece| drop(fs::remove_file(piece)));
+            return Err(e);
+        }
+    }
+    Ok(pieces)
+}
+
+// Letters for the `index`th piece, like coreutils split: aa, ab, ..., zz
+fn suffix(index: u64, letters: u32) -> String {
+    (0..letters)
+        .rev()
+        .map(|position| (b'a' + (index / 26u64.pow(position) % 26) as u8) as char)
+        .collect()
+}
+
+// Joins `pieces` in order into a new file `output`
+pub fn merge(pieces: &[PathBuf], output: &Path, copied: &mut dyn FnMut(u64)) -> CrateResult<()> {
+    if output.exists() {
+        return Err(anyhow!(trf("'{}' already exists", &[&output.display()])));
+    }
+
+    let mut writer = fs::File::create(output).map_err(|e| errors::with_path(e, &errors::parent_of(output), Access::Write))?;
+    for piece in pieces {
+        let copied = fs::File::open(piece)
+            .map_err(|e| errors::with_path(e, piece, Access::Read))
+            .and_then(|mut reader| copy_stream(&mut reader, &mut writer, copied));
+        if let Err(e) = copied {
+            let _ = fs::remove_file(output);
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+// Copies in blocks so progress can be shown and Ctrl+C can stop it
+fn copy_stream(reader: &mut dyn io::Read, writer: &mut dyn io::Write, copied: &mut dyn FnMut(u64)) -> CrateResult<()> {
+    let mut buffer = vec![0; 256 * 1024];
+    loop {
+        if interrupt::requested() {
+            return Err(anyhow!(tr("interrupted")));
+        }
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(());
+        }
+        writer.write_all(&buffer[..read])?;
+        copied(read as u64);
+    }
+}
+
+pub fn cat(path: &Path, options: &CatOptions) -> CrateResult<String> {
+    if options.is_plain() {
+        let contents = fs::read_to_string(path).map_err(|e| errors::with_path(e, path, Access::Read))?;
+        return Ok(contents);
+    }
+
+    // Read raw bytes so -v can show what isn't valid text
+    let bytes = fs::read(path).map_err(|e| errors::with_path(e, path, Access::Read))?;
+    Ok(format_cat(&bytes, options))
+}
+
+// cat's options applied to bytes already read, e.g. from a named pipe
+pub fn format_cat(bytes: &[u8], options: &CatOptions) -> String {
+    if options.is_plain() {
+        return String::from_utf8_lossy(bytes).into_owned();
+    }
+
+    let mut result = String::with_capacity(bytes.len());
+    let mut blank_run = 0;
+    for line in bytes.split_inclusive(|&b| b == b'\n') {
+        format_cat_line(line, options, &mut blank_run, &mut result);
+    }
+    result
+}
+
+// cat of standard input, written out as it comes in rather than read whole
+// first, so an endless stream never piles up in memory. Plain output goes
+// in chunks, formatted output a line at a time. Returns whether the output
+// ended a line.
+pub fn cat_stream(input: &mut impl BufRead, output: &mut impl Write, options: &CatOptions) -> io::Result<bool> {
+    let mut ends_line = true;
+    if options.is_plain() {
+        loop {
+            let chunk = input.fill_buf()?;
+            let Some(&last) = chunk.last() else {
+                return Ok(ends_line);
+            };
+            output.write_all(chunk)?;
+            ends_line = last == b'\n';
+            let length = chunk.len();
+            input.consume(length);
+        }
+    }
+
+    let (mut line, mut text) = (Vec::new(), String::new());
+    let mut blank_run = 0;
+    while input.read_until(b'\n', &mut line)? > 0 {
+        format_cat_line(&line, options, &mut blank_run, &mut text);
+        output.write_all(text.as_bytes())?;
+        ends_line = line.ends_with(b"\n");
+        line.clear();
+        text.clear();
+    }
+    Ok(ends_line)
+}
+
+// One line of `format_cat`, newline and all; `blank_run` counts the blank
+// lines just before it, for -s
+fn format_cat_line(line: &[u8], options: &CatOptions, blank_run: &mut usize, result: &mut String) {
+    let (text, newline) = match line.strip_suffix(b"\n") {
+        Some(text) => (text, true),
+        None => (line, false),
+    };
+
+    if text.is_empty() && newline {
+        *blank_run += 1;
+        if options.squeeze_blank && *blank_run > 1 {
+            return;
+        }
+    } else {
+        *blank_run = 0;
+    }
+
+    if options.show_nonprinting {
+        text.iter().for_each(|&b| push_visible(result, b, options.show_tabs));
+    } else {
+        let text = String::from_utf8_lossy(text);
+        if options.show_tabs {
+            result.push_str(&text.replace('\t', "^I"));
+        } else {
+            result.push_str(&text);
+        }
+    }
 
-    let joined_path = std::path::Path::new(&pwd).join(path);
-    let contents = fs::read_to_string(joined_path)?;
+    if newline {
+        if options.show_ends {
+            result.push('$');
+        }
+        result.push('\n');
+    }
+}
 
-    Ok(contents)
+// cat -v notation: ^X for control characters, ^? for DEL and M- for bytes
+// with the high bit set
+fn push_visible(result: &mut String, byte: u8, show_tabs: bool) {
+    let (meta, byte) = if byte >= 0x80 { (true, byte - 0x80) } else { (false, byte) };
+    if meta {
+        result.push_str("M-");
+    }
+    match byte {
+        b'\t' if !meta && !show_tabs => result.push('\t'),
+        0x7f => result.push_str("^?"),
+        0..=0x1f => {
+            result.push('^');
+            result.push((byte + 0x40) as char);
+        }
+        _ => result.push(byte as char),
+    }
 }
 
-pub fn stat(path: &str) -> CrateResult<String> {
+pub fn stat(path: &Path) -> CrateResult<String> {
     let metadata = fs::metadata(path)?;
     let mut result = String::new();
     
-    result.push_str(&format!("File: {}\n", path));
+    result.push_str(&format!("File: {}\n", path.display()));
     result.push_str(&format!("Size: {} bytes\n", metadata.len()));
     result.push_str(&format!("Type: {}\n", 
         if metadata.is_file() { "Regular File" } 
@@ -256,6 +679,9 @@ pub fn stat(path: &str) -> CrateResult<String> {
         else { "Special File" }));
     
     result.push_str(&format!("Permissions: {:o}\n", metadata.permissions().mode() & 0o777));
+    if metadata.is_dir() {
+        result.push_str(&format!("Mount point: {}\n", if is_mount_point(path) { "yes" } else { "no" }));
+    }
     
     if let Ok(created) = metadata.created() {
         if let Ok(time) = created.duration_since(UNIX_EPOCH) {
@@ -287,47 +713,276 @@ pub fn stat(path: &str) -> CrateResult<String> {
     Ok(result)
 }
 
-pub fn find(dir: &str, pattern: &str) -> CrateResult<Vec<PathBuf>> {
-    let mut results = Vec::new();
-    find_recursive(dir, pattern, &mut results)?;
-    Ok(results)
+// Calls `found` with each match as the walk reaches it, deepest entries
+// first. The walk stops when `found` returns false or Ctrl+C is pressed.
+// Symlinked directories are not followed.
+pub fn find(dir: &Path, matcher: &Matcher, filters: &FindFilters, found: &mut dyn FnMut(&Path) -> bool) -> CrateResult<()> {
+    let device = match filters.one_file_system {
+        true => Some(fs::metadata(dir)?.dev()),
+        false => None,
+    };
+    find_recursive(dir, matcher, filters, device, found)?;
+    Ok(())
 }
 
-fn find_recursive(dir: &str, pattern: &str, results: &mut Vec<PathBuf>) -> io::Result<()> {
+struct Walked {
+    keep_going: bool,
+    // Whether every entry of the directory matched
+    all_matched: bool,
+}
+
+fn find_recursive(
+    dir: &Path,
+    matcher: &Matcher,
+    filters: &FindFilters,
+    // With --one-file-system, the device the walk stays on
+    device: Option<u64>,
+    found: &mut dyn FnMut(&Path) -> bool,
+) -> io::Result<Walked> {
+    let stop = Walked { keep_going: false, all_matched: false };
+    let mut all_matched = true;
+
     for entry in fs::read_dir(dir)? {
+        if interrupt::requested() {
+            return Ok(stop);
+        }
         let entry = entry?;
         let path = entry.path();
+        let metadata = fs::symlink_metadata(&path)?;
         
-        if path.is_dir() {
-            find_recursive(path.to_str().unwrap_or(""), pattern, results)?;
+        let mut children_matched = true;
+        if metadata.is_dir() && device.is_some_and(|device| metadata.dev() != device) {
+            // A mount point is listed but not entered, and never counts as empty
+            children_matched = false;
+        } else if metadata.is_dir() {
+            let walked = find_recursive(&path, matcher, filters, device, found)?;
+            if !walked.keep_going {
+                return Ok(stop);
+            }
+            children_matched = walked.all_matched;
         }
         
-        if let Some(name) = path.file_name() {
-            if let Some(name_str) = name.to_str() {
-                if name_str.contains(pattern) {
-                    results.push(path.clone());
-                }
+        let name_matches = path.file_name().and_then(|name| name.to_str()).is_some_and(|name| matcher.is_match(name));
+        if name_matches && filters.accepts(&path, &metadata, children_matched) {
+            if !found(&path) {
+                return Ok(stop);
             }
+        } else {
+            all_matched = false;
         }
     }
     
-    Ok(())
+    Ok(Walked { keep_going: true, all_matched })
 }
 
-pub fn grep(path: &str, pattern: &str) -> CrateResult<String> {
-    let content = fs::read_to_string(path)?;
-    let mut result = String::new();
-    
-    for (i, line) in content.lines().enumerate() {
-        if line.contains(pattern) {
-            result.push_str(&format!("{}:{}\n", i + 1, line));
+// Calls `found` with each matching line number and line, reading the file a
+// line at a time. Stops like find().
+pub fn grep(path: &Path, matcher: &Matcher, found: &mut dyn FnMut(usize, &str) -> bool) -> CrateResult<()> {
+    let file = fs::File::open(path).map_err(|e| errors::with_path(e, path, Access::Read))?;
+    grep_reader(io::BufReader::new(file), matcher, found)
+}
+
+pub fn grep_reader(reader: impl BufRead, matcher: &Matcher, found: &mut dyn FnMut(usize, &str) -> bool) -> CrateResult<()> {
+    for (i, line) in reader.lines().enumerate() {
+        if interrupt::requested() {
+            break;
+        }
+        let line = line?;
+        if matcher.is_match(&line) && !found(i + 1, &line) {
+            break;
         }
     }
     
-    Ok(result)
+    Ok(())
 }
 
-pub fn ln(target: &str, link_name: &str) -> CrateResult<()> {
-    std::os::unix::fs::symlink(target, link_name)?;
-    Ok(())
+// `target` is the path as typed (stored as-is in plain symlinks), `resolved`
+// the same path against the session directory. Returns where the link went.
+pub fn ln(target: &str, resolved: &Path, link_name: &Path, options: &LinkOptions) -> CrateResult<PathBuf> {
+    // Like coreutils, `ln file dir` puts the link inside dir
+    let link = match resolved.file_name() {
+        Some(name) if link_name.is_dir() => link_name.join(name),
+        _ => link_name.to_path_buf(),
+    };
+
+    if !options.symbolic && resolved.is_dir() {
+        return Err(anyhow!(trf("'{}' is a directory; hard links to directories aren't allowed (use ln -s)", &[&target])));
+    }
+    // The bare error would not say which path is missing
+    let dir = errors::parent_of(&link);
+    if !dir.exists() {
+        return Err(anyhow!(trf("ln: directory '{}' does not exist", &[&dir.display()])));
+    }
+
+    if options.force && fs::symlink_metadata(&link).is_ok_and(|metadata| !metadata.is_dir()) {
+        fs::remove_file(&link).map_err(|e| errors::with_path(e, &dir, Access::Write))?;
+    }
+
+    let result = if options.symbolic {
+        let stored = if options.relative {
+            relative_target(resolved, &dir)?
+        } else {
+            PathBuf::from(target)
+        };
+        std::os::unix::fs::symlink(stored, &link)
+    } else {
+        fs::hard_link(resolved, &link)
+    };
+
+    result.map_err(|e| match e.kind() {
+        io::ErrorKind::AlreadyExists => anyhow!(trf("'{}' already exists; use ln -f to replace it", &[&link.display()])),
+        io::ErrorKind::NotFound => anyhow!(trf("ln: '{}' does not exist", &[&target])),
+        _ => errors::with_path(e, &dir, Access::Write),
+    })?;
+    Ok(link)
+}
+
+// The path that leads from `dir` to `target`, e.g. ../docs/a.txt. Both are
+// canonicalized first so symlinked directories don't skew the result.
+fn relative_target(target: &Path, dir: &Path) -> CrateResult<PathBuf> {
+    let dir = fs::canonicalize(dir).map_err(|e| errors::with_path(e, dir, Access::Read))?;
+    let target = match (fs::canonicalize(errors::parent_of(target)), target.file_name()) {
+        (Ok(parent), Some(name)) => parent.join(name),
+        _ => target.to_path_buf(),
+    };
+
+    let common = dir.components().zip(target.components()).take_while(|(a, b)| a == b).count();
+    let mut relative = PathBuf::new();
+    for _ in dir.components().skip(common) {
+        relative.push("..");
+    }
+    relative.extend(target.components().skip(common));
+
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    Ok(relative)
+}
+
+pub fn terminal_width() -> usize {
+    // Ask the terminal first, then fall back to $COLUMNS and a classic 80 columns
+    if let Some((columns, _)) = crate::terminal::window_size() {
+        return columns;
+    }
+
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|cols| cols.parse().ok())
+        .unwrap_or(80)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+
+    // The options `cat` parses from `flags`
+    fn cat_options(flags: &str) -> CatOptions {
+        match Command::try_from(format!("cat {} file", flags).as_str()).unwrap() {
+            Command::Cat(_, options) => options,
+            other => panic!("parsed as {:?}", other),
+        }
+    }
+
+    #[test]
+    fn cat_v_shows_control_and_high_bytes() {
+        let options = cat_options("-v");
+        assert_eq!(format_cat(b"a\x01\x7f\xe9\tb\n", &options), "a^A^?M-i\tb\n");
+    }
+
+    #[test]
+    fn cat_t_shows_tabs() {
+        let options = cat_options("-T");
+        assert_eq!(format_cat(b"a\tb\n\x01\n", &options), "a^Ib\n\x01\n");
+    }
+
+    #[test]
+    fn cat_e_marks_line_ends() {
+        let options = cat_options("-E");
+        assert_eq!(format_cat(b"a\n\nno end", &options), "a$\n$\nno end");
+    }
+
+    #[test]
+    fn cat_s_squeezes_blank_lines() {
+        let options = cat_options("-s");
+        assert_eq!(format_cat(b"a\n\n\n\nb\n\n", &options), "a\n\nb\n\n");
+    }
+
+    #[test]
+    fn cat_a_is_v_t_and_e() {
+        let options = cat_options("-A");
+        assert_eq!(options, cat_options("-vTE"));
+        assert_eq!(format_cat(b"\ta\x1b\n", &options), "^Ia^[$\n");
+    }
+
+    #[test]
+    fn cat_h_only_adds_headers() {
+        let options = cat_options("-H");
+        assert!(options.headers && options.is_plain());
+        assert_eq!(format_cat(b"a\tb\n", &options), "a\tb\n");
+    }
+
+    #[test]
+    fn ln_names_what_is_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("a");
+        fs::write(&target, "").unwrap();
+        let relative = LinkOptions { symbolic: true, relative: true, ..Default::default() };
+        for options in [LinkOptions::default(), relative] {
+            let error = ln("a", &target, &dir.path().join("sub/link"), &options).unwrap_err();
+            assert_eq!(error.to_string(), format!("ln: directory '{}' does not exist", dir.path().join("sub").display()));
+        }
+        let error = ln("nope", &dir.path().join("nope"), &dir.path().join("link"), &LinkOptions::default()).unwrap_err();
+        assert_eq!(error.to_string(), "ln: 'nope' does not exist");
+    }
+
+    // No directory is on this device, so with it each one looks like a
+    // mount point of another filesystem
+    const ELSEWHERE: Option<u64> = Some(u64::MAX);
+
+    #[test]
+    fn one_file_system_stays_on_its_device() {
+        let dir = tempfile::tempdir().unwrap();
+        let tree = dir.path().join("tree");
+        fs::create_dir_all(tree.join("mount")).unwrap();
+        fs::write(tree.join("mount/inside.txt"), "").unwrap();
+        fs::write(tree.join("top.txt"), "").unwrap();
+
+        // find lists a mount point but doesn't go in
+        let mut found = Vec::new();
+        let matcher = Matcher::new("", crate::search::CaseMode::Sensitive);
+        find_recursive(&tree, &matcher, &FindFilters::default(), ELSEWHERE, &mut |path| {
+            found.push(path.to_path_buf());
+            true
+        })
+        .unwrap();
+        found.sort();
+        assert_eq!(found, [tree.join("mount"), tree.join("top.txt")]);
+
+        // cp -r copies it as an empty directory
+        copy_dir_recursive(&tree.join("mount"), &dir.path().join("copy"), ELSEWHERE).unwrap();
+        assert!(dir.path().join("copy").is_dir() && !dir.path().join("copy/inside.txt").exists());
+
+        // rm -r leaves it in place, and the directories holding it
+        let mut kept = Vec::new();
+        remove_same_device(&tree.join("mount"), u64::MAX, &mut kept).unwrap();
+        assert_eq!(kept, [tree.join("mount")]);
+        assert!(tree.join("mount/inside.txt").exists());
+
+        // Everything on the same device goes as usual
+        assert!(rmdir_r(&tree, true).unwrap().is_empty());
+        assert!(!tree.exists());
+    }
+
+    #[test]
+    fn streaming_matches_reading_whole() {
+        let input = b"a\t\x01\n\n\n\nlast";
+        for flags in ["-H", "-A", "-s", "-sE"] {
+            let options = cat_options(flags);
+            let mut output = Vec::new();
+            let ends_line = cat_stream(&mut &input[..], &mut output, &options).unwrap();
+            assert_eq!(String::from_utf8(output).unwrap(), format_cat(input, &options), "{}", flags);
+            assert!(!ends_line);
+        }
+    }
 }
@@ -0,0 +1,105 @@
+use anyhow::anyhow;
+
+use crate::errors::CrateResult;
+use crate::i18n::trf;
+
+// Expands history references in a line typed at the prompt, as bash does:
+// `!!` is the line before, `!$` its last word and `!*` its words after the
+// first, while `^old^new` at the start is the line before with `old`
+// changed to `new`. None when there was nothing to expand.
+pub fn expand(line: &str, history: &[String]) -> CrateResult<Option<String>> {
+    let previous = history.last().map(String::as_str);
+    if let Some(rest) = line.strip_prefix('^') {
+        return substitute(line, rest, previous).map(Some);
+    }
+
+    let mut expanded = String::new();
+    let mut changed = false;
+    let mut quote = None;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            // Single quotes and a backslash keep a `!` as it is
+            '\\' if quote != Some('\'') => {
+                expanded.push(c);
+                expanded.extend(chars.next());
+            }
+            '\'' | '"' if quote.is_none() => {
+                quote = Some(c);
+                expanded.push(c);
+            }
+            c if quote == Some(c) => {
+                quote = None;
+                expanded.push(c);
+            }
+            '!' if quote != Some('\'') && matches!(chars.peek(), Some('!' | '$' | '*')) => {
+                let designator = chars.next().unwrap_or_default();
+                let previous = previous.ok_or_else(|| anyhow!(trf("!{}: event not found", &[&designator])))?;
+                let words = words(previous);
+                match designator {
+                    '!' => expanded.push_str(previous),
+                    '$' => expanded.push_str(words.last().copied().unwrap_or_default()),
+                    _ => expanded.push_str(&words.get(1..).unwrap_or_default().join(" ")),
+                }
+                changed = true;
+            }
+            c => expanded.push(c),
+        }
+    }
+    Ok(changed.then_some(expanded))
+}
+
+// `^old^new^rest`: the line before with the first `old` made `new`, and
+// `rest` added to the end
+fn substitute(line: &str, rest: &str, previous: Option<&str>) -> CrateResult<String> {
+    let mut parts = rest.splitn(3, '^');
+    let (old, new, tail) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+    match previous {
+        Some(previous) if !old.is_empty() && previous.contains(old) => Ok(format!("{}{}", previous.replacen(old, new, 1), tail)),
+        Some(_) => Err(anyhow!(trf("{}: substitution failed", &[&line]))),
+        None => Err(anyhow!(trf("{}: event not found", &[&line]))),
+    }
+}
+
+// The words of `line` as they were typed, quotes and all
+fn words(line: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let mut quote = None;
+    let mut chars = line.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() && quote.is_none() => {
+                if let Some(start) = start.take() {
+                    words.push(&line[start..index]);
+                }
+                continue;
+            }
+            '\\' if quote != Some('\'') => drop(chars.next()),
+            '\'' | '"' if quote.is_none() => quote = Some(c),
+            c if quote == Some(c) => quote = None,
+            _ => {}
+        }
+        start.get_or_insert(index);
+    }
+    words.extend(start.map(|start| &line[start..]));
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_references_to_the_line_before() {
+        let history = ["cd /tmp".to_string(), "cp 'My File.txt' backup/".to_string()];
+        let expand = |line: &str| expand(line, &history).unwrap();
+        assert_eq!(expand("ls !$"), Some("ls backup/".to_string()));
+        assert_eq!(expand("mv !*"), Some("mv 'My File.txt' backup/".to_string()));
+        assert_eq!(expand("sudo !!"), Some("sudo cp 'My File.txt' backup/".to_string()));
+        assert_eq!(expand("^backup^copies^ -v"), Some("cp 'My File.txt' copies/ -v".to_string()));
+        assert_eq!(expand("echo '!!' \\!$ a != b"), None);
+        assert!(super::expand("^nothing^here", &history).is_err());
+        assert!(super::expand("echo !$", &[]).is_err());
+    }
+}
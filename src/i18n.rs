@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+use crate::config;
+
+// Message catalogs, keyed by the English source string. English needs no
+// catalog: untranslated messages fall back to the key itself.
+const CATALOGS: [(&str, &str); 1] = [("es", include_str!("../locales/es.toml"))];
+
+static CATALOG: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+// Picks the locale from the rc file, then LC_ALL / LC_MESSAGES / LANG
+pub fn init() {
+    let locale = config::get().locale.clone().or_else(|| {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+    });
+
+    let language = language(locale.as_deref().unwrap_or("en"));
+    let catalog = CATALOGS
+        .iter()
+        .find(|(code, _)| *code == language)
+        .and_then(|(_, source)| toml::from_str(source).ok())
+        .unwrap_or_default();
+
+    let _ = CATALOG.set(catalog);
+}
+
+// Whether messages can be shown in the language of `locale`
+pub fn is_supported(locale: &str) -> bool {
+    let language = language(locale);
+    language == "en" || language == "c" || language == "posix" || CATALOGS.iter().any(|(code, _)| *code == language)
+}
+
+// "es_ES.UTF-8" → "es"
+fn language(locale: &str) -> String {
+    locale.split(['_', '.', '-']).next().unwrap_or_default().to_lowercase()
+}
+
+pub fn tr(message: &'static str) -> &'static str {
+    CATALOG
+        .get()
+        .and_then(|catalog| catalog.get(message))
+        .map(String::as_str)
+        .unwrap_or(message)
+}
+
+// Translates `template` and fills its `{}` placeholders in order
+pub fn trf(template: &'static str, args: &[&dyn Display]) -> String {
+    let mut result = String::new();
+    let mut args = args.iter();
+    let mut pieces = tr(template).split("{}").peekable();
+
+    while let Some(piece) = pieces.next() {
+        result.push_str(piece);
+        if pieces.peek().is_some() {
+            if let Some(arg) = args.next() {
+                result.push_str(&arg.to_string());
+            }
+        }
+    }
+
+    result
+}
@@ -0,0 +1,44 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Set by Ctrl+C; long-running builtins poll it and stop early
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+// Set once Ctrl+Z suspends foreground programs rather than the shell
+static JOB_CONTROL: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_sigint(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+// Makes Ctrl+C stop the running builtin instead of the whole shell. External
+// programs still get the signal from the terminal and stop on their own.
+pub fn install() {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        // Don't fail the pending read of the next command line
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut());
+        // Ctrl+Z stops the foreground program, never the shell itself, and
+        // the shell can take the terminal back from a program's process
+        // group; programs get the defaults back when they start (see
+        // state::command)
+        for signal in [libc::SIGTSTP, libc::SIGTTOU, libc::SIGTTIN] {
+            libc::signal(signal, libc::SIG_IGN);
+        }
+    }
+    JOB_CONTROL.store(true, Ordering::SeqCst);
+}
+
+pub fn job_control() -> bool {
+    JOB_CONTROL.load(Ordering::SeqCst)
+}
+
+pub fn requested() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+// Forgets a Ctrl+C that arrived before the next command started
+pub fn clear() {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+}
@@ -0,0 +1,183 @@
+use std::fmt;
+use std::future::Future;
+use std::os::unix::process::ExitStatusExt;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use colored::Colorize;
+use tokio::process::Child;
+use tokio::task::AbortHandle;
+
+use crate::errors::CrateResult;
+use crate::helpers;
+use crate::i18n::{tr, trf};
+use crate::output;
+
+// A builtin running in the background, like `serve`, or a process started
+// with `nohup`
+#[derive(Clone, Debug)]
+pub struct Job {
+    pub id: usize,
+    pub command: String,
+    pub started: Instant,
+    // Set for a job that runs as its own process and can outlive the shell
+    pub pid: Option<u32>,
+    // Where such a job's output goes
+    pub log: Option<PathBuf>,
+    // A program suspended with Ctrl+Z and not continued since
+    pub stopped: bool,
+    // The terminal settings such a program had, for `fg` to put back
+    pub modes: Option<libc::termios>,
+    handle: AbortHandle,
+}
+
+// A job's process ended with a status other than 0
+#[derive(Debug)]
+pub struct Exited(pub i32);
+
+impl fmt::Display for Exited {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", trf("exited with status {}", &[&self.0]))
+    }
+}
+
+impl std::error::Error for Exited {}
+
+impl Job {
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+static JOBS: LazyLock<Mutex<Vec<Job>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+
+fn table() -> std::sync::MutexGuard<'static, Vec<Job>> {
+    JOBS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// Runs `task` in the background as a new job and returns its number. The job
+// leaves the table when the task ends, with a notice saying how it went.
+pub fn spawn<F>(command: &str, task: F) -> usize
+where
+    F: Future<Output = CrateResult<()>> + Send + 'static,
+{
+    start(None, command, task)
+}
+
+// Like `spawn`, keeping the number `id` when there is one
+fn start<F>(id: Option<usize>, command: &str, task: F) -> usize
+where
+    F: Future<Output = CrateResult<()>> + Send + 'static,
+{
+    let mut jobs = table();
+    // Numbers count up from the highest running job, as in bash
+    let id = id.unwrap_or_else(|| jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1);
+    let handle = tokio::spawn(async move {
+        let result = task.await;
+        if let Some(job) = remove(id) {
+            let status = match &result {
+                Ok(()) => 0,
+                Err(e) => e.downcast_ref::<Exited>().map(|exited| exited.0).unwrap_or(1),
+            };
+            let details = trf("(exit status {}, {})", &[&status, &helpers::human_duration(job.elapsed())]).bright_black();
+            output::notify(&match result {
+                Ok(()) => format!("[{}]  {}  {}  {}", id, tr("Done").green(), job.command, details),
+                Err(e) if e.is::<Exited>() => format!("[{}]  {}  {}  {}", id, tr("Failed").red(), job.command, details),
+                Err(e) => format!("[{}]  {}  {}  {}: {}", id, tr("Failed").red(), job.command, details, e),
+            });
+        }
+    })
+    .abort_handle();
+    jobs.push(Job { id, command: command.to_string(), started: Instant::now(), pid: None, log: None, stopped: false, modes: None, handle });
+    jobs.sort_by_key(|job| job.id);
+    id
+}
+
+// Makes a job of a foreground program that Ctrl+Z just stopped. It is
+// watched from here on: `bg` and `kill` continue it, and its end is
+// announced like any other job's. `id` is the number it had before `fg`.
+pub fn suspended(command: &str, pid: u32, id: Option<usize>, modes: Option<libc::termios>) -> usize {
+    let id = start(id, command, async move {
+        loop {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let mut status = 0;
+            match unsafe { libc::waitpid(pid as libc::pid_t, &mut status, libc::WNOHANG | libc::WUNTRACED | libc::WCONTINUED) } {
+                0 => {}
+                -1 => return Err(std::io::Error::last_os_error().into()),
+                _ if libc::WIFSTOPPED(status) => set_stopped(pid, true),
+                _ if libc::WIFCONTINUED(status) => set_stopped(pid, false),
+                _ => return exit_result(std::process::ExitStatus::from_raw(status)),
+            }
+        }
+    });
+    if let Some(job) = table().iter_mut().find(|job| job.id == id) {
+        job.pid = Some(pid);
+        job.stopped = true;
+        job.modes = modes;
+    }
+    id
+}
+
+fn set_stopped(pid: u32, stopped: bool) {
+    if let Some(job) = table().iter_mut().find(|job| job.pid == Some(pid)) {
+        job.stopped = stopped;
+    }
+}
+
+// Killed by a signal: 128 plus its number, as shells report it
+pub fn exit_result(status: std::process::ExitStatus) -> CrateResult<()> {
+    match status.code().or_else(|| status.signal().map(|signal| 128 + signal)) {
+        Some(0) => Ok(()),
+        code => Err(Exited(code.unwrap_or(1)).into()),
+    }
+}
+
+// Makes a job of a process started in the background, writing to `log`
+pub fn spawn_process(command: &str, mut child: Child, log: PathBuf) -> usize {
+    let pid = child.id();
+    let id = spawn(command, async move { exit_result(child.wait().await?) });
+    if let Some(job) = table().iter_mut().find(|job| job.id == id) {
+        job.pid = pid;
+        job.log = Some(log);
+    }
+    id
+}
+
+// Running jobs, oldest first
+pub fn list() -> Vec<Job> {
+    table().clone()
+}
+
+// Stops a job; None if there is no job with that number
+pub fn kill(id: usize) -> Option<Job> {
+    let job = remove(id)?;
+    job.handle.abort();
+    Some(job)
+}
+
+// Takes a job out of the table without stopping it; it ends unannounced
+pub fn disown(id: usize) -> Option<Job> {
+    remove(id)
+}
+
+// Takes a suspended program's job out of the table for `fg`, which waits
+// for the program itself
+pub fn resume(id: usize) -> Option<Job> {
+    let job = remove(id)?;
+    job.handle.abort();
+    Some(job)
+}
+
+// Waits until every job has ended
+pub async fn wait_all() {
+    while !table().is_empty() {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+fn remove(id: usize) -> Option<Job> {
+    let mut jobs = table();
+    let index = jobs.iter().position(|job| job.id == id)?;
+    Some(jobs.remove(index))
+}
@@ -0,0 +1,317 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use filetime::FileTime;
+
+use crate::dirs;
+use crate::errors::CrateResult;
+use crate::i18n::{tr, trf};
+
+const JOURNAL_FILE: &str = "journal";
+const JOURNAL_LIMIT: usize = 100;
+
+// A file operation together with the metadata needed to reverse it.
+#[derive(Clone, Debug)]
+pub enum Operation {
+    // A rename; `mtime` lets us notice if the destination was edited afterwards
+    Move { from: PathBuf, to: PathBuf, mtime: FileTime },
+    // A copy that created `dest`; for directory trees `mtime` is when the copy finished
+    Copy { dest: PathBuf, mtime: FileTime },
+    // `touch` on a missing file, or a pipe made by mkfifo
+    Create { path: PathBuf, mtime: FileTime },
+    // `touch` on an existing file: the times it had before
+    Retime { path: PathBuf, atime: FileTime, mtime: FileTime, new_mtime: FileTime },
+    // A bulk `rename`, undone as a whole: each file's old and new path, plus
+    // the mtime it had after the rename
+    Rename { moves: Vec<(PathBuf, PathBuf, FileTime)> },
+    // Directories created by mkdir, outermost first
+    Mkdir { paths: Vec<PathBuf> },
+    // An operation that overwrote data and can't be reverted
+    Irreversible { description: String },
+}
+
+impl Operation {
+    fn encode(&self) -> String {
+        match self {
+            Operation::Move { from, to, mtime } => {
+                format!("mv\t{}\t{}\t{}", encode_time(mtime), from.display(), to.display())
+            }
+            Operation::Copy { dest, mtime } => format!("cp\t{}\t{}", encode_time(mtime), dest.display()),
+            Operation::Create { path, mtime } => format!("create\t{}\t{}", encode_time(mtime), path.display()),
+            Operation::Retime { path, atime, mtime, new_mtime } => format!(
+                "retime\t{}\t{}\t{}\t{}",
+                encode_time(atime),
+                encode_time(mtime),
+                encode_time(new_mtime),
+                path.display()
+            ),
+            Operation::Rename { moves } => {
+                let fields: Vec<String> = moves
+                    .iter()
+                    .map(|(from, to, mtime)| format!("{}\t{}\t{}", encode_time(mtime), from.display(), to.display()))
+                    .collect();
+                format!("rename\t{}", fields.join("\t"))
+            }
+            Operation::Mkdir { paths } => {
+                let paths: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+                format!("mkdir\t{}", paths.join("\t"))
+            }
+            Operation::Irreversible { description } => format!("irreversible\t{}", description),
+        }
+    }
+
+    fn decode(line: &str) -> Option<Operation> {
+        let fields: Vec<&str> = line.split('\t').collect();
+
+        match fields.as_slice() {
+            ["mv", mtime, from, to] => Some(Operation::Move {
+                from: PathBuf::from(from),
+                to: PathBuf::from(to),
+                mtime: decode_time(mtime)?,
+            }),
+            ["cp", mtime, dest] => Some(Operation::Copy { dest: PathBuf::from(dest), mtime: decode_time(mtime)? }),
+            ["create", mtime, path] => Some(Operation::Create { path: PathBuf::from(path), mtime: decode_time(mtime)? }),
+            ["retime", atime, mtime, new_mtime, path] => Some(Operation::Retime {
+                path: PathBuf::from(path),
+                atime: decode_time(atime)?,
+                mtime: decode_time(mtime)?,
+                new_mtime: decode_time(new_mtime)?,
+            }),
+            ["rename", moves @ ..] if !moves.is_empty() && moves.len() % 3 == 0 => Some(Operation::Rename {
+                moves: moves
+                    .chunks(3)
+                    .map(|fields| Some((PathBuf::from(fields[1]), PathBuf::from(fields[2]), decode_time(fields[0])?)))
+                    .collect::<Option<_>>()?,
+            }),
+            ["mkdir", paths @ ..] if !paths.is_empty() => Some(Operation::Mkdir {
+                paths: paths.iter().map(PathBuf::from).collect(),
+            }),
+            ["irreversible", description] => Some(Operation::Irreversible { description: description.to_string() }),
+            _ => None,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Operation::Move { from, to, .. } => format!("mv '{}' → '{}'", from.display(), to.display()),
+            Operation::Copy { dest, .. } => format!("copy to '{}'", dest.display()),
+            Operation::Create { path, .. } => format!("touch '{}' (created)", path.display()),
+            Operation::Retime { path, .. } => format!("touch '{}' (timestamps)", path.display()),
+            Operation::Rename { moves } => format!("rename ({} files)", moves.len()),
+            Operation::Mkdir { paths } => format!("mkdir '{}'", paths.last().map(|p| p.display().to_string()).unwrap_or_default()),
+            Operation::Irreversible { description } => description.clone(),
+        }
+    }
+}
+
+fn encode_time(time: &FileTime) -> String {
+    format!("{}:{}", time.unix_seconds(), time.nanoseconds())
+}
+
+fn decode_time(value: &str) -> Option<FileTime> {
+    let (secs, nanos) = value.split_once(':')?;
+    Some(FileTime::from_unix_time(secs.parse().ok()?, nanos.parse().ok()?))
+}
+
+pub fn journal_path() -> PathBuf {
+    dirs::state_dir().join(JOURNAL_FILE)
+}
+
+fn mtime_of(path: &Path) -> CrateResult<FileTime> {
+    Ok(FileTime::from_last_modification_time(&fs::symlink_metadata(path)?))
+}
+
+fn load() -> CrateResult<Vec<Operation>> {
+    let path = journal_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    Ok(fs::read_to_string(path)?.lines().filter_map(Operation::decode).collect())
+}
+
+fn save(operations: &[Operation]) -> CrateResult<()> {
+    let start = operations.len().saturating_sub(JOURNAL_LIMIT);
+    let mut contents = String::new();
+    for operation in &operations[start..] {
+        contents.push_str(&operation.encode());
+        contents.push('\n');
+    }
+
+    fs::write(journal_path(), contents)?;
+    Ok(())
+}
+
+fn push(operation: Operation) -> CrateResult<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(journal_path())?;
+    writeln!(file, "{}", operation.encode())?;
+    Ok(())
+}
+
+// The record_* functions take absolute paths, already resolved against the
+// shell's working directory, so undo works from anywhere.
+pub fn record_move(from: &Path, to: &Path, overwrote: bool) -> CrateResult<()> {
+    if overwrote {
+        return push(Operation::Irreversible {
+            description: format!("mv '{}' → '{}' (overwrote destination)", from.display(), to.display()),
+        });
+    }
+
+    let mtime = mtime_of(to)?;
+    push(Operation::Move { from: from.to_path_buf(), to: to.to_path_buf(), mtime })
+}
+
+pub fn record_copy(source: &Path, dest: &Path, overwrote: bool) -> CrateResult<()> {
+    if overwrote {
+        return push(Operation::Irreversible {
+            description: format!("copy '{}' → '{}' (overwrote destination)", source.display(), dest.display()),
+        });
+    }
+
+    let mtime = if dest.is_dir() { FileTime::now() } else { mtime_of(dest)? };
+    push(Operation::Copy { dest: dest.to_path_buf(), mtime })
+}
+
+pub fn record_touch(path: &Path, previous: Option<(FileTime, FileTime)>) -> CrateResult<()> {
+    let path = path.to_path_buf();
+    let new_mtime = mtime_of(&path)?;
+
+    match previous {
+        Some((atime, mtime)) => push(Operation::Retime { path, atime, mtime, new_mtime }),
+        None => push(Operation::Create { path, mtime: new_mtime }),
+    }
+}
+
+// `moves` are the renames that actually happened, in order
+pub fn record_rename(moves: &[(PathBuf, PathBuf)]) -> CrateResult<()> {
+    if moves.is_empty() {
+        return Ok(());
+    }
+
+    let moves = moves
+        .iter()
+        .map(|(from, to)| Ok((from.clone(), to.clone(), mtime_of(to)?)))
+        .collect::<CrateResult<_>>()?;
+    push(Operation::Rename { moves })
+}
+
+pub fn record_mkdir(created: Vec<PathBuf>) -> CrateResult<()> {
+    if created.is_empty() {
+        return Ok(());
+    }
+
+    push(Operation::Mkdir { paths: created })
+}
+
+// Captures the current timestamps of `path`, for journaling a touch.
+pub fn file_times(path: &Path) -> Option<(FileTime, FileTime)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((FileTime::from_last_access_time(&metadata), FileTime::from_last_modification_time(&metadata)))
+}
+
+// Lists the directories `mkdir -p` would have to create for `path`, outermost first.
+pub fn missing_dirs(path: &Path) -> Vec<PathBuf> {
+    let mut missing: Vec<PathBuf> = path
+        .ancestors()
+        .filter(|p| !p.as_os_str().is_empty() && !p.exists())
+        .map(Path::to_path_buf)
+        .collect();
+    missing.reverse();
+    missing
+}
+
+// Reverts the most recent journaled operation, returning a description of it
+// or `None` when the journal is empty. Refuses (leaving the journal untouched)
+// when reverting would destroy data written after the operation.
+pub fn undo_last() -> CrateResult<Option<String>> {
+    let mut operations = load()?;
+    let Some(operation) = operations.pop() else {
+        return Ok(None);
+    };
+
+    revert(&operation).map_err(|e| anyhow!(trf("Refusing to undo {}: {}", &[&operation.describe(), &e])))?;
+    save(&operations)?;
+
+    Ok(Some(operation.describe()))
+}
+
+fn revert(operation: &Operation) -> CrateResult<()> {
+    match operation {
+        Operation::Move { from, to, mtime } => {
+            if from.exists() {
+                return Err(anyhow!(trf("'{}' exists again", &[&from.display()])));
+            }
+            ensure_unchanged(to, mtime)?;
+            fs::rename(to, from)?;
+        }
+        Operation::Copy { dest, mtime } => {
+            if dest.is_dir() {
+                ensure_tree_unchanged(dest, mtime)?;
+                fs::remove_dir_all(dest)?;
+            } else {
+                ensure_unchanged(dest, mtime)?;
+                fs::remove_file(dest)?;
+            }
+        }
+        Operation::Create { path, mtime } => {
+            ensure_unchanged(path, mtime)?;
+            if fs::metadata(path)?.len() != 0 {
+                return Err(anyhow!(trf("'{}' is no longer empty", &[&path.display()])));
+            }
+            fs::remove_file(path)?;
+        }
+        Operation::Retime { path, atime, mtime, new_mtime } => {
+            ensure_unchanged(path, new_mtime)?;
+            filetime::set_file_times(path, *atime, *mtime)?;
+        }
+        Operation::Rename { moves } => {
+            // Check every file first so a refusal leaves all of them renamed
+            for (from, to, mtime) in moves {
+                if from.exists() {
+                    return Err(anyhow!(trf("'{}' exists again", &[&from.display()])));
+                }
+                ensure_unchanged(to, mtime)?;
+            }
+            for (from, to, _) in moves.iter().rev() {
+                fs::rename(to, from)?;
+            }
+        }
+        Operation::Mkdir { paths } => {
+            // Innermost first; remove_dir refuses to delete anything that gained contents
+            for path in paths.iter().rev() {
+                fs::remove_dir(path).map_err(|e| anyhow!(trf("cannot remove '{}': {}", &[&path.display(), &e])))?;
+            }
+        }
+        Operation::Irreversible { .. } => {
+            return Err(anyhow!(tr("the original data was overwritten")));
+        }
+    }
+
+    Ok(())
+}
+
+fn ensure_unchanged(path: &Path, mtime: &FileTime) -> CrateResult<()> {
+    let current = mtime_of(path).map_err(|_| anyhow!(trf("'{}' no longer exists", &[&path.display()])))?;
+    if current != *mtime {
+        return Err(anyhow!(trf("'{}' was modified afterwards", &[&path.display()])));
+    }
+    Ok(())
+}
+
+fn ensure_tree_unchanged(dir: &Path, mtime: &FileTime) -> CrateResult<()> {
+    if mtime_of(dir)? > *mtime {
+        return Err(anyhow!(trf("'{}' was modified afterwards", &[&dir.display()])));
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            ensure_tree_unchanged(&path, mtime)?;
+        } else if mtime_of(&path)? > *mtime {
+            return Err(anyhow!(trf("'{}' was modified afterwards", &[&path.display()])));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use anyhow::anyhow;
+
+use crate::errors::CrateResult;
+use crate::i18n::{tr, trf};
+
+// Every entry the shell keeps in the keyring is filed under this service
+const SERVICE: &str = "shellbasics";
+
+// The platform keyring is reached through its command-line tool: secret-tool
+// (libsecret: GNOME Keyring, KWallet) on Linux and security on macOS. The
+// keyring crate would need libdbus to build on Linux; these tools are there
+// wherever a keyring is.
+#[cfg(target_os = "macos")]
+const TOOL: &str = "security";
+#[cfg(not(target_os = "macos"))]
+const TOOL: &str = "secret-tool";
+
+// The stored value, or None if there is no entry called `name`
+pub fn get(name: &str) -> CrateResult<Option<String>> {
+    let mut command = Command::new(TOOL);
+    if cfg!(target_os = "macos") {
+        command.args(["find-generic-password", "-s", SERVICE, "-a", name, "-w"]);
+    } else {
+        command.args(["lookup", "service", SERVICE, "account", name]);
+    }
+    let output = command.stdin(Stdio::null()).output().map_err(not_installed)?;
+    // Both tools fail without a message when there is no such entry
+    if !output.status.success() {
+        return match String::from_utf8_lossy(&output.stderr).trim() {
+            "" => Ok(None),
+            message if message.contains("could not be found") => Ok(None),
+            message => Err(anyhow!(trf("keyring: {}", &[&message]))),
+        };
+    }
+    let value = String::from_utf8(output.stdout)?;
+    Ok(Some(value.strip_suffix('\n').unwrap_or(&value).to_string()))
+}
+
+// Stores `value` as `name`, replacing what was there
+pub fn set(name: &str, value: &str) -> CrateResult<()> {
+    // Both tools read the value from stdin, which keeps it out of `ps`
+    let mut command = Command::new(TOOL);
+    let input = if cfg!(target_os = "macos") {
+        // With -w last and no value, security asks for it and then again to
+        // confirm, a line each
+        if value.contains('\n') {
+            return Err(anyhow!(tr("keyring: security can't store a value with a line break")));
+        }
+        command.args(["add-generic-password", "-U", "-s", SERVICE, "-a", name, "-w"]);
+        format!("{}\n{}\n", value, value)
+    } else {
+        command.args(["store", &format!("--label={} {}", SERVICE, name), "service", SERVICE, "account", name]);
+        value.to_string()
+    };
+    let mut child = command.stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::piped()).spawn().map_err(not_installed)?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(anyhow!(trf("keyring: {}", &[&String::from_utf8_lossy(&output.stderr).trim()])));
+    }
+    Ok(())
+}
+
+fn not_installed(e: std::io::Error) -> anyhow::Error {
+    match e.kind() {
+        std::io::ErrorKind::NotFound => anyhow!(trf("{} is not installed; the keyring can't be reached", &[&TOOL])),
+        _ => e.into(),
+    }
+}
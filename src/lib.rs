@@ -0,0 +1,61 @@
+pub mod audit;
+pub mod bench;
+pub mod capture;
+pub mod cli;
+pub mod command;
+pub mod completion;
+pub mod condition;
+pub mod config;
+pub mod crash;
+pub mod dedupe;
+pub mod demo;
+pub mod dirs;
+pub mod dns;
+pub mod doctor;
+pub mod download;
+pub mod editor;
+pub mod encryption;
+pub mod environment;
+pub mod errors;
+pub mod executables;
+pub mod fifo;
+pub mod foreground;
+pub mod git;
+pub mod glob;
+pub mod helpers;
+pub mod history;
+pub mod i18n;
+pub mod interrupt;
+pub mod jobs;
+pub mod journal;
+pub mod keyring;
+pub mod limits;
+pub mod logging;
+pub mod menu;
+pub mod ports;
+pub mod monitor;
+pub mod output;
+pub mod practice;
+pub mod printf;
+pub mod processes;
+pub mod prompt;
+pub mod rename;
+pub mod search;
+pub mod serve;
+pub mod signals;
+pub mod spelling;
+pub mod ssh;
+pub mod state;
+pub mod stats;
+pub mod status;
+pub mod sysinfo;
+pub mod tasks;
+pub mod templates;
+pub mod terminal;
+pub mod theme;
+pub mod tokenizer;
+pub mod toolchains;
+pub mod transfer;
+pub mod tutorial;
+pub mod usage;
+pub mod widgets;
@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+use std::io;
+
+use crate::i18n::tr;
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type RawResource = libc::__rlimit_resource_t;
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+type RawResource = libc::c_int;
+
+// The limits `ulimit` knows, in `ulimit -a` order
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Resource {
+    CoreSize,
+    DataSize,
+    FileSize,
+    OpenFiles,
+    StackSize,
+    CpuTime,
+    Processes,
+    VirtualMemory,
+}
+
+pub const RESOURCES: [Resource; 8] = [
+    Resource::CoreSize,
+    Resource::DataSize,
+    Resource::FileSize,
+    Resource::OpenFiles,
+    Resource::StackSize,
+    Resource::CpuTime,
+    Resource::Processes,
+    Resource::VirtualMemory,
+];
+
+impl Resource {
+    // The ulimit flag, as in bash
+    pub fn flag(self) -> char {
+        match self {
+            Resource::CoreSize => 'c',
+            Resource::DataSize => 'd',
+            Resource::FileSize => 'f',
+            Resource::OpenFiles => 'n',
+            Resource::StackSize => 's',
+            Resource::CpuTime => 't',
+            Resource::Processes => 'u',
+            Resource::VirtualMemory => 'v',
+        }
+    }
+
+    pub fn from_flag(flag: char) -> Option<Resource> {
+        RESOURCES.into_iter().find(|resource| resource.flag() == flag)
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Resource::CoreSize => tr("core file size"),
+            Resource::DataSize => tr("data seg size"),
+            Resource::FileSize => tr("file size"),
+            Resource::OpenFiles => tr("open files"),
+            Resource::StackSize => tr("stack size"),
+            Resource::CpuTime => tr("cpu time"),
+            Resource::Processes => tr("max user processes"),
+            Resource::VirtualMemory => tr("virtual memory"),
+        }
+    }
+
+    // Sizes are shown and typed in kilobytes; the rest are plain counts
+    pub fn is_size(self) -> bool {
+        matches!(self, Resource::CoreSize | Resource::DataSize | Resource::FileSize | Resource::StackSize | Resource::VirtualMemory)
+    }
+
+    // Empty for counts
+    pub fn unit(self) -> &'static str {
+        match self {
+            Resource::CpuTime => tr("seconds"),
+            _ if self.is_size() => tr("kbytes"),
+            _ => "",
+        }
+    }
+
+    fn raw(self) -> RawResource {
+        match self {
+            Resource::CoreSize => libc::RLIMIT_CORE,
+            Resource::DataSize => libc::RLIMIT_DATA,
+            Resource::FileSize => libc::RLIMIT_FSIZE,
+            Resource::OpenFiles => libc::RLIMIT_NOFILE,
+            Resource::StackSize => libc::RLIMIT_STACK,
+            Resource::CpuTime => libc::RLIMIT_CPU,
+            Resource::Processes => libc::RLIMIT_NPROC,
+            Resource::VirtualMemory => libc::RLIMIT_AS,
+        }
+    }
+}
+
+// A soft limit in bytes, seconds or a count; None is unlimited
+pub type Limit = Option<u64>;
+
+// Soft limits the session sets for the programs it starts, on top of the ones
+// the shell itself runs with
+pub type Limits = BTreeMap<Resource, Limit>;
+
+// The shell's own soft and hard limit
+pub fn current(resource: Resource) -> (Limit, Limit) {
+    let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+    if unsafe { libc::getrlimit(resource.raw(), &mut limit) } != 0 {
+        return (None, None);
+    }
+    (from_raw(limit.rlim_cur), from_raw(limit.rlim_max))
+}
+
+// Lowers or raises soft limits in a child between fork and exec, so it has to
+// stick to async-signal-safe calls
+pub fn apply(limits: &Limits) -> io::Result<()> {
+    for (&resource, &soft) in limits {
+        let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+        if unsafe { libc::getrlimit(resource.raw(), &mut limit) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        limit.rlim_cur = soft.unwrap_or(libc::RLIM_INFINITY);
+        if unsafe { libc::setrlimit(resource.raw(), &limit) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+// Adds `increment` to the calling process's nice value; also used between
+// fork and exec
+pub fn nice(increment: i32) -> io::Result<()> {
+    if increment == 0 {
+        return Ok(());
+    }
+    let current = priority(0)?;
+    set_priority(0, current + increment)
+}
+
+// A process's nice value, -20 (first in line) to 19; 0 is the caller
+pub fn priority(pid: u32) -> io::Result<i32> {
+    // -1 is a valid answer, so errors are told apart by errno
+    unsafe { *libc::__errno_location() = 0 };
+    let priority = unsafe { libc::getpriority(libc::PRIO_PROCESS, pid as libc::id_t) };
+    match io::Error::last_os_error() {
+        e if priority == -1 && e.raw_os_error() != Some(0) => Err(e),
+        _ => Ok(priority),
+    }
+}
+
+pub fn set_priority(pid: u32, priority: i32) -> io::Result<()> {
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, pid as libc::id_t, priority.clamp(-20, 19)) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// How a limit is written, in the resource's unit
+pub fn format(resource: Resource, limit: Limit) -> String {
+    match limit {
+        None => "unlimited".to_string(),
+        Some(value) if resource.is_size() => (value / 1024).to_string(),
+        Some(value) => value.to_string(),
+    }
+}
+
+fn from_raw(value: libc::rlim_t) -> Limit {
+    (value != libc::RLIM_INFINITY).then_some(value)
+}
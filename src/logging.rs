@@ -0,0 +1,71 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use tracing::level_filters::LevelFilter;
+use tracing_appender::rolling::{Builder, Rotation};
+
+use crate::config;
+use crate::dirs;
+use crate::errors::{self, Access, CrateResult};
+
+const LOG_DIR: &str = "logs";
+const LOG_PREFIX: &str = "shell";
+const MAX_LOG_FILES: usize = 7;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Off,
+    Error,
+    #[default]
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Off => LevelFilter::OFF,
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Trace => LevelFilter::TRACE,
+        }
+    }
+}
+
+pub fn log_dir() -> PathBuf {
+    dirs::state_dir().join(LOG_DIR)
+}
+
+// Events only ever go to a daily-rotated file: writing them to the terminal
+// would interleave with prompts and corrupt raw-mode screens
+pub fn init(flag: Option<LogLevel>) -> CrateResult<()> {
+    let level = flag.or(config::get().log_level).unwrap_or_default();
+    if level == LogLevel::Off {
+        return Ok(());
+    }
+
+    // The appender prunes old files before it creates the directory, and
+    // complains on stderr if it isn't there yet
+    let dir = log_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| errors::with_path(e, &errors::parent_of(&dir), Access::Write))?;
+
+    let appender = Builder::new()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_PREFIX)
+        .filename_suffix("log")
+        .max_log_files(MAX_LOG_FILES)
+        .build(dir)?;
+
+    tracing_subscriber::fmt()
+        .with_max_level(LevelFilter::from(level))
+        .with_writer(appender)
+        .with_ansi(false)
+        .init();
+
+    Ok(())
+}
@@ -1,23 +1,35 @@
-use command::Command;
-use errors::CrateResult;
+use shell::command::{AbbrAction, Command, DedupeOptions, HashAction, Pipeline, Redirected, RemoveOptions, Stream, UlimitAction};
+use shell::errors::{self, Access, CrateResult, False, PermissionCause, PermissionError};
+use shell::i18n::{tr, trf};
+use shell::prompt::Segment;
+use shell::search::{Matcher, SearchOptions};
+use shell::spelling::Correction;
+use shell::state::ShellState;
+use shell::tokenizer::{self, quote, ProcessSubstitution};
+use shell::{audit, bench, capture, cli, condition, config, completion, crash, dedupe, demo, dirs, dns, doctor, download, editor, encryption, environment, executables, fifo, foreground, git, glob, helpers, history, i18n, interrupt, jobs, journal, keyring, limits, logging, menu, monitor, output, ports, practice, printf, processes, prompt, rename, serve, signals, spelling, ssh, stats, status, tasks, templates, terminal, theme, transfer, tutorial, usage, widgets};
+use anyhow::anyhow;
+use clap::Parser;
 use colored::*;
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
 use tokio::{
     io::{AsyncBufReadExt, AsyncWriteExt},
     task::JoinHandle,
 };
-use std::process::Command as ProcessCommand;
 
-mod command;
-mod errors;
-mod helpers;
-
-fn spawn_user_input_handler() -> JoinHandle<CrateResult<()>> {
-    tokio::spawn(async {
+fn spawn_user_input_handler(mut state: ShellState, demo_script: Vec<String>) -> JoinHandle<CrateResult<()>> {
+    tokio::spawn(async move {
         let stdin = tokio::io::stdin();
         let stdout = tokio::io::stdout();
 
         let mut reader = tokio::io::BufReader::new(stdin).lines();
+        let editing = std::io::stdin().is_terminal();
         let mut stdout = tokio::io::BufWriter::new(stdout);
+        // SIGWINCH: the prompt being typed at is drawn again for the new size
+        let mut resized = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::window_change())?;
 
         // Display a colorful welcome message with ASCII art
         let welcome_message = format!(
@@ -30,47 +42,193 @@ fn spawn_user_input_handler() -> JoinHandle<CrateResult<()>> {
  ____) | | | |  __/ | | | |_) | (_| \__ \ | (__\__ \__ \
 |_____/|_| |_|\___|_|_| |____/ \__,_|___/_|\___|___/___/
                               by Farhan Shahid                                
-Welcome to the Shell Basics v1.0! Type '{}' to see available commands.
+{}
 {}"#,
             "━".repeat(60).bright_blue(),
-            "help".bright_yellow(),
+            trf("Welcome to the Shell Basics v1.0! Type '{}' to see available commands.", &[&"help".bright_yellow()]),
             "━".repeat(60).bright_blue()
         );
 
-        stdout.write(welcome_message.as_bytes()).await?;
-        stdout.write(b"\n").await?;
+        stdout.write_all(welcome_message.as_bytes()).await?;
+        stdout.write_all(b"\n").await?;
+
+        // Presenter mode: type each scripted command, then run it on a keypress
+        for line in &demo_script {
+            let branch = Segment::Ready(prompt::git_branch(&state).await);
+            stdout.write_all(generate_prompt(&state, &branch)?.as_bytes()).await?;
+            stdout.flush().await?;
+
+            demo::type_out(line).await?;
+            if let Some(b'q') = tokio::task::spawn_blocking(terminal::wait_for_key).await? {
+                println!();
+                break;
+            }
+            println!();
 
+            if let Outcome::Exit = process_line(&mut state, line).await {
+                finish_session(&mut state).await;
+                return Ok(());
+            }
+        }
+
+        // Ctrl+D presses in a row, for ignoreeof
+        let mut eofs = 0;
         loop {
+            // The prompt shows the status from here on
+            status::clear();
+            // Jobs that finished while the last command ran
+            output::print_notices();
+            // Lines run meanwhile in other shells sharing the history
+            state.history.extend(audit::merge());
+
+            // Each session titles its own tab after its own directory and
+            // tells the terminal where new tabs should start
+            if terminal::is_ansi_terminal() {
+                if config::get().terminal_title.unwrap_or(true) {
+                    stdout.write_all(terminal::set_title(&format!("shell: {}", state.short_cwd())).as_bytes()).await?;
+                }
+                stdout.write_all(terminal::report_cwd(&state.cwd).as_bytes()).await?;
+            }
+
+            // Slow segments get a short head start; whatever isn't ready by
+            // then is drawn as a placeholder and filled in when it arrives
+            let mut pending_branch = tokio::spawn(prompt::git_branch(&state));
+            let branch = if terminal::is_ansi_terminal() {
+                match tokio::time::timeout(prompt::SEGMENT_BUDGET, &mut pending_branch).await {
+                    Ok(branch) => Segment::Ready(branch.unwrap_or(None)),
+                    Err(_) => Segment::Loading,
+                }
+            } else {
+                Segment::Ready((&mut pending_branch).await.unwrap_or(None))
+            };
+
             // Generate beautiful prompt with username and current directory
-            let prompt = generate_prompt()?;
-            stdout.write(prompt.as_bytes()).await?;
+            let mut prompt = generate_prompt(&state, &branch)?;
+            if !editing {
+                stdout.write_all(prompt.as_bytes()).await?;
+            }
             stdout.flush().await?;
 
-            if let Ok(Some(line)) = reader.next_line().await {
-                let trimmed_line = line.trim();
-                
-                if trimmed_line.is_empty() {
-                    continue;
+            // On a terminal the line editor prints the prompt and handles keys
+            // like Tab; otherwise lines are read as they come
+            let next_line = {
+                let (prompt, state) = (prompt.clone(), state.clone());
+                let reader = &mut reader;
+                async move {
+                    if editing {
+                        let history = state.history.clone();
+                        let complete = move |line: &str, cursor: usize| completion::complete(line, cursor, &state);
+                        return match tokio::task::spawn_blocking(move || editor::read_line(&prompt, &complete, &history)).await {
+                            Ok(Ok(input)) => input,
+                            _ => editor::Input::Eof,
+                        };
+                    }
+                    match reader.next_line().await {
+                        Ok(Some(line)) => editor::Input::Line(line),
+                        _ => editor::Input::Eof,
+                    }
                 }
-                
-                if trimmed_line == "help" {
-                    print_help();
+            };
+            tokio::pin!(next_line);
+
+            // Late segments and a running spinner redraw the top line of
+            // the prompt while the editor owns stdout
+            let mut branch = branch;
+            // The editor draws the prompt first
+            let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + status::FRAME_INTERVAL, status::FRAME_INTERVAL);
+            let input = loop {
+                tokio::select! {
+                    input = &mut next_line => {
+                        pending_branch.abort();
+                        break input;
+                    }
+                    found = &mut pending_branch, if matches!(branch, Segment::Loading) => {
+                        branch = Segment::Ready(found.unwrap_or(None));
+                        prompt = generate_prompt(&state, &branch)?;
+                        output::redraw_prompt(&prompt)?;
+                    }
+                    _ = ticker.tick(), if editing && terminal::is_ansi_terminal() && status::animating() => {
+                        prompt = generate_prompt(&state, &branch)?;
+                        output::redraw_prompt(&prompt)?;
+                    }
+                    // The right prompt is aligned to the window's width
+                    _ = resized.recv(), if editing && terminal::is_ansi_terminal() => {
+                        prompt = generate_prompt(&state, &branch)?;
+                        output::redraw_resized(&prompt)?;
+                    }
+                }
+            };
+
+            let line = match input {
+                editor::Input::Line(line) => line,
+                editor::Input::Interrupted => {
+                    eofs = 0;
                     continue;
                 }
-                
-                let command = handle_new_line(&trimmed_line).await;
-
-                if let Ok(command) = &command {
-                    match command {
-                        Command::Exit => {
-                            println!("{}", "Exiting the shell. Goodbye!".bright_cyan());
-                            break;
-                        }
-                        _ => {}
+                // Ctrl+D on an empty line, or the end of piped input
+                editor::Input::Eof => {
+                    eofs += 1;
+                    if editing && config::get().ignoreeof && eofs < IGNORED_EOFS {
+                        println!("{}", tr("Use \"exit\" to leave the shell."));
+                        continue;
                     }
-                } else {
-                    eprintln!("{} {}", "Error:".bright_red(), command.err().unwrap());
+                    if editing && !std::mem::take(&mut state.exit_warned) && !jobs::list().is_empty() {
+                        warn_running_jobs();
+                        state.exit_warned = true;
+                        continue;
+                    }
+                    println!("{}", tr("Exiting the shell. Goodbye!").bright_cyan());
+                    finish_session(&mut state).await;
+                    break;
                 }
+            };
+            eofs = 0;
+
+            // Only rewrite what the terminal echoed back to us
+            if config::get().transient_prompt && terminal::is_ansi_terminal() && std::io::stdin().is_terminal() {
+                let compact = format!("{}{}", theme::paint("prompt", "❯ "), line);
+                stdout.write_all(terminal::collapse_prompt(&prompt, &line, &compact).as_bytes()).await?;
+                stdout.flush().await?;
+            }
+
+            // `!!`, `!$`, `!*` and `^old^new` are filled in from the line
+            // before, and the line they make is shown before it runs
+            let (line, from_history) = match history::expand(&line, &state.history) {
+                Ok(Some(expanded)) => (expanded, true),
+                Ok(None) => (line, false),
+                Err(e) => {
+                    eprintln!("{} {}", theme::paint("error", tr("Error:")), e);
+                    continue;
+                }
+            };
+            // With confirm_expansions, globs are shown expanded too, and the
+            // line only runs once Enter is pressed again
+            if editing && config::get().confirm_expansions {
+                let full = glob::expand_line(&line, &state).unwrap_or_else(|_| line.clone());
+                let globbed = tokenizer::tokenize(&full).ok() != tokenizer::tokenize(&line).ok();
+                if (from_history || globbed) && !confirm_expansion(&full).await? {
+                    continue;
+                }
+            } else if from_history {
+                println!("{}", line);
+            }
+            // With `correct` set, a mistyped builtin or `cd` path is fixed
+            let correction = if editing { config::get().correct } else { Correction::Off };
+            let line = match correction {
+                Correction::Off => line,
+                _ => match spelling::correct(&line, &state) {
+                    Some(suggestion) if correction == Correction::Auto || terminal::confirm(&suggestion.question) => suggestion.line,
+                    _ => line,
+                },
+            };
+
+            match process_line(&mut state, &line).await {
+                Outcome::Exit => {
+                    finish_session(&mut state).await;
+                    break;
+                }
+                Outcome::Denied if editing => retry_with_sudo(&mut state, &line),
+                _ => {}
             }
         }
 
@@ -78,35 +236,200 @@ Welcome to the Shell Basics v1.0! Type '{}' to see available commands.
     })
 }
 
-fn get_git_branch() -> Option<String> {
-    let output = ProcessCommand::new("git")
-        .args(["rev-parse", "--abbrev-ref", "HEAD"])
-        .output()
-        .ok()?;
-    
-    if output.status.success() {
-        String::from_utf8(output.stdout)
-            .ok()
-            .map(|s| s.trim().to_string())
-    } else {
-        None
+// Shows `line` as it will run and waits for Enter; any other key drops it
+async fn confirm_expansion(line: &str) -> CrateResult<bool> {
+    println!("{}", line);
+    print!("{}", tr("Press Enter to run it, or any other key to cancel").bright_black());
+    std::io::Write::flush(&mut std::io::stdout())?;
+    let key = tokio::task::spawn_blocking(terminal::wait_for_key).await?;
+    println!();
+    Ok(matches!(key, Some(b'\r' | b'\n')))
+}
+
+// With ignoreeof, this many Ctrl+D presses in a row still leave, as in bash
+const IGNORED_EOFS: usize = 10;
+
+enum Outcome {
+    Success,
+    Failure,
+    // Failed because the kernel refused access; sudo may get past it
+    Denied,
+    Exit,
+}
+
+// Runs one line of input and reports how it went
+async fn process_line(state: &mut ShellState, line: &str) -> Outcome {
+    let trimmed_line = line.trim();
+
+    if trimmed_line.is_empty() {
+        return Outcome::Success;
+    }
+
+    if trimmed_line == "help" {
+        print_help();
+        return Outcome::Success;
+    }
+
+    interrupt::clear();
+    status::clear();
+    // A second `exit` only counts right after the first
+    let exit_warned = std::mem::take(&mut state.exit_warned);
+    // Lines matching history_ignore are run but not written down anywhere
+    let recorded = audit::should_record(line);
+    let logged = if recorded { trimmed_line } else { "(not recorded)" };
+    if recorded {
+        crash::record_input(&state.cwd, trimmed_line);
+        state.history.push(trimmed_line.to_string());
+    }
+    tracing::info!(line = logged, "running command");
+    let started = std::time::Instant::now();
+    let command = handle_new_line(state, trimmed_line).await;
+    match &command {
+        Ok(_) => tracing::debug!(line = logged, elapsed = ?started.elapsed(), "command finished"),
+        Err(e) => tracing::warn!(line = logged, error = %e, "command failed"),
+    }
+
+    if recorded {
+        if let Err(e) = audit::record(trimmed_line, command.is_ok()) {
+            tracing::error!(error = %e, "audit log write failed");
+            eprintln!("{} {}", tr("Warning: could not write audit log:").yellow(), e);
+        }
+    }
+
+    match tutorial::after_command(&state.cwd, trimmed_line) {
+        Ok(Some(feedback)) => print!("{}", feedback),
+        Ok(None) => {}
+        Err(e) => eprintln!("{} {}", tr("Warning:").yellow(), e),
+    }
+
+    state.last_success = command.is_ok();
+
+    match command {
+        Ok(Command::Exit) if !exit_warned && !jobs::list().is_empty() => {
+            warn_running_jobs();
+            state.exit_warned = true;
+            Outcome::Success
+        }
+        Ok(Command::Exit) => {
+            println!("{}", tr("Exiting the shell. Goodbye!").bright_cyan());
+            Outcome::Exit
+        }
+        Ok(_) => Outcome::Success,
+        Err(e) if e.is::<False>() => Outcome::Failure,
+        Err(e) => {
+            eprintln!("{} {}", theme::paint("error", tr("Error:")), e);
+            match e.downcast_ref::<PermissionError>() {
+                Some(denied) if !matches!(denied.cause, PermissionCause::ReadOnlyFilesystem) => Outcome::Denied,
+                _ => Outcome::Failure,
+            }
+        }
+    }
+}
+
+fn warn_running_jobs() {
+    println!("{}", tr("There are running jobs:").yellow());
+    for job in jobs::list() {
+        match job.pid {
+            Some(_) => println!("  [{}]  {}  {}", job.id, job.command, tr("(keeps running)").bright_black()),
+            None => println!("  [{}]  {}", job.id, job.command),
+        }
+    }
+    println!("{}", tr("Type exit or press Ctrl+D again to leave anyway; jobs that run inside the shell end with it."));
+}
+
+// Runs the `on_exit` lines and makes sure the audit log is on disk before an
+// interactive session ends
+async fn finish_session(state: &mut ShellState) {
+    let lines = config::get().on_exit.clone();
+    for line in lines {
+        process_line(state, &line).await;
+    }
+    if let Err(e) = audit::sync() {
+        tracing::error!(error = %e, "audit log sync failed");
+    }
+}
+
+// Offers to run a line the kernel refused again, in a copy of this shell
+// under sudo. sudo asks for the password on the terminal itself with echo
+// off; the line editor isn't reading while a command runs, so the terminal
+// is in its normal mode by then.
+fn retry_with_sudo(state: &mut ShellState, line: &str) {
+    if unsafe { libc::geteuid() } == 0 || !terminal::confirm(tr("Run it again with sudo? [y/N] ")) {
+        return;
+    }
+    let status = std::env::current_exe().and_then(|shell| foreground::run(state.command("sudo").arg("--").arg(shell).arg("-c").arg(line), &format!("sudo {}", line)));
+    match status {
+        Ok(status) => state.last_success = status.success(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            eprintln!("{} {}", theme::paint("error", tr("Error:")), trf("{} is not installed", &[&"sudo"]));
+        }
+        Err(e) => eprintln!("{} {}", theme::paint("error", tr("Error:")), e),
+    }
+}
+
+// Runs a program that isn't a builtin on the terminal, from PATH or a path
+// with a slash, and waits for it. A failing exit status fails the line
+// quietly, as the program has said why itself.
+fn run_program(state: &ShellState, name: &str, args: &[String], line: &str) -> CrateResult<()> {
+    let status = match foreground::run(state.user_command(name).args(args), line) {
+        Ok(status) => status,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(unknown_command(name, state)),
+        Err(e) => return Err(anyhow!(trf("{} could not be run: {}", &[&name, &e]))),
+    };
+    // Stopped with Ctrl+Z counts as a failure too; it is a job now
+    match status.success() {
+        true => Ok(()),
+        false => Err(False.into()),
     }
 }
 
-fn is_git_repository() -> bool {
-    ProcessCommand::new("git")
-        .args(["rev-parse", "--is-inside-work-tree"])
-        .output()
-        .map(|output| output.status.success())
-        .unwrap_or(false)
+// The error for a line whose first word is no builtin or program, with the
+// builtin or program on PATH it was probably meant to be
+fn unknown_command(name: &str, state: &ShellState) -> anyhow::Error {
+    let path = state.var("PATH").unwrap_or_default();
+    let programs = executables::starting_with("", path);
+    let names = completion::BUILTINS.iter().map(|builtin| builtin.name).chain(programs.iter().map(String::as_str));
+    match spelling::closest(name, names) {
+        Some(closest) => anyhow!(trf("Unknown command '{}'; did you mean '{}'?", &[&name, &closest])),
+        None => anyhow!(tr("Unknown command")),
+    }
 }
 
-fn generate_prompt() -> CrateResult<String> {
+// The lines `fc` runs: `command` as changed in $VISUAL or $EDITOR, or in
+// the line editor when neither is set. Empty when the editing was given up.
+fn edit_command(state: &ShellState, command: &str) -> CrateResult<Vec<String>> {
+    let editor = state.var("VISUAL").or(state.var("EDITOR")).unwrap_or_default();
+    let words = tokenizer::tokenize(editor).unwrap_or_default();
+    let Some((program, args)) = words.split_first() else {
+        let complete = |line: &str, cursor: usize| completion::complete(line, cursor, state);
+        return Ok(match editor::edit_line(&theme::paint("prompt", "fc❯ "), command, &complete)? {
+            editor::Input::Line(line) if !line.trim().is_empty() => vec![line],
+            _ => Vec::new(),
+        });
+    };
+
+    let (path, _) = capture::temporary_file()?;
+    let edited = std::fs::write(&path, format!("{}\n", command))
+        .and_then(|_| foreground::run(state.command(program).args(args).arg(&path), editor))
+        .and_then(|status| match status.success() {
+            true => std::fs::read_to_string(&path).map(Some),
+            false => Ok(None),
+        });
+    let _ = std::fs::remove_file(&path);
+    match edited {
+        Ok(Some(text)) => Ok(text.lines().map(str::trim).filter(|line| !line.is_empty()).map(String::from).collect()),
+        Ok(None) => Err(anyhow!(trf("fc: {} failed; nothing was run", &[&editor]))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(anyhow!(trf("{} is not installed", &[program]))),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn generate_prompt(state: &ShellState, branch: &Segment<Option<String>>) -> CrateResult<String> {
     // Get username - fallback to "user" if we can't get it
-    let username = std::env::var("USER").unwrap_or_else(|_| "farhan".to_string());
+    let username = state.var("USER").unwrap_or("farhan").to_string();
     
     // Get current directory
-    let current_dir = std::env::current_dir()?;
+    let current_dir = &state.cwd;
     let dir_name = current_dir
         .file_name()
         .map(|name| name.to_string_lossy().to_string())
@@ -120,188 +443,1943 @@ fn generate_prompt() -> CrateResult<String> {
         .unwrap_or_else(|| "~".to_string());
     
     // Get git branch if in a git repository
-    let git_branch_info = if is_git_repository() {
-        if let Some(branch) = get_git_branch() {
-            format!(" on {}", branch.purple().bold())
-        } else {
-            String::new()
-        }
-    } else {
-        String::new()
+    let git_branch_info = match branch {
+        Segment::Loading => trf(" on {}", &[&prompt::PLACEHOLDER.bright_black()]),
+        Segment::Ready(Some(branch)) => trf(" on {}", &[&theme::paint("git_branch", branch).bold()]),
+        Segment::Ready(None) => String::new(),
     };
+
+    // A spinner or progress bar started by a script stays in view
+    let status_info = status::segment().map(|segment| format!(" {}", segment)).unwrap_or_default();
     
     // Format the prompt with colors
     let prompt = format!(
-        "{} {} {} {} {} {}{} ", 
-        "┌─[".bright_green(),
-        username.bright_cyan(),
+        "{} {} {} {} {} {}{}{} ", 
+        theme::paint("prompt", "┌─["),
+        theme::paint("user", &username),
         "@shell".bright_blue(),
-        "]─[".bright_green(),
-        format!("{}/{}", parent_dir, dir_name).yellow(),
-        "]".bright_green(),
-        git_branch_info
+        theme::paint("prompt", "]─["),
+        theme::paint("path", &format!("{}/{}", parent_dir, dir_name)),
+        theme::paint("prompt", "]"),
+        git_branch_info,
+        status_info
     );
+
+    let right = config::get().right_prompt.as_deref().map(|template| prompt::right_segment(template, state));
+    let prompt = match right {
+        Some(right) => prompt::align_right(&prompt, &right, helpers::terminal_width()),
+        None => prompt,
+    };
     
     // Add a new line and the input prompt
     let prompt = format!(
         "{}\n{}",
         prompt,
-        "└─$ ".bright_green()
+        theme::paint("prompt", "└─$ ")
     );
     
     Ok(prompt)
 }
 
 fn print_help() {
-    println!("\n{}", "=== Available Commands ===".bright_yellow().bold());
+    println!("\n{}", tr("=== Available Commands ===").bright_yellow().bold());
     
-    println!("{}", "File Operations:".cyan().bold());
-    println!("  {} - {}", "ls".green(), "List files in the current directory");
-    println!("  {} - {}", "ls -l".green(), "List files with detailed information");
-    println!("  {} - {}", "pwd".green(), "Print working directory");
-    println!("  {} - {}", "cd <directory>".green(), "Change directory");
-    println!("  {} - {}", "touch <file>".green(), "Create a new file or update timestamp");
-    println!("  {} - {}", "rm <file>".green(), "Remove a file");
-    println!("  {} - {}", "cat <file>".green(), "Display file contents");
+    println!("{}", tr("File Operations:").cyan().bold());
+    println!("  {} - {}", "ls".green(), tr("List files in the current directory"));
+    println!("  {} - {}", "ls -l[n]".green(), tr("List files with detailed information (-n: numeric owner and group)"));
+    println!("  {} - {}", "pwd".green(), tr("Print working directory"));
+    println!("  {} - {}", "cd <directory>".green(), tr("Change directory"));
+    println!("  {} - {}", "touch [-acm] [-t stamp|-d date] <file>...".green(), tr("Create a new file or update its timestamps"));
+    println!("  {} - {}", "rm <file>...".green(), tr("Remove a file"));
+    println!("  {} - {}", "rm -r[f] [--one-file-system] <path>...".green(), tr("Remove directories and their contents (-f: no questions, ignore missing)"));
+    println!("  {} - {}", "cat [-AvTEsH] [file...]".green(), tr("Display file contents"));
     
-    println!("\n{}", "Directory Operations:".cyan().bold());
-    println!("  {} - {}", "mkdir <directory>".green(), "Create a directory");
-    println!("  {} - {}", "mkdir -p <directory>".green(), "Create a directory and its parents");
-    println!("  {} - {}", "rmdir <directory>".green(), "Remove an empty directory");
-    println!("  {} - {}", "rmdir -r <directory>".green(), "Remove a directory and its contents");
+    println!("\n{}", tr("Directory Operations:").cyan().bold());
+    println!("  {} - {}", "mkdir <directory>...".green(), tr("Create a directory"));
+    println!("  {} - {}", "mkdir -p <directory>...".green(), tr("Create a directory and its parents"));
+    println!("  {} - {}", "mkfifo [-m mode] <name>...".green(), tr("Create a named pipe; cat and grep wait for a writer without blocking the shell"));
+    println!("  {} - {}", "rm --git <path>... (grm)".green(), tr("Remove tracked files with git rm, so the deletion is staged"));
+    println!("  {} - {}", "rmdir <directory>...".green(), tr("Remove an empty directory"));
+    println!("  {} - {}", "rmdir -r <directory>...".green(), tr("Same as rm -r"));
     
-    println!("\n{}", "File Management:".cyan().bold());
-    println!("  {} - {}", "cp <source> <dest>".green(), "Copy a file");
-    println!("  {} - {}", "cp -r <source>".green(), "Copy directory recursively");
-    println!("  {} - {}", "mv <source> <dest>".green(), "Move/rename files or directories");
-    println!("  {} - {}", "stat <file/dir>".green(), "Display file or directory information");
-    println!("  {} - {}", "ln <target> <link_name>".green(), "Create symbolic link");
+    println!("\n{}", tr("File Management:").cyan().bold());
+    println!("  {} - {}", "cp <source> <dest>".green(), tr("Copy a file"));
+    println!("  {} - {}", "cp -r [--one-file-system] <source> <dest>".green(), tr("Copy directory recursively"));
+    println!("  {} - {}", "mv <source> <dest>".green(), tr("Move/rename files or directories"));
+    println!("  {} - {}", "mv --git <source> <dest> (gmv)".green(), tr("Move a tracked file with git mv, so the rename is staged"));
+    println!("  {} - {}", "du [-s] [--one-file-system] [path...]".green(), tr("Show disk usage per directory, with a grand total (-s: one line per path)"));
+    println!("  {} - {}", "usage [dir]".green(), tr("Explore disk usage interactively, biggest first, and delete what takes space"));
+    println!("  {} - {}", "stat <file/dir>...".green(), tr("Display file or directory information"));
+    println!("  {} - {}", "ln [-sfr] <target> <link_name>".green(), tr("Create a hard link (-s symbolic, -f replace, -r relative target)"));
+    println!("  {} - {}", "dedupe [--auto] [--keep newest|oldest] [--link] <dir>".green(), tr("Find identical files and keep one copy (--link: hard-link the others)"));
+    println!("  {} - {}", "split -b SIZE <file> [prefix]".green(), tr("Cut a file into pieces of SIZE bytes (prefixaa, prefixab, ...)"));
+    println!("  {} - {}", "merge <piece>... <output>".green(), tr("Join pieces back into one file"));
+    println!("  {} - {}", "truncate [-c] -s [+|-]SIZE <file>...".green(), tr("Create or resize files; growing makes sparse files"));
+    println!("  {} - {}", "rename [--dry-run] 's/old/new/[gi]' <file>...".green(), tr("Rename files with a regular expression"));
+    println!("  {} - {}", "undo [n]".green(), tr("Revert the last n mv/cp/touch/mkdir operations"));
     
-    println!("\n{}", "Search and Information:".cyan().bold());
-    println!("  {} - {}", "find <dir> <pattern>".green(), "Find files matching pattern");
-    println!("  {} - {}", "grep <file> <pattern>".green(), "Search for pattern in file");
-    println!("  {} - {}", "echo <text>".green(), "Print text to the terminal");
+    println!("\n{}", tr("Search and Information:").cyan().bold());
+    println!("  {} - {}", "find [-isS] [--max-results N] [--larger-than SIZE] [--smaller-than SIZE] [--empty] [--delete [--yes]] [--one-file-system] <dir> [pattern]".green(), tr("Find files matching pattern"));
+    println!("  {} - {}", "grep [-isS] [--max-results N] [file] <pattern>".green(), tr("Search for pattern in file"));
+    println!("  {} - {}", "echo [-neE] <text>".green(), tr("Print text to the terminal"));
+    println!("  {} - {}", "printf <format> [args...]".green(), tr("Print formatted text (%s, %d, %f, %x, ... with widths and precision)"));
+    println!("  {} - {}", "stats".green(), tr("Show usage charts from the audit log"));
+    println!("  {} - {}", "history".green(), tr("List the command lines in the audit log"));
+    println!("  {} - {}", "history scrub <pattern>".green(), tr("Remove entries matching a regex from the audit log"));
+    println!("  {} - {}", "fc [number]".green(), tr("Edit the last command line (or entry number) in $EDITOR or the line editor, then run it"));
+    println!("  {} - {}", "spinner start <message>".green(), tr("Show a spinner with a message until 'spinner stop'"));
+    println!("  {} - {}", "progress <current> <total> [label]".green(), tr("Show a progress bar; it goes away when current reaches total"));
+    println!("  {} - {}", "secret get <name>".green(), tr("Print a secret kept in the OS keyring"));
+    println!("  {} - {}", "secret set <name>".green(), tr("Store a secret in the OS keyring; the value is typed without echo"));
+    println!("  {} - {}", "hosts".green(), tr("List the hosts in ~/.ssh/config and ~/.ssh/known_hosts"));
+    println!("  {} - {}", "ssh <host>, scp <from> <to>".green(), tr("Run the system ssh and scp; Tab completes host names"));
+    println!("  {} - {}", "push [-c] <file> <host:path>".green(), tr("Upload a file over SFTP; -c resumes a partial upload"));
+    println!("  {} - {}", "pull [-c] <host:path> <path>".green(), tr("Download a file over SFTP; -c resumes a partial download"));
+    println!("  {} - {}", "serve [dir] [--port 8080]".green(), tr("Share a directory over HTTP as a background job"));
+    println!("  {} - {}", "jobs".green(), tr("List background jobs"));
+    println!("  {} - {}", "nohup <command> &".green(), tr("Run a command as its own process that survives the shell exiting; output goes to nohup.out"));
+    println!("  {} - {}", "disown %job...".green(), tr("Stop tracking a nohup job so it keeps running after exit"));
+    println!("  {} - {}", "fg [%job]".green(), tr("Bring a program suspended with Ctrl+Z back to the foreground"));
+    println!("  {} - {}", "bg [%job]".green(), tr("Let a program suspended with Ctrl+Z go on in the background"));
+    println!("  {} - {}", "kill [-SIGNAL] %job|pid...".green(), tr("Stop a background job or send a signal (TERM by default) to a process; kill -l lists signals"));
+    println!("  {} - {}", "killall [-SIGNAL] <name>".green(), tr("Send a signal to every process with that name"));
+    println!("  {} - {}", "ports [--kill <port>]".green(), tr("List listening TCP and UDP ports and their processes"));
+    println!("  {} - {}", "resolve [-t type] [--server addr] <name>".green(), tr("Look up A, AAAA, CNAME, MX and TXT records"));
+    println!("  {} - {}", "download [-o file] [--sha256 hex] [-b] <url>".green(), tr("Download a file, resuming a partial one; -b runs it as a job"));
+    println!("  {} - {}", "printenv [name...]".green(), tr("Print variables, or the values of the named ones"));
+    println!("  {} - {}", "env [--diff] [--sorted] [--grep pattern]".green(), tr("List variables, or those changed since the shell started"));
+    println!("  {} - {}", "which <name>...".green(), tr("Tell whether a name is an abbreviation, a builtin or a program on PATH"));
+    println!("  {} - {}", "hash [-r] [-d name...] [name...]".green(), tr("List the programs run so far and where from, or remember or forget some"));
+    println!("  {} - {}", "rehash".green(), tr("Forget where programs are and read the directories on PATH again"));
+    println!("  {} - {}", "export NAME=value... / unset NAME...".green(), tr("Set or remove variables for this session and the programs it runs"));
+    println!("  {} - {}", "abbr [<name> <expansion> | -e <name>]".green(), tr("List, add or remove abbreviations, which expand in place when typed as a command and followed by a space"));
+    println!("  {} - {}", "choose [-v NAME] <option>...".green(), tr("Pick an option from an arrow-key menu; it is printed and stored in $CHOICE"));
+    println!("  {} - {}", "test <expression>, [ <expression> ]".green(), tr("Check files, strings or numbers; fails quietly when false"));
+    println!("  {} - {}", "ulimit [-a] [-c|-d|-f|-n|-s|-t|-u|-v [limit]]".green(), tr("Show or set resource limits for the programs the shell starts"));
+    println!("  {} - {}", "limit [--mem size] [--cpu secs] [--files n] [--procs n] <command>".green(), tr("Run one command with tighter resource limits"));
+    println!("  {} - {}", "nice [-n increment] <command>".green(), tr("Run one command so the programs it starts get a lower priority"));
+    println!("  {} - {}", "renice [-n] <priority> <pid|%job>...".green(), tr("Change the priority of running processes"));
+    println!("  {} - {}", "pstree [pid]".green(), tr("Show the process tree, marking this shell, what it started and its jobs"));
+    println!("  {} - {}", "monitor [-n secs] [--sort cpu|mem|pid|name]".green(), tr("Full-screen view of CPU, memory and processes; q quits"));
+    println!("  {} - {}", "new <template> <name> | new --list".green(), tr("Create a project from a template in ~/.config/shellbasics/templates"));
+    println!("  {} - {}", "task <name> | task --list".green(), tr("Run a task from .shellbasics-tasks.toml after the tasks it depends on"));
+    println!("  {} - {}", "gst".green(), tr("Git status: branch, upstream and changes grouped by staged, not staged and untracked"));
+    println!("  {} - {}", "glog [-n count] [path]".green(), tr("Git log as a graph with branches and tags (default 20 commits)"));
+    println!("  {} - {}", "gdiff [--staged] [path]".green(), tr("Git diff word by word, removed words in red and added in green"));
+    println!("  {} - {}", "bench [-n runs] <command>".green(), tr("Time a builtin against the system equivalent"));
     
-    println!("\n{}", "Shell Control:".cyan().bold());
-    println!("  {} - {}", "help".green(), "Display this help message");
-    println!("  {} - {}", "exit".green(), "Exit the shell");
+    println!("\n{}", tr("Shell Control:").cyan().bold());
+    println!("  {} - {}", "tutorial [stop|reset|status]".green(), tr("Learn the basics step by step"));
+    println!("  {} - {}", "practice list|start <name>|task|check".green(), tr("Hands-on exercises in a sandbox directory"));
+    println!("  {} - {}", "reload".green(), tr("Read the settings file again, keeping the settings in use if it has a mistake"));
+    println!("  {} - {}", "doctor".green(), tr("Look for problems with the settings, history, PATH, terminal and locale, and say how to fix them"));
+    println!("  {} - {}", "help".green(), tr("Display this help message"));
+    println!("  {} - {}", "clear".green(), tr("Clear the screen"));
+    println!("  {} - {}", "exit".green(), tr("Exit the shell"));
+
+    println!("\n{}", tr("Keys:").cyan().bold());
+    println!("  {} - {}", "Tab".green(), tr("Complete a command, flag, value or path, or pick from a menu of what fits"));
+    println!("  {} - {}", "Ctrl+A/Ctrl+E".green(), tr("Jump to the start or end of the line"));
+    println!("  {} - {}", "Ctrl+U/Ctrl+K/Ctrl+W".green(), tr("Delete before the cursor, after it, or the previous word"));
+    println!("  {} - {}", "Ctrl+Y/Alt+Y".green(), tr("Put back the text deleted last, then swap it for what was deleted before"));
+    println!("  {} - {}", "Alt+B/Alt+F".green(), tr("Move back or forward a word"));
+    println!("  {} - {}", "Ctrl+L".green(), tr("Clear the screen, keeping the line being typed"));
+    println!("  {} - {}", "Up/Down".green(), tr("Go back or forward through the history to lines starting with what is typed"));
+    println!("  {} - {}", "Ctrl+P/Ctrl+N".green(), tr("Go back or forward through the history a line at a time"));
     
-    println!("");
+    println!();
+}
+
+// The file name filters take to mean standard input, as in coreutils
+const STDIN: &str = "-";
+
+// Reads the named pipes among `paths` first, keyed by the path as typed.
+// The wait for a writer doesn't block the shell; None if Ctrl+C ended it.
+// Standard input (`-`) is left to stream as it is printed.
+async fn read_pipes(state: &ShellState, paths: &[String]) -> CrateResult<Option<HashMap<String, Vec<u8>>>> {
+    let mut piped = HashMap::new();
+    for s in paths {
+        if s == STDIN {
+            continue;
+        }
+        let path = state.resolve(s);
+        if !fifo::is_fifo(&path) {
+            continue;
+        }
+        match fifo::read(&path).await? {
+            Some(data) => piped.insert(s.clone(), data),
+            None => return Ok(None),
+        };
+    }
+    Ok(Some(piped))
+}
+
+// `ls` of each of `paths`: a directory's entries, under its name when
+// there are several, and a file by itself. None lists the working directory.
+fn list(
+    state: &ShellState,
+    paths: &[String],
+    directory: impl Fn(&Path) -> CrateResult<()>,
+    file: impl Fn(&str, &std::fs::Metadata) -> CrateResult<()>,
+) -> CrateResult<()> {
+    if paths.is_empty() {
+        return directory(&state.cwd);
+    }
+    let mut first = true;
+    for_each_path(paths, |path| {
+        let target = state.resolve(path);
+        let metadata = match std::fs::metadata(&target) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(anyhow!(trf("ls: '{}' does not exist", &[&path]))),
+            Err(e) => return Err(errors::with_path(e, &target, Access::Read)),
+        };
+        if !metadata.is_dir() {
+            return file(path, &metadata);
+        }
+        if paths.len() > 1 {
+            println!("{}{}:", if first { "" } else { "\n" }, path);
+        }
+        first = false;
+        directory(&target)
+    })
+}
+
+// Runs `action` on each path argument, reporting failures as they happen so
+// one bad path doesn't stop the rest. Fails overall if any path failed.
+fn for_each_path(paths: &[String], mut action: impl FnMut(&str) -> CrateResult<()>) -> CrateResult<()> {
+    if let [path] = paths {
+        return action(path);
+    }
+
+    let mut failed = 0;
+    for path in paths {
+        if let Err(e) = action(path) {
+            eprintln!("{} {}: {}", theme::paint("error", tr("Error:")), path, e);
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        return Err(anyhow!(trf("{} of {} paths failed", &[&failed, &paths.len()])));
+    }
+    Ok(())
+}
+
+// Says how a streamed find/grep ended
+fn print_search_summary(options: &SearchOptions, count: usize) {
+    if interrupt::requested() {
+        println!("{}", trf("Interrupted after {} matches", &[&count]).yellow());
+    } else if !options.wants_more(count) {
+        println!("{}", trf("Stopped after {} matches (--max-results)", &[&count]).yellow());
+    } else {
+        println!("{} {}", tr("Found").bright_green(), trf("{} matches", &[&count.to_string().yellow()]).bright_green());
+    }
+}
+
+// The --delete action of find. Matches come deepest first, so directories
+// emptied by the earlier removals can go too.
+// Without a terminal to ask on, only --yes lets it go ahead.
+fn delete_found(state: &ShellState, found: &[std::path::PathBuf], yes: bool) -> CrateResult<()> {
+    if !yes {
+        if !std::io::stdin().is_terminal() {
+            return Err(anyhow!(trf("find: not deleting {} entries without a terminal to confirm on; add --yes to delete them", &[&found.len()])));
+        }
+        if !terminal::confirm(&trf("Delete these {} entries? [y/N] ", &[&found.len()])) {
+            println!("{}", tr("Nothing was deleted.").yellow());
+            return Ok(());
+        }
+    }
+
+    let mut failed = 0;
+    for path in found {
+        let removed = if path.is_dir() && !path.is_symlink() { std::fs::remove_dir(path) } else { std::fs::remove_file(path) };
+        if let Err(e) = removed {
+            let shown = path.strip_prefix(&state.cwd).unwrap_or(path);
+            eprintln!("{} {}: {}", theme::paint("error", tr("Error:")), shown.display(), e);
+            failed += 1;
+        }
+    }
+
+    println!("{}", trf("Deleted {} entries.", &[&(found.len() - failed)]).bright_red());
+    if failed > 0 {
+        return Err(anyhow!(trf("{} of {} paths failed", &[&failed, &found.len()])));
+    }
+    Ok(())
+}
+
+// Lists each group of identical files and, with --auto or on a terminal,
+// keeps one copy and deletes or hard-links the rest
+fn dedupe(state: &ShellState, dir: &str, options: &DedupeOptions) -> CrateResult<()> {
+    let groups = dedupe::scan(&state.resolve(dir))?;
+    if groups.is_empty() {
+        println!("{}", trf("No duplicate files under {}", &[&dir]).bright_green());
+        return Ok(());
+    }
+
+    let interactive = !options.auto && std::io::stdin().is_terminal();
+    let shown = |path: &std::path::Path| path.strip_prefix(&state.cwd).unwrap_or(path).display().to_string();
+    let (mut resolved, mut reclaimed, mut failed) = (0, 0, 0);
+    for (number, group) in groups.iter().enumerate() {
+        println!(
+            "{}",
+            trf(
+                "Group {}: {} copies of {} ({} reclaimable)",
+                &[&(number + 1), &group.paths.len(), &helpers::human_size(group.size), &helpers::human_size(group.reclaimable())]
+            )
+            .bright_yellow()
+        );
+        for (i, path) in group.paths.iter().enumerate() {
+            println!("  [{}] {}", i + 1, shown(path).cyan());
+        }
+
+        let keep = if options.auto {
+            group.pick(options.keep)
+        } else if interactive {
+            match ask_which_to_keep(group.paths.len()) {
+                Some(keep) => keep,
+                None => continue,
+            }
+        } else {
+            continue;
+        };
+
+        let original = &group.paths[keep];
+        for copy in group.paths.iter().filter(|path| *path != original) {
+            let result = if options.link { dedupe::link(original, copy) } else { helpers::rm(copy) };
+            match result {
+                Ok(()) if options.link => println!("  {} {} → {}", theme::paint("success", tr("Linked:")), shown(copy), shown(original)),
+                Ok(()) => println!("  {} {}", tr("Removed:").bright_red(), shown(copy)),
+                Err(e) => {
+                    eprintln!("  {} {}: {}", theme::paint("error", tr("Error:")), shown(copy), e);
+                    failed += 1;
+                    continue;
+                }
+            }
+            resolved += 1;
+            reclaimed += group.size;
+        }
+    }
+
+    let total: u64 = groups.iter().map(dedupe::Group::reclaimable).sum();
+    println!("{}", trf("{} groups of duplicates, {} reclaimable", &[&groups.len(), &helpers::human_size(total)]).bright_green());
+    if resolved > 0 {
+        println!("{}", trf("Resolved {} copies, freeing {}", &[&resolved, &helpers::human_size(reclaimed)]).bright_green());
+    } else if !options.auto && !interactive {
+        println!("{}", tr("Nothing was changed; run with --auto to keep one copy of each.").yellow());
+    }
+    if failed > 0 {
+        return Err(anyhow!(trf("{} copies could not be resolved", &[&failed])));
+    }
+    Ok(())
+}
+
+// Index of the copy to keep, or None to leave the group alone
+fn ask_which_to_keep(copies: usize) -> Option<usize> {
+    loop {
+        print!("{}", trf("Keep which copy? [1-{}, Enter to skip] ", &[&copies]));
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer).unwrap_or(0) == 0 || interrupt::requested() {
+            return None;
+        }
+        match answer.trim() {
+            "" | "s" => return None,
+            answer => match answer.parse::<usize>() {
+                Ok(choice) if (1..=copies).contains(&choice) => return Some(choice - 1),
+                _ => println!("{}", trf("Please answer a number from 1 to {}.", &[&copies]).yellow()),
+            },
+        }
+    }
+}
+
+fn report_transfer(transfer: transfer::Transfer) {
+    let size = helpers::human_size(transfer.size);
+    let message = match transfer.resumed {
+        0 => trf("Copied {} to {}.", &[&size, &transfer.destination]),
+        resumed => trf("Copied {} to {} (resumed after {}).", &[&size, &transfer.destination, &helpers::human_size(resumed)]),
+    };
+    println!("{}", theme::paint("success", &message));
+}
+
+fn list_ports() {
+    let listeners = ports::listeners();
+    if listeners.is_empty() {
+        println!("{}", tr("Nothing is listening.").yellow());
+        return;
+    }
+    let width = listeners.iter().map(|listener| listener.address.to_string().len()).max().unwrap_or(0).max(7);
+    println!("{}", format!("{:5} {:width$} {:>5}  {:>7}  {}", tr("Proto"), tr("Address"), tr("Port"), "PID", tr("Process")).bold());
+    for listener in &listeners {
+        let (pid, name) = match &listener.process {
+            Some((pid, name)) => (pid.to_string(), name.as_str()),
+            None => ("-".to_string(), "-"),
+        };
+        println!("{:5} {:width$} {:>5}  {:>7}  {}", listener.protocol, listener.address, listener.port.to_string().cyan(), pid, name.green());
+    }
+}
+
+// Sends SIGTERM to whatever holds `port`, after asking
+fn kill_port(port: u16) -> CrateResult<()> {
+    let mut owners: Vec<(u32, String)> = ports::listeners()
+        .into_iter()
+        .filter(|listener| listener.port == port)
+        .filter_map(|listener| listener.process)
+        .collect();
+    owners.sort();
+    owners.dedup();
+    if owners.is_empty() {
+        return Err(anyhow!(trf("no process we can see is listening on port {}", &[&port])));
+    }
+
+    for (pid, name) in owners {
+        // A `serve` job of our own would take the whole shell down with it
+        if pid == std::process::id() {
+            return Err(anyhow!(trf("port {} is held by this shell; stop the job with jobs and kill %N", &[&port])));
+        }
+        let question = trf("Send SIGTERM to {} (pid {}) on port {}? [y/N] ", &[&name, &pid, &port]);
+        if std::io::stdin().is_terminal() && !terminal::confirm(&question) {
+            continue;
+        }
+        send_signal(pid, libc::SIGTERM)?;
+        println!("{} {} ({})", theme::paint("success", tr("Terminated:")), name, pid);
+    }
+    Ok(())
+}
+
+// The process tree from `root` (init by default), with this shell and what
+// runs under it picked out
+fn print_process_tree(root: Option<u32>) -> CrateResult<()> {
+    let all = processes::all();
+    let root = match root {
+        Some(pid) if all.iter().any(|process| process.pid == pid) => pid,
+        Some(pid) => return Err(anyhow!(trf("pstree: no process {}", &[&pid]))),
+        // Inside a container pid 1 may be missing; start at the oldest orphan
+        None => all.iter().find(|process| !all.iter().any(|parent| parent.pid == process.ppid)).map(|process| process.pid).unwrap_or(1),
+    };
+    let shell = std::process::id();
+    let running = jobs::list();
+
+    for (prefix, process) in processes::tree(&all, root) {
+        let label = format!("{}({})", process.name, process.pid);
+        let label = if process.pid == shell {
+            let mut label = format!("{} {}", label.green().bold(), tr("← this shell").bright_black());
+            if !running.is_empty() {
+                let jobs: Vec<String> = running.iter().map(|job| format!("[{}] {}", job.id, job.command)).collect();
+                label = format!("{} {}", label, trf("with jobs {}", &[&jobs.join(", ")]).yellow());
+            }
+            label
+        } else if processes::descends_from(&all, process.pid, shell) {
+            label.cyan().to_string()
+        } else {
+            label
+        };
+        println!("{}{}", prefix.bright_black(), label);
+    }
+    Ok(())
+}
+
+fn list_tasks(state: &ShellState) -> CrateResult<()> {
+    let file = tasks::load(&state.cwd)?;
+    println!("{}", trf("Tasks in {}", &[&file.dir.join(tasks::TASKS_FILE).display()]).bold());
+    let width = file.tasks.keys().map(|name| name.chars().count()).max().unwrap_or(0);
+    for (name, task) in &file.tasks {
+        let mut about = task.description.clone().unwrap_or_default();
+        if !task.deps.is_empty() {
+            about = format!("{} {}", about, trf("(after {})", &[&task.deps.join(", ")]).bright_black()).trim().to_string();
+        }
+        println!("  {:width$}  {}", name.cyan(), about);
+    }
+    Ok(())
+}
+
+// Runs `name` after what it depends on. Each task starts in the tasks file's
+// directory with its own env on top of the session's, and the first line
+// that fails stops the rest.
+async fn run_task(state: &mut ShellState, name: &str) -> CrateResult<()> {
+    let file = tasks::load(&state.cwd)?;
+    let plan = file.plan(name)?;
+    let (saved_cwd, saved_env) = (state.cwd.clone(), state.env.clone());
+    let result = async {
+        for task_name in plan {
+            let task = &file.tasks[task_name];
+            println!("{} {}", "▶".cyan(), task_name.bold());
+            state.cwd = file.dir.clone();
+            state.env = saved_env.clone();
+            state.env.extend(task.env.clone());
+            for line in task.run.iter() {
+                println!("{} {}", "$".bright_black(), line.bright_black());
+                Box::pin(handle_new_line(state, line))
+                    .await
+                    .map_err(|e| anyhow!(trf("task {} failed at '{}': {}", &[&task_name, line, &e])))?;
+            }
+        }
+        Ok(())
+    }
+    .await;
+    state.cwd = saved_cwd;
+    state.env = saved_env;
+    result
+}
+
+fn find_job(id: usize) -> Option<jobs::Job> {
+    jobs::list().into_iter().find(|job| job.id == id)
+}
+
+// The job `fg` or `bg` acts on: a program suspended with Ctrl+Z, the latest
+// one unless `target` names another
+fn suspended_job(target: Option<usize>, name: &str) -> CrateResult<jobs::Job> {
+    let Some(id) = target else {
+        return jobs::list().into_iter().rev().find(|job| job.pid.is_some() && job.log.is_none()).ok_or_else(|| anyhow!(trf("{}: no suspended programs", &[&name])));
+    };
+    let job = find_job(id).ok_or_else(|| anyhow!(trf("{}: no such job %{}", &[&name, &id])))?;
+    match (job.pid, &job.log) {
+        (Some(_), None) => Ok(job),
+        (Some(_), Some(_)) => Err(anyhow!(trf("{}: %{} runs detached from the terminal", &[&name, &id]))),
+        (None, _) => Err(anyhow!(trf("{}: %{} runs inside the shell and has no foreground", &[&name, &id]))),
+    }
+}
+
+// Like nohup(1): nohup.out in the working directory, or in the home
+// directory when that one can't be written
+fn nohup_log(state: &ShellState) -> CrateResult<(PathBuf, std::fs::File)> {
+    let open = |path: &Path| std::fs::OpenOptions::new().create(true).append(true).open(path);
+    let here = state.cwd.join("nohup.out");
+    if let Ok(file) = open(&here) {
+        return Ok((here, file));
+    }
+    let home = PathBuf::from(state.var("HOME").unwrap_or(".")).join("nohup.out");
+    let file = open(&home).map_err(|e| errors::with_path(e, &home, Access::Write))?;
+    Ok((home, file))
+}
+
+fn send_signal(pid: u32, signal: i32) -> CrateResult<()> {
+    if unsafe { libc::kill(pid as libc::pid_t, signal) } != 0 {
+        return Err(anyhow!(trf("kill: ({}): {}", &[&pid, &std::io::Error::last_os_error()])));
+    }
+    Ok(())
+}
+
+// Signals every process called `name` except this shell, asking first when
+// the name matches more than one
+fn killall(signal: i32, name: &str) -> CrateResult<()> {
+    // /proc/<pid>/comm keeps only the first 15 bytes of a name
+    let short: String = name.chars().take(15).collect();
+    let matches: Vec<u32> = processes::all()
+        .into_iter()
+        .filter(|process| process.pid != std::process::id() && (process.name == name || process.name == short))
+        .map(|process| process.pid)
+        .collect();
+    if matches.is_empty() {
+        return Err(anyhow!(trf("killall: no process named {}", &[&name])));
+    }
+
+    let pids = matches.iter().map(u32::to_string).collect::<Vec<_>>().join(", ");
+    let question = trf("Send {} to {} processes named {} ({})? [y/N] ", &[&signals::name(signal), &matches.len(), &name, &pids]);
+    if matches.len() > 1 && std::io::stdin().is_terminal() && !terminal::confirm(&question) {
+        return Ok(());
+    }
+    for pid in matches {
+        send_signal(pid, signal)?;
+        println!("{}", theme::paint("success", &trf("Sent {} to {} ({})", &[&signals::name(signal), &name, &pid])));
+    }
+    Ok(())
+}
+
+fn download_report(name: &str, options: &shell::command::DownloadOptions, downloaded: &download::Downloaded) -> String {
+    let size = helpers::human_size(downloaded.size);
+    let mut message = match downloaded.resumed {
+        0 => trf("Saved {} ({}).", &[&name, &size]),
+        resumed => trf("Saved {} ({}, resumed after {}).", &[&name, &size, &helpers::human_size(resumed)]),
+    };
+    if options.sha256.is_some() {
+        message = format!("{} {}", message, tr("SHA-256 matches."));
+    }
+    theme::paint("success", &message).to_string()
+}
+
+// What programs started now get: the session's own setting, or the shell's
+fn effective_limit(state: &ShellState, resource: limits::Resource) -> limits::Limit {
+    state.limits.get(&resource).copied().unwrap_or_else(|| limits::current(resource).0)
+}
+
+// Only soft limits are set, so they can go back up later, but never above
+// the hard limit
+fn set_limit(state: &mut ShellState, resource: limits::Resource, limit: limits::Limit) -> CrateResult<()> {
+    if let (_, Some(hard)) = limits::current(resource) {
+        if limit.is_none_or(|limit| limit > hard) {
+            let requested = limits::format(resource, limit);
+            return Err(anyhow!(trf("ulimit: {} is above the hard limit of {}", &[&requested, &limits::format(resource, Some(hard))])));
+        }
+    }
+    state.limits.insert(resource, limit);
+    Ok(())
+}
+
+fn print_limits(state: &ShellState) {
+    let labels: Vec<String> = limits::RESOURCES
+        .iter()
+        .map(|resource| match resource.unit() {
+            "" => format!("{} (-{})", resource.description(), resource.flag()),
+            unit => format!("{} ({}, -{})", resource.description(), unit, resource.flag()),
+        })
+        .collect();
+    let width = labels.iter().map(|label| label.chars().count()).max().unwrap_or(0);
+    for (label, resource) in labels.iter().zip(limits::RESOURCES) {
+        let value = limits::format(resource, effective_limit(state, resource));
+        let value = if state.limits.contains_key(&resource) { value.yellow() } else { value.normal() };
+        println!("{:width$}  {}", label, value);
+    }
+}
+
+// `env`: every variable, or with --diff what changed since startup, either
+// way narrowed by --grep (case-insensitive, on names and values)
+fn print_env(state: &ShellState, options: &shell::command::EnvOptions) -> CrateResult<()> {
+    let pattern = match &options.grep {
+        Some(pattern) => Some(
+            regex::RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .build()
+                .map_err(|e| anyhow!(trf("env: bad pattern '{}': {}", &[pattern, &e])))?,
+        ),
+        None => None,
+    };
+    let wanted = |name: &str, values: &[&str]| {
+        pattern.as_ref().is_none_or(|pattern| pattern.is_match(name) || values.iter().any(|value| pattern.is_match(value)))
+    };
+
+    if !options.diff {
+        for (name, value) in environment::variables(state, options.sorted) {
+            if wanted(name, &[value]) {
+                println!("{}={}", name.cyan(), value);
+            }
+        }
+        return Ok(());
+    }
+
+    let changes: Vec<environment::Change> = environment::changes(&state.startup_env, &state.env)
+        .into_iter()
+        .filter(|change| match change {
+            environment::Change::Added(name, value) | environment::Change::Removed(name, value) => wanted(name, &[value]),
+            environment::Change::Changed(name, old, new) => wanted(name, &[old, new]),
+        })
+        .collect();
+    if changes.is_empty() {
+        println!("{}", tr("No variables changed since the shell started.").yellow());
+    }
+    for change in changes {
+        match change {
+            environment::Change::Added(name, value) => println!("{}", format!("+ {}={}", name, value).green()),
+            environment::Change::Removed(name, value) => println!("{}", format!("- {}={}", name, value).red()),
+            environment::Change::Changed(name, old, new) => {
+                println!("{}", format!("- {}={}", name, old).red());
+                println!("{}", format!("+ {}={}", name, new).green());
+            }
+        }
+    }
+    Ok(())
+}
+
+// Hosts from ~/.ssh/config with where they lead, then the other known hosts
+fn list_hosts(state: &ShellState) {
+    let home = state.resolve("~");
+    let configured = ssh::configured_hosts(&home);
+    let known: Vec<String> = ssh::known_hosts(&home)
+        .into_iter()
+        .filter(|name| !configured.iter().any(|host| &host.name == name))
+        .collect();
+    if configured.is_empty() && known.is_empty() {
+        println!("{}", tr("No hosts in ~/.ssh/config or ~/.ssh/known_hosts.").yellow());
+        return;
+    }
+
+    if !configured.is_empty() {
+        println!("{}", tr("Configured in ~/.ssh/config:").cyan().bold());
+        let width = configured.iter().map(|host| host.name.chars().count()).max().unwrap_or(0);
+        for host in &configured {
+            let user = host.user.as_ref().map(|user| format!("{}@", user)).unwrap_or_default();
+            let port = host.port.map(|port| format!(":{}", port)).unwrap_or_default();
+            let address = host.hostname.as_deref().unwrap_or(&host.name);
+            println!("  {:width$}  {}{}{}", host.name.green(), user, address, port);
+        }
+    }
+    if !known.is_empty() {
+        println!("{}", tr("Known hosts:").cyan().bold());
+        for name in &known {
+            println!("  {}", name.green());
+        }
+    }
+}
+
+// Shows every old → new name, then renames them all unless it's a dry run or
+// any of them conflicts. The renames are journaled as one operation for undo.
+fn bulk_rename(state: &ShellState, expression: &str, files: &[String], dry_run: bool) -> CrateResult<()> {
+    let substitution = rename::Substitution::parse(expression)?;
+    let mut paths = Vec::new();
+    for file in files {
+        let path = state.resolve(file);
+        if std::fs::symlink_metadata(&path).is_err() {
+            return Err(anyhow!(trf("rename: '{}' does not exist", &[&file])));
+        }
+        paths.push(path);
+    }
+    let renames = rename::plan(&substitution, &paths);
+    if renames.is_empty() {
+        println!("{}", tr("No file names match; nothing to rename.").yellow());
+        return Ok(());
+    }
+
+    let shown = |path: &std::path::Path| path.strip_prefix(&state.cwd).unwrap_or(path).display().to_string();
+    let width = renames.iter().map(|rename| shown(&rename.from).chars().count()).max().unwrap_or(0);
+    for rename in &renames {
+        let old = format!("{:width$}", shown(&rename.from), width = width);
+        match &rename.conflict {
+            Some(reason) => println!("  {} → {}  {}", old, shown(&rename.to).red(), format!("({})", reason).red()),
+            None => println!("  {} → {}", old, shown(&rename.to).cyan()),
+        }
+    }
+
+    let conflicts = renames.iter().filter(|rename| rename.conflict.is_some()).count();
+    if conflicts > 0 {
+        return Err(anyhow!(trf("{} conflicting names; nothing was renamed", &[&conflicts])));
+    }
+    if dry_run {
+        println!("{}", trf("Dry run: {} files would be renamed.", &[&renames.len()]).yellow());
+        return Ok(());
+    }
+
+    let mut done = Vec::new();
+    let mut result = Ok(());
+    for rename in &renames {
+        if let Err(e) = std::fs::rename(&rename.from, &rename.to) {
+            result = Err(anyhow!(trf("could not rename '{}': {}", &[&shown(&rename.from), &e])));
+            break;
+        }
+        done.push((rename.from.clone(), rename.to.clone()));
+    }
+    // Whatever did get renamed can still be undone
+    journal::record_rename(&done)?;
+    result?;
+    println!("{}", theme::paint("success", &trf("Renamed {} files.", &[&done.len()])));
+    Ok(())
+}
+
+// Trees with more entries than this are summarized before `rm -r` deletes them
+const LARGE_TREE: u64 = 100;
+
+// rm --git: git rm deletes the tracked files and stages that; untracked files
+// under a directory stay where they are
+fn remove_tracked(state: &ShellState, s: &str, options: &RemoveOptions) -> CrateResult<()> {
+    let path = state.resolve(s);
+    if path.is_dir() {
+        if !options.recursive {
+            return Err(anyhow!(trf("'{}' is a directory; use rm -r to remove it and its contents", &[&s])));
+        }
+        if !options.no_preserve_root {
+            helpers::check_removal(&path, &state.cwd)?;
+        }
+    }
+    git::remove(state, &path, options.recursive, options.force)?;
+    println!("{} {}", tr("Removed and staged in git:").bright_red(), s);
+    if path.exists() {
+        println!("{}", trf("Untracked files under '{}' were kept", &[&s]).yellow());
+    }
+    Ok(())
+}
+
+fn remove(state: &ShellState, s: &str, options: &RemoveOptions) -> CrateResult<()> {
+    let path = state.resolve(s);
+    let metadata = match std::fs::symlink_metadata(&path) {
+        Ok(metadata) => metadata,
+        // Like coreutils, -f is quiet about paths that aren't there
+        Err(e) if options.force && e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    if !metadata.is_dir() {
+        helpers::rm(&path)?;
+        println!("{} {}", tr("Removed:").bright_red(), s);
+        return Ok(());
+    }
+    if !options.recursive {
+        return Err(anyhow!(trf("'{}' is a directory; use rm -r to remove it and its contents", &[&s])));
+    }
+    if !options.no_preserve_root {
+        helpers::check_removal(&path, &state.cwd)?;
+    }
+
+    let summary = helpers::summarize(&path);
+    if summary.files + summary.dirs > LARGE_TREE {
+        println!(
+            "{}",
+            trf(
+                "'{}' contains {} files and {} directories ({}).",
+                &[&s, &summary.files, &summary.dirs, &helpers::human_size(summary.bytes)]
+            )
+            .yellow()
+        );
+        if !options.force && std::io::stdin().is_terminal() && !terminal::confirm(tr("Remove all of it? [y/N] ")) {
+            println!("{} {}", tr("Kept:").yellow(), s);
+            return Ok(());
+        }
+    }
+
+    let kept = helpers::rmdir_r(&path, options.one_file_system)?;
+    if !kept.is_empty() {
+        for mount in &kept {
+            println!("{} {}", tr("Kept (other filesystem):").yellow(), mount.strip_prefix(&state.cwd).unwrap_or(mount).display());
+        }
+        return Err(anyhow!(trf("'{}' was only partly removed: it holds other filesystems", &[&s])));
+    }
+    println!("{} {}", tr("Directory and contents removed:").bright_red(), s);
+    Ok(())
+}
+
+// Runs each `<(command)` first with its output going to a temporary file,
+// then the line with the files' paths in their place. The files are removed
+// once the line has run.
+async fn substitute_processes(state: &mut ShellState, line: &str, substitutions: Vec<ProcessSubstitution>) -> CrateResult<Command> {
+    let mut files = Vec::new();
+    let result = async {
+        for substitution in &substitutions {
+            let (path, file) = capture::temporary_file()?;
+            files.push(path);
+            let _capture = capture::Capture::start(&file)?;
+            Box::pin(handle_new_line(state, &substitution.command))
+                .await
+                .map_err(|e| anyhow!(trf("<({}) failed: {}", &[&substitution.command, &e])))?;
+        }
+        let mut expanded = line.to_string();
+        for (substitution, path) in substitutions.iter().zip(&files).rev() {
+            expanded.replace_range(substitution.range.clone(), &quote(&path.to_string_lossy()));
+        }
+        Box::pin(handle_new_line(state, &expanded)).await
+    }
+    .await;
+    for path in &files {
+        let _ = std::fs::remove_file(path);
+    }
+    result
+}
+
+// Runs the stages of `pipeline` at once, each in a copy of the shell that
+// reads what the stage before prints through a pipe, so output flows as it
+// is written and a stage that stops reading ends the ones feeding it. As in
+// other shells, the stages run apart from this one, so a `cd` or `export`
+// in one changes nothing here. Each reports its own errors, and the line
+// succeeds or fails as its last stage does.
+async fn run_pipeline(state: &mut ShellState, pipeline: Pipeline) -> CrateResult<Command> {
+    let shell = std::env::current_exe()?;
+    let colors = colored::control::SHOULD_COLORIZE.should_colorize();
+    let last = pipeline.stages.len() - 1;
+    let commands = pipeline
+        .stages
+        .iter()
+        .enumerate()
+        .map(|(index, stage)| {
+            let mut command = state.command(&shell.to_string_lossy());
+            // Only the last stage prints where colors can show
+            command.args(["--color", if index == last && colors { "always" } else { "never" }]);
+            command.args(config::override_args()).args(["--stage", stage]);
+            command
+        })
+        .collect();
+    std::io::Write::flush(&mut std::io::stdout())?;
+    let status = foreground::run_pipeline(commands, &pipeline.stages.join(" | "))?;
+    if !status.success() {
+        return Err(False.into());
+    }
+    Ok(Command::Pipeline(pipeline.stages))
+}
+
+// Runs the line with its output and errors going to the files its
+// redirections name, each opened (and created) before it starts. The
+// line's own error goes where `2>` sends errors too.
+async fn run_redirected(state: &mut ShellState, redirected: Redirected) -> CrateResult<Command> {
+    let mut files = Vec::new();
+    for redirection in &redirected.redirections {
+        let path = state.resolve(&redirection.path);
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(redirection.append)
+            .truncate(!redirection.append)
+            .open(&path)
+            .map_err(|e| errors::with_path(e, &if path.exists() { path.clone() } else { errors::parent_of(&path) }, Access::Write))?;
+        files.push((redirection.stream, file));
+    }
+    let mut captures = Vec::new();
+    for (stream, file) in &files {
+        captures.push(match stream {
+            Stream::Output => capture::Capture::start(file)?,
+            Stream::Errors => capture::Capture::errors(file)?,
+        });
+    }
+    let result = match Box::pin(handle_new_line(state, &redirected.line)).await {
+        Err(e) if !e.is::<False>() && redirected.redirections.iter().any(|redirection| redirection.stream == Stream::Errors) => {
+            eprintln!("{} {}", theme::paint("error", tr("Error:")), e);
+            Err(False.into())
+        }
+        result => result,
+    };
+    // Put back in the opposite order, as each saved what the one before set
+    while let Some(capture) = captures.pop() {
+        drop(capture);
+    }
+    result
 }
 
-async fn handle_new_line(line: &str) -> CrateResult<Command> {
-    let command: Command = line.try_into()?;
+async fn handle_new_line(state: &mut ShellState, line: &str) -> CrateResult<Command> {
+    let substitutions = tokenizer::process_substitutions(line)?;
+    if !substitutions.is_empty() {
+        return substitute_processes(state, line, substitutions).await;
+    }
+    let pipeline = Pipeline::try_from(line)?;
+    if pipeline.stages.len() > 1 {
+        return run_pipeline(state, pipeline).await;
+    }
+    let redirected = Redirected::try_from(line)?;
+    if !redirected.redirections.is_empty() {
+        return run_redirected(state, redirected).await;
+    }
+    let line = glob::expand_line(line, state)?;
+    let command: Command = match line.as_str().try_into() {
+        Ok(command) => command,
+        // With autocd, a directory named on its own is a `cd` into it
+        Err(e) => match tokenizer::tokenize(&line)?.as_slice() {
+            [path] if config::get().autocd && !completion::is_builtin(path) && state.resolve(path).is_dir() => Command::Cd(path.clone()),
+            // Any other name is a program to run
+            [name, args @ ..] if name != "[" && !completion::is_builtin(name) && !name.contains('=') => Command::Program(name.clone(), args.to_vec()),
+            _ => return Err(e),
+        },
+    };
 
     match command.clone() {
-        Command::Ls => {
-            helpers::ls()?;
+        Command::Ls(paths) => list(state, &paths, helpers::ls, |name, metadata| {
+            helpers::ls_entry(name, metadata);
+            Ok(())
+        })?,
+        Command::LsDetailed(options, paths) => list(
+            state,
+            &paths,
+            |dir| helpers::ls_detailed(dir, &options),
+            |name, metadata| helpers::ls_detailed_entries(&[(name.to_string(), metadata.clone())], &options),
+        )?,
+        Command::Echo(s, options) => {
+            let (text, stopped) = if options.escapes { printf::unescape(&s) } else { (s, false) };
+            // \c also drops the newline
+            if options.no_newline || stopped {
+                print!("{}", text);
+                std::io::Write::flush(&mut std::io::stdout())?;
+            } else {
+                println!("{}", text);
+            }
         }
-        Command::LsDetailed => {
-            helpers::ls_detailed()?;
+        Command::Printf(format, args) => {
+            print!("{}", printf::format(&format, &args)?);
+            std::io::Write::flush(&mut std::io::stdout())?;
         }
-        Command::Echo(s) => {
-            println!("{}", s);
+        // Piped or redirected output gets nothing, as with `clear` elsewhere
+        Command::Clear if terminal::is_ansi_terminal() => {
+            print!("{}", terminal::CLEAR_SCREEN);
+            std::io::Write::flush(&mut std::io::stdout())?;
         }
+        Command::Clear => {}
         Command::Pwd => {
-            println!("{}", helpers::pwd()?.bright_yellow());
+            println!("{}", state.cwd.display().to_string().bright_yellow());
         }
         Command::Cd(s) => {
-            helpers::cd(&s)?;
+            state.cd(&s)?;
         }
-        Command::Touch(s) => {
-            helpers::touch(&s)?;
-            println!("{} {}", "Created/Updated:".bright_green(), s);
-        }
-        Command::Rm(s) => {
-            helpers::rm(&s)?;
-            println!("{} {}", "Removed:".bright_red(), s);
+        Command::Touch(paths, options) => for_each_path(&paths, |s| {
+            let path = state.resolve(s);
+            let previous = journal::file_times(&path);
+            // With -c a missing file is silently skipped, as in coreutils
+            if helpers::touch(&path, &options)? {
+                journal::record_touch(&path, previous)?;
+                println!("{} {}", theme::paint("success", tr("Created/Updated:")), s);
+            }
+            Ok(())
+        })?,
+        Command::Rm(paths, options) => {
+            // With --git, or git_aware_rm_mv in the config, tracked paths go through git rm
+            let git = options.git || config::get().git_aware_rm_mv;
+            for_each_path(&paths, |s| match git && git::tracks(state, &state.resolve(s), options.git)? {
+                true => remove_tracked(state, s, &options),
+                false if options.git => Err(anyhow!(trf("'{}' is not tracked by git; use rm without --git", &[&s]))),
+                false => remove(state, s, &options),
+            })?
         }
-        Command::Cat(s) => {
-            let contents = helpers::cat(&s)?;
+        Command::Cat(paths, options) if paths.len() == 1 && !options.headers => {
+            let terminal = std::io::stdout().is_terminal();
+            if paths[0] == STDIN {
+                if terminal {
+                    println!("{}", format!("=== {} ===", tr("(standard input)")).bright_yellow());
+                }
+                helpers::cat_stream(&mut std::io::stdin().lock(), &mut std::io::stdout().lock(), &options)?;
+                if terminal {
+                    println!("\n{}", "==========".bright_yellow());
+                }
+                std::io::Write::flush(&mut std::io::stdout())?;
+                return Ok(command);
+            }
+            let Some(piped) = read_pipes(state, &paths).await? else {
+                return Ok(command);
+            };
+            let contents = match piped.get(&paths[0]) {
+                Some(data) => helpers::format_cat(data, &options),
+                None => helpers::cat(&state.resolve(&paths[0]), &options)?,
+            };
+            // Into a pipe or a file the contents go on their own
+            if !terminal {
+                print!("{}", contents);
+                std::io::Write::flush(&mut std::io::stdout())?;
+                return Ok(command);
+            }
             println!("{}\n{}\n{}", 
-                format!("=== {} ===", s).bright_yellow(), 
+                format!("=== {} ===", paths[0]).bright_yellow(), 
                 contents,
                 "==========".bright_yellow());
         }
-        Command::Mkdir(s) => {
-            helpers::mkdir(&s)?;
-            println!("{} {}", "Directory created:".bright_green(), s);
+        Command::Cat(paths, options) => {
+            let Some(piped) = read_pipes(state, &paths).await? else {
+                return Ok(command);
+            };
+            // Several files are concatenated, as with coreutils
+            let mut first = true;
+            let mut ends_line = true;
+            let result = for_each_path(&paths, |s| {
+                if s == STDIN {
+                    if options.headers {
+                        let separator = if first { "" } else { "\n" };
+                        println!("{}{}", separator, format!("==> {} <==", s).bright_yellow());
+                    }
+                    first = false;
+                    ends_line = helpers::cat_stream(&mut std::io::stdin().lock(), &mut std::io::stdout().lock(), &options)?;
+                    std::io::Write::flush(&mut std::io::stdout())?;
+                    return Ok(());
+                }
+                let read = match piped.get(s) {
+                    Some(data) => Ok(helpers::format_cat(data, &options)),
+                    None => helpers::cat(&state.resolve(s), &options),
+                };
+                let contents = match read {
+                    Ok(contents) => contents,
+                    // The error is reported on its own line
+                    Err(e) if !ends_line => {
+                        println!();
+                        ends_line = true;
+                        return Err(e);
+                    }
+                    Err(e) => return Err(e),
+                };
+                if options.headers {
+                    let separator = if first { "" } else { "\n" };
+                    println!("{}{}", separator, format!("==> {} <==", s).bright_yellow());
+                }
+                print!("{}", contents);
+                // Errors for later files go to stderr; keep them in order
+                std::io::Write::flush(&mut std::io::stdout())?;
+                first = false;
+                ends_line = contents.is_empty() || contents.ends_with('\n');
+                Ok(())
+            });
+            // Keep the prompt off the last line of output
+            if !ends_line {
+                println!();
+            }
+            result?;
         }
-        Command::MkdirP(s) => {
-            helpers::mkdir_p(&s)?;
-            println!("{} {}", "Directory hierarchy created:".bright_green(), s);
+        Command::Mkdir(paths) => for_each_path(&paths, |s| {
+            let path = state.resolve(s);
+            helpers::mkdir(&path)?;
+            journal::record_mkdir(vec![path])?;
+            println!("{} {}", theme::paint("success", tr("Directory created:")), s);
+            Ok(())
+        })?,
+        Command::MkdirP(paths) => for_each_path(&paths, |s| {
+            let path = state.resolve(s);
+            let created = journal::missing_dirs(&path);
+            helpers::mkdir_p(&path)?;
+            journal::record_mkdir(created)?;
+            println!("{} {}", theme::paint("success", tr("Directory hierarchy created:")), s);
+            Ok(())
+        })?,
+        Command::Mkfifo(paths, mode) => for_each_path(&paths, |s| {
+            let path = state.resolve(s);
+            fifo::create(&path, mode)?;
+            journal::record_touch(&path, None)?;
+            println!("{} {}", theme::paint("success", tr("Named pipe created:")), s);
+            Ok(())
+        })?,
+        Command::Rmdir(paths) => for_each_path(&paths, |s| {
+            helpers::rmdir(&state.resolve(s))?;
+            println!("{} {}", tr("Directory removed:").bright_red(), s);
+            Ok(())
+        })?,
+        Command::Cp(src, dest) => {
+            let (source, destination) = (state.resolve(&src), state.resolve(&dest));
+            let overwrote = destination.exists();
+            helpers::cp(&source, &destination)?;
+            journal::record_copy(&source, &destination, overwrote)?;
+            println!("{} '{}' → '{}'", theme::paint("success", tr("Copied:")), src, dest);
         }
-        Command::Rmdir(s) => {
-            helpers::rmdir(&s)?;
-            println!("{} {}", "Directory removed:".bright_red(), s);
+        Command::CpR(src, dest, options) => {
+            let (source, destination) = (state.resolve(&src), state.resolve(&dest));
+            let overwrote = destination.exists();
+            helpers::cp_r(&source, &destination, &options)?;
+            journal::record_copy(&source, &destination, overwrote)?;
+            println!("{} '{}' → '{}'", theme::paint("success", tr("Recursively copied:")), src, dest);
         }
-        Command::RmdirR(s) => {
-            helpers::rmdir_r(&s)?;
-            println!("{} {}", "Directory and contents removed:".bright_red(), s);
+        Command::Mv(src, dest, options) => {
+            let (source, destination) = (state.resolve(&src), state.resolve(&dest));
+            if (options.git || config::get().git_aware_rm_mv) && git::tracks(state, &source, options.git)? {
+                git::rename(state, &source, &destination)?;
+                println!("{} '{}' → '{}'", tr("Moved and staged in git:").bright_blue(), src, dest);
+            } else if options.git {
+                return Err(anyhow!(trf("'{}' is not tracked by git; use mv without --git", &[&src])));
+            } else {
+                let overwrote = destination.exists();
+                helpers::mv(&source, &destination)?;
+                journal::record_move(&source, &destination, overwrote)?;
+                println!("{} '{}' → '{}'", tr("Moved:").bright_blue(), src, dest);
+            }
         }
-        Command::Cp(src, dest) => {
-            helpers::cp(&src, &dest)?;
-            println!("{} '{}' → '{}'", "Copied:".bright_green(), src, dest);
-        }
-        Command::CpR(src, dest) => {
-            helpers::cp_r(&src, &dest)?;
-            println!("{} '{}' → '{}'", "Recursively copied:".bright_green(), src, dest);
-        }
-        Command::Mv(src, dest) => {
-            helpers::mv(&src, &dest)?;
-            println!("{} '{}' → '{}'", "Moved:".bright_blue(), src, dest);
-        }
-        Command::Stat(path) => {
-            let info = helpers::stat(&path)?;
-            println!("{}\n{}", format!("=== Statistics for {} ===", path).bright_yellow(), info);
-        }
-        Command::Find(dir, pattern) => {
-            let results = helpers::find(&dir, &pattern)?;
-            println!("{} {} {}", 
-                "Found".bright_green(), 
-                results.len().to_string().yellow(), 
-                "matches:".bright_green());
-            
-            for path in results {
-                println!("  {}", path.display().to_string().cyan());
-            }
-        }
-        Command::Grep(file, pattern) => {
-            let results = helpers::grep(&file, &pattern)?;
-            if results.is_empty() {
-                println!("{} {}", "No matches found in".yellow(), file);
+        Command::Stat(paths) => for_each_path(&paths, |path| {
+            let info = helpers::stat(&state.resolve(path))?;
+            println!("{}\n{}", trf("=== Statistics for {} ===", &[&path]).bright_yellow(), info);
+            Ok(())
+        })?,
+        Command::Find(dir, pattern, options, filters) => {
+            let matcher = Matcher::new(&pattern, options.case());
+            let mut found = Vec::new();
+            helpers::find(&state.resolve(&dir), &matcher, &filters, &mut |path| {
+                // Show matches the way the user would type them from here
+                let shown = path.strip_prefix(&state.cwd).unwrap_or(path);
+                println!("  {}", shown.display().to_string().cyan());
+                found.push(path.to_path_buf());
+                options.wants_more(found.len())
+            })?;
+            print_search_summary(&options, found.len());
+
+            if filters.delete && !found.is_empty() && !interrupt::requested() {
+                delete_found(state, &found, filters.yes)?;
+            }
+        }
+        Command::Grep(file, pattern, options) => {
+            let matcher = Matcher::new(&pattern, options.case());
+            let shown = if file == STDIN { tr("(standard input)") } else { file.as_str() };
+            let mut count = 0;
+            // Into a pipe or a file only the matching lines go, as grep prints them
+            let plain = !std::io::stdout().is_terminal();
+            let mut found = |number: usize, line: &str| {
+                if plain {
+                    println!("{}", line);
+                    count += 1;
+                    return options.wants_more(count);
+                }
+                if count == 0 {
+                    println!("{} {}:", tr("Matches in").bright_green(), shown.yellow());
+                }
+                println!("{}{}", format!("{}:", number).yellow(), line);
+                count += 1;
+                options.wants_more(count)
+            };
+            let path = state.resolve(&file);
+            if file == STDIN {
+                helpers::grep_reader(std::io::stdin().lock(), &matcher, &mut found)?;
+            } else if fifo::is_fifo(&path) {
+                let Some(data) = fifo::read(&path).await? else {
+                    return Ok(command);
+                };
+                helpers::grep_reader(&data[..], &matcher, &mut found)?;
             } else {
-                println!("{} {}:", "Matches in".bright_green(), file.yellow());
-                
-                // Colorize the output: line numbers in yellow, matched text highlighted
-                for line in results.lines() {
-                    if let Some(pos) = line.find(':') {
-                        let (line_num, content) = line.split_at(pos + 1);
-                        println!("{}{}", line_num.yellow(), content);
+                helpers::grep(&path, &matcher, &mut found)?;
+            }
+
+            match count {
+                _ if plain => {}
+                0 if !interrupt::requested() => println!("{} {}", tr("No matches found in").yellow(), shown),
+                _ => print_search_summary(&options, count),
+            }
+        }
+        Command::Ln(target, link_name, options) => {
+            let created = helpers::ln(&target, &state.resolve(&target), &state.resolve(&link_name), &options)?;
+            let shown = created.strip_prefix(&state.cwd).unwrap_or(&created).display();
+            if options.symbolic {
+                // Show what was stored, which differs from what was typed with -r
+                let stored = std::fs::read_link(&created).map(|p| p.display().to_string()).unwrap_or(target);
+                println!("{} '{}' → '{}'", theme::paint("success", tr("Created symbolic link:")), shown, stored);
+            } else {
+                println!("{} '{}' → '{}'", theme::paint("success", tr("Created hard link:")), shown, target);
+            }
+        }
+        Command::Dedupe(dir, options) => dedupe(state, &dir, &options)?,
+        Command::Split(file, prefix, chunk) => {
+            let source = state.resolve(&file);
+            let size = std::fs::metadata(&source).map(|metadata| metadata.len()).unwrap_or(0);
+            let mut progress = terminal::Progress::new(&trf("Splitting {}", &[&file]), size);
+            let pieces = helpers::split(&source, &state.resolve(&prefix), chunk, &mut |bytes| progress.advance(bytes));
+            progress.finish();
+            let pieces = pieces?;
+            for piece in &pieces {
+                println!("  {}", piece.strip_prefix(&state.cwd).unwrap_or(piece).display().to_string().cyan());
+            }
+            println!("{}", theme::paint("success", &trf("Split {} into {} pieces.", &[&file, &pieces.len()])));
+        }
+        Command::Merge(pieces, output) => {
+            let pieces: Vec<std::path::PathBuf> = pieces.iter().map(|piece| state.resolve(piece)).collect();
+            let total = pieces.iter().filter_map(|piece| std::fs::metadata(piece).ok()).map(|metadata| metadata.len()).sum();
+            let mut progress = terminal::Progress::new(&trf("Merging into {}", &[&output]), total);
+            let merged = helpers::merge(&pieces, &state.resolve(&output), &mut |bytes| progress.advance(bytes));
+            progress.finish();
+            merged?;
+            println!("{}", theme::paint("success", &trf("Merged {} pieces into {} ({}).", &[&pieces.len(), &output, &helpers::human_size(total)])));
+        }
+        Command::Truncate(paths, options) => for_each_path(&paths, |s| {
+            if let Some(size) = helpers::truncate(&state.resolve(s), &options)? {
+                println!("{} {} ({})", theme::paint("success", tr("Resized:")), s, helpers::human_size(size));
+            }
+            Ok(())
+        })?,
+        Command::Du(paths, options) => {
+            let mut grand_total = 0;
+            for_each_path(&paths, |s| {
+                let total = helpers::du(&state.resolve(s), &options, &mut |dir, size| {
+                    let shown = match dir.strip_prefix(&state.cwd) {
+                        Ok(relative) if relative.as_os_str().is_empty() => std::path::Path::new("."),
+                        Ok(relative) => relative,
+                        Err(_) => dir,
+                    };
+                    println!("{:>10}  {}", helpers::human_size(size).cyan(), shown.display());
+                })?;
+                if options.summarize {
+                    println!("{:>10}  {}", helpers::human_size(total).cyan(), s);
+                }
+                grand_total += total;
+                Ok(())
+            })?;
+            println!("{:>10}  {}", helpers::human_size(grand_total).bright_green().bold(), tr("total").bold());
+            if interrupt::requested() {
+                println!("{}", tr("Interrupted; the sizes above are incomplete.").yellow());
+            }
+        }
+        Command::Rename(expression, files, dry_run) => bulk_rename(state, &expression, &files, dry_run)?,
+        Command::Hosts => list_hosts(state),
+        Command::Program(name, args) => run_program(state, &name, &args, &line)?,
+        Command::Remote(program, args) => {
+            let status = ssh::run(state, &program, &args)?;
+            // Suspended with Ctrl+Z; it is a job now
+            if status.stopped_signal().is_some() {
+                return Err(False.into());
+            }
+            if !status.success() {
+                return Err(anyhow!(trf("{} exited with {}", &[&program, &status])));
+            }
+        }
+        Command::Push(local, remote, options) => {
+            let remote = transfer::Remote::parse(&remote)?;
+            report_transfer(transfer::push(state, &local, &remote, &options).await?);
+        }
+        Command::Pull(remote, local, options) => {
+            let remote = transfer::Remote::parse(&remote)?;
+            report_transfer(transfer::pull(state, &remote, &local, &options).await?);
+        }
+        Command::Serve(dir, port) => {
+            let root = state.resolve(&dir);
+            if !root.is_dir() {
+                return Err(anyhow!(trf("'{}' is not a directory", &[&dir])));
+            }
+            let listener = serve::bind(port).await?;
+            // Port 0 lets the system pick a free one
+            let port = listener.local_addr()?.port();
+            let id = jobs::spawn(&format!("serve {} --port {}", dir, port), serve::run(listener, root));
+            println!(
+                "{}",
+                theme::paint("success", &trf("Serving {} at http://localhost:{}/ as job [{}]; stop it with kill %{}", &[&dir, &port, &id, &id]))
+            );
+        }
+        Command::Jobs => {
+            let running = jobs::list();
+            if running.is_empty() {
+                println!("{}", tr("No background jobs.").yellow());
+            }
+            for job in running {
+                let elapsed = helpers::human_duration(job.elapsed());
+                let process = match (job.pid, &job.log) {
+                    (Some(pid), Some(log)) => format!("  {}", trf("pid {}, output in {}", &[&pid, &log.display()]).bright_black()),
+                    _ => String::new(),
+                };
+                let state = if job.stopped { tr("Stopped").yellow() } else { tr("Running").green() };
+                println!("[{}]  {}  {}  {}{}", job.id, state, job.command, elapsed.bright_black(), process);
+            }
+        }
+        Command::Kill(signal, targets) => for_each_path(&targets, |target| {
+            let Some(number) = target.strip_prefix('%') else {
+                return send_signal(target.parse()?, signal);
+            };
+            let job = number.parse().ok().and_then(find_job).ok_or_else(|| anyhow!(trf("kill: no such job {}", &[&target])))?;
+            // A nohup job or a suspended program is a process of its own; its
+            // notice says how it ended
+            if let Some(pid) = job.pid {
+                send_signal(pid, signal)?;
+                // A stopped program only acts on the signal once continued
+                if job.stopped && signal != libc::SIGSTOP {
+                    send_signal(pid, libc::SIGCONT)?;
+                }
+                return Ok(());
+            }
+            // Other jobs are tasks of the shell, not processes; all they can do is end
+            if !signals::terminates(signal) {
+                return Err(anyhow!(trf("kill: {} runs inside the shell and can only be stopped, not sent {}", &[&target, &signals::name(signal)])));
+            }
+            if let Some(job) = jobs::kill(job.id) {
+                println!("[{}]  {}  {}", job.id, tr("Stopped").yellow(), job.command);
+            }
+            Ok(())
+        })?,
+        Command::Nohup(line) => {
+            let (log, file) = nohup_log(state)?;
+            // The shell itself runs the line, in a session of its own so the
+            // terminal closing doesn't end it
+            let mut command = state.command(&std::env::current_exe()?.to_string_lossy());
+            command.args(["--color", "never", "-c", &line]).stdin(Stdio::null()).stdout(file.try_clone()?).stderr(file);
+            unsafe {
+                command.pre_exec(|| {
+                    libc::setsid();
+                    libc::signal(libc::SIGHUP, libc::SIG_IGN);
+                    Ok(())
+                });
+            }
+            let child = tokio::process::Command::from(command).spawn()?;
+            let pid = child.id().unwrap_or_default();
+            let id = jobs::spawn_process(&format!("nohup {}", line), child, log.clone());
+            println!("[{}] {}", id, pid);
+            println!("{}", trf("nohup: output goes to {}", &[&log.display()]));
+        }
+        Command::Fg(target) => {
+            let job = suspended_job(target, "fg")?;
+            let (Some(pid), Some(job)) = (job.pid, jobs::resume(job.id)) else {
+                return Err(anyhow!(trf("{}: no such job %{}", &[&"fg", &job.id])));
+            };
+            println!("{}", job.command);
+            let status = foreground::resume(&job, pid)?;
+            if status.stopped_signal().is_some() {
+                return Err(False.into());
+            }
+            if !status.success() {
+                return Err(anyhow!(trf("{} exited with {}", &[&job.command, &status])));
+            }
+        }
+        Command::Bg(target) => {
+            let job = suspended_job(target, "bg")?;
+            if !job.stopped {
+                return Err(anyhow!(trf("bg: job %{} is already running", &[&job.id])));
+            }
+            // The whole process group, as Ctrl+Z stopped it
+            if unsafe { libc::kill(-(job.pid.unwrap_or_default() as libc::pid_t), libc::SIGCONT) } != 0 {
+                return Err(std::io::Error::last_os_error().into());
+            }
+            println!("[{}]  {} &", job.id, job.command);
+        }
+        Command::Disown(ids) => {
+            for id in ids {
+                let job = find_job(id).ok_or_else(|| anyhow!(trf("disown: no such job %{}", &[&id])))?;
+                let (Some(pid), Some(log)) = (job.pid, &job.log) else {
+                    if job.pid.is_some() {
+                        return Err(anyhow!(trf("disown: %{} is attached to this terminal; only nohup jobs can be disowned", &[&id])));
+                    }
+                    return Err(anyhow!(trf("disown: %{} runs inside the shell and ends with it; start it with nohup <command> & to keep it running", &[&id])));
+                };
+                jobs::disown(id);
+                println!("{}", trf("[{}] {} (pid {}) is no longer a job and keeps running after the shell exits; its output goes to {}", &[&id, &job.command, &pid, &log.display()]));
+            }
+        }
+        Command::KillList => {
+            for (name, number) in signals::SIGNALS {
+                println!("{:>2}) {}", number, format!("SIG{}", name).cyan());
+            }
+        }
+        Command::Killall(signal, name) => killall(signal, &name)?,
+        Command::Pstree(root) => print_process_tree(root)?,
+        Command::NewList => {
+            let dir = templates::templates_dir();
+            let names = templates::list(&dir);
+            if names.is_empty() {
+                println!("{}", trf("No templates yet; each directory in {} becomes one", &[&dir.display()]));
+            }
+            for name in names {
+                println!("  {}", name.cyan());
+            }
+        }
+        Command::New(template, name) => {
+            let (dir, target) = (templates::templates_dir(), state.resolve(&name));
+            let project = target.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or(name.clone());
+            let written = templates::create(&dir, &template, &target, &templates::variables(&project))?;
+            journal::record_copy(&dir.join(&template), &target, false)?;
+            println!("{}", theme::paint("success", &trf("Created {} from the {} template ({} files)", &[&name, &template, &written])));
+        }
+        Command::Task(None) => list_tasks(state)?,
+        Command::Task(Some(name)) => run_task(state, &name).await?,
+        Command::GitStatus => git::print_status(&git::status(state).await?),
+        Command::GitLog(count, path) => {
+            for line in git::log(state, count, path.as_deref()).await? {
+                println!("{}", line);
+            }
+        }
+        Command::GitDiff(staged, path) => {
+            let lines = git::diff(state, staged, path.as_deref()).await?;
+            if lines.is_empty() {
+                println!("{}", tr("No changes"));
+            }
+            for line in lines {
+                println!("{}", line);
+            }
+        }
+        Command::Usage(dir) => {
+            let (root, cwd) = (state.resolve(&dir), state.cwd.clone());
+            tokio::task::spawn_blocking(move || usage::run(root, cwd)).await??
+        }
+        Command::Monitor(interval, sort) => tokio::task::spawn_blocking(move || monitor::run(interval, sort)).await??,
+        Command::Ports(None) => list_ports(),
+        Command::Ports(Some(port)) => kill_port(port)?,
+        Command::Resolve(name, options) => {
+            let answers = dns::lookup(&name, &options).await?;
+            if answers.is_empty() {
+                return Err(anyhow!(trf("no records found for {}", &[&name])));
+            }
+            let width = answers.iter().map(|answer| answer.name.chars().count()).max().unwrap_or(0);
+            println!("{}", format!("{:6} {:width$} {:>6}  {}", tr("Type"), tr("Name"), "TTL", tr("Value")).bold());
+            for answer in &answers {
+                let kind = match answer.kind.as_str() {
+                    "A" | "AAAA" => answer.kind.green(),
+                    "CNAME" => answer.kind.cyan(),
+                    "MX" => answer.kind.magenta(),
+                    _ => answer.kind.yellow(),
+                };
+                println!("{:6} {:width$} {:>6}  {}", kind, answer.name, answer.ttl, answer.value);
+            }
+        }
+        Command::Download(url, options) => {
+            let name = match &options.output {
+                Some(output) => output.clone(),
+                None => download::file_name(&url)?,
+            };
+            let target = state.resolve(&name);
+            if options.background {
+                let command = format!("download {}", url);
+                let saved_as = name.clone();
+                let id = jobs::spawn(&command, async move {
+                    let downloaded = download::download(&url, &target, &options, false).await?;
+                    output::println(&download_report(&saved_as, &options, &downloaded));
+                    Ok(())
+                });
+                println!("{}", trf("Downloading {} as job [{}]", &[&name, &id]));
+            } else {
+                let downloaded = download::download(&url, &target, &options, true).await?;
+                println!("{}", download_report(&name, &options, &downloaded));
+            }
+        }
+        Command::Printenv(names) => {
+            if names.is_empty() {
+                for (name, value) in environment::variables(state, false) {
+                    println!("{}={}", name, value);
+                }
+            }
+            let mut missing = Vec::new();
+            for name in &names {
+                match state.var(name) {
+                    Some(value) => println!("{}", value),
+                    None => missing.push(name.as_str()),
+                }
+            }
+            if !missing.is_empty() {
+                return Err(anyhow!(trf("printenv: {} not set", &[&missing.join(", ")])));
+            }
+        }
+        Command::Which(names) => {
+            let mut missing = Vec::new();
+            for name in &names {
+                // In the order they're tried: abbreviations expand as they're typed
+                if let Some(expansion) = config::get().abbr.get(name) {
+                    println!("{}", trf("{}: abbreviation for '{}'", &[name, expansion]));
+                } else if completion::is_builtin(name) {
+                    println!("{}", trf("{}: shell builtin", &[name]));
+                } else if let Some(path) = executables::find(name, state.var("PATH").unwrap_or_default()) {
+                    println!("{}", path.display());
+                } else {
+                    missing.push(name.as_str());
+                }
+            }
+            if !missing.is_empty() {
+                return Err(anyhow!(trf("which: no {} in PATH", &[&missing.join(", ")])));
+            }
+        }
+        Command::Hash(action) => {
+            let path = state.var("PATH").unwrap_or_default();
+            match action {
+                HashAction::List => {
+                    let hashed = executables::hashed(path);
+                    if hashed.is_empty() {
+                        println!("{}", tr("hash: hash table empty"));
                     } else {
-                        println!("{}", line);
+                        let width = tr("hits").chars().count().max(4);
+                        println!("{:>width$}  {}", tr("hits"), tr("command"));
+                        for (_, hashed) in hashed {
+                            println!("{:>width$}  {}", hashed.hits, hashed.path.display());
+                        }
+                    }
+                }
+                HashAction::Add(names) => {
+                    // A builtin that isn't also a program (as ssh is) has nothing to remember
+                    let missing: Vec<&str> = names
+                        .iter()
+                        .filter(|name| executables::remember(name, path, 0).is_none() && !completion::is_builtin(name))
+                        .map(String::as_str)
+                        .collect();
+                    if !missing.is_empty() {
+                        return Err(anyhow!(trf("hash: {} not found", &[&missing.join(", ")])));
                     }
                 }
+                HashAction::Forget(names) => {
+                    let missing: Vec<&str> = names.iter().filter(|name| !executables::forget(name, path)).map(String::as_str).collect();
+                    if !missing.is_empty() {
+                        return Err(anyhow!(trf("hash: {} not found", &[&missing.join(", ")])));
+                    }
+                }
+                HashAction::Clear => executables::forget_all(path),
+            }
+        }
+        Command::Rehash => {
+            let path = state.var("PATH").unwrap_or_default();
+            executables::forget_all(path);
+            executables::rebuild(path);
+        }
+        Command::Reload => {
+            // The language of messages is picked once, at startup
+            let locale = config::get().locale.clone();
+            config::load()?;
+            config::apply_color_mode(None);
+            println!("{} {}", theme::paint("success", tr("Settings reloaded from")), config::rc_path().display());
+            if config::get().locale != locale {
+                println!("{}", tr("The new locale applies to shells started from now on").yellow());
+            }
+        }
+        Command::Doctor => {
+            let problems = doctor::diagnose(state);
+            for problem in &problems {
+                println!("{} {}", theme::paint("error", "✗"), problem.what);
+                println!("  {} {}", tr("Fix:").cyan(), problem.fix);
+            }
+            if !problems.is_empty() {
+                let found = match problems.len() {
+                    1 => tr("1 problem found").to_string(),
+                    count => trf("{} problems found", &[&count]),
+                };
+                println!("{}", found.yellow());
+                return Err(False.into());
+            }
+            println!("{} {}", theme::paint("success", "✓"), tr("No problems found"));
+        }
+        Command::Env(options) => print_env(state, &options)?,
+        Command::Export(pairs) => state.env.extend(pairs),
+        Command::WithEnv(pairs, line) => {
+            let saved: Vec<(String, Option<String>)> = pairs.iter().map(|(name, _)| (name.clone(), state.env.get(name).cloned())).collect();
+            state.env.extend(pairs);
+            let result = Box::pin(handle_new_line(state, &line)).await;
+            for (name, value) in saved {
+                match value {
+                    Some(value) => state.env.insert(name, value),
+                    None => state.env.remove(&name),
+                };
+            }
+            // The inner command is what counts, so `FOO=1 exit` still exits
+            return result;
+        }
+        Command::Unset(names) => {
+            for name in &names {
+                state.env.remove(name);
+            }
+        }
+        Command::Abbr(AbbrAction::List) => {
+            let abbreviations = config::get().abbr.clone();
+            if abbreviations.is_empty() {
+                println!("{}", tr("No abbreviations yet; add one with abbr <name> <expansion>"));
+            }
+            let mut names: Vec<&String> = abbreviations.keys().collect();
+            names.sort();
+            for name in names {
+                println!("abbr {} {}", name.green(), quote(&abbreviations[name]));
+            }
+        }
+        Command::Abbr(AbbrAction::Add(name, expansion)) => {
+            config::set_abbr(&name, Some(&expansion))?;
+            println!("{}", trf("{} now expands to {} (saved in {})", &[&name, &expansion, &config::rc_path().display()]).green());
+        }
+        Command::Abbr(AbbrAction::Erase(names)) => {
+            for name in &names {
+                if !config::get().abbr.contains_key(name) {
+                    return Err(anyhow!(trf("abbr: no abbreviation named '{}'", &[name])));
+                }
+                config::set_abbr(name, None)?;
+            }
+        }
+        Command::Ulimit(UlimitAction::All) => print_limits(state),
+        Command::Ulimit(UlimitAction::Show(resource)) => {
+            println!("{}", limits::format(resource, effective_limit(state, resource)));
+        }
+        Command::Ulimit(UlimitAction::Set(resource, limit)) => set_limit(state, resource, limit)?,
+        Command::Limit(overrides, line) => {
+            let saved = state.limits.clone();
+            let result = async {
+                for (resource, limit) in overrides {
+                    set_limit(state, resource, limit)?;
+                }
+                Box::pin(handle_new_line(state, &line)).await
+            }
+            .await;
+            state.limits = saved;
+            result?;
+        }
+        Command::Nice(increment, line) => {
+            if increment < 0 && unsafe { libc::geteuid() } != 0 {
+                return Err(anyhow!(tr("nice: only root can give a command a higher priority")));
+            }
+            let saved = state.niceness;
+            state.niceness = (saved + increment).clamp(-40, 39);
+            let result = Box::pin(handle_new_line(state, &line)).await;
+            state.niceness = saved;
+            result?;
+        }
+        Command::Renice(priority, targets) => for_each_path(&targets, |target| {
+            let pid: u32 = match target.strip_prefix('%') {
+                Some(number) => {
+                    let job = number.parse().ok().and_then(find_job).ok_or_else(|| anyhow!(trf("renice: no such job {}", &[&target])))?;
+                    // A nohup job or a suspended program is a process of its
+                    // own; other jobs are tasks of the shell's
+                    job.pid.ok_or_else(|| {
+                        anyhow!(trf("renice: job [{}] runs inside the shell; renice the shell ({}) instead", &[&job.id, &std::process::id()]))
+                    })?
+                }
+                None => target.parse()?,
+            };
+            let old = limits::priority(pid).map_err(|e| anyhow!(trf("renice: ({}): {}", &[&pid, &e])))?;
+            limits::set_priority(pid, priority).map_err(|e| anyhow!(trf("renice: ({}): {}", &[&pid, &e])))?;
+            println!("{}", trf("{}: priority {} → {}", &[&pid, &old, &limits::priority(pid)?]));
+            Ok(())
+        })?,
+        Command::Undo(count) => {
+            for _ in 0..count {
+                match journal::undo_last()? {
+                    Some(description) => println!("{} {}", theme::paint("success", tr("Undone:")), description),
+                    None => {
+                        println!("{}", tr("Nothing left to undo.").yellow());
+                        break;
+                    }
+                }
+            }
+        }
+        Command::Tutorial(action) => match action.as_str() {
+            "stop" => {
+                tutorial::stop()?;
+                println!("{}", tr("Tutorial paused. Type 'tutorial' to pick up where you left off.").yellow());
+            }
+            "reset" => {
+                tutorial::reset()?;
+                println!("{}", tr("Tutorial progress cleared.").yellow());
+            }
+            "status" => print!("{}", tutorial::status()),
+            _ => print!("{}", tutorial::start()?),
+        },
+        Command::Practice(action, name) => match (action.as_str(), name) {
+            ("list", _) => {
+                println!("{}", tr("Available exercises:").cyan().bold());
+                print!("{}", practice::list()?);
+            }
+            ("start", Some(name)) => print!("{}", practice::start(&name)?),
+            ("check", _) => print!("{}", practice::check()?),
+            _ => print!("{}", practice::task()?),
+        },
+        Command::Bench(runs, line) => {
+            println!("{}", trf("=== Benchmark: {} ===", &[&line]).bright_yellow().bold());
+            print!("{}", bench::bench(state, runs, &line)?);
+        }
+        Command::History => {
+            let entries = audit::load()?;
+            let width = entries.len().to_string().len();
+            for (number, entry) in entries.iter().enumerate() {
+                println!("{}  {}", format!("{:>width$}", number + 1).bright_black(), entry.command);
             }
         }
-        Command::Ln(target, link_name) => {
-            helpers::ln(&target, &link_name)?;
-            println!("{} '{}' → '{}'", "Created symbolic link:".bright_green(), link_name, target);
+        Command::HistoryScrub(pattern) => {
+            let regex = regex::Regex::new(&pattern).map_err(|e| anyhow!(trf("history scrub: bad pattern: {}", &[&e])))?;
+            let matching = audit::load()?.iter().filter(|entry| regex.is_match(&entry.command)).count();
+            if matching == 0 {
+                println!("{}", tr("No history entries match"));
+            } else if std::io::stdin().is_terminal() && !terminal::confirm(&trf("Remove {} matching entries from the history? [y/N] ", &[&matching])) {
+                println!("{}", tr("The history was left as it is"));
+            } else {
+                let removed = audit::scrub(&regex)?;
+                state.history.retain(|line| !regex.is_match(line));
+                println!("{}", theme::paint("success", &trf("Removed {} entries from {}", &[&removed, &audit::audit_path().display()])));
+            }
+        }
+        Command::Fc(number) => {
+            let entries = audit::load()?;
+            let index = match number {
+                None => entries.len().checked_sub(1),
+                Some(number) if number < 0 => entries.len().checked_sub(number.unsigned_abs() as usize),
+                Some(number) => Some(number as usize - 1),
+            };
+            let Some(entry) = index.and_then(|index| entries.get(index)) else {
+                return Err(match number {
+                    Some(number) => anyhow!(trf("fc: no history entry {}", &[&number])),
+                    None => anyhow!(tr("fc: the history is empty")),
+                });
+            };
+            // Each line is shown as it runs, and what the last one does
+            // counts, so an edited `exit` still exits
+            let mut command = Command::Fc(number);
+            for line in edit_command(state, &entry.command)? {
+                println!("{}", line);
+                state.history.push(line.clone());
+                command = Box::pin(handle_new_line(state, &line)).await?;
+            }
+            return Ok(command);
+        }
+        Command::Choose(options, variable) => {
+            // Cancelling fails quietly, so a script can tell it apart
+            let Some(index) = menu::choose(&options)? else {
+                return Err(False.into());
+            };
+            println!("{}", options[index]);
+            state.env.insert(variable, options[index].clone());
+        }
+        Command::Test(args) => match condition::evaluate(&args, &|path| state.resolve(path))? {
+            true => {}
+            false => return Err(False.into()),
+        },
+        Command::SpinnerStart(message) => status::start_spinner(&message),
+        Command::SpinnerStop => {
+            let Some((message, elapsed)) = status::stop_spinner() else {
+                return Err(anyhow!(tr("spinner: no spinner is running")));
+            };
+            status::clear();
+            println!("{} {} ({})", theme::paint("success", "✔"), message, helpers::human_duration(elapsed));
+        }
+        Command::Progress(current, total, label) => status::set_progress(current, total, label),
+        Command::SecretGet(name) => match keyring::get(&name)? {
+            Some(value) => println!("{}", value),
+            None => return Err(anyhow!(trf("secret: no secret '{}' in the keyring", &[&name]))),
+        },
+        Command::SecretSet(name) => {
+            if name == encryption::KEYRING_ENTRY {
+                return Err(anyhow!(trf("secret: '{}' holds the history key and can't be set by hand", &[&name])));
+            }
+            // Piped input is read as it is, so scripts can store what another program produced
+            let value = if std::io::stdin().is_terminal() {
+                terminal::read_hidden(&trf("Value for {}: ", &[&name]))
+            } else {
+                let mut line = String::new();
+                std::io::stdin().read_line(&mut line)?;
+                Some(line.trim_end_matches(['\n', '\r']).to_string())
+            };
+            let value = value.filter(|value| !value.is_empty()).ok_or_else(|| anyhow!(tr("secret: no value given; nothing was stored")))?;
+            keyring::set(&name, &value)?;
+            println!("{}", theme::paint("success", &trf("Stored {} in the keyring", &[&name])));
+        }
+        Command::Stats => {
+            let entries = audit::load()?;
+            println!("{}", tr("=== Shell Usage Statistics ===").bright_yellow().bold());
+            print!("{}", stats::render(&entries, helpers::terminal_width()));
         }
         _ => {}
     }
     Ok(command)
 }
 
+// When what the shell prints into goes away, as in `shell -c 'cat a.txt' |
+// head -1`, it ends quietly like any program instead of failing to print.
+// Rust ignores SIGPIPE by default; sockets still don't raise it.
+fn end_on_closed_pipe() {
+    unsafe { libc::signal(libc::SIGPIPE, libc::SIG_DFL) };
+}
+
 #[tokio::main]
 async fn main() {
-    // Enable colored output
-    colored::control::set_override(true);
+    let cli = cli::Cli::parse();
+    crash::install();
+    // Without it background jobs print wherever fd 1 and 2 point
+    let _ = output::keep_shell_output();
+
+    if let Some(cli::CliCommand::Completions { shell }) = cli.subcommand {
+        end_on_closed_pipe();
+        cli::print_completions(shell);
+        return;
+    }
+
+    if let Some(path) = cli.config {
+        config::set_rc_path(path);
+    }
+    // Before the settings are read, in case they are still in the old place
+    let moves = dirs::migrate();
+    if let Err(e) = config::load() {
+        eprintln!("{} {}", tr("Warning:").yellow(), e);
+    }
+    config::apply_color_mode(cli.color);
+    i18n::init();
+    for (from, to, moved) in moves {
+        match moved {
+            Ok(()) => eprintln!("{}", trf("Moved {} to {}", &[&from.display(), &to.display()])),
+            Err(e) => eprintln!(
+                "{} {}",
+                tr("Warning:").yellow(),
+                trf("could not move {} to {} ({}); move it yourself to keep it", &[&from.display(), &to.display(), &e])
+            ),
+        }
+    }
+    if let Err(e) = logging::init(cli.log_level) {
+        eprintln!("{} {}", tr("Warning: could not open log file:").yellow(), e);
+    }
+    tracing::debug!(config = %config::rc_path().display(), "shell starting");
+
+    if let Some(name) = cli.theme {
+        if !theme::exists(&name) {
+            eprintln!("{} {}", tr("Shell Error:").bright_red().bold(), trf("Unknown theme '{}'", &[&name]));
+            std::process::exit(2);
+        }
+        config::set_theme(name);
+    }
+
+    let mut state = match ShellState::from_process() {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("{} {}", tr("Shell Error:").bright_red().bold(), e);
+            std::process::exit(2);
+        }
+    };
+
+    // Passphrases are only asked for when someone is there to type one
+    let interactive = cli.command.is_none()
+        && matches!(cli.subcommand, Some(cli::CliCommand::Run { .. }) | None)
+        && std::io::stdin().is_terminal();
+    match encryption::unlock(interactive) {
+        Ok(()) => match audit::encrypt_plain_entries() {
+            Ok(0) => {}
+            Ok(count) => tracing::info!(count, "encrypted plain history entries"),
+            Err(e) => eprintln!("{} {}", tr("Warning: could not encrypt the history:").yellow(), e),
+        },
+        Err(e) if interactive => eprintln!("{} {}", tr("Warning: history is not recorded this session:").yellow(), e),
+        Err(e) => tracing::info!(error = %e, "history locked; not recorded"),
+    }
+
+    let mut demo_path = cli.demo;
+    let mut command_line = cli.command;
+    match cli.subcommand {
+        Some(cli::CliCommand::Run { demo }) => demo_path = demo.or(demo_path),
+        Some(cli::CliCommand::Exec { command }) => command_line = Some(command),
+        Some(cli::CliCommand::Replay { script, stop_on_error }) => {
+            state.options.errexit = stop_on_error;
+            std::process::exit(replay(&mut state, &script).await);
+        }
+        Some(cli::CliCommand::Ssh { host, command }) => {
+            let args: Vec<String> = std::iter::once(host).chain(command).collect();
+            match ssh::run(&state, "ssh", &args) {
+                Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+                Err(e) => {
+                    eprintln!("{} {}", tr("Shell Error:").bright_red().bold(), e);
+                    std::process::exit(2);
+                }
+            }
+        }
+        Some(cli::CliCommand::Completions { .. }) | None => {}
+    }
+
+    // One stage of a pipeline another shell started (see run_pipeline). It
+    // stays out of the history, which has the whole line already.
+    if let Some(line) = cli.stage {
+        end_on_closed_pipe();
+        let code = match handle_new_line(&mut state, &line).await {
+            Ok(_) => 0,
+            Err(e) if e.is::<False>() => 1,
+            Err(e) => {
+                eprintln!("{} {}", theme::paint("error", tr("Error:")), e);
+                1
+            }
+        };
+        jobs::wait_all().await;
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+        std::process::exit(code);
+    }
+
+    if let Some(line) = command_line {
+        end_on_closed_pipe();
+        let code = match process_line(&mut state, &line).await {
+            Outcome::Failure | Outcome::Denied => 1,
+            Outcome::Success | Outcome::Exit => 0,
+        };
+        // A job started by the line, like `serve`, runs until it ends
+        jobs::wait_all().await;
+        std::process::exit(code);
+    }
+
+    let demo_script = match demo_path.map(|path| demo::load_script(&path)).transpose() {
+        Ok(script) => script.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("{} {}", tr("Shell Error:").bright_red().bold(), e);
+            std::process::exit(2);
+        }
+    };
     
+    // Up and Down at the prompt go back through earlier sessions too
+    state.history = audit::begin_session();
+    widgets::start();
+    executables::start(state.var("PATH").unwrap_or_default().to_string());
+    interrupt::install();
+
     // Start the user input handler
-    let user_input_handler = spawn_user_input_handler().await;
+    let user_input_handler = spawn_user_input_handler(state, demo_script).await;
+
+    match user_input_handler {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => {
+            eprintln!("{} {}", tr("Shell Error:").bright_red().bold(), e);
+            crash::report_fatal(&e);
+            std::process::exit(1);
+        }
+        // The panic hook has already restored the terminal and written a report
+        Err(_) => std::process::exit(101),
+    }
+}
+
+// Runs a script non-interactively, echoing each command after the prompt;
+// the exit code is 1 if any command failed
+async fn replay(state: &mut ShellState, path: &str) -> i32 {
+    let script = match demo::load_script(path) {
+        Ok(script) => script,
+        Err(e) => {
+            eprintln!("{} {}", tr("Shell Error:").bright_red().bold(), e);
+            return 2;
+        }
+    };
+
+    let mut code = 0;
+    for line in &script {
+        let branch = Segment::Ready(prompt::git_branch(state).await);
+        match generate_prompt(state, &branch) {
+            Ok(prompt) => println!("{}{}", prompt, line),
+            Err(_) => println!("$ {}", line),
+        }
 
-    if let Ok(Err(e)) = user_input_handler {
-        eprintln!("{} {}", "Shell Error:".bright_red().bold(), e);
+        match process_line(state, line).await {
+            Outcome::Exit => break,
+            Outcome::Failure | Outcome::Denied => {
+                code = 1;
+                if state.options.errexit {
+                    break;
+                }
+            }
+            Outcome::Success => {}
+        }
     }
+    code
 }
@@ -0,0 +1,99 @@
+use std::io::{BufRead, IsTerminal, Write};
+
+use colored::Colorize;
+
+use crate::editor::{self, Key};
+use crate::errors::CrateResult;
+use crate::i18n::{tr, trf};
+use crate::terminal::{self, Keys, RawMode};
+
+// Asks for one of `options`: an arrow-key menu on a terminal, a numbered
+// list and a typed number otherwise. The menu is drawn on stderr so the
+// caller's output can go elsewhere. None when cancelled or at end of input.
+pub fn choose(options: &[String]) -> CrateResult<Option<usize>> {
+    let terminal = std::io::stderr().is_terminal() && std::env::var("TERM").map(|term| term != "dumb").unwrap_or(true);
+    if terminal {
+        if let Some(_raw) = RawMode::enable_for_editing() {
+            return menu(options);
+        }
+    }
+    numbered(options)
+}
+
+fn menu(options: &[String]) -> CrateResult<Option<usize>> {
+    let (width, height) = terminal::window_size().unwrap_or((80, 24));
+    // Room for the hint line and the line the prompt returns to
+    let rows = options.len().min(height.saturating_sub(2).max(1));
+    let mut stderr = std::io::stderr();
+    let (mut selected, mut top) = (0, 0);
+    let mut drawn = false;
+
+    let choice = loop {
+        if selected < top {
+            top = selected;
+        } else if selected >= top + rows {
+            top = selected + 1 - rows;
+        }
+        let mut screen = String::new();
+        if drawn {
+            screen.push_str(&format!("\x1b[{}A", rows + 1));
+        }
+        screen.push_str("\r\x1b[J");
+        for (index, option) in options.iter().enumerate().skip(top).take(rows) {
+            let text: String = option.chars().take(width.saturating_sub(3)).collect();
+            match index == selected {
+                true => screen.push_str(&format!("{} {}\r\n", "❯".cyan().bold(), text.bold())),
+                false => screen.push_str(&format!("  {}\r\n", text)),
+            }
+        }
+        let hint = match options.len() > rows {
+            true => trf("↑/↓ move · Enter choose · q cancel ({} of {})", &[&(selected + 1), &options.len()]),
+            false => tr("↑/↓ move · Enter choose · q cancel").to_string(),
+        };
+        screen.push_str(&format!("{}\r\n", hint.bright_black()));
+        stderr.write_all(screen.as_bytes())?;
+        stderr.flush()?;
+        drawn = true;
+
+        match editor::read_key(&mut Keys)? {
+            None | Some(Key::Char('q') | Key::Control('c') | Key::Control('d')) => break None,
+            Some(Key::Enter) => break Some(selected),
+            Some(Key::Up | Key::Char('k')) => selected = selected.saturating_sub(1),
+            Some(Key::Down | Key::Char('j')) => selected = (selected + 1).min(options.len() - 1),
+            Some(Key::Home) => selected = 0,
+            Some(Key::End) => selected = options.len() - 1,
+            // 1 to 9 pick directly
+            Some(Key::Char(digit @ '1'..='9')) => {
+                let index = digit as usize - '1' as usize;
+                if index < options.len() {
+                    break Some(index);
+                }
+            }
+            Some(_) => {}
+        }
+    };
+
+    // The menu is erased; the caller shows what was chosen
+    write!(stderr, "\x1b[{}A\r\x1b[J", rows + 1)?;
+    stderr.flush()?;
+    Ok(choice)
+}
+
+fn numbered(options: &[String]) -> CrateResult<Option<usize>> {
+    let width = options.len().to_string().len();
+    for (index, option) in options.iter().enumerate() {
+        eprintln!("{:>width$}) {}", index + 1, option);
+    }
+    let mut stdin = std::io::stdin().lock();
+    loop {
+        eprint!("#? ");
+        let mut line = String::new();
+        if stdin.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        match line.trim().parse::<usize>() {
+            Ok(number) if (1..=options.len()).contains(&number) => return Ok(Some(number - 1)),
+            _ => eprintln!("{}", trf("Type a number from 1 to {}", &[&options.len()])),
+        }
+    }
+}
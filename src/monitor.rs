@@ -0,0 +1,283 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use colored::Colorize;
+
+use crate::errors::CrateResult;
+use crate::helpers;
+use crate::i18n::{tr, trf};
+use crate::interrupt;
+use crate::processes::{self, Process};
+use crate::sysinfo;
+use crate::terminal::{self, AlternateScreen, RawMode};
+
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(2);
+const MIN_INTERVAL: Duration = Duration::from_millis(500);
+const MAX_INTERVAL: Duration = Duration::from_secs(10);
+
+// What the process table is ordered by
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Column {
+    Pid,
+    Cpu,
+    Memory,
+    Name,
+}
+
+impl Column {
+    pub fn parse(text: &str) -> Option<Column> {
+        match text {
+            "pid" => Some(Column::Pid),
+            "cpu" => Some(Column::Cpu),
+            "mem" | "memory" => Some(Column::Memory),
+            "name" => Some(Column::Name),
+            _ => None,
+        }
+    }
+
+    fn from_key(key: u8) -> Option<Column> {
+        match key {
+            b'p' => Some(Column::Pid),
+            b'c' => Some(Column::Cpu),
+            b'm' => Some(Column::Memory),
+            b'n' => Some(Column::Name),
+            _ => None,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Column::Pid => tr("pid"),
+            Column::Cpu => tr("cpu"),
+            Column::Memory => tr("memory"),
+            Column::Name => tr("name"),
+        }
+    }
+}
+
+// Everything read at one moment; CPU use is the difference between two
+struct Sample {
+    cpu: CpuTimes,
+    memory: Option<(u64, u64)>,
+    processes: Vec<Process>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct CpuTimes {
+    total: u64,
+    idle: u64,
+    cores: usize,
+}
+
+// One line of the process table
+#[derive(Debug, PartialEq)]
+struct Row {
+    pid: u32,
+    user: String,
+    // Percent of one core, as top counts it
+    cpu: f64,
+    memory: u64,
+    name: String,
+    command: String,
+}
+
+// Takes over the screen until q or Ctrl+C, redrawing every `interval`.
+// c, m, p and n re-sort the table, + and - change the interval.
+pub fn run(mut interval: Duration, mut sort: Column) -> CrateResult<()> {
+    if !terminal::is_ansi_terminal() || !std::io::stdin().is_terminal() {
+        return Err(anyhow!(tr("monitor needs a terminal")));
+    }
+    let _raw = RawMode::enable();
+    let _screen = AlternateScreen::enter();
+
+    let mut before = sample();
+    // The first frame comes quickly, from a short measurement
+    let mut wait = Duration::from_millis(300);
+    let mut frame = None;
+    loop {
+        let key = terminal::poll_key(wait);
+        if interrupt::requested() || matches!(key, Some(b'q' | b'Q')) {
+            break;
+        }
+        match key.and_then(Column::from_key) {
+            Some(column) => sort = column,
+            None => match key {
+                Some(b'+') => interval = (interval * 2).min(MAX_INTERVAL),
+                Some(b'-') => interval = (interval / 2).max(MIN_INTERVAL),
+                _ => {}
+            },
+        }
+
+        // A key only redraws; new numbers come when the interval is up
+        let (cpu, memory, mut rows) = match (key, frame.take()) {
+            (Some(_), Some(frame)) => frame,
+            _ => {
+                let after = sample();
+                let frame = measure(&before, &after);
+                before = after;
+                wait = interval;
+                frame
+            }
+        };
+        sort_rows(&mut rows, sort);
+        draw(cpu, memory, &rows, interval, sort)?;
+        frame = Some((cpu, memory, rows));
+    }
+    Ok(())
+}
+
+fn sample() -> Sample {
+    Sample {
+        cpu: fs::read_to_string("/proc/stat").ok().and_then(|stat| cpu_times(&stat)).unwrap_or_default(),
+        memory: fs::read_to_string("/proc/meminfo").ok().and_then(|meminfo| memory(&meminfo)),
+        processes: processes::all(),
+    }
+}
+
+// (busy percent, cores), (used, total) memory and the process table
+type Frame = ((f64, usize), Option<(u64, u64)>, Vec<Row>);
+
+fn measure(before: &Sample, after: &Sample) -> Frame {
+    let elapsed = after.cpu.total.saturating_sub(before.cpu.total);
+    let idle = after.cpu.idle.saturating_sub(before.cpu.idle);
+    let busy = if elapsed > 0 { 100.0 * elapsed.saturating_sub(idle) as f64 / elapsed as f64 } else { 0.0 };
+    // `elapsed` counts every core's ticks; a process is measured against one
+    let per_core = elapsed as f64 / after.cpu.cores.max(1) as f64;
+
+    let rows = after
+        .processes
+        .iter()
+        .map(|process| {
+            let previous = before.processes.iter().find(|old| old.pid == process.pid).map_or(0, |old| old.cpu_ticks);
+            let ticks = process.cpu_ticks.saturating_sub(previous);
+            Row {
+                pid: process.pid,
+                user: sysinfo::user_name(process.uid),
+                cpu: if per_core > 0.0 { 100.0 * ticks as f64 / per_core } else { 0.0 },
+                memory: process.memory,
+                name: process.name.clone(),
+                command: process.command.clone(),
+            }
+        })
+        .collect();
+    ((busy, after.cpu.cores), after.memory, rows)
+}
+
+fn sort_rows(rows: &mut [Row], sort: Column) {
+    match sort {
+        Column::Pid => rows.sort_by_key(|row| row.pid),
+        Column::Cpu => rows.sort_by(|a, b| b.cpu.total_cmp(&a.cpu).then(b.memory.cmp(&a.memory))),
+        Column::Memory => rows.sort_by_key(|row| std::cmp::Reverse(row.memory)),
+        Column::Name => rows.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()).then(a.pid.cmp(&b.pid))),
+    }
+}
+
+fn draw((busy, cores): (f64, usize), memory: Option<(u64, u64)>, rows: &[Row], interval: Duration, sort: Column) -> CrateResult<()> {
+    let (width, height) = terminal::window_size().unwrap_or((80, 24));
+    let fit = |line: String| line.chars().take(width).collect::<String>();
+    let shell = std::process::id();
+
+    let mut screen = String::from("\x1b[H");
+    let mut line = |text: String| {
+        let _ = write!(screen, "{}\x1b[K\r\n", text);
+    };
+    let seconds = format!("{:.1}s", interval.as_secs_f64());
+    line(fit(trf("monitor: every {}, sorted by {}", &[&seconds, &sort.label()])).bold().to_string());
+    line(format!("{} {} {}", "CPU".cyan(), bar(busy / 100.0), trf("{}% of {} cores", &[&format!("{:5.1}", busy), &cores])));
+    match memory {
+        Some((used, total)) => line(format!(
+            "{} {} {}",
+            "Mem".cyan(),
+            bar(used as f64 / total.max(1) as f64),
+            trf("{} of {}", &[&helpers::human_size(used), &helpers::human_size(total)])
+        )),
+        None => line(String::new()),
+    }
+    line(format!("{} {}   {} {}", tr("Load").cyan(), sysinfo::load_average().unwrap_or_default(), tr("Tasks").cyan(), rows.len()));
+    line(String::new());
+    line(fit(format!("{:>7} {:<10} {:>6} {:>9}  {:<16} {}", "PID", tr("USER"), "CPU%", "MEM", tr("NAME"), tr("COMMAND"))).reversed().to_string());
+
+    // Six lines of header, then a gap above the help line
+    for row in rows.iter().take(height.saturating_sub(8)) {
+        let text = fit(format!(
+            "{:>7} {:<10} {:>6.1} {:>9}  {:<16} {}",
+            row.pid,
+            row.user.chars().take(10).collect::<String>(),
+            row.cpu,
+            helpers::human_size(row.memory),
+            row.name.chars().take(16).collect::<String>(),
+            row.command
+        ));
+        line(if row.pid == shell { text.green().to_string() } else { text });
+    }
+    screen.push_str("\x1b[J");
+    let help = fit(tr("q quit · c/m/p/n sort by cpu, memory, pid, name · +/- refresh interval").to_string());
+    let _ = write!(screen, "\x1b[{};1H{}", height, help.bright_black());
+
+    let mut stdout = std::io::stdout();
+    stdout.write_all(screen.as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
+
+fn bar(fraction: f64) -> String {
+    const WIDTH: usize = 20;
+    let filled = ((fraction.clamp(0.0, 1.0) * WIDTH as f64).round() as usize).min(WIDTH);
+    let used = "#".repeat(filled);
+    let color = match fraction {
+        f if f > 0.9 => used.red(),
+        f if f > 0.6 => used.yellow(),
+        _ => used.green(),
+    };
+    format!("[{}{}]", color, ".".repeat(WIDTH - filled).bright_black())
+}
+
+// The `cpu` line of /proc/stat: ticks spent in each state since boot, and
+// one `cpuN` line per core
+fn cpu_times(stat: &str) -> Option<CpuTimes> {
+    let line = stat.lines().find(|line| line.starts_with("cpu "))?;
+    let ticks: Vec<u64> = line.split_whitespace().skip(1).filter_map(|field| field.parse().ok()).collect();
+    // Guest time is already counted in user time
+    let total = ticks.iter().take(8).sum();
+    // idle plus iowait
+    let idle = ticks.get(3)? + ticks.get(4).unwrap_or(&0);
+    let cores = stat.lines().filter(|line| line.starts_with("cpu") && !line.starts_with("cpu ")).count();
+    Some(CpuTimes { total, idle, cores })
+}
+
+// Used and total memory in bytes; what the kernel can hand out without
+// swapping (MemAvailable) counts as free
+fn memory(meminfo: &str) -> Option<(u64, u64)> {
+    let field = |name: &str| -> Option<u64> {
+        let line = meminfo.lines().find(|line| line.starts_with(name))?;
+        Some(line.split_whitespace().nth(1)?.parse::<u64>().ok()? * 1024)
+    };
+    let total = field("MemTotal:")?;
+    Some((total.saturating_sub(field("MemAvailable:")?), total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_cpu_and_memory() {
+        let stat = "cpu  100 0 50 800 50 0 0 0 0 0\ncpu0 50 0 25 400 25 0 0 0 0 0\ncpu1 50 0 25 400 25 0 0 0 0 0\nintr 1\n";
+        assert_eq!(cpu_times(stat), Some(CpuTimes { total: 1000, idle: 850, cores: 2 }));
+        let meminfo = "MemTotal:       16000 kB\nMemFree:         1000 kB\nMemAvailable:    4000 kB\n";
+        assert_eq!(memory(meminfo), Some((12000 * 1024, 16000 * 1024)));
+    }
+
+    #[test]
+    fn cpu_is_measured_against_one_core() {
+        let process = |ticks| Process { pid: 7, ppid: 1, name: "busy".into(), uid: 0, state: 'R', cpu_ticks: ticks, memory: 0, command: String::new() };
+        let before = Sample { cpu: CpuTimes { total: 0, idle: 0, cores: 2 }, memory: None, processes: vec![process(0)] };
+        let after = Sample { cpu: CpuTimes { total: 200, idle: 100, cores: 2 }, memory: None, processes: vec![process(100)] };
+        let ((busy, cores), _, rows) = measure(&before, &after);
+        assert_eq!((busy, cores), (50.0, 2));
+        assert_eq!(rows[0].cpu, 100.0);
+    }
+}
@@ -0,0 +1,207 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::fd::AsFd;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+use crate::helpers;
+use crate::terminal;
+
+// The line being typed at the prompt. Background jobs print above it and
+// it is drawn again underneath, as zsh does, instead of being written into
+// the middle of it.
+struct Edit {
+    prompt: String,
+    text: String,
+    // Characters between the cursor and the end of the text
+    back: usize,
+}
+
+static EDIT: Mutex<Option<Edit>> = Mutex::new(None);
+// The shell's own output and errors, kept from startup. A redirection points
+// fd 1 and 2 at its file while the command runs, and background jobs must
+// not print into it.
+static SHELL_OUTPUT: OnceLock<(File, File)> = OnceLock::new();
+// Notices held back while a command runs, for the next prompt
+static NOTICES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+pub fn keep_shell_output() -> io::Result<()> {
+    let output = File::from(io::stdout().as_fd().try_clone_to_owned()?);
+    let errors = File::from(io::stderr().as_fd().try_clone_to_owned()?);
+    let _ = SHELL_OUTPUT.set((output, errors));
+    Ok(())
+}
+
+fn edit() -> MutexGuard<'static, Option<Edit>> {
+    EDIT.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// Held by the line editor while a line is typed on the terminal
+pub struct Editing;
+
+impl Editing {
+    pub fn start(prompt: &str) -> Editing {
+        let mut edit = edit();
+        // Anything that came in since the prompt loop looked
+        print_notices();
+        *edit = Some(Edit { prompt: prompt.to_string(), text: String::new(), back: 0 });
+        Editing
+    }
+
+    // The prompt as it is on screen now, which changes when late segments
+    // arrive or the window is resized
+    pub fn prompt(&self) -> String {
+        edit().as_ref().map(|edit| edit.prompt.clone()).unwrap_or_default()
+    }
+
+    // Writes what the editor drew and remembers the line it now shows
+    pub fn write(&self, out: &str, text: &str, back: usize) -> io::Result<()> {
+        let mut edit = edit();
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(out.as_bytes())?;
+        stdout.flush()?;
+        if let Some(edit) = edit.as_mut() {
+            edit.text = text.to_string();
+            edit.back = back;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Editing {
+    fn drop(&mut self) {
+        *edit() = None;
+    }
+}
+
+// Rewrites the first line of the prompt, for segments that arrive or change
+// while a line is typed
+pub fn redraw_prompt(prompt: &str) -> io::Result<()> {
+    let mut edit = edit();
+    let first_line = prompt.split('\n').next().unwrap_or_default();
+    let mut stdout = io::stdout().lock();
+    // It sits right above the line being typed, which may have wrapped
+    let rows = edit.as_ref().map(|edit| rows_of_last_line(edit) + 1).unwrap_or(1);
+    stdout.write_all(terminal::redraw_line_above(first_line, rows).as_bytes())?;
+    stdout.flush()?;
+    if let Some(edit) = edit.as_mut() {
+        edit.prompt = prompt.to_string();
+    }
+    Ok(())
+}
+
+// Draws `prompt`, laid out for a new window size, and the typed text again.
+// Terminals rewrap what is on screen to the new width, so the old prompt
+// takes as many rows as it would at the new width.
+pub fn redraw_resized(prompt: &str) -> io::Result<()> {
+    let mut edit = edit();
+    let Some(edit) = edit.as_mut() else {
+        return Ok(());
+    };
+    let mut stdout = io::stdout().lock();
+    erase(edit, &mut stdout)?;
+    edit.prompt = prompt.to_string();
+    draw(edit, &mut stdout)
+}
+
+// For output from background jobs: printed as usual, or above the prompt
+// while a line is typed
+pub fn println(line: &str) {
+    emit(line, false);
+}
+
+pub fn eprintln(line: &str) {
+    emit(line, true);
+}
+
+// For news like a finished job: shown at once while the shell waits at the
+// prompt, or before the next prompt while a command runs
+pub fn notify(line: &str) {
+    let edit = edit();
+    match edit.as_ref() {
+        Some(edit) => above(edit, line, false),
+        None => NOTICES.lock().unwrap_or_else(|e| e.into_inner()).push(line.to_string()),
+    }
+}
+
+// Prints the notices held back, before a prompt is drawn
+pub fn print_notices() {
+    for notice in std::mem::take(&mut *NOTICES.lock().unwrap_or_else(|e| e.into_inner())) {
+        println!("{}", notice);
+    }
+}
+
+fn emit(line: &str, error: bool) {
+    let edit = edit();
+    let Some(edit) = edit.as_ref() else {
+        match (SHELL_OUTPUT.get(), error) {
+            (Some((_, errors)), true) => {
+                let _ = writeln!(&*errors, "{}", line);
+            }
+            (Some((output, _)), false) => {
+                let _ = writeln!(&*output, "{}", line);
+            }
+            (None, true) => eprintln!("{}", line),
+            (None, false) => println!("{}", line),
+        }
+        return;
+    };
+
+    above(edit, line, error);
+}
+
+// Prints `line` where the prompt was and draws the prompt and the typed
+// text again below it
+fn above(edit: &Edit, line: &str, error: bool) {
+    let mut stdout = io::stdout().lock();
+    let _ = erase(edit, &mut stdout);
+    match error {
+        true => eprintln!("{}", line),
+        false => {
+            let _ = writeln!(stdout, "{}", line);
+        }
+    }
+    let _ = draw(edit, &mut stdout);
+}
+
+// Clears from the top of the prompt down, leaving the cursor there
+fn erase(edit: &Edit, out: &mut impl Write) -> io::Result<()> {
+    let rows = rows_above_cursor(edit);
+    let up = if rows > 0 { format!("\x1b[{}A", rows) } else { String::new() };
+    write!(out, "\r{}\x1b[J", up)?;
+    out.flush()
+}
+
+// Prints the prompt and the typed text with the cursor where it was
+fn draw(edit: &Edit, out: &mut impl Write) -> io::Result<()> {
+    let columns = helpers::terminal_width();
+    let end = last_line_width(edit) + edit.text.chars().count();
+    write!(out, "{}{}", edit.prompt, edit.text)?;
+    if edit.back > 0 {
+        write!(out, "{}", terminal::cursor_motion(terminal::written_to(end, columns), end - edit.back, columns))?;
+    }
+    out.flush()
+}
+
+// Rows between the top of the prompt and the cursor, wrapped lines included
+fn rows_above_cursor(edit: &Edit) -> usize {
+    let columns = helpers::terminal_width().max(1);
+    let lines: Vec<&str> = edit.prompt.split('\n').collect();
+    let above: usize = lines[..lines.len() - 1].iter().map(|line| terminal::visible_width(line).max(1).div_ceil(columns)).sum();
+    above + rows_of_last_line(edit)
+}
+
+// Rows from the start of the prompt's last line down to the cursor, as the
+// typed text wraps
+fn rows_of_last_line(edit: &Edit) -> usize {
+    let columns = helpers::terminal_width().max(1);
+    let column = last_line_width(edit) + edit.text.chars().count() - edit.back;
+    // The editor keeps the cursor at the end of the text where writing left it
+    match edit.back {
+        0 => terminal::written_to(column, columns) / columns,
+        _ => column / columns,
+    }
+}
+
+fn last_line_width(edit: &Edit) -> usize {
+    terminal::visible_width(edit.prompt.rsplit('\n').next().unwrap_or_default())
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::sysinfo;
+
+// A socket waiting for connections (TCP) or datagrams (UDP)
+#[derive(Debug, PartialEq)]
+pub struct Listener {
+    pub protocol: &'static str,
+    pub address: IpAddr,
+    pub port: u16,
+    // Pid and name of the process holding it; None when /proc doesn't let us
+    // see it, usually because another user owns it
+    pub process: Option<(u32, String)>,
+    inode: u64,
+}
+
+// Listening sockets from /proc/net, sorted by port
+pub fn listeners() -> Vec<Listener> {
+    let mut listeners = Vec::new();
+    // TCP_LISTEN is 0A; a bound UDP socket shows as TCP_CLOSE, 07
+    for (protocol, file, state) in [("tcp", "tcp", "0A"), ("tcp", "tcp6", "0A"), ("udp", "udp", "07"), ("udp", "udp6", "07")] {
+        let Ok(table) = fs::read_to_string(format!("/proc/net/{}", file)) else {
+            continue;
+        };
+        listeners.extend(table.lines().skip(1).filter_map(|line| parse_line(protocol, state, line)));
+    }
+
+    let owners = socket_owners();
+    for listener in &mut listeners {
+        listener.process = owners.get(&listener.inode).map(|&pid| (pid, sysinfo::process_name(pid).unwrap_or_default()));
+    }
+    listeners.sort_by(|a, b| (a.port, a.protocol, a.address).cmp(&(b.port, b.protocol, b.address)));
+    listeners
+}
+
+// One row of /proc/net/{tcp,udp}[6] if the socket is in `state`
+fn parse_line(protocol: &'static str, state: &str, line: &str) -> Option<Listener> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.get(3) != Some(&state) {
+        return None;
+    }
+    let (address, port) = fields.get(1)?.split_once(':')?;
+    let inode = fields.get(9)?.parse().ok()?;
+    // UDP sockets connected to a peer aren't listening
+    if protocol == "udp" && !fields.get(2)?.trim_start_matches('0').starts_with(':') {
+        return None;
+    }
+    Some(Listener { protocol, address: parse_address(address)?, port: u16::from_str_radix(port, 16).ok()?, process: None, inode })
+}
+
+// Addresses are written as 32-bit words in the kernel's byte order
+fn parse_address(hex: &str) -> Option<IpAddr> {
+    let mut bytes = Vec::with_capacity(16);
+    for word in hex.as_bytes().chunks(8) {
+        let word = u32::from_str_radix(std::str::from_utf8(word).ok()?, 16).ok()?;
+        bytes.extend(word.to_ne_bytes());
+    }
+    match bytes.len() {
+        4 => Some(IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(bytes).ok()?))),
+        16 => Some(IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?))),
+        _ => None,
+    }
+}
+
+// Socket inode → pid, from the /proc/<pid>/fd links we are allowed to read
+fn socket_owners() -> HashMap<u64, u32> {
+    let mut owners = HashMap::new();
+    let Ok(processes) = fs::read_dir("/proc") else {
+        return owners;
+    };
+    for process in processes.flatten() {
+        let Some(pid) = process.file_name().to_str().and_then(|name| name.parse().ok()) else {
+            continue;
+        };
+        let Ok(fds) = fs::read_dir(process.path().join("fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            let Ok(target) = fs::read_link(fd.path()) else {
+                continue;
+            };
+            let inode = target.to_str().and_then(|target| target.strip_prefix("socket:[")?.strip_suffix(']')?.parse().ok());
+            if let Some(inode) = inode {
+                owners.entry(inode).or_insert(pid);
+            }
+        }
+    }
+    owners
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_proc_net_rows() {
+        let tcp = "   0: 0100007F:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 4242 1 0000000000000000 100 0 0 10 0";
+        let listener = parse_line("tcp", "0A", tcp).unwrap();
+        assert_eq!((listener.address, listener.port, listener.inode), ("127.0.0.1".parse().unwrap(), 8080, 4242));
+
+        let established = tcp.replacen(" 0A ", " 01 ", 1);
+        assert_eq!(parse_line("tcp", "0A", &established), None);
+
+        let udp6 = "  12: 00000000000000000000000001000000:0035 00000000000000000000000000000000:0000 07 00000000:00000000 00:00000000 00000000   101        0 777 2 0000000000000000 0";
+        let listener = parse_line("udp", "07", udp6).unwrap();
+        assert_eq!((listener.address, listener.port), ("::1".parse().unwrap(), 53));
+    }
+}
@@ -0,0 +1,220 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use colored::*;
+use serde::Deserialize;
+
+use crate::dirs;
+use crate::errors::CrateResult;
+use crate::i18n::{tr, trf};
+
+const ACTIVE_FILE: &str = "practice";
+const SANDBOX_DIR: &str = "shellbasics_practice";
+// Instructors can drop their own scenario files here
+const USER_SCENARIO_DIR: &str = "scenarios";
+
+const BUILTIN_SCENARIOS: [&str; 2] = [
+    include_str!("../scenarios/scattered-logs.toml"),
+    include_str!("../scenarios/broken-files.toml"),
+];
+
+#[derive(Debug, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    pub title: String,
+    pub description: String,
+    #[serde(default)]
+    files: Vec<FileSpec>,
+    #[serde(default)]
+    dirs: Vec<String>,
+    #[serde(default)]
+    checks: Vec<Check>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FileSpec {
+    path: String,
+    #[serde(default)]
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "check", rename_all = "snake_case")]
+enum Check {
+    Exists { path: String },
+    Missing { path: String },
+    File { path: String },
+    Dir { path: String },
+    Empty { path: String },
+    Contains { path: String, text: String },
+    NotContains { path: String, text: String },
+    FileCount { dir: String, extension: String, count: usize },
+}
+
+impl Check {
+    fn describe(&self) -> String {
+        match self {
+            Check::Exists { path } => trf("'{}' exists", &[path]),
+            Check::Missing { path } => trf("'{}' is gone", &[path]),
+            Check::File { path } => trf("'{}' is a file", &[path]),
+            Check::Dir { path } => trf("'{}' is a directory", &[path]),
+            Check::Empty { path } => trf("'{}' is empty", &[path]),
+            Check::Contains { path, text } => trf("'{}' contains \"{}\"", &[path, text]),
+            Check::NotContains { path, text } => trf("'{}' does not contain \"{}\"", &[path, text]),
+            Check::FileCount { dir, extension, count } => {
+                trf("'{}' holds {} .{} file(s)", &[dir, count, extension])
+            }
+        }
+    }
+
+    fn passes(&self, sandbox: &Path) -> bool {
+        let read = |path: &str| fs::read_to_string(sandbox.join(path)).ok();
+
+        match self {
+            Check::Exists { path } => sandbox.join(path).exists(),
+            Check::Missing { path } => !sandbox.join(path).exists(),
+            Check::File { path } => sandbox.join(path).is_file(),
+            Check::Dir { path } => sandbox.join(path).is_dir(),
+            Check::Empty { path } => {
+                let path = sandbox.join(path);
+                if path.is_dir() {
+                    fs::read_dir(path).map(|mut entries| entries.next().is_none()).unwrap_or(false)
+                } else {
+                    fs::metadata(path).map(|m| m.len() == 0).unwrap_or(false)
+                }
+            }
+            Check::Contains { path, text } => read(path).map(|c| c.contains(text.as_str())).unwrap_or(false),
+            Check::NotContains { path, text } => read(path).map(|c| !c.contains(text.as_str())).unwrap_or(false),
+            Check::FileCount { dir, extension, count } => fs::read_dir(sandbox.join(dir))
+                .map(|entries| {
+                    entries
+                        .filter_map(Result::ok)
+                        .filter(|e| e.path().extension().map(|ext| ext == extension.as_str()).unwrap_or(false))
+                        .count()
+                        == *count
+                })
+                .unwrap_or(false),
+        }
+    }
+}
+
+fn home() -> PathBuf {
+    PathBuf::from(std::env::var("HOME").unwrap_or_else(|_| ".".to_string()))
+}
+
+fn sandbox(name: &str) -> PathBuf {
+    home().join(SANDBOX_DIR).join(name)
+}
+
+pub fn scenarios() -> CrateResult<Vec<Scenario>> {
+    let mut scenarios = Vec::new();
+    for source in BUILTIN_SCENARIOS {
+        scenarios.push(toml::from_str(source)?);
+    }
+
+    let user_dir = dirs::config_dir().join(USER_SCENARIO_DIR);
+    if user_dir.is_dir() {
+        for entry in fs::read_dir(&user_dir)? {
+            let path = entry?.path();
+            if path.extension().map(|ext| ext == "toml").unwrap_or(false) {
+                let scenario = toml::from_str(&fs::read_to_string(&path)?)
+                    .map_err(|e| anyhow!(trf("Invalid scenario {}: {}", &[&path.display(), &e])))?;
+                scenarios.push(scenario);
+            }
+        }
+    }
+
+    Ok(scenarios)
+}
+
+fn find(name: &str) -> CrateResult<Scenario> {
+    scenarios()?
+        .into_iter()
+        .find(|s| s.name == name)
+        .ok_or_else(|| anyhow!(trf("No scenario named '{}'. Try 'practice list'.", &[&name])))
+}
+
+fn active() -> CrateResult<Scenario> {
+    let name = fs::read_to_string(dirs::state_dir().join(ACTIVE_FILE)).unwrap_or_default();
+    if name.trim().is_empty() {
+        return Err(anyhow!(tr("No exercise in progress. Start one with 'practice start <name>'.")));
+    }
+    find(name.trim())
+}
+
+fn describe(scenario: &Scenario) -> String {
+    format!(
+        "{} {}\n{}\n{} {}\n",
+        trf("Exercise '{}':", &[&scenario.name]).bright_yellow().bold(),
+        scenario.title.cyan().bold(),
+        scenario.description.trim_end(),
+        tr("Sandbox:").bright_black(),
+        sandbox(&scenario.name).display()
+    )
+}
+
+pub fn list() -> CrateResult<String> {
+    let mut result = String::new();
+    for scenario in scenarios()? {
+        result.push_str(&format!("  {} - {}\n", scenario.name.green(), scenario.title));
+    }
+    Ok(result)
+}
+
+// Recreates the sandbox from scratch so an exercise can always be retried
+pub fn start(name: &str) -> CrateResult<String> {
+    let scenario = find(name)?;
+    let dir = sandbox(&scenario.name);
+
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(&dir)?;
+
+    for spec in &scenario.dirs {
+        fs::create_dir_all(dir.join(spec))?;
+    }
+    for spec in &scenario.files {
+        let path = dir.join(&spec.path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, &spec.content)?;
+    }
+
+    fs::write(dirs::state_dir().join(ACTIVE_FILE), &scenario.name)?;
+    Ok(describe(&scenario))
+}
+
+pub fn task() -> CrateResult<String> {
+    Ok(describe(&active()?))
+}
+
+pub fn check() -> CrateResult<String> {
+    let scenario = active()?;
+    let dir = sandbox(&scenario.name);
+    let mut result = String::new();
+    let mut passed = 0;
+
+    for check in &scenario.checks {
+        if check.passes(&dir) {
+            passed += 1;
+            result.push_str(&format!("  {} {}\n", "✔".green(), check.describe()));
+        } else {
+            result.push_str(&format!("  {} {}\n", "✘".red(), check.describe()));
+        }
+    }
+
+    if passed == scenario.checks.len() {
+        result.push_str(&format!("{}\n", tr("All checks passed. Exercise complete!").bright_green().bold()));
+        fs::remove_file(dirs::state_dir().join(ACTIVE_FILE))?;
+    } else {
+        result.push_str(&format!(
+            "{}\n",
+            trf("{} of {} checks passed. Keep going!", &[&passed, &scenario.checks.len()]).yellow()
+        ));
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,381 @@
+use anyhow::anyhow;
+
+use crate::errors::CrateResult;
+use crate::i18n::trf;
+
+// Interprets backslash escapes as `echo -e` does: \n \t \\ \a \b \e \f \r \v,
+// \xHH, \0NNN (octal) and \c, which ends the output. Returns the text and
+// whether \c was seen.
+pub fn unescape(text: &str) -> (String, bool) {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('\\') => result.push('\\'),
+            Some('a') => result.push('\x07'),
+            Some('b') => result.push('\x08'),
+            Some('e') | Some('E') => result.push('\x1b'),
+            Some('f') => result.push('\x0c'),
+            Some('r') => result.push('\r'),
+            Some('v') => result.push('\x0b'),
+            Some('c') => return (result, true),
+            Some('x') => {
+                let digits = take_while_max(&mut chars, 2, |c| c.is_ascii_hexdigit());
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(byte) => result.push(byte as char),
+                    // \x without digits stays as typed
+                    Err(_) => result.push_str("\\x"),
+                }
+            }
+            Some('0') => {
+                let digits = take_while_max(&mut chars, 3, |c| ('0'..='7').contains(&c));
+                result.push(u8::from_str_radix(&digits, 8).unwrap_or(0) as char);
+            }
+            Some(other) => {
+                result.push('\\');
+                result.push(other);
+            }
+            None => result.push('\\'),
+        }
+    }
+
+    (result, false)
+}
+
+fn take_while_max(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    max: usize,
+    accept: impl Fn(char) -> bool,
+) -> String {
+    let mut taken = String::new();
+    while taken.len() < max {
+        match chars.peek() {
+            Some(&c) if accept(c) => {
+                taken.push(c);
+                chars.next();
+            }
+            _ => break,
+        }
+    }
+    taken
+}
+
+// One %-conversion of a printf format
+struct Spec {
+    left: bool,
+    zero: bool,
+    plus: bool,
+    space: bool,
+    alternate: bool,
+    width: usize,
+    precision: Option<usize>,
+    conversion: char,
+}
+
+// Formats `args` with a C-style `format`: %s %b %c %d %i %u %o %x %X %e %f %g
+// and %%, with flags (-, 0, +, space, #), width and precision, where * takes
+// the value from the arguments. Like bash, the format is reused while
+// arguments remain, and missing arguments count as empty or zero.
+pub fn format(format: &str, args: &[String]) -> CrateResult<String> {
+    // \c in the format ends it, like everywhere else
+    let (format, stop) = unescape(format);
+    let mut output = String::new();
+    let mut args = args.iter().map(String::as_str);
+
+    loop {
+        let mut chars = format.chars().peekable();
+        let mut used = 0;
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                output.push(c);
+                continue;
+            }
+            if chars.peek() == Some(&'%') {
+                chars.next();
+                output.push('%');
+                continue;
+            }
+
+            let mut spec = Spec {
+                left: false,
+                zero: false,
+                plus: false,
+                space: false,
+                alternate: false,
+                width: 0,
+                precision: None,
+                conversion: 's',
+            };
+            while let Some(&flag) = chars.peek() {
+                match flag {
+                    '-' => spec.left = true,
+                    '0' => spec.zero = true,
+                    '+' => spec.plus = true,
+                    ' ' => spec.space = true,
+                    '#' => spec.alternate = true,
+                    _ => break,
+                }
+                chars.next();
+            }
+
+            if chars.peek() == Some(&'*') {
+                chars.next();
+                used += 1;
+                let width = integer(args.next().unwrap_or(""))?;
+                spec.left |= width < 0;
+                spec.width = width.unsigned_abs() as usize;
+            } else {
+                spec.width = take_while_max(&mut chars, usize::MAX, |c| c.is_ascii_digit()).parse().unwrap_or(0);
+            }
+
+            if chars.peek() == Some(&'.') {
+                chars.next();
+                if chars.peek() == Some(&'*') {
+                    chars.next();
+                    used += 1;
+                    spec.precision = Some(integer(args.next().unwrap_or(""))?.max(0) as usize);
+                } else {
+                    let digits = take_while_max(&mut chars, usize::MAX, |c| c.is_ascii_digit());
+                    spec.precision = Some(digits.parse().unwrap_or(0));
+                }
+            }
+
+            let Some(conversion) = chars.next() else {
+                return Err(anyhow!(trf("printf: '{}': missing conversion at the end of the format", &[&format])));
+            };
+            spec.conversion = conversion;
+
+            used += 1;
+            let arg = args.next().unwrap_or("");
+            match convert(&spec, arg)? {
+                Conversion::Text(text) => output.push_str(&text),
+                // %b with \c stops all output
+                Conversion::Stop(text) => {
+                    output.push_str(&text);
+                    return Ok(output);
+                }
+            }
+        }
+
+        // Reuse the format for leftover arguments, but only if it takes any
+        if stop || used == 0 || args.clone().next().is_none() {
+            break;
+        }
+    }
+
+    Ok(output)
+}
+
+enum Conversion {
+    Text(String),
+    Stop(String),
+}
+
+fn convert(spec: &Spec, arg: &str) -> CrateResult<Conversion> {
+    let text = match spec.conversion {
+        's' => {
+            let text: String = match spec.precision {
+                Some(precision) => arg.chars().take(precision).collect(),
+                None => arg.to_string(),
+            };
+            pad(spec, text, false)
+        }
+        'b' => {
+            let (text, stop) = unescape(arg);
+            let text = pad(spec, text, false);
+            return Ok(if stop { Conversion::Stop(text) } else { Conversion::Text(text) });
+        }
+        'c' => pad(spec, arg.chars().next().map(String::from).unwrap_or_default(), false),
+        'd' | 'i' => {
+            let value = integer(arg)?;
+            let digits = with_precision(spec, value.unsigned_abs().to_string());
+            pad(spec, signed(spec, value < 0, digits), true)
+        }
+        'u' | 'o' | 'x' | 'X' => {
+            let value = integer(arg)? as u64;
+            let (digits, prefix) = match spec.conversion {
+                'o' => (format!("{:o}", value), "0"),
+                'x' => (format!("{:x}", value), "0x"),
+                'X' => (format!("{:X}", value), "0X"),
+                _ => (value.to_string(), ""),
+            };
+            let digits = with_precision(spec, digits);
+            let prefix = if spec.alternate && value != 0 { prefix } else { "" };
+            pad(spec, format!("{}{}", prefix, digits), true)
+        }
+        'f' | 'F' | 'e' | 'E' | 'g' | 'G' => {
+            let value = float(arg)?;
+            let precision = spec.precision.unwrap_or(6);
+            let digits = match spec.conversion {
+                'f' | 'F' => format!("{:.*}", precision, value.abs()),
+                'e' => exponent(value.abs(), precision),
+                'E' => exponent(value.abs(), precision).to_uppercase(),
+                'g' => general(value.abs(), precision),
+                _ => general(value.abs(), precision).to_uppercase(),
+            };
+            pad(spec, signed(spec, value.is_sign_negative() && value != 0.0, digits), true)
+        }
+        other => return Err(anyhow!(trf("printf: %{}: unsupported conversion", &[&other]))),
+    };
+    Ok(Conversion::Text(text))
+}
+
+fn integer(arg: &str) -> CrateResult<i64> {
+    let arg = arg.trim();
+    if arg.is_empty() {
+        return Ok(0);
+    }
+    // 'A is the character code of A, as in C printf
+    if let Some(quoted) = arg.strip_prefix('\'').or_else(|| arg.strip_prefix('"')) {
+        return Ok(quoted.chars().next().map(|c| c as i64).unwrap_or(0));
+    }
+
+    let (negative, digits) = match arg.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, arg.strip_prefix('+').unwrap_or(arg)),
+    };
+    let parsed = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        i64::from_str_radix(&digits[1..], 8)
+    } else {
+        digits.parse()
+    };
+
+    match parsed {
+        Ok(value) if negative => Ok(-value),
+        Ok(value) => Ok(value),
+        Err(_) => Err(anyhow!(trf("printf: '{}': expected a number", &[&arg]))),
+    }
+}
+
+fn float(arg: &str) -> CrateResult<f64> {
+    let arg = arg.trim();
+    if arg.is_empty() {
+        return Ok(0.0);
+    }
+    arg.parse().or_else(|_| integer(arg).map(|value| value as f64))
+}
+
+// C's %e: one digit before the point and at least two exponent digits
+fn exponent(value: f64, precision: usize) -> String {
+    let formatted = format!("{:.*e}", precision, value);
+    let (mantissa, exp) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+    let exp: i32 = exp.parse().unwrap_or(0);
+    format!("{}e{}{:02}", mantissa, if exp < 0 { '-' } else { '+' }, exp.abs())
+}
+
+// C's %g: %e or %f, whichever is shorter for the value, without trailing zeros
+fn general(value: f64, precision: usize) -> String {
+    let precision = precision.max(1);
+    if value == 0.0 {
+        return "0".to_string();
+    }
+
+    let exp = value.abs().log10().floor() as i32;
+    if exp < -4 || exp >= precision as i32 {
+        let formatted = exponent(value, precision - 1);
+        let (mantissa, exp) = formatted.split_once('e').unwrap_or((&formatted, ""));
+        format!("{}e{}", trim_fraction(mantissa), exp)
+    } else {
+        let decimals = (precision as i32 - 1 - exp).max(0) as usize;
+        trim_fraction(&format!("{:.*}", decimals, value))
+    }
+}
+
+fn trim_fraction(number: &str) -> String {
+    if number.contains('.') {
+        number.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        number.to_string()
+    }
+}
+
+// Integers with a precision get at least that many digits
+fn with_precision(spec: &Spec, digits: String) -> String {
+    match spec.precision {
+        Some(precision) if digits.len() < precision => format!("{}{}", "0".repeat(precision - digits.len()), digits),
+        _ => digits,
+    }
+}
+
+fn signed(spec: &Spec, negative: bool, digits: String) -> String {
+    let sign = if negative {
+        "-"
+    } else if spec.plus {
+        "+"
+    } else if spec.space {
+        " "
+    } else {
+        ""
+    };
+    format!("{}{}", sign, digits)
+}
+
+fn pad(spec: &Spec, text: String, numeric: bool) -> String {
+    let length = text.chars().count();
+    if length >= spec.width {
+        return text;
+    }
+
+    let fill = spec.width - length;
+    if spec.left {
+        format!("{}{}", text, " ".repeat(fill))
+    } else if spec.zero && numeric && !(spec.precision.is_some() && "diuoxX".contains(spec.conversion)) {
+        // Zeros go after the sign or 0x prefix
+        let split = text
+            .find(|c: char| c.is_ascii_digit())
+            .map(|index| if text[index..].starts_with("0x") || text[index..].starts_with("0X") { index + 2 } else { index })
+            .unwrap_or(0);
+        format!("{}{}{}", &text[..split], "0".repeat(fill), &text[split..])
+    } else {
+        format!("{}{}", " ".repeat(fill), text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn printf(format_string: &str, args: &[&str]) -> String {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        format(format_string, &args).unwrap()
+    }
+
+    #[test]
+    fn echo_escapes() {
+        assert_eq!(unescape(r"a\tb\n\x1b[0m\0101"), ("a\tb\n\x1b[0mA".to_string(), false));
+        assert_eq!(unescape(r"stop\chere"), ("stop".to_string(), true));
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(printf("%s=%d\\n", &["x", "42"]), "x=42\n");
+        assert_eq!(printf("[%5s|%-5s]", &["ab", "cd"]), "[   ab|cd   ]");
+        assert_eq!(printf("%05.1f %+d %x %#o", &["3.14159", "7", "255", "8"]), "003.1 +7 ff 010");
+        assert_eq!(printf("%e %g %g", &["1234.5", "0.0001", "1e10"]), "1.234500e+03 0.0001 1e+10");
+        assert_eq!(printf("%*d|%.3d", &["4", "7", "5"]), "   7|005");
+        assert_eq!(printf("%c%%", &["hello"]), "h%");
+    }
+
+    #[test]
+    fn reuses_the_format_for_extra_arguments() {
+        assert_eq!(printf("%s\\n", &["a", "b", "c"]), "a\nb\nc\n");
+        assert_eq!(printf("%s-%s\\n", &["a", "b", "c"]), "a-b\nc-\n");
+        assert_eq!(printf("plain\\n", &["ignored"]), "plain\n");
+    }
+
+    #[test]
+    fn rejects_bad_numbers() {
+        assert!(format("%d", &["abc".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+
+// One running process, as read from /proc/<pid>
+#[derive(Clone, Debug, PartialEq)]
+pub struct Process {
+    pub pid: u32,
+    pub ppid: u32,
+    pub name: String,
+    pub uid: u32,
+    // R running, S sleeping, Z zombie, ...
+    pub state: char,
+    // User plus system time so far, in clock ticks
+    pub cpu_ticks: u64,
+    // Resident memory in bytes
+    pub memory: u64,
+    // The full command line; kernel threads have none and show [name]
+    pub command: String,
+}
+
+// Every process we can read, by pid
+pub fn all() -> Vec<Process> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(0) as u64;
+    let mut processes: Vec<Process> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+            let mut process = parse_stat(pid, &fs::read_to_string(entry.path().join("stat")).ok()?)?;
+            process.memory *= page_size;
+            process.uid = entry.metadata().map(|metadata| metadata.uid()).unwrap_or_default();
+            let command = fs::read(entry.path().join("cmdline")).unwrap_or_default();
+            process.command = match command.split(|&byte| byte == 0).filter(|part| !part.is_empty()).collect::<Vec<_>>() {
+                parts if parts.is_empty() => format!("[{}]", process.name),
+                parts => parts.iter().map(|part| String::from_utf8_lossy(part)).collect::<Vec<_>>().join(" "),
+            };
+            Some(process)
+        })
+        .collect();
+    processes.sort_by_key(|process| process.pid);
+    processes
+}
+
+// /proc/<pid>/stat: `pid (name) state ppid ...`. The name may itself hold
+// spaces and parentheses, so the fields are counted from the last `)`.
+// Memory comes back in pages.
+fn parse_stat(pid: u32, stat: &str) -> Option<Process> {
+    let (name, rest) = stat.split_once(" (")?.1.rsplit_once(") ")?;
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    let ticks = |index: usize| fields.get(index).and_then(|field| field.parse::<u64>().ok());
+    Some(Process {
+        pid,
+        ppid: fields.get(1)?.parse().ok()?,
+        name: name.to_string(),
+        uid: 0,
+        state: fields.first()?.chars().next()?,
+        cpu_ticks: ticks(11)? + ticks(12)?,
+        memory: ticks(21)?,
+        command: String::new(),
+    })
+}
+
+// The tree under `root` in drawing order, each process with the box-drawing
+// prefix that puts it under its parent
+pub fn tree(processes: &[Process], root: u32) -> Vec<(String, &Process)> {
+    let mut children: HashMap<u32, Vec<&Process>> = HashMap::new();
+    for process in processes {
+        if process.pid != process.ppid {
+            children.entry(process.ppid).or_default().push(process);
+        }
+    }
+
+    let mut lines = Vec::new();
+    if let Some(process) = processes.iter().find(|process| process.pid == root) {
+        lines.push((String::new(), process));
+        walk(&children, root, "", &mut lines);
+    }
+    lines
+}
+
+fn walk<'a>(children: &HashMap<u32, Vec<&'a Process>>, pid: u32, indent: &str, lines: &mut Vec<(String, &'a Process)>) {
+    let Some(kids) = children.get(&pid) else {
+        return;
+    };
+    for (i, child) in kids.iter().enumerate() {
+        let last = i + 1 == kids.len();
+        lines.push((format!("{}{}", indent, if last { "└─ " } else { "├─ " }), child));
+        walk(children, child.pid, &format!("{}{}", indent, if last { "   " } else { "│  " }), lines);
+    }
+}
+
+// Whether `pid` is `ancestor` or runs under it
+pub fn descends_from(processes: &[Process], pid: u32, ancestor: u32) -> bool {
+    let parents: HashMap<u32, u32> = processes.iter().map(|process| (process.pid, process.ppid)).collect();
+    let mut current = pid;
+    // The depth bound guards against a cycle from pids reused mid-read
+    for _ in 0..processes.len() + 1 {
+        if current == ancestor {
+            return true;
+        }
+        match parents.get(&current) {
+            Some(&parent) if parent != current => current = parent,
+            _ => return false,
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, ppid: u32, name: &str) -> Process {
+        Process { pid, ppid, name: name.to_string(), uid: 0, state: 'S', cpu_ticks: 0, memory: 0, command: String::new() }
+    }
+
+    #[test]
+    fn reads_stat_lines() {
+        let stat = "4242 (tmux: server (1)) S 1 4242 4242 0 -1 4194560 1 0 0 0 120 30 0 0 20 0 1 0 100 10000 256 18446744073709551615";
+        let process = parse_stat(4242, stat).unwrap();
+        assert_eq!((process.name.as_str(), process.ppid, process.state), ("tmux: server (1)", 1, 'S'));
+        assert_eq!((process.cpu_ticks, process.memory), (150, 256));
+    }
+
+    #[test]
+    fn draws_the_tree() {
+        let processes = [process(1, 0, "init"), process(2, 1, "sshd"), process(3, 2, "bash"), process(4, 1, "cron")];
+        let lines: Vec<String> = tree(&processes, 1).iter().map(|(prefix, process)| format!("{}{}", prefix, process.name)).collect();
+        assert_eq!(lines, ["init", "├─ sshd", "│  └─ bash", "└─ cron"]);
+        assert!(descends_from(&processes, 3, 2) && !descends_from(&processes, 4, 2));
+    }
+}
@@ -0,0 +1,109 @@
+use std::future::Future;
+use std::process::Stdio;
+use std::time::Duration;
+
+use colored::*;
+
+use crate::state::ShellState;
+use crate::sysinfo;
+use crate::terminal;
+use crate::theme;
+use crate::toolchains::{self, Language, LANGUAGES};
+use crate::widgets;
+
+// Expands the `right_prompt` template: {time}, {status}, {battery}, {load},
+// {host}, {ssh}, {rust}, {node}, {python}, {toolchains} and {widget:<name>}.
+// Segments with nothing to show expand to nothing.
+pub fn right_segment(template: &str, state: &ShellState) -> String {
+    let status = if state.last_success { String::new() } else { theme::paint("error", "✘").to_string() };
+    let battery = sysinfo::battery().map(|level| format!("BAT {}", level)).unwrap_or_default();
+    let load = sysinfo::load_average().map(|load| format!("load {}", load)).unwrap_or_default();
+    let ssh = if sysinfo::is_ssh(state) { "ssh".bright_magenta().to_string() } else { String::new() };
+
+    let mut expanded = template
+        .replace("{time}", &chrono::Local::now().format("%H:%M:%S").to_string().bright_black().to_string())
+        .replace("{status}", &status)
+        .replace("{battery}", &battery)
+        .replace("{load}", &load)
+        .replace("{host}", &sysinfo::hostname())
+        .replace("{ssh}", &ssh);
+
+    // Versions are only looked up for templates that show them
+    if expanded.contains("{toolchains}") || LANGUAGES.iter().any(|language| expanded.contains(&format!("{{{}}}", language.name()))) {
+        let versions = toolchains::cached(state);
+        let segment = |language: Language| versions.iter().find(|(found, _)| *found == language).map(|(_, version)| toolchain_segment(language, version));
+        let all: Vec<String> = LANGUAGES.into_iter().filter_map(segment).collect();
+        expanded = expanded.replace("{toolchains}", &all.join(" "));
+        for language in LANGUAGES {
+            expanded = expanded.replace(&format!("{{{}}}", language.name()), &segment(language).unwrap_or_default());
+        }
+    }
+
+    // Scan forward only, so fetched text is never expanded again
+    let mut from = 0;
+    while let Some(start) = expanded[from..].find("{widget:").map(|i| from + i) {
+        let Some(length) = expanded[start..].find('}') else {
+            break;
+        };
+        let name = &expanded[start + "{widget:".len()..start + length];
+        let text = widgets::value(name).unwrap_or_default();
+        expanded.replace_range(start..=start + length, &text);
+        from = start + text.len();
+    }
+
+    expanded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn toolchain_segment(language: Language, version: &str) -> String {
+    let name = match language {
+        Language::Rust => language.name().red(),
+        Language::Node => language.name().green(),
+        Language::Python => language.name().blue(),
+    };
+    format!("{} {}", name, version)
+}
+
+// Pads `line` so `right` ends one column short of the edge (writing the last
+// column makes some terminals wrap). Leaves `line` alone if both don't fit.
+pub fn align_right(line: &str, right: &str, columns: usize) -> String {
+    let used = terminal::visible_width(line) + terminal::visible_width(right);
+    if right.is_empty() || used + 2 > columns {
+        return line.to_string();
+    }
+    format!("{}{}{}", line, " ".repeat(columns - used - 1), right)
+}
+
+// How long the prompt waits for slow segments before drawing a placeholder
+pub const SEGMENT_BUDGET: Duration = Duration::from_millis(50);
+pub const PLACEHOLDER: &str = "…";
+
+// A segment whose provider may still be running
+pub enum Segment<T> {
+    Loading,
+    Ready(T),
+}
+
+// The current git branch, or None outside a work tree. The command is built
+// up front so the future can outlive the borrow of `state`.
+pub fn git_branch(state: &ShellState) -> impl Future<Output = Option<String>> + Send + 'static {
+    let mut command = tokio::process::Command::from(state.command("git"));
+    command
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+
+    async move {
+        let output = command
+            .output()
+            .await
+            .inspect_err(|e| tracing::debug!(error = %e, "git branch lookup failed"))
+            .ok()?;
+
+        if output.status.success() {
+            String::from_utf8(output.stdout).ok().map(|s| s.trim().to_string())
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use regex::{Regex, RegexBuilder};
+
+use crate::errors::CrateResult;
+use crate::i18n::{tr, trf};
+
+// A sed-style `s/pattern/replacement/flags` expression. Any character after
+// the `s` can be the delimiter; flags are g (every match) and i (ignore case).
+#[derive(Debug)]
+pub struct Substitution {
+    regex: Regex,
+    replacement: String,
+    global: bool,
+}
+
+impl Substitution {
+    pub fn parse(expression: &str) -> CrateResult<Substitution> {
+        let invalid = || anyhow!(trf("rename: expected s/pattern/replacement/, got '{}'", &[&expression]));
+        let mut chars = expression.chars();
+        if chars.next() != Some('s') {
+            return Err(invalid());
+        }
+        let delimiter = chars.next().filter(|c| !c.is_alphanumeric() && *c != '\\').ok_or_else(invalid)?;
+
+        // Splits on unescaped delimiters; an escaped delimiter stands for itself
+        let mut parts = vec![String::new()];
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if chars.clone().next() == Some(delimiter) => {
+                    parts.last_mut().unwrap().push(delimiter);
+                    chars.next();
+                }
+                '\\' => {
+                    parts.last_mut().unwrap().push(c);
+                    parts.last_mut().unwrap().extend(chars.next());
+                }
+                c if c == delimiter => parts.push(String::new()),
+                c => parts.last_mut().unwrap().push(c),
+            }
+        }
+        let [pattern, replacement, flags] = <[String; 3]>::try_from(parts).map_err(|_| invalid())?;
+
+        let mut global = false;
+        let mut ignore_case = false;
+        for flag in flags.chars() {
+            match flag {
+                'g' => global = true,
+                'i' => ignore_case = true,
+                _ => return Err(anyhow!(trf("rename: unknown flag '{}'", &[&flag]))),
+            }
+        }
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|e| anyhow!(trf("rename: bad pattern: {}", &[&e])))?;
+        Ok(Substitution { regex, replacement, global })
+    }
+
+    pub fn apply(&self, name: &str) -> String {
+        let limit = if self.global { 0 } else { 1 };
+        self.regex.replacen(name, limit, self.replacement.as_str()).into_owned()
+    }
+}
+
+// One file's new name, and why it can't have it if it can't
+#[derive(Debug)]
+pub struct Rename {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub conflict: Option<&'static str>,
+}
+
+// Works out the new name of each file. Only the last component is rewritten,
+// so files stay in their directories. Files the expression doesn't change
+// are left out.
+pub fn plan(substitution: &Substitution, paths: &[PathBuf]) -> Vec<Rename> {
+    let mut renames = Vec::new();
+    for path in paths {
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let new_name = substitution.apply(name);
+        if new_name != name {
+            let invalid = new_name.is_empty() || new_name.contains('/') || new_name == "." || new_name == "..";
+            let conflict = invalid.then(|| tr("not a valid file name"));
+            renames.push(Rename { from: path.clone(), to: path.with_file_name(&new_name), conflict });
+        }
+    }
+
+    let sources: HashSet<PathBuf> = renames.iter().map(|rename| rename.from.clone()).collect();
+    let mut targets: HashMap<PathBuf, usize> = HashMap::new();
+    for rename in &renames {
+        *targets.entry(rename.to.clone()).or_default() += 1;
+    }
+    for rename in renames.iter_mut().filter(|rename| rename.conflict.is_none()) {
+        rename.conflict = if targets[&rename.to] > 1 {
+            Some(tr("another file gets the same name"))
+        } else if sources.contains(&rename.to) {
+            Some(tr("that file is being renamed too"))
+        } else if std::fs::symlink_metadata(&rename.to).is_ok() {
+            Some(tr("already exists"))
+        } else {
+            None
+        };
+    }
+    renames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substitutions_parse_and_apply() {
+        let lower = Substitution::parse("s/IMG_(\\d+)\\.JPG$/photo-${1}.jpg/").unwrap();
+        assert_eq!(lower.apply("IMG_0042.JPG"), "photo-0042.jpg");
+        assert_eq!(lower.apply("notes.txt"), "notes.txt");
+
+        assert_eq!(Substitution::parse("s/-/_/").unwrap().apply("a-b-c"), "a_b-c");
+        assert_eq!(Substitution::parse("s/-/_/g").unwrap().apply("a-b-c"), "a_b_c");
+        assert_eq!(Substitution::parse("s/A/x/gi").unwrap().apply("aA"), "xx");
+        assert_eq!(Substitution::parse("s|/|-|").unwrap().apply("a/b"), "a-b");
+        assert_eq!(Substitution::parse(r"s/\//-/").unwrap().apply("a/b"), "a-b");
+
+        assert!(Substitution::parse("s/a/b").is_err());
+        assert!(Substitution::parse("y/a/b/").is_err());
+        assert!(Substitution::parse("s/(/b/").is_err());
+        assert!(Substitution::parse("s/a/b/q").is_err());
+    }
+
+    #[test]
+    fn conflicts_are_flagged() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a1", "a2", "b1", "taken"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        let paths: Vec<PathBuf> = ["a1", "a2", "b1"].iter().map(|name| dir.path().join(name)).collect();
+
+        let renames = plan(&Substitution::parse("s/\\d//").unwrap(), &paths);
+        let conflicts: Vec<_> = renames.iter().map(|rename| rename.conflict.is_some()).collect();
+        assert_eq!(conflicts, [true, true, false]);
+
+        let renames = plan(&Substitution::parse("s/^b1$/taken/").unwrap(), &paths);
+        assert_eq!(renames.len(), 1);
+        assert_eq!(renames[0].conflict, Some("already exists"));
+    }
+}
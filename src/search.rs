@@ -0,0 +1,129 @@
+use std::fs::{self, Metadata};
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::config;
+
+// How find and grep compare letters
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseMode {
+    #[default]
+    Sensitive,
+    Insensitive,
+    // Insensitive unless the pattern has an uppercase letter
+    Smart,
+}
+
+// Flags shared by find and grep
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchOptions {
+    // -i, -s or -S; None falls back to `search_case` in the rc file
+    pub case: Option<CaseMode>,
+    // --max-results N: stop after N matches
+    pub max_results: Option<usize>,
+}
+
+impl SearchOptions {
+    // Handles a flag shared by find and grep; false if `flag` isn't one
+    pub fn parse_flag(&mut self, flag: char) -> bool {
+        self.case = Some(match flag {
+            'i' => CaseMode::Insensitive,
+            's' => CaseMode::Sensitive,
+            'S' => CaseMode::Smart,
+            _ => return false,
+        });
+        true
+    }
+
+    // Whether another match may be reported after `count` of them
+    pub fn wants_more(&self, count: usize) -> bool {
+        self.max_results.is_none_or(|max| count < max)
+    }
+
+    pub fn case(&self) -> CaseMode {
+        self.case.or(config::get().search_case).unwrap_or_default()
+    }
+}
+
+// Extra tests `find` applies to each match
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FindFilters {
+    // --larger-than / --smaller-than, in bytes; only files pass these
+    pub larger_than: Option<u64>,
+    pub smaller_than: Option<u64>,
+    // --empty: zero-byte files and directories with nothing in them
+    pub empty: bool,
+    // --delete: remove what was found, after confirming
+    pub delete: bool,
+    // --yes: delete without asking, as is needed without a terminal
+    pub yes: bool,
+    // --one-file-system: don't descend into directories on other filesystems
+    pub one_file_system: bool,
+}
+
+impl FindFilters {
+    // `children_matched` says whether everything inside a directory matched;
+    // with --delete that content goes first, so the directory will be empty too
+    pub fn accepts(&self, path: &Path, metadata: &Metadata, children_matched: bool) -> bool {
+        let is_dir = metadata.is_dir();
+        if self.larger_than.is_some_and(|size| is_dir || metadata.len() <= size) {
+            return false;
+        }
+        if self.smaller_than.is_some_and(|size| is_dir || metadata.len() >= size) {
+            return false;
+        }
+        if self.empty {
+            return match (is_dir, self.delete) {
+                (true, true) => children_matched,
+                (true, false) => fs::read_dir(path).is_ok_and(|mut entries| entries.next().is_none()),
+                (false, _) => metadata.len() == 0,
+            };
+        }
+        true
+    }
+}
+
+// A substring pattern with its case rule applied once up front
+pub struct Matcher {
+    pattern: String,
+    ignore_case: bool,
+}
+
+impl Matcher {
+    pub fn new(pattern: &str, case: CaseMode) -> Matcher {
+        let ignore_case = match case {
+            CaseMode::Sensitive => false,
+            CaseMode::Insensitive => true,
+            CaseMode::Smart => !pattern.chars().any(char::is_uppercase),
+        };
+        let pattern = if ignore_case { pattern.to_lowercase() } else { pattern.to_string() };
+        Matcher { pattern, ignore_case }
+    }
+
+    pub fn ignores_case(&self) -> bool {
+        self.ignore_case
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        if self.ignore_case {
+            text.to_lowercase().contains(&self.pattern)
+        } else {
+            text.contains(&self.pattern)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smart_case_follows_the_pattern() {
+        assert!(Matcher::new("error", CaseMode::Smart).is_match("ERROR: disk full"));
+        assert!(!Matcher::new("Error", CaseMode::Smart).is_match("ERROR: disk full"));
+        assert!(Matcher::new("Error", CaseMode::Insensitive).is_match("ERROR: disk full"));
+        assert!(!Matcher::new("error", CaseMode::Sensitive).is_match("ERROR: disk full"));
+    }
+}
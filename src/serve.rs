@@ -0,0 +1,243 @@
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::anyhow;
+use colored::Colorize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::errors::CrateResult;
+use crate::helpers;
+use crate::i18n::trf;
+use crate::output;
+
+pub const DEFAULT_PORT: u16 = 8080;
+
+// Opens the port up front so a port that is taken is reported by the
+// command itself rather than by the background job
+pub async fn bind(port: u16) -> CrateResult<TcpListener> {
+    TcpListener::bind(("0.0.0.0", port))
+        .await
+        .map_err(|e| anyhow!(trf("serve: could not listen on port {}: {}", &[&port, &e])))
+}
+
+// A response body: generated text, or a file streamed from disk
+enum Body {
+    Bytes(Vec<u8>),
+    File(tokio::fs::File, u64),
+}
+
+impl Body {
+    fn len(&self) -> u64 {
+        match self {
+            Body::Bytes(bytes) => bytes.len() as u64,
+            Body::File(_, size) => *size,
+        }
+    }
+}
+
+// Answers requests until the job is killed, printing one line per request
+pub async fn run(listener: TcpListener, root: PathBuf) -> CrateResult<()> {
+    loop {
+        let (stream, client) = listener.accept().await?;
+        let root = root.clone();
+        tokio::spawn(async move {
+            if let Some((method, path, status, size)) = respond(stream, &root).await {
+                let status = match status {
+                    200 => status.to_string().green(),
+                    _ => status.to_string().yellow(),
+                };
+                output::println(&format!("{} {} {} {} {}", client.ip(), method, path, status, helpers::human_size(size).bright_black()));
+            }
+        });
+    }
+}
+
+// Handles one request on its own connection; returns what to log
+async fn respond(mut stream: TcpStream, root: &Path) -> Option<(String, String, u16, u64)> {
+    let mut head = Vec::new();
+    let mut buffer = [0; 4096];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buffer).await.ok()?;
+        if read == 0 || head.len() > 64 * 1024 {
+            return None;
+        }
+        head.extend_from_slice(&buffer[..read]);
+    }
+    let head = String::from_utf8_lossy(&head);
+    let mut request = head.lines().next().unwrap_or_default().split_whitespace();
+    let (method, target) = (request.next().unwrap_or_default().to_string(), request.next().unwrap_or_default().to_string());
+
+    let (status, content_type, body) = match method.as_str() {
+        "GET" | "HEAD" => match resolve(root, &target) {
+            Some(path) => page(&path, &target).await,
+            None => error(400),
+        },
+        _ => error(405),
+    };
+
+    let header = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        reason(status),
+        content_type,
+        body.len()
+    );
+    stream.write_all(header.as_bytes()).await.ok()?;
+    let size = body.len();
+    if method != "HEAD" {
+        match body {
+            Body::Bytes(bytes) => stream.write_all(&bytes).await.ok()?,
+            Body::File(mut file, _) => {
+                tokio::io::copy(&mut file, &mut stream).await.ok()?;
+            }
+        }
+    }
+    let _ = stream.shutdown().await;
+    Some((method, target, status, size))
+}
+
+// Maps a request path onto the served directory; anything that would
+// climb out of it is refused
+fn resolve(root: &Path, target: &str) -> Option<PathBuf> {
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+    let path = percent_decode(path)?;
+    let mut resolved = root.to_path_buf();
+    for component in Path::new(&path).components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::RootDir | Component::CurDir => {}
+            Component::ParentDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(resolved)
+}
+
+// The file itself, a directory's index.html, or a listing of the directory
+async fn page(path: &Path, target: &str) -> (u16, &'static str, Body) {
+    let mut file = path.to_path_buf();
+    if path.is_dir() {
+        file = path.join("index.html");
+        if !file.is_file() {
+            return match listing(path, target) {
+                Some(html) => (200, "text/html; charset=utf-8", Body::Bytes(html.into_bytes())),
+                None => error(403),
+            };
+        }
+    }
+    let opened = match tokio::fs::File::open(&file).await {
+        Ok(opened) => opened,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return error(404),
+        Err(_) => return error(403),
+    };
+    match opened.metadata().await {
+        Ok(metadata) if metadata.is_file() => (200, content_type(&file), Body::File(opened, metadata.len())),
+        _ => error(403),
+    }
+}
+
+fn listing(dir: &Path, target: &str) -> Option<String> {
+    let mut entries: Vec<(String, bool, u64)> = std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let metadata = entry.metadata().ok()?;
+            Some((name, metadata.is_dir(), metadata.len()))
+        })
+        .filter(|(name, _, _)| !name.starts_with('.'))
+        .collect();
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    // Links are absolute so they work whether or not the URL ended in /
+    let mut base = target.split(['?', '#']).next().unwrap_or_default().to_string();
+    if !base.ends_with('/') {
+        base.push('/');
+    }
+    let title = escape_html(&percent_decode(&base).unwrap_or_default());
+    let mut html = format!("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{0}</title></head>\n<body><h1>{0}</h1>\n<ul>\n", title);
+    if base != "/" {
+        html.push_str(&format!("<li><a href=\"{}../\">../</a></li>\n", escape_html(&base)));
+    }
+    for (name, is_dir, size) in entries {
+        let slash = if is_dir { "/" } else { "" };
+        let size = if is_dir { String::new() } else { format!(" ({})", helpers::human_size(size)) };
+        html.push_str(&format!(
+            "<li><a href=\"{}{}{}\">{}{}</a>{}</li>\n",
+            escape_html(&base),
+            percent_encode(&name),
+            slash,
+            escape_html(&name),
+            slash,
+            size
+        ));
+    }
+    html.push_str("</ul></body></html>\n");
+    Some(html)
+}
+
+fn error(status: u16) -> (u16, &'static str, Body) {
+    (status, "text/plain; charset=utf-8", Body::Bytes(format!("{} {}\n", status, reason(status)).into_bytes()))
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Error",
+    }
+}
+
+fn content_type(path: &Path) -> &'static str {
+    let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+    match extension.to_lowercase().as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" | "map" => "application/json",
+        "txt" | "md" | "log" | "toml" | "rs" => "text/plain; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "ico" => "image/x-icon",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,59 @@
+use libc::c_int;
+
+// The signals `kill` knows by name, in number order
+pub const SIGNALS: [(&str, c_int); 15] = [
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("SEGV", libc::SIGSEGV),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("WINCH", libc::SIGWINCH),
+];
+
+// A signal as typed: TERM, SIGTERM, term or 15
+pub fn parse(text: &str) -> Option<c_int> {
+    if let Ok(number) = text.parse::<c_int>() {
+        return (1..=64).contains(&number).then_some(number);
+    }
+    let upper = text.to_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    SIGNALS.iter().find(|(known, _)| *known == name).map(|(_, number)| *number)
+}
+
+// SIGTERM for 15; the number for signals without a name here
+pub fn name(signal: c_int) -> String {
+    match SIGNALS.iter().find(|(_, number)| *number == signal) {
+        Some((name, _)) => format!("SIG{}", name),
+        None => signal.to_string(),
+    }
+}
+
+// Signals that end a process unless it catches them; the only ones a job,
+// which runs inside the shell, can honour
+pub fn terminates(signal: c_int) -> bool {
+    [libc::SIGHUP, libc::SIGINT, libc::SIGQUIT, libc::SIGKILL, libc::SIGTERM].contains(&signal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_names_and_numbers() {
+        assert_eq!(parse("TERM"), Some(libc::SIGTERM));
+        assert_eq!(parse("sigkill"), Some(libc::SIGKILL));
+        assert_eq!(parse("9"), Some(9));
+        assert_eq!(parse("0"), None);
+        assert_eq!(parse("NOPE"), None);
+        assert_eq!(name(libc::SIGHUP), "SIGHUP");
+    }
+}
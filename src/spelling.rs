@@ -0,0 +1,135 @@
+use std::fs;
+
+use serde::Deserialize;
+
+use crate::completion;
+use crate::config;
+use crate::executables;
+use crate::i18n::trf;
+use crate::state::ShellState;
+use crate::tokenizer::{self, quote};
+
+// What happens to a mistyped builtin name or `cd` path, as zsh's correct
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Correction {
+    #[default]
+    Off,
+    // The corrected line runs if the answer is yes, the typed one otherwise
+    Ask,
+    // The corrected line runs without asking
+    Auto,
+}
+
+// A line that was probably meant, and how to ask about it
+pub struct Suggestion {
+    pub line: String,
+    pub question: String,
+}
+
+// What `line` was probably meant to be: a builtin close to a first word
+// that isn't one, or a `cd` to a directory close to one that doesn't exist
+pub fn correct(line: &str, state: &ShellState) -> Option<Suggestion> {
+    let words = tokenizer::tokenize(line).ok()?;
+    let (first, rest) = words.split_first()?;
+    if first == "cd" && !rest.is_empty() {
+        let path = correct_path(&rest.join(" "), state)?;
+        return Some(Suggestion { line: format!("cd {}", quote(&path)), question: trf("cd to '{}' instead? [y/n] ", &[&path]) });
+    }
+    // `[` and `NAME=value` are commands too, and so is a directory with autocd
+    let config = config::get();
+    if first == "[" || first.contains('=') || completion::is_builtin(first) || config.abbr.contains_key(first) {
+        return None;
+    }
+    // Programs run as typed, and so do paths to them
+    if first.contains('/') || executables::find(first, state.var("PATH").unwrap_or_default()).is_some() {
+        return None;
+    }
+    if config.autocd && words.len() == 1 && state.resolve(first).is_dir() {
+        return None;
+    }
+    let name = closest(first, completion::BUILTINS.iter().map(|builtin| builtin.name))?;
+    // Only the name changes, so quotes and globs after it stay as typed
+    let start = line.len() - line.trim_start().len();
+    let end = line[start..].find(char::is_whitespace).map_or(line.len(), |length| start + length);
+    Some(Suggestion { line: format!("{}{}{}", &line[..start], name, &line[end..]), question: trf("run '{}' instead? [y/n] ", &[&name]) })
+}
+
+// `path` with each part that doesn't exist swapped for the closest
+// directory name where it should be, if that makes a directory
+fn correct_path(path: &str, state: &ShellState) -> Option<String> {
+    if state.resolve(path).is_dir() {
+        return None;
+    }
+    let mut corrected = String::new();
+    for (index, component) in path.split('/').enumerate() {
+        if index > 0 {
+            corrected.push('/');
+        }
+        let exists = fs::symlink_metadata(state.resolve(format!("{}{}", corrected, component))).is_ok();
+        if exists || matches!(component, "" | "." | ".." | "~") {
+            corrected.push_str(component);
+            continue;
+        }
+        let dir = state.resolve(if corrected.is_empty() { "." } else { &corrected });
+        let names: Vec<String> = fs::read_dir(dir)
+            .ok()?
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|name| !name.starts_with('.') || component.starts_with('.'))
+            .collect();
+        corrected.push_str(closest(component, names.iter().map(String::as_str))?);
+    }
+    state.resolve(&corrected).is_dir().then_some(corrected)
+}
+
+// The candidate fewest edits away from `word`, if one is near enough to be
+// a typo. Ties go to the first.
+pub fn closest<'a>(word: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let length = word.chars().count();
+    if length < 2 {
+        return None;
+    }
+    let most = (length / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| (distance(word, candidate), candidate))
+        .filter(|(distance, _)| *distance <= most)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+// Edits to turn `a` into `b`: characters inserted, deleted, changed, or two
+// next to each other swapped, the commonest slip when typing
+fn distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut table: Vec<Vec<usize>> = (0..=a.len()).map(|i| (0..=b.len()).map(|j| if i == 0 { j } else if j == 0 { i } else { 0 }).collect()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let change = usize::from(a[i - 1] != b[j - 1]);
+            let mut edits = (table[i - 1][j] + 1).min(table[i][j - 1] + 1).min(table[i - 1][j - 1] + change);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                edits = edits.min(table[i - 2][j - 2] + 1);
+            }
+            table[i][j] = edits;
+        }
+    }
+    table[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_nearest_name() {
+        assert_eq!(distance("lcoal", "local"), 1);
+        assert_eq!(distance("grpe", "grep"), 1);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(closest("ehco", ["echo", "exit", "env"]), Some("echo"));
+        assert_eq!(closest("gerp", ["grep", "gst"]), Some("grep"));
+        assert_eq!(closest("zzz", ["echo", "ls"]), None);
+        assert_eq!(closest("l", ["ls"]), None);
+    }
+}
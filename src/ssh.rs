@@ -0,0 +1,155 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::ExitStatus;
+
+use anyhow::anyhow;
+
+use crate::errors::CrateResult;
+use crate::foreground;
+use crate::i18n::trf;
+use crate::state::ShellState;
+
+// A `Host` block of ~/.ssh/config
+#[derive(Debug, Default, PartialEq)]
+pub struct Host {
+    pub name: String,
+    pub hostname: Option<String>,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+}
+
+// Hosts named in ~/.ssh/config; patterns with wildcards aren't hosts you can
+// connect to, so they are left out
+pub fn configured_hosts(home: &Path) -> Vec<Host> {
+    fs::read_to_string(home.join(".ssh/config")).map(|text| parse_config(&text)).unwrap_or_default()
+}
+
+// Host names from ~/.ssh/known_hosts; hashed entries can't be read back
+pub fn known_hosts(home: &Path) -> Vec<String> {
+    fs::read_to_string(home.join(".ssh/known_hosts")).map(|text| parse_known_hosts(&text)).unwrap_or_default()
+}
+
+// Every host name worth offering for ssh and scp, configured ones first
+pub fn host_names(home: &Path) -> Vec<String> {
+    let mut names: Vec<String> = configured_hosts(home).into_iter().map(|host| host.name).collect();
+    for name in known_hosts(home) {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+// Runs the system ssh or scp on this terminal and waits for it to finish
+// or be suspended
+pub fn run(state: &ShellState, program: &str, args: &[String]) -> CrateResult<ExitStatus> {
+    let line = std::iter::once(program).chain(args.iter().map(String::as_str)).collect::<Vec<_>>().join(" ");
+    foreground::run(state.user_command(program).args(args), &line).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => anyhow!(trf("{} is not installed", &[&program])),
+        _ => e.into(),
+    })
+}
+
+fn parse_config(text: &str) -> Vec<Host> {
+    let mut hosts: Vec<Host> = Vec::new();
+    // How many hosts the current block applies to
+    let mut block = 0;
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // Both `Key value` and `Key=value` are allowed
+        let (key, value) = match line.split_once(|c: char| c.is_whitespace() || c == '=') {
+            Some((key, value)) => (key.to_lowercase(), value.trim_start_matches(|c: char| c.is_whitespace() || c == '=').trim()),
+            None => continue,
+        };
+
+        match key.as_str() {
+            "host" => {
+                let names: Vec<&str> = value.split_whitespace().filter(|name| !name.contains(['*', '?', '!'])).collect();
+                block = names.len();
+                hosts.extend(names.into_iter().map(|name| Host { name: name.to_string(), ..Host::default() }));
+            }
+            "match" => block = 0,
+            _ => {
+                let start = hosts.len() - block;
+                for host in &mut hosts[start..] {
+                    // As in ssh, the first value given for a host wins
+                    match key.as_str() {
+                        "hostname" => host.hostname = host.hostname.take().or(Some(value.to_string())),
+                        "user" => host.user = host.user.take().or(Some(value.to_string())),
+                        "port" => host.port = host.port.or(value.parse().ok()),
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+    hosts
+}
+
+fn parse_known_hosts(text: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        // Markers like @cert-authority come before the host field
+        let field = match line.split_whitespace().next() {
+            Some(marker) if marker.starts_with('@') => line.split_whitespace().nth(1),
+            field => field,
+        };
+        let Some(field) = field.filter(|field| !field.starts_with('#') && !field.starts_with('|')) else {
+            continue;
+        };
+        for name in field.split(',') {
+            // [host]:port for servers on another port
+            let name = match name.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+                Some((host, _)) => host,
+                None => name,
+            };
+            if !name.is_empty() && !name.contains(['*', '?', '!']) && !names.iter().any(|known| known == name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_config_and_known_hosts() {
+        let config = "\
+# work machines
+Host build web1 *.internal
+    HostName 10.0.0.5
+    User deploy
+Host=pi
+    Port=2222
+    HostName raspberrypi.local
+    HostName ignored.local
+Host *
+    User nobody
+";
+        assert_eq!(
+            parse_config(config),
+            [
+                Host { name: "build".into(), hostname: Some("10.0.0.5".into()), user: Some("deploy".into()), port: None },
+                Host { name: "web1".into(), hostname: Some("10.0.0.5".into()), user: Some("deploy".into()), port: None },
+                Host { name: "pi".into(), hostname: Some("raspberrypi.local".into()), user: None, port: Some(2222) },
+            ]
+        );
+
+        let known = "\
+github.com,140.82.121.4 ssh-ed25519 AAAA
+[git.example.com]:2200 ssh-rsa AAAA
+|1|hashed= ssh-rsa AAAA
+@revoked old.example.com ssh-rsa AAAA
+github.com ssh-rsa AAAA
+";
+        assert_eq!(parse_known_hosts(known), ["github.com", "140.82.121.4", "git.example.com", "old.example.com"]);
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::os::unix::process::CommandExt;
+use std::process::Command as ProcessCommand;
+
+use anyhow::anyhow;
+
+use crate::errors::{self, Access, CrateResult};
+use crate::executables;
+use crate::i18n::trf;
+use crate::limits::{self, Limits};
+
+#[derive(Clone, Debug, Default)]
+pub struct ShellOptions {
+    // Stop running a script at the first command that fails
+    pub errexit: bool,
+}
+
+// Everything a session owns. Builtins resolve paths against `cwd` and
+// externals are started with `cwd`/`env` explicitly, so the process-wide
+// current directory and environment are never changed after startup.
+#[derive(Clone, Debug)]
+pub struct ShellState {
+    pub cwd: PathBuf,
+    pub env: HashMap<String, String>,
+    // The environment the shell was launched with, in its original order
+    pub startup_env: Vec<(String, String)>,
+    // Soft limits from `ulimit` for the programs the session starts
+    pub limits: Limits,
+    // Added to the nice value of the programs the session starts
+    pub niceness: i32,
+    // Command lines entered this session, oldest first
+    pub history: Vec<String>,
+    pub options: ShellOptions,
+    // Whether the last command succeeded, for the prompt
+    pub last_success: bool,
+    // The last line was an `exit` held back because jobs were running
+    pub exit_warned: bool,
+}
+
+impl ShellState {
+    // Starts from the directory and environment the shell was launched with
+    pub fn from_process() -> CrateResult<ShellState> {
+        let startup_env: Vec<(String, String)> = std::env::vars().collect();
+        Ok(ShellState {
+            cwd: std::env::current_dir()?,
+            env: startup_env.iter().cloned().collect(),
+            startup_env,
+            limits: Limits::new(),
+            niceness: 0,
+            history: Vec::new(),
+            options: ShellOptions::default(),
+            last_success: true,
+            exit_warned: false,
+        })
+    }
+
+    pub fn var(&self, name: &str) -> Option<&str> {
+        self.env.get(name).map(String::as_str)
+    }
+
+    // The working directory with $HOME shortened to ~, for titles and prompts
+    pub fn short_cwd(&self) -> String {
+        match self.var("HOME").and_then(|home| self.cwd.strip_prefix(home).ok()) {
+            Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+            Some(rest) => format!("~/{}", rest.display()),
+            None => self.cwd.display().to_string(),
+        }
+    }
+
+    // Interprets a path argument the way it was meant: a leading ~ is $HOME,
+    // relative paths start at the session's directory, and `.`, `..` and
+    // repeated slashes are folded away
+    pub fn resolve(&self, path: impl AsRef<Path>) -> PathBuf {
+        let path = path.as_ref();
+        match (path.strip_prefix("~"), self.var("HOME")) {
+            (Ok(rest), Some(home)) => normalize(&self.cwd.join(home).join(rest)),
+            _ => normalize(&self.cwd.join(path)),
+        }
+    }
+
+    pub fn cd(&mut self, path: &str) -> CrateResult<()> {
+        let target = self.resolve(path);
+        let target = fs::canonicalize(&target).map_err(|e| errors::with_path(e, &target, Access::Read))?;
+
+        if !target.is_dir() {
+            return Err(anyhow!(trf("'{}' is not a directory", &[&path])));
+        }
+        // Entering a directory needs its search bit, which canonicalize doesn't check
+        if !errors::can_search(&target) {
+            return Err(errors::with_path(io::Error::from_raw_os_error(libc::EACCES), &target, Access::Read));
+        }
+
+        self.cwd = target;
+        Ok(())
+    }
+
+    // An external program that runs in this session's directory, environment,
+    // resource limits and priority. A name is run from where `hash` has it,
+    // keeping the name as the program's argv[0].
+    pub fn command(&self, program: &str) -> ProcessCommand {
+        self.command_from(program, executables::locate(program, self.var("PATH").unwrap_or_default()))
+    }
+
+    // The same for a program the user runs, which `hash` counts
+    pub fn user_command(&self, program: &str) -> ProcessCommand {
+        self.command_from(program, executables::resolve(program, self.var("PATH").unwrap_or_default()))
+    }
+
+    fn command_from(&self, program: &str, found: Option<PathBuf>) -> ProcessCommand {
+        let mut command = match found {
+            Some(path) => {
+                let mut command = ProcessCommand::new(path);
+                command.arg0(program);
+                command
+            }
+            // A relative path is from the session's directory, not the shell's
+            None if program.contains('/') => {
+                let mut command = ProcessCommand::new(self.resolve(program));
+                command.arg0(program);
+                command
+            }
+            None => ProcessCommand::new(program),
+        };
+        command.current_dir(&self.cwd).env_clear().envs(&self.env);
+        // The shell ignores the job control signals, which programs would inherit
+        unsafe {
+            command.pre_exec(|| {
+                for signal in [libc::SIGTSTP, libc::SIGTTOU, libc::SIGTTIN] {
+                    libc::signal(signal, libc::SIG_DFL);
+                }
+                Ok(())
+            });
+        }
+        if !self.limits.is_empty() || self.niceness != 0 {
+            let (limits, niceness) = (self.limits.clone(), self.niceness);
+            unsafe {
+                command.pre_exec(move || {
+                    limits::apply(&limits)?;
+                    limits::nice(niceness)
+                });
+            }
+        }
+        command
+    }
+}
+
+// Removes `.` and `..` components without touching the filesystem, so `..`
+// undoes the previous component as typed (the logical view, like `cd` in bash)
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                // `..` at the root stays at the root
+                if !normalized.pop() && !path.has_root() {
+                    normalized.push("..");
+                }
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate, Utc};
+use colored::*;
+
+use crate::audit::AuditEntry;
+use crate::i18n::{tr, trf};
+
+const TOP_COMMANDS: usize = 10;
+const SPARKLINE_DAYS: i64 = 14;
+const SPARK_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const BAR_PARTIALS: [char; 8] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+
+pub fn render(entries: &[AuditEntry], width: usize) -> String {
+    let mut result = String::new();
+
+    if entries.is_empty() {
+        result.push_str(&format!("{}\n", tr("No commands recorded yet.").yellow()));
+        return result;
+    }
+
+    let total = entries.len();
+    let failures = entries.iter().filter(|e| !e.success).count();
+    let failure_rate = failures as f64 / total as f64;
+
+    result.push_str(&format!("{}\n", tr("Overview:").cyan().bold()));
+    result.push_str(&format!("  {:16}{}\n", tr("Total commands:"), total.to_string().yellow()));
+    result.push_str(&format!(
+        "  {:16}{} {}\n",
+        tr("Failure rate:"),
+        format!("{:5.1}%", failure_rate * 100.0).yellow(),
+        bar(failure_rate, width.saturating_sub(24).min(40)).red()
+    ));
+
+    // Top commands by frequency
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for entry in entries {
+        *counts.entry(entry.name()).or_insert(0) += 1;
+    }
+    let mut counts: Vec<(&str, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    counts.truncate(TOP_COMMANDS);
+
+    let name_width = counts.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0);
+    let count_width = counts.first().map(|(_, n)| n.to_string().len()).unwrap_or(1);
+    // Leave room for indentation, the name column, the count and separators
+    let bar_width = width.saturating_sub(name_width + count_width + 6).max(10);
+    let max_count = counts.first().map(|(_, n)| *n).unwrap_or(1);
+
+    result.push_str(&format!("\n{}\n", tr("Top commands:").cyan().bold()));
+    for (name, count) in &counts {
+        result.push_str(&format!(
+            "  {:name_width$} {} {}\n",
+            name.green(),
+            bar(*count as f64 / max_count as f64, bar_width).blue(),
+            count.to_string().yellow(),
+        ));
+    }
+
+    // Commands per day over the last two weeks
+    let today = Utc::now().date_naive();
+    let first_day = today - Duration::days(SPARKLINE_DAYS - 1);
+    let mut per_day: HashMap<NaiveDate, usize> = HashMap::new();
+    for entry in entries {
+        if let Some(dt) = chrono::DateTime::<Utc>::from_timestamp(entry.timestamp, 0) {
+            let day = dt.date_naive();
+            if day >= first_day {
+                *per_day.entry(day).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let daily: Vec<usize> = (0..SPARKLINE_DAYS)
+        .map(|offset| *per_day.get(&(first_day + Duration::days(offset))).unwrap_or(&0))
+        .collect();
+
+    result.push_str(&format!(
+        "\n{} {}\n",
+        tr("Commands per day").cyan().bold(),
+        format!("({} → {}):", first_day.format("%m-%d"), today.format("%m-%d")).bright_black()
+    ));
+    result.push_str(&format!(
+        "  {} {}\n",
+        sparkline(&daily).green(),
+        trf("max {}", &[daily.iter().max().unwrap_or(&0)]).bright_black()
+    ));
+
+    result
+}
+
+fn bar(fraction: f64, width: usize) -> String {
+    let eighths = (fraction.clamp(0.0, 1.0) * width as f64 * 8.0).round() as usize;
+    let mut bar = "█".repeat(eighths / 8);
+    if !eighths.is_multiple_of(8) {
+        bar.push(BAR_PARTIALS[eighths % 8 - 1]);
+    }
+    bar
+}
+
+fn sparkline(values: &[usize]) -> String {
+    let max = values.iter().copied().max().unwrap_or(0);
+
+    values
+        .iter()
+        .map(|&value| {
+            if max == 0 || value == 0 {
+                ' '
+            } else {
+                SPARK_CHARS[(value * (SPARK_CHARS.len() - 1)) / max]
+            }
+        })
+        .collect()
+}
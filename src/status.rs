@@ -0,0 +1,121 @@
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use colored::Colorize;
+
+use crate::terminal;
+
+const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+// How often a spinner moves on
+pub const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+const BAR_WIDTH: usize = 30;
+
+// What a script asked to show with `spinner start` or `progress`. It is
+// drawn on the current line while commands run and next to the prompt
+// while a line is typed.
+enum Indicator {
+    Spinner { message: String, started: Instant },
+    Bar { label: String, current: u64, total: u64 },
+}
+
+static INDICATOR: Mutex<Option<Indicator>> = Mutex::new(None);
+// Whether the cursor's line holds a status that the next output replaces
+static DRAWN: AtomicBool = AtomicBool::new(false);
+
+// `[#####     ]  50%`, the bar every progress display uses
+pub fn bar(fraction: f64) -> String {
+    let fraction = fraction.clamp(0.0, 1.0);
+    let filled = ((fraction * BAR_WIDTH as f64) as usize).min(BAR_WIDTH);
+    format!("[{}{}] {:3.0}%", "#".repeat(filled), " ".repeat(BAR_WIDTH - filled), fraction * 100.0)
+}
+
+// Puts `line` on the cursor's line, in place of whatever status was there.
+// Nothing is drawn unless stdout is a terminal.
+pub fn draw(line: &str) {
+    if !terminal::is_ansi_terminal() {
+        return;
+    }
+    print!("\r\x1b[2K{}", line);
+    let _ = std::io::stdout().flush();
+    DRAWN.store(true, Ordering::Relaxed);
+}
+
+// Erases a drawn status so the next output starts on a clean line
+pub fn clear() {
+    if DRAWN.swap(false, Ordering::Relaxed) {
+        print!("\r\x1b[2K");
+        let _ = std::io::stdout().flush();
+    }
+}
+
+pub fn start_spinner(message: &str) {
+    *INDICATOR.lock().unwrap() = Some(Indicator::Spinner { message: message.to_string(), started: Instant::now() });
+    show();
+}
+
+// The message and how long the spinner ran, if one was running
+pub fn stop_spinner() -> Option<(String, Duration)> {
+    let mut indicator = INDICATOR.lock().unwrap();
+    match indicator.take() {
+        Some(Indicator::Spinner { message, started }) => Some((message, started.elapsed())),
+        other => {
+            *indicator = other;
+            None
+        }
+    }
+}
+
+// Moves the bar to `current` of `total`; reaching the total takes it away.
+// Without a label the one from the last call is kept.
+pub fn set_progress(current: u64, total: u64, label: Option<String>) {
+    {
+        let mut indicator = INDICATOR.lock().unwrap();
+        if current >= total {
+            if let Some(Indicator::Bar { .. }) = *indicator {
+                *indicator = None;
+            }
+        } else {
+            let label = label.or_else(|| match &*indicator {
+                Some(Indicator::Bar { label, .. }) => Some(label.clone()),
+                _ => None,
+            });
+            *indicator = Some(Indicator::Bar { label: label.unwrap_or_default(), current, total });
+        }
+    }
+    match current >= total {
+        true => clear(),
+        false => show(),
+    }
+}
+
+// Draws the status on the cursor's line
+fn show() {
+    if let Some(text) = segment() {
+        draw(&text);
+    }
+}
+
+// Whether the status changes on its own and needs redrawing
+pub fn animating() -> bool {
+    matches!(*INDICATOR.lock().unwrap(), Some(Indicator::Spinner { .. }))
+}
+
+// The status as it looks right now, if there is one
+pub fn segment() -> Option<String> {
+    let indicator = INDICATOR.lock().unwrap();
+    Some(match indicator.as_ref()? {
+        Indicator::Spinner { message, started } => {
+            let frame = FRAMES[(started.elapsed().as_millis() / FRAME_INTERVAL.as_millis()) as usize % FRAMES.len()];
+            format!("{} {}", frame.to_string().cyan(), message)
+        }
+        Indicator::Bar { label, current, total } => {
+            let text = format!("{} {}/{}", bar(*current as f64 / *total as f64), current, total);
+            match label.is_empty() {
+                true => text,
+                false => format!("{} {}", label, text),
+            }
+        }
+    })
+}
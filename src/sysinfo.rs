@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::fs;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::state::ShellState;
+
+// How often each provider is re-read; in between, the cached value is used
+const BATTERY_INTERVAL: Duration = Duration::from_secs(60);
+const LOAD_INTERVAL: Duration = Duration::from_secs(5);
+
+// Provider name → when it was last read and what it returned
+type Cache = HashMap<&'static str, (Instant, Option<String>)>;
+
+static CACHE: LazyLock<Mutex<Cache>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn cached(name: &'static str, interval: Duration, fetch: impl FnOnce() -> Option<String>) -> Option<String> {
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((fetched, value)) = cache.get(name) {
+        if fetched.elapsed() < interval {
+            return value.clone();
+        }
+    }
+
+    let value = fetch();
+    cache.insert(name, (Instant::now(), value.clone()));
+    value
+}
+
+// Charge of the first battery, e.g. "85%"; None on machines without one
+pub fn battery() -> Option<String> {
+    cached("battery", BATTERY_INTERVAL, || {
+        fs::read_dir("/sys/class/power_supply")
+            .ok()?
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("BAT"))
+            .find_map(|entry| fs::read_to_string(entry.path().join("capacity")).ok())
+            .map(|capacity| format!("{}%", capacity.trim()))
+    })
+}
+
+// 1-minute load average, e.g. "0.42"
+pub fn load_average() -> Option<String> {
+    cached("load", LOAD_INTERVAL, || {
+        let mut loads = [0f64; 3];
+        if unsafe { libc::getloadavg(loads.as_mut_ptr(), 3) } < 1 {
+            return None;
+        }
+        Some(format!("{:.2}", loads[0]))
+    })
+}
+
+// The host name doesn't change while we run, so it is read once
+pub fn hostname() -> String {
+    cached("hostname", Duration::MAX, || {
+        let mut buffer = [0u8; 256];
+        if unsafe { libc::gethostname(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len()) } != 0 {
+            return None;
+        }
+        let length = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+        Some(String::from_utf8_lossy(&buffer[..length]).to_string())
+    })
+    .unwrap_or_default()
+}
+
+// uid/gid → name; listings look up the same few ids over and over
+static USERS: LazyLock<Mutex<HashMap<u32, String>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+static GROUPS: LazyLock<Mutex<HashMap<u32, String>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Short name of a running process, as ps shows it
+pub fn process_name(pid: u32) -> Option<String> {
+    fs::read_to_string(format!("/proc/{}/comm", pid)).ok().map(|name| name.trim_end().to_string())
+}
+
+// Login name for `uid`, or the number if it has none
+pub fn user_name(uid: u32) -> String {
+    let mut users = USERS.lock().unwrap_or_else(|e| e.into_inner());
+    users
+        .entry(uid)
+        .or_insert_with(|| {
+            let passwd = unsafe { libc::getpwuid(uid) };
+            if passwd.is_null() {
+                return uid.to_string();
+            }
+            unsafe { CStr::from_ptr((*passwd).pw_name) }.to_string_lossy().to_string()
+        })
+        .clone()
+}
+
+// Group name for `gid`, or the number if it has none
+pub fn group_name(gid: u32) -> String {
+    let mut groups = GROUPS.lock().unwrap_or_else(|e| e.into_inner());
+    groups
+        .entry(gid)
+        .or_insert_with(|| {
+            let group = unsafe { libc::getgrgid(gid) };
+            if group.is_null() {
+                return gid.to_string();
+            }
+            unsafe { CStr::from_ptr((*group).gr_name) }.to_string_lossy().to_string()
+        })
+        .clone()
+}
+
+// Whether this session was started over SSH
+pub fn is_ssh(state: &ShellState) -> bool {
+    ["SSH_CONNECTION", "SSH_CLIENT", "SSH_TTY"].iter().any(|name| state.var(name).is_some())
+}
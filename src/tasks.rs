@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use serde::Deserialize;
+
+use crate::errors::CrateResult;
+use crate::i18n::trf;
+
+pub const TASKS_FILE: &str = ".shellbasics-tasks.toml";
+
+// A project's tasks file:
+//
+//   [tasks.test]
+//   description = "Run the tests"
+//   deps = ["build"]
+//   env = { RUST_LOG = "debug" }
+//   run = ["cd tests", "ls"]
+#[derive(Debug, Deserialize)]
+pub struct TaskFile {
+    #[serde(default)]
+    pub tasks: BTreeMap<String, Task>,
+    // Where the file was found; tasks run from there
+    #[serde(skip)]
+    pub dir: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Task {
+    #[serde(default)]
+    pub description: Option<String>,
+    // Tasks to run first, each once even if several tasks need it
+    #[serde(default)]
+    pub deps: Vec<String>,
+    // Variables set while this task's lines run
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    // Shell lines, one after another; a single line may be a plain string
+    #[serde(default)]
+    pub run: Lines,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(untagged)]
+pub enum Lines {
+    #[default]
+    None,
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Lines {
+    pub fn iter(&self) -> impl Iterator<Item = &String> {
+        let lines: &[String] = match self {
+            Lines::None => &[],
+            Lines::One(line) => std::slice::from_ref(line),
+            Lines::Many(lines) => lines,
+        };
+        lines.iter()
+    }
+}
+
+// The tasks file in `dir` or the nearest directory above it, like make and
+// just find theirs
+pub fn find(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors().map(|dir| dir.join(TASKS_FILE)).find(|path| path.is_file())
+}
+
+pub fn load(dir: &Path) -> CrateResult<TaskFile> {
+    let path = find(dir).ok_or_else(|| anyhow!(trf("no {} here or in any directory above", &[&TASKS_FILE])))?;
+    let mut file: TaskFile = toml::from_str(&fs::read_to_string(&path)?).map_err(|e| anyhow!(trf("Invalid tasks file {}: {}", &[&path.display(), &e])))?;
+    file.dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    Ok(file)
+}
+
+impl TaskFile {
+    // `name` and everything it depends on, dependencies first
+    pub fn plan<'a>(&'a self, name: &'a str) -> CrateResult<Vec<&'a str>> {
+        let mut order = Vec::new();
+        self.visit(name, &mut Vec::new(), &mut order)?;
+        Ok(order)
+    }
+
+    fn visit<'a>(&'a self, name: &'a str, path: &mut Vec<&'a str>, order: &mut Vec<&'a str>) -> CrateResult<()> {
+        if order.contains(&name) {
+            return Ok(());
+        }
+        if path.contains(&name) {
+            path.push(name);
+            return Err(anyhow!(trf("task: dependency cycle {}", &[&path.join(" → ")])));
+        }
+        let task = self.tasks.get(name).ok_or_else(|| match path.last() {
+            Some(parent) => anyhow!(trf("task: '{}' depends on '{}', which is not defined", &[parent, &name])),
+            None => anyhow!(trf("task: no task '{}'; task --list shows them", &[&name])),
+        })?;
+
+        path.push(name);
+        for dep in &task.deps {
+            self.visit(dep, path, order)?;
+        }
+        path.pop();
+        order.push(name);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> TaskFile {
+        toml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn plans_dependencies_first() {
+        let file = parse(
+            r#"
+            [tasks.build]
+            run = "ls"
+            [tasks.lint]
+            deps = ["build"]
+            run = ["ls", "pwd"]
+            [tasks.ci]
+            deps = ["lint", "build"]
+            env = { CI = "1" }
+            "#,
+        );
+        assert_eq!(file.plan("ci").unwrap(), ["build", "lint", "ci"]);
+        assert_eq!(file.tasks["lint"].run.iter().collect::<Vec<_>>(), ["ls", "pwd"]);
+        assert!(file.plan("deploy").is_err());
+    }
+
+    #[test]
+    fn finds_cycles() {
+        let file = parse("[tasks.a]\ndeps = [\"b\"]\n[tasks.b]\ndeps = [\"a\"]\n");
+        assert!(file.plan("a").unwrap_err().to_string().contains("a → b → a"));
+    }
+}
@@ -0,0 +1,107 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+
+use crate::dirs;
+use crate::errors::{self, Access, CrateResult};
+use crate::i18n::trf;
+
+// Each directory in here is a template, copied by `new <template> <name>`
+const TEMPLATES_DIR: &str = "templates";
+
+pub fn templates_dir() -> PathBuf {
+    dirs::config_dir().join(TEMPLATES_DIR)
+}
+
+// Names of the templates in `dir`, sorted
+pub fn list(dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| !name.starts_with('.'))
+        .collect();
+    names.sort();
+    names
+}
+
+// What {{name}} and {{date}} stand for
+pub fn variables(name: &str) -> Vec<(&'static str, String)> {
+    vec![("name", name.to_string()), ("date", chrono::Local::now().format("%Y-%m-%d").to_string())]
+}
+
+// Fills `{{variable}}` placeholders; unknown ones are left for the user to see
+pub fn substitute(text: &str, variables: &[(&str, String)]) -> String {
+    variables.iter().fold(text.to_string(), |text, (name, value)| text.replace(&format!("{{{{{}}}}}", name), value))
+}
+
+// Copies the template from `dir` into `target`, which must not exist yet, filling the
+// placeholders in file contents and in file and directory names. Files that
+// aren't text are copied as they are. Returns how many files were written.
+pub fn create(dir: &Path, template: &str, target: &Path, variables: &[(&str, String)]) -> CrateResult<usize> {
+    let source = dir.join(template);
+    if !source.is_dir() {
+        let known = list(dir);
+        return Err(match known.is_empty() {
+            true => anyhow!(trf("new: no templates yet; add directories to {}", &[&dir.display()])),
+            false => anyhow!(trf("new: no template '{}'; there are {}", &[&template, &known.join(", ")])),
+        });
+    }
+    if target.exists() {
+        return Err(anyhow!(trf("new: '{}' already exists", &[&target.display()])));
+    }
+    copy(&source, target, variables)
+}
+
+fn copy(source: &Path, target: &Path, variables: &[(&str, String)]) -> CrateResult<usize> {
+    fs::create_dir(target).map_err(|e| errors::with_path(e, target, Access::Write))?;
+    let mut written = 0;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let name = substitute(&entry.file_name().to_string_lossy(), variables);
+        let (from, to) = (entry.path(), target.join(name));
+        if entry.file_type()?.is_dir() {
+            written += copy(&from, &to, variables)?;
+            continue;
+        }
+        match fs::read_to_string(&from) {
+            Ok(text) => fs::write(&to, substitute(&text, variables))?,
+            Err(_) => drop(fs::copy(&from, &to)?),
+        }
+        // Keeps scripts executable
+        fs::set_permissions(&to, fs::metadata(&from)?.permissions())?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_names_and_contents() {
+        let home = tempfile::tempdir().unwrap();
+        let dir = home.path().join("templates");
+        let template = dir.join("exercise");
+        fs::create_dir_all(template.join("src")).unwrap();
+        fs::write(template.join("README.md"), "# {{name}}\nStarted {{date}} {{unknown}}\n").unwrap();
+        fs::write(template.join("src/{{name}}.txt"), "hello").unwrap();
+        fs::write(template.join("data.bin"), [0xff, 0xfe, 0x00]).unwrap();
+
+        let target = home.path().join("lab1");
+        let variables = vec![("name", "lab1".to_string()), ("date", "2024-09-01".to_string())];
+        assert_eq!(create(&dir, "exercise", &target, &variables).unwrap(), 3);
+        assert_eq!(fs::read_to_string(target.join("README.md")).unwrap(), "# lab1\nStarted 2024-09-01 {{unknown}}\n");
+        assert!(target.join("src/lab1.txt").is_file());
+        assert_eq!(fs::read(target.join("data.bin")).unwrap(), [0xff, 0xfe, 0x00]);
+
+        assert!(create(&dir, "exercise", &target, &variables).is_err());
+        assert!(create(&dir, "missing", &home.path().join("x"), &variables).is_err());
+        assert_eq!(list(&dir), ["exercise"]);
+    }
+}
@@ -0,0 +1,355 @@
+use std::io::{IsTerminal, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Settings to put back if we crash while raw mode is on
+static SAVED: Mutex<Option<libc::termios>> = Mutex::new(None);
+
+// Puts stdin in non-canonical, no-echo mode until dropped
+pub struct RawMode {
+    original: libc::termios,
+}
+
+impl RawMode {
+    pub fn enable() -> Option<RawMode> {
+        RawMode::enable_clearing(libc::ICANON | libc::ECHO)
+    }
+
+    // Also delivers Ctrl+C and Ctrl+Z as keys instead of signals, for the line editor
+    pub fn enable_for_editing() -> Option<RawMode> {
+        RawMode::enable_clearing(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN)
+    }
+
+    fn enable_clearing(local_flags: libc::tcflag_t) -> Option<RawMode> {
+        if !std::io::stdin().is_terminal() {
+            return None;
+        }
+
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+            return None;
+        }
+
+        let mut raw = original;
+        raw.c_lflag &= !local_flags;
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) } != 0 {
+            tracing::warn!(error = %std::io::Error::last_os_error(), "could not enter raw mode");
+            return None;
+        }
+
+        *SAVED.lock().unwrap_or_else(|e| e.into_inner()) = Some(original);
+        tracing::trace!("raw mode on");
+        Some(RawMode { original })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+        *SAVED.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        tracing::trace!("raw mode off");
+    }
+}
+
+// Leaves raw mode immediately; used by the panic hook, which runs before any
+// RawMode guard gets dropped. Returns whether raw mode was on.
+pub fn restore() -> bool {
+    let saved = SAVED.lock().unwrap_or_else(|e| e.into_inner()).take();
+    match saved {
+        Some(original) => {
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &original);
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+// The terminal's current settings, if stdin is a terminal
+pub fn modes() -> Option<libc::termios> {
+    let mut modes: libc::termios = unsafe { std::mem::zeroed() };
+    match std::io::stdin().is_terminal() && unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut modes) } == 0 {
+        true => Some(modes),
+        false => None,
+    }
+}
+
+// Puts back settings from `modes`, once output already written has gone out
+pub fn set_modes(modes: &libc::termios) {
+    unsafe {
+        libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, modes);
+    }
+}
+
+// Keys read straight from the terminal. io::stdin() reads ahead into a
+// buffer of its own, which would take keys typed for the next program away
+// from it; this takes one byte at a time.
+pub struct Keys;
+
+impl Read for Keys {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let length = buf.len().min(1);
+        match unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), length) } {
+            read if read < 0 => Err(std::io::Error::last_os_error()),
+            read => Ok(read as usize),
+        }
+    }
+}
+
+// Columns and rows of the terminal on stdout, if it is one
+pub fn window_size() -> Option<(usize, usize)> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0 && size.ws_col > 0 {
+        Some((size.ws_col as usize, size.ws_row as usize))
+    } else {
+        None
+    }
+}
+
+// Whether stdout is a terminal that understands ANSI/OSC escape sequences
+pub fn is_ansi_terminal() -> bool {
+    std::io::stdout().is_terminal() && std::env::var("TERM").map(|term| term != "dumb").unwrap_or(true)
+}
+
+// Clears the screen and puts the cursor at the top left, like `clear`
+pub const CLEAR_SCREEN: &str = "\x1b[H\x1b[2J";
+
+// OSC 2: sets the window or tab title
+pub fn set_title(title: &str) -> String {
+    // Control characters would end the sequence early
+    let title: String = title.chars().filter(|c| !c.is_control()).collect();
+    format!("\x1b]2;{}\x07", title)
+}
+
+// OSC 7: reports the working directory so the terminal can open new tabs
+// and splits in the same place
+pub fn report_cwd(cwd: &Path) -> String {
+    let mut path = String::new();
+    for &byte in cwd.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            path.push(byte as char);
+        } else {
+            path.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    format!("\x1b]7;file://{}{}\x07", crate::sysinfo::hostname(), path)
+}
+
+// Columns `text` takes on screen, skipping escape sequences
+pub fn visible_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            width += 1;
+        }
+    }
+    width
+}
+
+// Replaces the prompt and the line typed after it, which the cursor has just
+// left, with `replacement`. Wrapped lines are accounted for using the current width.
+pub fn collapse_prompt(prompt: &str, typed: &str, replacement: &str) -> String {
+    let columns = window_size().map(|(columns, _)| columns).unwrap_or(80);
+    let rows: usize = format!("{}{}", prompt, typed)
+        .split('\n')
+        .map(|line| visible_width(line).max(1).div_ceil(columns))
+        .sum();
+    format!("\x1b[{}A\r\x1b[J{}\n", rows, replacement)
+}
+
+// Rewrites the line `rows` above the cursor without moving the cursor, for
+// filling in prompt segments after the user may have started typing
+pub fn redraw_line_above(line: &str, rows: usize) -> String {
+    format!("\x1b7\x1b[{}A\r\x1b[2K{}\x1b8", rows.max(1), line)
+}
+
+// Moves the cursor between two places in text that wraps at `columns`,
+// each given as the number of columns before it counted from the start of
+// the text's first row
+pub fn cursor_motion(from: usize, to: usize, columns: usize) -> String {
+    let columns = columns.max(1);
+    let (from_row, from_column) = (from / columns, from % columns);
+    let (to_row, to_column) = (to / columns, to % columns);
+    let mut motion = String::new();
+    if to_row < from_row {
+        motion.push_str(&format!("\x1b[{}A", from_row - to_row));
+    } else if to_row > from_row {
+        motion.push_str(&format!("\x1b[{}B", to_row - from_row));
+    }
+    if to_column < from_column {
+        motion.push_str(&format!("\x1b[{}D", from_column - to_column));
+    } else if to_column > from_column {
+        motion.push_str(&format!("\x1b[{}C", to_column - from_column));
+    }
+    motion
+}
+
+// Where the cursor is, for `cursor_motion`, once text was written out to
+// `end`: text that ends right at the margin leaves it on the last column
+// until something more is written, not at the start of the next row
+pub fn written_to(end: usize, columns: usize) -> usize {
+    match end > 0 && end.is_multiple_of(columns.max(1)) {
+        true => end - 1,
+        false => end,
+    }
+}
+
+// A one-line progress bar for long copies, with the speed and time left.
+// Draws nothing unless stdout is a terminal, and at most ten times a second.
+pub struct Progress {
+    label: String,
+    // 0 when the size isn't known up front
+    total: u64,
+    done: u64,
+    // Bytes that were already there (a resumed transfer) and don't count
+    // towards the speed
+    skipped: u64,
+    started: Instant,
+    drawn: Option<Instant>,
+}
+
+impl Progress {
+    pub fn new(label: &str, total: u64) -> Progress {
+        Progress { label: label.to_string(), total, done: 0, skipped: 0, started: Instant::now(), drawn: None }
+    }
+
+    // Starts the bar at `bytes` without counting them as copied just now
+    pub fn skip(&mut self, bytes: u64) {
+        self.done += bytes;
+        self.skipped += bytes;
+    }
+
+    pub fn advance(&mut self, bytes: u64) {
+        self.done += bytes;
+        if !is_ansi_terminal() || self.drawn.is_some_and(|drawn| drawn.elapsed() < Duration::from_millis(100)) {
+            return;
+        }
+        self.drawn = Some(Instant::now());
+
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 { (self.done - self.skipped) as f64 / elapsed } else { 0.0 };
+        let speed = format!("{}/s", crate::helpers::human_size(rate as u64));
+        if self.total == 0 {
+            crate::status::draw(&format!("{} {} {}", self.label, crate::helpers::human_size(self.done), speed));
+            return;
+        }
+
+        // The first half second says too little about the speed to guess
+        let left = match self.total.saturating_sub(self.done) {
+            remaining if remaining > 0 && rate > 0.0 && elapsed > 0.5 => {
+                format!(" ETA {}", crate::helpers::human_duration(Duration::from_secs_f64(remaining as f64 / rate)))
+            }
+            _ => String::new(),
+        };
+        crate::status::draw(&format!(
+            "{} {} {} / {} {}{}",
+            self.label,
+            crate::status::bar(self.done as f64 / self.total as f64),
+            crate::helpers::human_size(self.done),
+            crate::helpers::human_size(self.total),
+            speed,
+            left
+        ));
+    }
+
+    // Clears the bar so the summary line takes its place
+    pub fn finish(self) {
+        crate::status::clear();
+    }
+}
+
+// Asks a yes/no question on the terminal; anything but y/yes is a no
+pub fn confirm(question: &str) -> bool {
+    print!("{}", question);
+    let _ = std::io::stdout().flush();
+
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+// Reads a line without echoing it, for passphrases and secrets; None without
+// a terminal or at end of input
+pub fn read_hidden(prompt: &str) -> Option<String> {
+    print!("{}", prompt);
+    let _ = std::io::stdout().flush();
+    let line = {
+        // Only echo is turned off, so the line is still edited and ended as usual
+        let _quiet = RawMode::enable_clearing(libc::ECHO)?;
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line).ok().filter(|read| *read > 0)?;
+        line
+    };
+    println!();
+    Some(line.trim_end_matches(['\n', '\r']).to_string())
+}
+
+// Blocks until a single key is pressed; returns immediately when stdin isn't a terminal
+pub fn wait_for_key() -> Option<u8> {
+    let _raw = RawMode::enable()?;
+    let mut byte = [0u8; 1];
+    Keys.read_exact(&mut byte).ok()?;
+    Some(byte[0])
+}
+
+// Waits up to `timeout` for a key; needs raw mode to be on already
+pub fn poll_key(timeout: Duration) -> Option<u8> {
+    let mut poll = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+    if unsafe { libc::poll(&mut poll, 1, timeout.as_millis().min(i32::MAX as u128) as i32) } <= 0 {
+        return None;
+    }
+    let mut byte = [0u8; 1];
+    match Keys.read(&mut byte) {
+        Ok(1) => Some(byte[0]),
+        _ => None,
+    }
+}
+
+// Switches to the terminal's alternate screen with the cursor hidden, for
+// full-screen views; the shell's screen comes back when dropped
+pub struct AlternateScreen;
+
+impl AlternateScreen {
+    pub fn enter() -> AlternateScreen {
+        print!("\x1b[?1049h\x1b[?25l");
+        let _ = std::io::stdout().flush();
+        AlternateScreen
+    }
+}
+
+impl Drop for AlternateScreen {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = std::io::stdout().flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_moves_across_wrapped_rows() {
+        assert_eq!(cursor_motion(25, 3, 20), "\x1b[1A\x1b[2D");
+        assert_eq!(cursor_motion(3, 45, 20), "\x1b[2B\x1b[2C");
+        assert_eq!(cursor_motion(40, 39, 20), "\x1b[1A\x1b[19C");
+        assert_eq!(cursor_motion(7, 7, 20), "");
+        assert_eq!((written_to(40, 20), written_to(39, 20), written_to(0, 20)), (39, 39, 0));
+    }
+}
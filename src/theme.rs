@@ -0,0 +1,113 @@
+use colored::*;
+
+use crate::config;
+
+// Elements whose colors can be overridden under [colors] in the settings file
+pub const ELEMENTS: [(&str, Color); 9] = [
+    ("directory", Color::Blue),
+    ("executable", Color::Green),
+    ("source", Color::Yellow),
+    ("error", Color::BrightRed),
+    ("success", Color::BrightGreen),
+    ("prompt", Color::BrightGreen),
+    ("user", Color::BrightCyan),
+    ("path", Color::Yellow),
+    ("git_branch", Color::Magenta),
+];
+
+// Built-in palettes selectable with `theme = "..."` or --theme; elements they
+// don't mention fall back to the default palette
+const OCEAN: [(&str, Color); 7] = [
+    ("directory", Color::BrightCyan),
+    ("executable", Color::BrightBlue),
+    ("source", Color::Cyan),
+    ("success", Color::BrightCyan),
+    ("prompt", Color::Blue),
+    ("user", Color::BrightWhite),
+    ("path", Color::Cyan),
+];
+
+const FOREST: [(&str, Color); 6] = [
+    ("directory", Color::Green),
+    ("executable", Color::BrightYellow),
+    ("source", Color::BrightGreen),
+    ("prompt", Color::Green),
+    ("user", Color::Yellow),
+    ("path", Color::BrightGreen),
+];
+
+const MONO: [(&str, Color); 9] = [
+    ("directory", Color::BrightWhite),
+    ("executable", Color::White),
+    ("source", Color::White),
+    ("error", Color::BrightWhite),
+    ("success", Color::White),
+    ("prompt", Color::BrightBlack),
+    ("user", Color::White),
+    ("path", Color::White),
+    ("git_branch", Color::BrightBlack),
+];
+
+pub const THEMES: [(&str, &[(&str, Color)]); 4] = [
+    ("default", &ELEMENTS),
+    ("ocean", &OCEAN),
+    ("forest", &FOREST),
+    ("mono", &MONO),
+];
+
+pub fn exists(name: &str) -> bool {
+    THEMES.iter().any(|(theme, _)| *theme == name)
+}
+
+fn lookup(palette: &[(&str, Color)], element: &str) -> Option<Color> {
+    palette.iter().find(|(name, _)| *name == element).map(|(_, color)| *color)
+}
+
+pub fn color(element: &str) -> Color {
+    let config = config::get();
+
+    if let Some(color) = config.colors.get(element).and_then(|value| parse_color(value)) {
+        return color;
+    }
+
+    config
+        .theme
+        .as_deref()
+        .and_then(|theme| THEMES.iter().find(|(name, _)| *name == theme))
+        .and_then(|(_, palette)| lookup(palette, element))
+        .or_else(|| lookup(&ELEMENTS, element))
+        .unwrap_or(Color::White)
+}
+
+pub fn paint(element: &str, text: &str) -> ColoredString {
+    text.color(color(element))
+}
+
+// Accepts color names understood by `colored` ("red", "bright blue") and #rrggbb
+pub fn parse_color(value: &str) -> Option<Color> {
+    if let Some(hex) = value.strip_prefix('#') {
+        // Checked first, as slicing in the middle of a character panics
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        return Some(Color::TrueColor { r: channel(0)?, g: channel(2)?, b: channel(4)? });
+    }
+
+    value.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_names_and_hex_colors() {
+        assert_eq!(parse_color("#ff8000"), Some(Color::TrueColor { r: 255, g: 128, b: 0 }));
+        assert_eq!(parse_color("bright blue"), Some(Color::BrightBlue));
+        assert_eq!(parse_color("#aé123"), None);
+        assert_eq!(parse_color("#gg0000"), None);
+        assert_eq!(parse_color("#fff"), None);
+        assert_eq!(parse_color("no such color"), None);
+    }
+}
@@ -0,0 +1,332 @@
+use std::iter::Peekable;
+use std::ops::Range;
+use std::str::CharIndices;
+
+use anyhow::anyhow;
+
+use crate::errors::CrateResult;
+use crate::i18n::tr;
+
+// A word of a command line. `pattern` is set when the word has unquoted
+// glob characters; it is the word with its quoted parts backslash-escaped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Word {
+    pub text: String,
+    pub pattern: Option<String>,
+}
+
+// Splits a command line into words the way a POSIX shell would for the
+// quoting it supports: 'single quotes' are literal, "double quotes" allow
+// \" and \\ escapes, and a backslash outside quotes escapes the next character.
+pub fn tokenize(line: &str) -> CrateResult<Vec<String>> {
+    Ok(words(line)?.into_iter().map(|word| word.text).collect())
+}
+
+pub fn words(line: &str) -> CrateResult<Vec<Word>> {
+    let mut words = Vec::new();
+    let mut current = WordBuilder::default();
+    // Distinguishes an empty quoted word ("") from no word at all
+    let mut in_word = false;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current).finish());
+                    in_word = false;
+                }
+            }
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => current.quoted(c),
+                        None => return Err(anyhow!(tr("Unterminated single quote"))),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => current.quoted(c),
+                            Some(c) => {
+                                current.quoted('\\');
+                                current.quoted(c);
+                            }
+                            None => return Err(anyhow!(tr("Unterminated double quote"))),
+                        },
+                        Some(c) => current.quoted(c),
+                        None => return Err(anyhow!(tr("Unterminated double quote"))),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                // A trailing backslash has nothing to escape and stays literal
+                current.quoted(chars.next().unwrap_or('\\'));
+            }
+            c => {
+                in_word = true;
+                current.unquoted(c);
+            }
+        }
+    }
+
+    if in_word {
+        words.push(current.finish());
+    }
+    Ok(words)
+}
+
+#[derive(Default)]
+struct WordBuilder {
+    text: String,
+    pattern: String,
+    globbing: bool,
+}
+
+impl WordBuilder {
+    fn quoted(&mut self, c: char) {
+        self.text.push(c);
+        if "*?[\\".contains(c) {
+            self.pattern.push('\\');
+        }
+        self.pattern.push(c);
+    }
+
+    fn unquoted(&mut self, c: char) {
+        self.text.push(c);
+        self.pattern.push(c);
+        self.globbing |= "*?[".contains(c);
+    }
+
+    fn finish(self) -> Word {
+        Word { text: self.text, pattern: self.globbing.then_some(self.pattern) }
+    }
+}
+
+// A `<(command)` word: where it is in the line and the command inside
+#[derive(Debug, PartialEq)]
+pub struct ProcessSubstitution {
+    pub range: Range<usize>,
+    pub command: String,
+}
+
+// Finds the `<(command)` words outside quotes. Parentheses nest, and quotes
+// inside the command are left for the command's own parse.
+pub fn process_substitutions(line: &str) -> CrateResult<Vec<ProcessSubstitution>> {
+    let mut found = Vec::new();
+    let mut chars = line.char_indices().peekable();
+    let mut word_start = true;
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                word_start = true;
+                continue;
+            }
+            '\'' | '"' => skip_quoted(&mut chars, c),
+            '\\' => drop(chars.next()),
+            '<' if word_start && chars.peek().is_some_and(|&(_, c)| c == '(') => {
+                chars.next();
+                let mut depth = 1;
+                let end = loop {
+                    match chars.next() {
+                        Some((end, ')')) if depth == 1 => break end,
+                        Some((_, ')')) => depth -= 1,
+                        Some((_, '(')) => depth += 1,
+                        Some((_, quote @ ('\'' | '"'))) => skip_quoted(&mut chars, quote),
+                        Some((_, '\\')) => drop(chars.next()),
+                        Some(_) => {}
+                        None => return Err(anyhow!(tr("Unterminated <( )"))),
+                    }
+                };
+                found.push(ProcessSubstitution { range: start..end + 1, command: line[start + 2..end].to_string() });
+            }
+            _ => {}
+        }
+        word_start = false;
+    }
+    Ok(found)
+}
+
+// The parts of `line` between the `|`s outside quotes and `<( )`, as typed
+pub fn split_pipes(line: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut depth = 0;
+    let mut chars = line.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\'' | '"' => skip_quoted(&mut chars, c),
+            '\\' => drop(chars.next()),
+            '<' if chars.peek().is_some_and(|&(_, c)| c == '(') => {
+                chars.next();
+                depth += 1;
+            }
+            '(' if depth > 0 => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            '|' if depth == 0 => {
+                parts.push(&line[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&line[start..]);
+    parts
+}
+
+// A `>`, `>>`, `2>` or `2>>` outside quotes with the word after it: where
+// they are in the line, the operator, and the word as typed
+#[derive(Debug, PartialEq)]
+pub struct RedirectionWord {
+    pub range: Range<usize>,
+    pub operator: &'static str,
+    pub target: String,
+}
+
+// Finds the redirections in `line`. `2>` only counts as a word of its own,
+// so `a2>b` sends the output of `a2` to `b`.
+pub fn redirection_words(line: &str) -> Vec<RedirectionWord> {
+    let mut found = Vec::new();
+    let mut chars = line.char_indices().peekable();
+    let mut word_start = true;
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                word_start = true;
+                continue;
+            }
+            '\'' | '"' => skip_quoted(&mut chars, c),
+            '\\' => drop(chars.next()),
+            '>' | '2' if c == '>' || (word_start && chars.peek().is_some_and(|&(_, c)| c == '>')) => {
+                let errors = c == '2';
+                if errors {
+                    chars.next();
+                }
+                let append = chars.next_if(|&(_, c)| c == '>').is_some();
+                while chars.next_if(|&(_, c)| c.is_whitespace()).is_some() {}
+                let target = chars.peek().map_or(line.len(), |&(index, _)| index);
+                while let Some((_, c)) = chars.next_if(|&(_, c)| !c.is_whitespace() && c != '>') {
+                    match c {
+                        '\'' | '"' => skip_quoted(&mut chars, c),
+                        '\\' => drop(chars.next()),
+                        _ => {}
+                    }
+                }
+                let end = chars.peek().map_or(line.len(), |&(index, _)| index);
+                let operator = match (errors, append) {
+                    (false, false) => ">",
+                    (false, true) => ">>",
+                    (true, false) => "2>",
+                    (true, true) => "2>>",
+                };
+                found.push(RedirectionWord { range: start..end, operator, target: line[target..end].to_string() });
+            }
+            _ => {}
+        }
+        word_start = false;
+    }
+    found
+}
+
+// Moves past the closing quote; an unterminated one is reported by words()
+fn skip_quoted(chars: &mut Peekable<CharIndices>, quote: char) {
+    while let Some((_, c)) = chars.next() {
+        match c {
+            '\\' if quote == '"' => drop(chars.next()),
+            c if c == quote => return,
+            _ => {}
+        }
+    }
+}
+
+// Quotes `word` so that tokenize() gives it back unchanged
+pub fn quote(word: &str) -> String {
+    let plain = |c: char| c.is_alphanumeric() || "-_./=:,+@%~".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        return word.to_string();
+    }
+
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn splits_and_unquotes() {
+        let tokens = tokenize(r#"cp 'My File.txt' "dir/with \"quotes\"" plain\ space"#).unwrap();
+        assert_eq!(tokens, ["cp", "My File.txt", r#"dir/with "quotes""#, "plain space"]);
+    }
+
+    #[test]
+    fn keeps_empty_quoted_words() {
+        assert_eq!(tokenize(r#"echo "" ''"#).unwrap(), ["echo", "", ""]);
+    }
+
+    #[test]
+    fn rejects_unbalanced_quotes() {
+        assert!(tokenize("cat 'notes.txt").is_err());
+        assert!(tokenize(r#"cat "notes.txt"#).is_err());
+        assert!(tokenize(r#"cat "notes\"#).is_err());
+    }
+
+    #[test]
+    fn marks_unquoted_globs() {
+        let words = words(r#"rm *.tmp '*.log' "a?"b? \[x"#).unwrap();
+        let patterns: Vec<_> = words.iter().map(|word| word.pattern.as_deref()).collect();
+        assert_eq!(patterns, [None, Some("*.tmp"), None, Some(r"a\?b?"), None]);
+    }
+
+    #[test]
+    fn finds_process_substitutions() {
+        let line = r#"cat <(find . '*.rs') '<(not this)' x<(nor) <(echo "(a)" (b))"#;
+        let found = process_substitutions(line).unwrap();
+        let commands: Vec<_> = found.iter().map(|found| found.command.as_str()).collect();
+        assert_eq!(commands, ["find . '*.rs'", r#"echo "(a)" (b)"#]);
+        assert_eq!(&line[found[0].range.clone()], "<(find . '*.rs')");
+        assert!(process_substitutions("cat <(ls").is_err());
+    }
+
+    #[test]
+    fn splits_at_pipes() {
+        assert_eq!(split_pipes("cat notes.txt | grep error|cat"), ["cat notes.txt ", " grep error", "cat"]);
+        assert_eq!(split_pipes(r#"echo 'a|b' "c|d" e\|f <(ls | cat)"#).len(), 1);
+        assert_eq!(split_pipes("echo a ||"), ["echo a ", "", ""]);
+    }
+
+    #[test]
+    fn finds_redirections() {
+        let line = r#"echo 'a > b' x2>no 2> "err file" >>log.txt >"#;
+        let found = redirection_words(line);
+        let words: Vec<_> = found.iter().map(|found| (found.operator, found.target.as_str())).collect();
+        assert_eq!(words, [(">", "no"), ("2>", r#""err file""#), (">>", "log.txt"), (">", "")]);
+        assert_eq!(&line[found[1].range.clone()], r#"2> "err file""#);
+    }
+
+    proptest! {
+        #[test]
+        fn never_panics(line in "\\PC*") {
+            let _ = tokenize(&line);
+        }
+
+        #[test]
+        fn never_panics_on_shell_syntax(line in "[ '\"\\\\a-z\u{0}é🦀\t]*") {
+            let _ = tokenize(&line);
+        }
+
+        #[test]
+        fn quote_round_trips(words in prop::collection::vec(any::<String>(), 0..6)) {
+            let line = words.iter().map(|word| quote(word)).collect::<Vec<_>>().join(" ");
+            prop_assert_eq!(tokenize(&line).unwrap(), words);
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{LazyLock, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::state::ShellState;
+
+// How long a directory's versions are shown before they are looked up again
+const REFRESH_AFTER: Duration = Duration::from_secs(30);
+
+// The languages a project can pin a version of
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Language {
+    Rust,
+    Node,
+    Python,
+}
+
+pub const LANGUAGES: [Language; 3] = [Language::Rust, Language::Node, Language::Python];
+
+impl Language {
+    // Also the prompt placeholder, {rust} and so on
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::Rust => "rust",
+            Language::Node => "node",
+            Language::Python => "python",
+        }
+    }
+
+    // Files that pin a version, nearest first
+    fn pin_files(self) -> &'static [&'static str] {
+        match self {
+            Language::Rust => &["rust-toolchain.toml", "rust-toolchain"],
+            Language::Node => &[".nvmrc", ".node-version"],
+            Language::Python => &[".python-version"],
+        }
+    }
+
+    // Files that mark a project without pinning anything; the installed
+    // version is asked for instead
+    fn project_files(self) -> &'static [&'static str] {
+        match self {
+            Language::Rust => &["Cargo.toml"],
+            Language::Node => &["package.json"],
+            Language::Python => &["pyproject.toml", "setup.py", "requirements.txt"],
+        }
+    }
+
+    fn program(self) -> &'static str {
+        match self {
+            Language::Rust => "rustc",
+            Language::Node => "node",
+            Language::Python => "python3",
+        }
+    }
+}
+
+// What was found for one directory, and when
+struct Entry {
+    versions: Vec<(Language, String)>,
+    checked: Instant,
+}
+
+static CACHE: LazyLock<RwLock<HashMap<PathBuf, Entry>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+// Directories with a lookup under way, so each prompt doesn't start another
+static PENDING: LazyLock<RwLock<HashSet<PathBuf>>> = LazyLock::new(|| RwLock::new(HashSet::new()));
+
+// The versions last found for the working directory. The prompt never waits:
+// a missing or old entry starts a lookup in the background, and the next
+// prompt shows what it found.
+pub fn cached(state: &ShellState) -> Vec<(Language, String)> {
+    let cache = CACHE.read().unwrap_or_else(|e| e.into_inner());
+    let entry = cache.get(&state.cwd);
+    if entry.is_none_or(|entry| entry.checked.elapsed() > REFRESH_AFTER) {
+        refresh(state);
+    }
+    entry.map(|entry| entry.versions.clone()).unwrap_or_default()
+}
+
+fn refresh(state: &ShellState) {
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    if !PENDING.write().unwrap_or_else(|e| e.into_inner()).insert(state.cwd.clone()) {
+        return;
+    }
+
+    // Commands are built up front so the task can outlive the borrow of `state`
+    let cwd = state.cwd.clone();
+    let mut commands: Vec<tokio::process::Command> = LANGUAGES.iter().map(|language| tokio::process::Command::from(state.command(language.program()))).collect();
+    runtime.spawn(async move {
+        let mut versions = Vec::new();
+        for (language, command) in LANGUAGES.into_iter().zip(&mut commands) {
+            let version = match detect(&cwd, language) {
+                Some(Found::Pinned(version)) => Some(version),
+                Some(Found::Project) => installed(command, language).await,
+                None => None,
+            };
+            versions.extend(version.map(|version| (language, version)));
+        }
+        CACHE.write().unwrap_or_else(|e| e.into_inner()).insert(cwd.clone(), Entry { versions, checked: Instant::now() });
+        PENDING.write().unwrap_or_else(|e| e.into_inner()).remove(&cwd);
+    });
+}
+
+#[derive(Debug, PartialEq)]
+enum Found {
+    Pinned(String),
+    Project,
+}
+
+// Looks in `dir` and each directory above it; the nearest pin or project
+// file decides
+fn detect(dir: &Path, language: Language) -> Option<Found> {
+    for dir in dir.ancestors() {
+        for name in language.pin_files() {
+            if let Some(version) = fs::read_to_string(dir.join(name)).ok().and_then(|text| pinned(language, &text)) {
+                return Some(Found::Pinned(version));
+            }
+        }
+        if language.project_files().iter().any(|name| dir.join(name).is_file()) {
+            return Some(Found::Project);
+        }
+    }
+    None
+}
+
+// The version a pin file asks for. rust-toolchain may be TOML
+// (`[toolchain] channel = "1.80"`) or, in its legacy form, just the channel.
+fn pinned(language: Language, text: &str) -> Option<String> {
+    if language == Language::Rust && text.contains("[toolchain]") {
+        let table: toml::Table = toml::from_str(text).ok()?;
+        return table.get("toolchain")?.get("channel")?.as_str().map(str::to_string);
+    }
+    let line = text.lines().map(str::trim).find(|line| !line.is_empty() && !line.starts_with('#'))?;
+    Some(line.trim_start_matches('v').to_string())
+}
+
+async fn installed(command: &mut tokio::process::Command, language: Language) -> Option<String> {
+    command.arg("--version").stdin(Stdio::null()).stderr(Stdio::null()).kill_on_drop(true);
+    let output = tokio::time::timeout(Duration::from_secs(5), command.output()).await.ok()?.ok()?;
+    if !output.status.success() {
+        tracing::debug!(program = language.program(), "version lookup failed");
+        return None;
+    }
+    version_number(&String::from_utf8_lossy(&output.stdout))
+}
+
+// The first word that looks like a version: `rustc 1.80.0 (051478957 2024-07-21)`,
+// `v20.11.1`, `Python 3.12.2`
+fn version_number(output: &str) -> Option<String> {
+    output
+        .split_whitespace()
+        .map(|word| word.trim_start_matches('v'))
+        .find(|word| word.starts_with(|c: char| c.is_ascii_digit()) && word.contains('.'))
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_pins_and_versions() {
+        assert_eq!(pinned(Language::Rust, "[toolchain]\nchannel = \"1.80.0\"\ncomponents = [\"clippy\"]\n").as_deref(), Some("1.80.0"));
+        assert_eq!(pinned(Language::Rust, "nightly-2024-05-01\n").as_deref(), Some("nightly-2024-05-01"));
+        assert_eq!(pinned(Language::Node, "\nv20.11.1\n").as_deref(), Some("20.11.1"));
+        assert_eq!(pinned(Language::Python, "").as_deref(), None);
+        assert_eq!(version_number("rustc 1.80.0 (051478957 2024-07-21)").as_deref(), Some("1.80.0"));
+        assert_eq!(version_number("Python 3.12.2\n").as_deref(), Some("3.12.2"));
+    }
+
+    #[test]
+    fn nearest_file_decides() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".python-version"), "3.11\n").unwrap();
+        fs::create_dir(dir.path().join("app")).unwrap();
+        fs::write(dir.path().join("app/package.json"), "{}").unwrap();
+        let app = dir.path().join("app");
+        assert_eq!(detect(&app, Language::Python), Some(Found::Pinned("3.11".to_string())));
+        assert_eq!(detect(&app, Language::Node), Some(Found::Project));
+    }
+}
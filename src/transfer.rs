@@ -0,0 +1,224 @@
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use anyhow::anyhow;
+use openssh_sftp_client::file::TokioCompatFile;
+use openssh_sftp_client::{Sftp, SftpOptions};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+use tokio::process::Child;
+
+use crate::command::TransferOptions;
+use crate::errors::CrateResult;
+use crate::helpers;
+use crate::i18n::trf;
+use crate::interrupt;
+use crate::state::ShellState;
+use crate::terminal::Progress;
+
+// The `host:path` side of push and pull. An empty path is the remote home.
+#[derive(Debug, PartialEq)]
+pub struct Remote {
+    pub host: String,
+    pub path: String,
+}
+
+impl Remote {
+    pub fn parse(spec: &str) -> CrateResult<Remote> {
+        match spec.split_once(':') {
+            Some((host, path)) if !host.is_empty() && !host.contains('/') => {
+                Ok(Remote { host: host.to_string(), path: path.to_string() })
+            }
+            _ => Err(anyhow!(trf("expected host:path, got '{}'", &[&spec]))),
+        }
+    }
+}
+
+// What a finished transfer did
+pub struct Transfer {
+    // Where the file ended up, as the user would write it
+    pub destination: String,
+    pub size: u64,
+    // Bytes that were already there and skipped by -c
+    pub resumed: u64,
+}
+
+// An SFTP session over the system ssh, so ~/.ssh/config, keys and the agent
+// all work as they do for ssh itself
+struct Session {
+    sftp: Sftp,
+    ssh: Child,
+}
+
+impl Session {
+    async fn open(state: &ShellState, host: &str) -> CrateResult<Session> {
+        let mut command = tokio::process::Command::from(state.command("ssh"));
+        command.args(["-s", "--", host, "sftp"]).stdin(Stdio::piped()).stdout(Stdio::piped()).kill_on_drop(true);
+        let mut ssh = command.spawn().map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => anyhow!(trf("{} is not installed", &[&"ssh"])),
+            _ => e.into(),
+        })?;
+
+        let (Some(stdin), Some(stdout)) = (ssh.stdin.take(), ssh.stdout.take()) else {
+            return Err(anyhow!(trf("could not start an SFTP session with {}", &[&host])));
+        };
+        // ssh exits early when it can't connect or log in, and has already
+        // said why on stderr
+        let sftp = tokio::select! {
+            sftp = Sftp::new(stdin, stdout, SftpOptions::default()) => sftp.ok(),
+            _ = ssh.wait() => None,
+        };
+        match sftp {
+            Some(sftp) => Ok(Session { sftp, ssh }),
+            None => Err(anyhow!(trf("could not start an SFTP session with {}", &[&host]))),
+        }
+    }
+
+    async fn close(mut self) -> CrateResult<()> {
+        self.sftp.close().await?;
+        self.ssh.wait().await?;
+        Ok(())
+    }
+
+    // Size of a remote file, None if there is nothing there; errors for directories
+    async fn size(&self, path: &Path) -> CrateResult<Option<u64>> {
+        match self.sftp.fs().metadata(path).await {
+            Ok(metadata) if metadata.file_type().is_some_and(|kind| kind.is_dir()) => {
+                Err(anyhow!(trf("'{}' is a directory", &[&path.display()])))
+            }
+            Ok(metadata) => Ok(Some(metadata.len().unwrap_or(0))),
+            Err(_) => Ok(None),
+        }
+    }
+
+    async fn is_dir(&self, path: &Path) -> bool {
+        let metadata = self.sftp.fs().metadata(path).await;
+        metadata.is_ok_and(|metadata| metadata.file_type().is_some_and(|kind| kind.is_dir()))
+    }
+}
+
+// Uploads `local` to `remote`. A remote directory (or a path ending in /)
+// gets the file under its own name. With -c an existing shorter file on the
+// host is taken as an interrupted upload and only the rest is sent.
+pub async fn push(state: &ShellState, local: &str, remote: &Remote, options: &TransferOptions) -> CrateResult<Transfer> {
+    let source = state.resolve(local);
+    let mut file = tokio::fs::File::open(&source).await?;
+    let size = file.metadata().await?.len();
+    let name = source.file_name().map(PathBuf::from).unwrap_or_default();
+
+    let session = Session::open(state, &remote.host).await?;
+    let mut target = PathBuf::from(&remote.path);
+    if remote.path.is_empty() || remote.path.ends_with('/') || session.is_dir(&target).await {
+        target.push(&name);
+    }
+    let destination = format!("{}:{}", remote.host, target.display());
+
+    let existing = session.size(&target).await?;
+    let resumed = resume_offset(options, existing, size, &destination)?;
+    let remote_file = session.sftp.options().write(true).create(true).truncate(resumed == 0).open(&target).await?;
+    let mut remote_file = Box::pin(TokioCompatFile::new(remote_file));
+
+    file.seek(SeekFrom::Start(resumed)).await?;
+    remote_file.seek(SeekFrom::Start(resumed)).await?;
+    let label = trf("Uploading {}", &[&name.display()]);
+    copy(&mut file, &mut remote_file, &label, resumed, size).await?;
+    remote_file.flush().await?;
+    drop(remote_file);
+
+    session.close().await?;
+    Ok(Transfer { destination, size, resumed })
+}
+
+// Downloads `remote` to `local`, which may be a directory. With -c an
+// existing shorter local file is taken as an interrupted download.
+pub async fn pull(state: &ShellState, remote: &Remote, local: &str, options: &TransferOptions) -> CrateResult<Transfer> {
+    let session = Session::open(state, &remote.host).await?;
+    let source = PathBuf::from(&remote.path);
+    let Some(size) = session.size(&source).await? else {
+        return Err(anyhow!(trf("'{}:{}' does not exist", &[&remote.host, &remote.path])));
+    };
+
+    let mut target = state.resolve(local);
+    let mut destination = local.to_string();
+    if target.is_dir() {
+        let name = source.file_name().map(PathBuf::from).unwrap_or_default();
+        target.push(&name);
+        destination = Path::new(local).join(&name).display().to_string();
+    }
+
+    let existing = std::fs::metadata(&target).ok().map(|metadata| metadata.len());
+    let resumed = resume_offset(options, existing, size, &destination)?;
+    let mut file = tokio::fs::OpenOptions::new().write(true).create(true).truncate(resumed == 0).open(&target).await?;
+    let remote_file = session.sftp.open(&source).await?;
+    let mut remote_file = Box::pin(TokioCompatFile::new(remote_file));
+
+    remote_file.seek(SeekFrom::Start(resumed)).await?;
+    file.seek(SeekFrom::Start(resumed)).await?;
+    let name = source.file_name().map(PathBuf::from).unwrap_or_default();
+    let label = trf("Downloading {}", &[&name.display()]);
+    copy(&mut remote_file, &mut file, &label, resumed, size).await?;
+    file.flush().await?;
+    drop(remote_file);
+
+    session.close().await?;
+    Ok(Transfer { destination, size, resumed })
+}
+
+// Where a transfer starts: 0 unless -c finds a partial copy to continue
+fn resume_offset(options: &TransferOptions, existing: Option<u64>, size: u64, destination: &str) -> CrateResult<u64> {
+    match existing {
+        Some(existing) if options.resume && existing > size => {
+            Err(anyhow!(trf("'{}' is larger than the source; not resuming", &[&destination])))
+        }
+        Some(existing) if options.resume => Ok(existing),
+        _ => Ok(0),
+    }
+}
+
+async fn copy(
+    from: &mut (impl AsyncRead + Unpin),
+    to: &mut (impl AsyncWrite + Unpin),
+    label: &str,
+    start: u64,
+    size: u64,
+) -> CrateResult<()> {
+    let mut progress = Progress::new(label, size);
+    progress.skip(start);
+    let mut done = start;
+    let mut buffer = vec![0; 256 * 1024];
+    let result = async {
+        loop {
+            if interrupt::requested() {
+                return Err(anyhow!(trf("interrupted after {}; run again with -c to resume", &[&helpers::human_size(done)])));
+            }
+            let read = from.read(&mut buffer).await?;
+            if read == 0 {
+                return Ok(());
+            }
+            to.write_all(&buffer[..read]).await?;
+            done += read as u64;
+            progress.advance(read as u64);
+        }
+    }
+    .await;
+    progress.finish();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remote_specs() {
+        assert_eq!(Remote::parse("pi:backups/a.tar").unwrap(), Remote { host: "pi".into(), path: "backups/a.tar".into() });
+        assert_eq!(Remote::parse("me@pi:").unwrap(), Remote { host: "me@pi".into(), path: String::new() });
+        assert!(Remote::parse("notes.txt").is_err());
+        assert!(Remote::parse("./a:b").is_err());
+
+        let resume = TransferOptions { resume: true };
+        assert_eq!(resume_offset(&resume, Some(10), 30, "x").unwrap(), 10);
+        assert_eq!(resume_offset(&TransferOptions::default(), Some(10), 30, "x").unwrap(), 0);
+        assert!(resume_offset(&resume, Some(40), 30, "x").is_err());
+    }
+}
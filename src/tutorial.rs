@@ -0,0 +1,165 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use colored::*;
+
+use crate::dirs;
+use crate::errors::CrateResult;
+use crate::i18n::{tr, trf};
+
+const PROGRESS_FILE: &str = "tutorial";
+const WORKSPACE_DIR: &str = "shellbasics_tutorial";
+
+const SAMPLE_LOG: &str = "INFO server started\nINFO request handled\nERROR disk almost full\nINFO request handled\n";
+
+struct Lesson {
+    title: &'static str,
+    instructions: &'static str,
+    // Receives the workspace directory, the shell's working directory and the
+    // last command line entered
+    check: fn(&Path, &Path, &str) -> bool,
+}
+
+const LESSONS: [Lesson; 7] = [
+    Lesson {
+        title: "Moving around",
+        instructions: "Change into the tutorial workspace with `cd {}`.",
+        check: |workspace, cwd, _| cwd == workspace,
+    },
+    Lesson {
+        title: "Creating directories",
+        instructions: "Create a directory called `notes` with `mkdir notes`.",
+        check: |workspace, _, _| workspace.join("notes").is_dir(),
+    },
+    Lesson {
+        title: "Creating files",
+        instructions: "Create an empty file `notes/todo.txt` with `touch`.",
+        check: |workspace, _, _| workspace.join("notes/todo.txt").is_file(),
+    },
+    Lesson {
+        title: "Copying files",
+        instructions: "Make a backup: copy `notes/todo.txt` to `notes/todo_backup.txt` with `cp`.",
+        check: |workspace, _, _| workspace.join("notes/todo_backup.txt").is_file(),
+    },
+    Lesson {
+        title: "Searching inside files",
+        instructions: "The file `server.log` has one error in it. Find it with `grep server.log ERROR`.",
+        check: |_, _, line| {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            words.first() == Some(&"grep") && words.contains(&"server.log") && words.contains(&"ERROR")
+        },
+    },
+    Lesson {
+        title: "Renaming files",
+        instructions: "Rename `notes/todo_backup.txt` to `notes/archive.txt` with `mv`.",
+        check: |workspace, _, _| {
+            workspace.join("notes/archive.txt").is_file() && !workspace.join("notes/todo_backup.txt").exists()
+        },
+    },
+    Lesson {
+        title: "Cleaning up",
+        instructions: "Remove the `notes` directory and everything in it with `rm -r notes`.",
+        check: |workspace, _, _| !workspace.join("notes").exists(),
+    },
+];
+
+// Saved between sessions as "<active> <lesson index>"
+struct Progress {
+    active: bool,
+    lesson: usize,
+}
+
+fn home() -> PathBuf {
+    PathBuf::from(std::env::var("HOME").unwrap_or_else(|_| ".".to_string()))
+}
+
+fn workspace() -> PathBuf {
+    home().join(WORKSPACE_DIR)
+}
+
+fn load() -> Progress {
+    let contents = fs::read_to_string(dirs::state_dir().join(PROGRESS_FILE)).unwrap_or_default();
+    let mut fields = contents.split_whitespace();
+
+    Progress {
+        active: fields.next() == Some("active"),
+        lesson: fields.next().and_then(|n| n.parse().ok()).unwrap_or(0),
+    }
+}
+
+fn save(progress: &Progress) -> CrateResult<()> {
+    let state = if progress.active { "active" } else { "paused" };
+    fs::write(dirs::state_dir().join(PROGRESS_FILE), format!("{} {}\n", state, progress.lesson))?;
+    Ok(())
+}
+
+fn describe(index: usize) -> String {
+    let Some(lesson) = LESSONS.get(index) else {
+        return format!("{}\n", tr("You have completed every lesson. Well done!").bright_green().bold());
+    };
+
+    let workspace = workspace();
+    format!(
+        "{} {}\n  {}\n",
+        trf("Lesson {}/{}:", &[&(index + 1), &LESSONS.len()]).bright_yellow().bold(),
+        tr(lesson.title).cyan().bold(),
+        trf(lesson.instructions, &[&workspace.display()])
+    )
+}
+
+pub fn start() -> CrateResult<String> {
+    let mut progress = load();
+    progress.active = true;
+    save(&progress)?;
+
+    // The workspace is recreated on demand so the grep lesson always has its log
+    let workspace = workspace();
+    fs::create_dir_all(&workspace)?;
+    fs::write(workspace.join("server.log"), SAMPLE_LOG)?;
+
+    Ok(describe(progress.lesson))
+}
+
+pub fn stop() -> CrateResult<()> {
+    let mut progress = load();
+    progress.active = false;
+    save(&progress)
+}
+
+pub fn reset() -> CrateResult<()> {
+    save(&Progress { active: false, lesson: 0 })
+}
+
+pub fn status() -> String {
+    let progress = load();
+    let done = progress.lesson.min(LESSONS.len());
+    let mut result = trf("Completed {} of {} lessons.", &[&done, &LESSONS.len()]);
+    result.push('\n');
+
+    for (i, lesson) in LESSONS.iter().enumerate() {
+        let mark = if i < done { "✔".green() } else { "·".bright_black() };
+        result.push_str(&format!("  {} {}\n", mark, tr(lesson.title)));
+    }
+
+    result
+}
+
+// Called after every command; returns feedback when the current lesson was just completed
+pub fn after_command(cwd: &Path, line: &str) -> CrateResult<Option<String>> {
+    let mut progress = load();
+    if !progress.active || progress.lesson >= LESSONS.len() {
+        return Ok(None);
+    }
+
+    if !(LESSONS[progress.lesson].check)(&workspace(), cwd, line) {
+        return Ok(None);
+    }
+
+    progress.lesson += 1;
+    if progress.lesson >= LESSONS.len() {
+        progress.active = false;
+    }
+    save(&progress)?;
+
+    Ok(Some(format!("{}\n{}", tr("✔ Lesson complete!").bright_green().bold(), describe(progress.lesson))))
+}
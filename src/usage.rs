@@ -0,0 +1,266 @@
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{IsTerminal, Read, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use colored::Colorize;
+
+use crate::editor::{self, Key};
+use crate::errors::{self, Access, CrateResult};
+use crate::helpers;
+use crate::i18n::{tr, trf};
+use crate::interrupt;
+use crate::terminal::{self, AlternateScreen, RawMode};
+
+// A file or directory and the space it takes, children biggest first
+#[derive(Debug)]
+pub struct Node {
+    pub name: String,
+    pub size: u64,
+    pub is_dir: bool,
+    pub children: Vec<Node>,
+}
+
+// What the scan has found so far, for the progress line
+#[derive(Default)]
+struct Counts {
+    files: AtomicU64,
+    bytes: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+// Where the explorer is: the child indexes leading to the directory shown,
+// and the selected row in it
+struct View {
+    path: Vec<usize>,
+    selected: usize,
+    top: usize,
+    message: Option<String>,
+}
+
+// Scans `root` in the background while showing progress, then lets the
+// user walk the tree biggest-first and delete what they find
+pub fn run(root: PathBuf, cwd: PathBuf) -> CrateResult<()> {
+    if !terminal::is_ansi_terminal() || !std::io::stdin().is_terminal() {
+        return Err(anyhow!(tr("usage needs a terminal; du works anywhere")));
+    }
+    let metadata = fs::symlink_metadata(&root).map_err(|e| errors::with_path(e, &root, Access::Read))?;
+    if !metadata.is_dir() {
+        return Err(anyhow!(trf("'{}' is not a directory", &[&root.display()])));
+    }
+
+    let _raw = RawMode::enable();
+    let _screen = AlternateScreen::enter();
+    let Some(tree) = scan_with_progress(&root, metadata)? else {
+        return Ok(());
+    };
+    browse(tree, &root, &cwd)
+}
+
+fn scan_with_progress(root: &Path, metadata: fs::Metadata) -> CrateResult<Option<Node>> {
+    let counts = Arc::new(Counts::default());
+    let scanner = {
+        let (root, counts) = (root.to_path_buf(), counts.clone());
+        std::thread::spawn(move || {
+            let name = root.display().to_string();
+            scan(&root, name, &metadata, metadata.dev(), &mut HashSet::new(), &counts)
+        })
+    };
+
+    while !scanner.is_finished() {
+        let line = trf(
+            "Scanning {}: {} files, {}",
+            &[&root.display(), &counts.files.load(Ordering::Relaxed), &helpers::human_size(counts.bytes.load(Ordering::Relaxed))],
+        );
+        print!("\x1b[H\x1b[2K{}\r\n\x1b[2K{}", line, tr("q stops the scan").bright_black());
+        std::io::stdout().flush()?;
+        if matches!(terminal::poll_key(Duration::from_millis(100)), Some(b'q' | b'Q')) || interrupt::requested() {
+            counts.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+    let tree = scanner.join().map_err(|_| anyhow!(tr("the scan failed")))?;
+    Ok((!counts.cancelled.load(Ordering::Relaxed)).then_some(tree))
+}
+
+// Sizes are allocated blocks, hard links count once and other filesystems
+// are skipped, as with du --one-file-system
+fn scan(path: &Path, name: String, metadata: &fs::Metadata, device: u64, seen: &mut HashSet<(u64, u64)>, counts: &Counts) -> Node {
+    let mut node = Node { name, size: 0, is_dir: metadata.is_dir(), children: Vec::new() };
+    if !seen.insert((metadata.dev(), metadata.ino())) || counts.cancelled.load(Ordering::Relaxed) {
+        return node;
+    }
+    node.size = metadata.blocks() * 512;
+    counts.files.fetch_add(1, Ordering::Relaxed);
+    counts.bytes.fetch_add(node.size, Ordering::Relaxed);
+    if !node.is_dir || metadata.dev() != device {
+        return node;
+    }
+
+    for entry in fs::read_dir(path).into_iter().flatten().flatten() {
+        if let Ok(child) = entry.metadata() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            node.children.push(scan(&entry.path(), name, &child, device, seen, counts));
+        }
+    }
+    node.size += node.children.iter().map(|child| child.size).sum::<u64>();
+    node.children.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+    node
+}
+
+fn browse(mut tree: Node, root: &Path, cwd: &Path) -> CrateResult<()> {
+    let mut view = View { path: Vec::new(), selected: 0, top: 0, message: None };
+    let mut stdin = terminal::Keys;
+    loop {
+        draw(&tree, root, &mut view)?;
+        let Some(key) = editor::read_key(&mut stdin)? else {
+            return Ok(());
+        };
+        view.message = None;
+        let count = directory(&tree, &view.path).children.len();
+        match key {
+            Key::Char('q') | Key::Control('c') => return Ok(()),
+            Key::Up | Key::Char('k') => view.selected = view.selected.saturating_sub(1),
+            Key::Down | Key::Char('j') => view.selected = (view.selected + 1).min(count.saturating_sub(1)),
+            Key::Home => view.selected = 0,
+            Key::End => view.selected = count.saturating_sub(1),
+            Key::Enter | Key::Right | Key::Char('l') => {
+                let entered = directory(&tree, &view.path).children.get(view.selected).is_some_and(|child| child.is_dir);
+                if entered {
+                    view.path.push(view.selected);
+                    (view.selected, view.top) = (0, 0);
+                }
+            }
+            Key::Left | Key::Backspace | Key::Char('h') => {
+                if let Some(index) = view.path.pop() {
+                    (view.selected, view.top) = (index, 0);
+                }
+            }
+            Key::Char('d') if count > 0 => delete(&mut tree, root, cwd, &mut view, &mut stdin)?,
+            _ => {}
+        }
+    }
+}
+
+// Removes the selected entry after a y on the confirmation line
+fn delete(tree: &mut Node, root: &Path, cwd: &Path, view: &mut View, input: &mut impl Read) -> CrateResult<()> {
+    let target = directory(tree, &view.path).children[view.selected].name.clone();
+    let path = location(tree, root, &view.path).join(&target);
+    let size = directory(tree, &view.path).children[view.selected].size;
+
+    let (_, height) = terminal::window_size().unwrap_or((80, 24));
+    let question = trf("Delete '{}' ({})? [y/N] ", &[&target, &helpers::human_size(size)]);
+    print!("\x1b[{};1H\x1b[2K{}", height, question.yellow().bold());
+    std::io::stdout().flush()?;
+    if editor::read_key(input)? != Some(Key::Char('y')) {
+        return Ok(());
+    }
+
+    let removed = helpers::check_removal(&path, cwd).and_then(|()| match fs::symlink_metadata(&path)?.is_dir() {
+        true => match helpers::rmdir_r(&path, true)?.as_slice() {
+            [] => Ok(()),
+            _ => Err(anyhow!(trf("'{}' was only partly removed: it holds other filesystems", &[&target]))),
+        },
+        false => helpers::rm(&path),
+    });
+    if let Err(e) = removed {
+        view.message = Some(e.to_string());
+        return Ok(());
+    }
+
+    // Take the entry out and its size off every directory above it
+    tree.size -= size;
+    let mut node = &mut *tree;
+    for &index in &view.path {
+        node = &mut node.children[index];
+        node.size -= size;
+    }
+    node.children.remove(view.selected);
+    view.selected = view.selected.min(node.children.len().saturating_sub(1));
+    view.message = Some(trf("Deleted {} ({})", &[&target, &helpers::human_size(size)]));
+    Ok(())
+}
+
+fn directory<'a>(tree: &'a Node, path: &[usize]) -> &'a Node {
+    path.iter().fold(tree, |node, &index| &node.children[index])
+}
+
+fn location(tree: &Node, root: &Path, path: &[usize]) -> PathBuf {
+    let mut node = tree;
+    let mut location = root.to_path_buf();
+    for &index in path {
+        node = &node.children[index];
+        location.push(&node.name);
+    }
+    location
+}
+
+fn draw(tree: &Node, root: &Path, view: &mut View) -> CrateResult<()> {
+    let (width, height) = terminal::window_size().unwrap_or((80, 24));
+    let fit = |line: String| line.chars().take(width).collect::<String>();
+    let dir = directory(tree, &view.path);
+    let rows = height.saturating_sub(4).max(1);
+    // Keep the selection on screen
+    if view.selected < view.top {
+        view.top = view.selected;
+    } else if view.selected >= view.top + rows {
+        view.top = view.selected + 1 - rows;
+    }
+
+    let mut screen = String::from("\x1b[H");
+    let heading = trf("{}  {} in {} entries", &[&location(tree, root, &view.path).display(), &helpers::human_size(dir.size), &dir.children.len()]);
+    let _ = write!(screen, "{}\x1b[K\r\n\x1b[K\r\n", fit(heading).bold());
+    if dir.children.is_empty() {
+        let _ = write!(screen, "{}\x1b[K\r\n", tr("(empty)").bright_black());
+    }
+    for (index, child) in dir.children.iter().enumerate().skip(view.top).take(rows) {
+        let share = if dir.size > 0 { child.size as f64 / dir.size as f64 } else { 0.0 };
+        let filled = (share * 10.0).round() as usize;
+        let name = if child.is_dir { format!("{}/", child.name) } else { child.name.clone() };
+        let text = fit(format!("{:>10}  [{}{}] {:5.1}%  {}", helpers::human_size(child.size), "#".repeat(filled), " ".repeat(10 - filled), share * 100.0, name));
+        let text = match (index == view.selected, child.is_dir) {
+            (true, _) => text.reversed().to_string(),
+            (false, true) => text.blue().bold().to_string(),
+            (false, false) => text,
+        };
+        let _ = write!(screen, "{}\x1b[K\r\n", text);
+    }
+    screen.push_str("\x1b[J");
+    let footer = match &view.message {
+        Some(message) => fit(message.clone()).yellow().to_string(),
+        None => fit(tr("↑/↓ move · Enter open · ← back · d delete · q quit").to_string()).bright_black().to_string(),
+    };
+    let _ = write!(screen, "\x1b[{};1H{}", height, footer);
+
+    let mut stdout = std::io::stdout();
+    stdout.write_all(screen.as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scans_biggest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("big")).unwrap();
+        fs::write(dir.path().join("big/data"), vec![1u8; 64 * 1024]).unwrap();
+        fs::write(dir.path().join("small"), b"x").unwrap();
+        fs::hard_link(dir.path().join("big/data"), dir.path().join("link")).unwrap();
+
+        let metadata = fs::metadata(dir.path()).unwrap();
+        let tree = scan(dir.path(), ".".to_string(), &metadata, metadata.dev(), &mut HashSet::new(), &Counts::default());
+        assert!(tree.children.windows(2).all(|pair| pair[0].size >= pair[1].size));
+        // The hard link is the same data, counted under whichever name came first
+        let size = |name: &str| tree.children.iter().find(|child| child.name == name).unwrap().size;
+        assert!(size("big").min(size("link")) < 64 * 1024 && size("big").max(size("link")) >= 64 * 1024);
+        assert_eq!(tree.size, tree.children.iter().map(|child| child.size).sum::<u64>() + metadata.blocks() * 512);
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::sync::{LazyLock, RwLock};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::config;
+
+const MAX_WIDTH: usize = 40;
+
+// A one-line text fetched from `url` every `interval` seconds, shown in the
+// prompt as {widget:<name>}
+#[derive(Clone, Debug, Deserialize)]
+pub struct Widget {
+    pub url: String,
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    // Shown until the first successful fetch, e.g. "offline"
+    #[serde(default)]
+    pub fallback: String,
+}
+
+fn default_interval() -> u64 {
+    600
+}
+
+fn default_timeout() -> u64 {
+    3
+}
+
+// Latest text of each widget; the prompt only ever reads from here, so a slow
+// or unreachable URL never delays it
+static VALUES: LazyLock<RwLock<HashMap<String, String>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+
+pub fn value(name: &str) -> Option<String> {
+    VALUES.read().unwrap_or_else(|e| e.into_inner()).get(name).cloned()
+}
+
+// Starts one refresh task per configured widget
+pub fn start() {
+    let widgets = config::get().widgets.clone();
+    for (name, widget) in widgets {
+        VALUES.write().unwrap_or_else(|e| e.into_inner()).insert(name.clone(), widget.fallback.clone());
+        tokio::spawn(refresh(name, widget));
+    }
+}
+
+async fn refresh(name: String, widget: Widget) {
+    let client = match reqwest::Client::builder().timeout(Duration::from_secs(widget.timeout)).build() {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::warn!(widget = %name, error = %e, "could not create HTTP client");
+            return;
+        }
+    };
+
+    let mut interval = tokio::time::interval(Duration::from_secs(widget.interval.max(1)));
+    loop {
+        interval.tick().await;
+
+        match fetch(&client, &widget.url).await {
+            Ok(text) => {
+                VALUES.write().unwrap_or_else(|e| e.into_inner()).insert(name.clone(), text);
+            }
+            // Keep showing the last good value (or the fallback) while offline
+            Err(e) => tracing::debug!(widget = %name, error = %e, "widget fetch failed"),
+        }
+    }
+}
+
+async fn fetch(client: &reqwest::Client, url: &str) -> reqwest::Result<String> {
+    let body = client.get(url).send().await?.error_for_status()?.text().await?;
+    let line = body.lines().map(str::trim).find(|line| !line.is_empty()).unwrap_or_default();
+    Ok(line.chars().filter(|c| !c.is_control()).take(MAX_WIDTH).collect())
+}
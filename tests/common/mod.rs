@@ -0,0 +1,214 @@
+// Drives the shell binary through a pseudo-terminal, the way a user would.
+// The pty comes from libc's openpty rather than portable-pty: the shell runs
+// on Unix only, and the harness makes the pty the shell's controlling
+// terminal and resizes it, which takes libc calls either way.
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use tempfile::TempDir;
+
+pub const PROMPT: &str = "└─$ ";
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// Where the settings file is with `home` as HOME
+pub fn rc_path(home: &Path) -> PathBuf {
+    home.join(".config/shellbasics/config.toml")
+}
+
+pub fn write_rc(home: &Path, rc: &str) {
+    let path = rc_path(home);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, rc).unwrap();
+}
+
+pub struct Shell {
+    master: File,
+    child: Child,
+    home: TempDir,
+    // Output received but not yet consumed by an expectation
+    pending: String,
+    // Payloads of OSC sequences (title, cwd reports) seen so far
+    pub osc: Vec<String>,
+}
+
+impl Shell {
+    pub fn spawn() -> Shell {
+        Shell::spawn_with(&[])
+    }
+
+    pub fn spawn_with(args: &[&str]) -> Shell {
+        Shell::spawn_in(tempfile::tempdir().expect("temporary home"), args, &[])
+    }
+
+    pub fn spawn_with_env(env: &[(&str, &str)]) -> Shell {
+        Shell::spawn_in(tempfile::tempdir().expect("temporary home"), &[], env)
+    }
+
+    // Starts with `rc` as the settings file
+    pub fn spawn_configured(rc: &str) -> Shell {
+        let home = tempfile::tempdir().expect("temporary home");
+        write_rc(home.path(), rc);
+        Shell::spawn_in(home, &[], &[])
+    }
+
+    fn spawn_in(home: TempDir, args: &[&str], env: &[(&str, &str)]) -> Shell {
+        let (master, slave) = open_pty();
+
+        let mut command = Command::new(env!("CARGO_BIN_EXE_shell"));
+        command
+            .args(["--color", "never"])
+            .args(args)
+            .current_dir(home.path())
+            .env("HOME", home.path())
+            .env("USER", "tester")
+            .env("LC_ALL", "C")
+            .env_remove("LANG")
+            .env_remove("LC_MESSAGES")
+            .env_remove("XDG_CONFIG_HOME")
+            .env_remove("XDG_STATE_HOME")
+            .envs(env.iter().copied())
+            .stdin(Stdio::from(slave.try_clone().unwrap()))
+            .stdout(Stdio::from(slave.try_clone().unwrap()))
+            .stderr(Stdio::from(slave));
+
+        // Make the pty the child's controlling terminal
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+
+        let child = command.spawn().expect("spawn shell");
+        let mut shell = Shell { master: File::from(master), child, home, pending: String::new(), osc: Vec::new() };
+        shell.expect(PROMPT);
+        shell
+    }
+
+    pub fn home(&self) -> &Path {
+        self.home.path()
+    }
+
+    pub fn send(&mut self, line: &str) {
+        self.master.write_all(format!("{}\n", line).as_bytes()).unwrap();
+    }
+
+    // Types without pressing Enter
+    pub fn type_keys(&mut self, keys: &str) {
+        self.master.write_all(keys.as_bytes()).unwrap();
+    }
+
+    // Changes the window size, which sends the shell SIGWINCH
+    pub fn resize(&mut self, columns: u16, rows: u16) {
+        let size = libc::winsize { ws_row: rows, ws_col: columns, ws_xpixel: 0, ws_ypixel: 0 };
+        assert_eq!(unsafe { libc::ioctl(self.master.as_raw_fd(), libc::TIOCSWINSZ, &size) }, 0);
+    }
+
+    // Reads until `needle` shows up and returns everything before it
+    pub fn expect(&mut self, needle: &str) -> String {
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            if let Some(index) = self.pending.find(needle) {
+                let before = self.pending[..index].to_string();
+                self.pending.drain(..index + needle.len());
+                return before;
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() || !self.read_some(remaining) {
+                panic!("timed out waiting for {:?}; got:\n{}", needle, self.pending);
+            }
+        }
+    }
+
+    // Runs one command line and returns its output, without the echoed line or the next prompt
+    pub fn run(&mut self, line: &str) -> String {
+        self.send(line);
+        self.expect(&format!("{}\n", line));
+        let output = self.expect(PROMPT);
+
+        // Drop the first half of the two-line prompt
+        match output.rfind("┌─[") {
+            Some(index) => output[..index].to_string(),
+            None => output,
+        }
+    }
+
+    // Sends `exit` and returns the process status code
+    pub fn exit(mut self) -> Option<i32> {
+        self.send("exit");
+        self.expect("Goodbye!");
+        self.wait()
+    }
+
+    // Waits for the shell to end on its own and returns the status code
+    pub fn wait(mut self) -> Option<i32> {
+        self.child.wait().unwrap().code()
+    }
+
+    fn read_some(&mut self, timeout: Duration) -> bool {
+        let mut poll = libc::pollfd { fd: self.master.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        if unsafe { libc::poll(&mut poll, 1, timeout.as_millis() as i32) } <= 0 {
+            return false;
+        }
+
+        let mut buffer = [0u8; 4096];
+        match self.master.read(&mut buffer) {
+            Ok(0) | Err(_) => false,
+            Ok(n) => {
+                let text = String::from_utf8_lossy(&buffer[..n]).replace('\r', "");
+                self.pending.push_str(&strip_escapes(&text, &mut self.osc));
+                true
+            }
+        }
+    }
+}
+
+impl Drop for Shell {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn open_pty() -> (OwnedFd, OwnedFd) {
+    let (mut master, mut slave) = (0, 0);
+    let result = unsafe {
+        libc::openpty(&mut master, &mut slave, std::ptr::null_mut(), std::ptr::null(), std::ptr::null())
+    };
+    assert_eq!(result, 0, "openpty: {}", std::io::Error::last_os_error());
+    unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) }
+}
+
+// Colors are off, but the shell still emits OSC sequences; collect those and
+// drop any other escape sequence
+fn strip_escapes(text: &str, osc: &mut Vec<String>) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            result.push(c);
+        } else {
+            match chars.next() {
+                Some(']') => osc.push(chars.by_ref().take_while(|&c| c != '\x07').collect()),
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if c.is_ascii_alphabetic() {
+                            break;
+                        }
+                    }
+                }
+                // Two-character sequences such as ESC 7 / ESC 8
+                _ => {}
+            }
+        }
+    }
+    result
+}
@@ -0,0 +1,1043 @@
+// Terminal sessions need a Unix pty; see common/mod.rs
+#![cfg(unix)]
+
+mod common;
+
+use std::io::{Read, Write};
+
+use common::Shell;
+
+#[test]
+fn prompt_shows_user_and_directory() {
+    let mut shell = Shell::spawn();
+    shell.send("");
+    let prompt = shell.expect(common::PROMPT);
+    let dir = shell.home().file_name().unwrap().to_string_lossy().to_string();
+
+    assert!(prompt.contains("tester @shell"), "{}", prompt);
+    assert!(prompt.contains(&dir), "{}", prompt);
+}
+
+#[test]
+fn mkdir_touch_and_ls() {
+    let mut shell = Shell::spawn();
+
+    assert!(shell.run("mkdir notes").contains("Directory created: notes"));
+    shell.run("touch notes/todo.txt");
+    assert!(shell.home().join("notes/todo.txt").is_file());
+
+    shell.run("cd notes");
+    let listing = shell.run("ls");
+    assert!(listing.contains("todo.txt"), "{}", listing);
+}
+
+#[test]
+fn cd_updates_prompt() {
+    let mut shell = Shell::spawn();
+    shell.run("mkdir projects");
+    shell.send("cd projects");
+    let prompt = shell.expect(common::PROMPT);
+
+    assert!(prompt.contains("/projects ]"), "{}", prompt);
+    assert!(shell.run("pwd").trim().ends_with("/projects"));
+}
+
+#[test]
+fn paths_expand_tilde_and_fold_dots() {
+    let mut shell = Shell::spawn();
+    std::fs::write(shell.home().join("notes.txt"), "remember the milk\n").unwrap();
+    shell.run("mkdir -p a/b");
+    shell.run("cd a/b");
+
+    assert!(shell.run("cat ~/notes.txt").contains("remember the milk"));
+    assert!(shell.run("cat ../../notes.txt").contains("remember the milk"));
+    shell.run("cd");
+    assert_eq!(shell.run("pwd").trim(), shell.home().canonicalize().unwrap().display().to_string());
+}
+
+#[test]
+fn title_follows_directory() {
+    let mut shell = Shell::spawn();
+    assert!(shell.osc.iter().any(|osc| osc == "2;shell: ~"), "{:?}", shell.osc);
+
+    shell.run("mkdir projects");
+    shell.run("cd projects");
+    assert!(shell.osc.iter().any(|osc| osc == "2;shell: ~/projects"), "{:?}", shell.osc);
+}
+
+#[test]
+fn reports_cwd_with_osc7() {
+    let mut shell = Shell::spawn();
+    shell.run("mkdir 'my project'");
+    shell.run("cd 'my project'");
+
+    let expected = format!("{}/my%20project", shell.home().display());
+    let report = shell.osc.iter().rev().find(|osc| osc.starts_with("7;file://")).expect("no OSC 7 report");
+    assert!(report.ends_with(&expected), "{}", report);
+}
+
+#[test]
+fn transient_prompt_collapses_to_one_line() {
+    let mut shell = Shell::spawn_configured("transient_prompt = true");
+    let output = shell.run("pwd");
+    assert!(output.starts_with("❯ pwd\n"), "{:?}", output);
+
+    let mut shell = Shell::spawn();
+    assert!(!shell.run("pwd").contains("❯"));
+}
+
+#[test]
+fn right_prompt_shows_failed_status() {
+    let mut shell = Shell::spawn_configured("right_prompt = \"{status}\"");
+    shell.send("cat missing.txt");
+    let prompt = shell.expect(common::PROMPT);
+    let first_line = prompt.lines().rev().find(|line| line.starts_with("┌─[")).unwrap();
+    assert!(first_line.trim_end().ends_with('✘'), "{:?}", first_line);
+
+    shell.send("pwd");
+    let prompt = shell.expect(common::PROMPT);
+    assert!(!prompt.contains('✘'), "{:?}", prompt);
+}
+
+#[test]
+fn slow_git_branch_is_filled_in_later() {
+    // A git that takes its time answering
+    let bin = tempfile::tempdir().unwrap();
+    let git = bin.path().join("git");
+    std::fs::write(&git, "#!/bin/sh\nsleep 0.5\necho feature\n").unwrap();
+    std::fs::set_permissions(&git, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+    let path = format!("{}:{}", bin.path().display(), std::env::var("PATH").unwrap());
+
+    let mut shell = Shell::spawn_with_env(&[("PATH", &path)]);
+    shell.send("");
+    let prompt = shell.expect(common::PROMPT);
+    assert!(prompt.contains("on …"), "{:?}", prompt);
+
+    // The redraw arrives on its own, without any input
+    shell.expect("on feature");
+}
+
+#[test]
+fn errors_are_reported_and_shell_keeps_running() {
+    let mut shell = Shell::spawn();
+
+    assert!(shell.run("cat missing.txt").contains("Error:"));
+    assert!(shell.run("frobnicate").contains("Unknown command"));
+    assert!(shell.run("pwd").contains(&shell.home().display().to_string()));
+}
+
+#[test]
+fn help_lists_builtins() {
+    let mut shell = Shell::spawn();
+    let help = shell.run("help");
+
+    for builtin in ["ls", "cd", "mkdir", "undo", "stats"] {
+        assert!(help.contains(builtin), "help is missing {}:\n{}", builtin, help);
+    }
+}
+
+#[test]
+fn undo_reverts_last_operation() {
+    let mut shell = Shell::spawn();
+    shell.run("touch draft.txt");
+    shell.run("undo");
+
+    assert!(!shell.home().join("draft.txt").exists());
+}
+
+#[test]
+fn rm_r_asks_before_removing_a_large_tree() {
+    let mut shell = Shell::spawn();
+    let tree = shell.home().join("cache");
+    std::fs::create_dir(&tree).unwrap();
+    for i in 0..150 {
+        std::fs::write(tree.join(i.to_string()), "").unwrap();
+    }
+
+    shell.send("rm -r cache");
+    assert!(shell.expect("[y/N]").contains("150 files"));
+    shell.send("n");
+    shell.expect(common::PROMPT);
+    assert!(tree.exists());
+
+    shell.send("rm -r cache");
+    shell.expect("[y/N]");
+    shell.send("y");
+    shell.expect(common::PROMPT);
+    assert!(!tree.exists());
+}
+
+#[test]
+fn rm_r_refuses_the_current_directory() {
+    let mut shell = Shell::spawn();
+    shell.run("mkdir work");
+    shell.run("cd work");
+
+    assert!(shell.run("rm -r ..").contains("Refusing to remove"));
+    assert!(shell.home().join("work").exists());
+}
+
+#[test]
+fn ctrl_c_keeps_the_shell_running() {
+    let mut shell = Shell::spawn();
+    // ^C discards the typed line; the newline after it is an empty command
+    shell.send("\x03");
+    shell.expect("^C");
+    shell.expect(common::PROMPT);
+    shell.expect(common::PROMPT);
+    let output = shell.run("echo still here");
+    assert!(output.contains("still here"), "{:?}", output);
+}
+
+#[test]
+fn find_stops_at_max_results() {
+    let mut shell = Shell::spawn();
+    for i in 0..5 {
+        std::fs::write(shell.home().join(format!("note{}.txt", i)), "").unwrap();
+    }
+
+    let output = shell.run("find . note --max-results 2");
+    assert_eq!(output.matches("note").count(), 2, "{}", output);
+    assert!(output.contains("Stopped after 2 matches"), "{}", output);
+}
+
+#[test]
+fn split_and_merge_round_trip() {
+    let mut shell = Shell::spawn();
+    let data: Vec<u8> = (0..5000u32).map(|i| (i % 251) as u8).collect();
+    std::fs::write(shell.home().join("data.bin"), &data).unwrap();
+
+    let output = shell.run("split -b 2K data.bin part_");
+    assert!(output.contains("into 3 pieces"), "{}", output);
+    assert_eq!(std::fs::metadata(shell.home().join("part_ac")).unwrap().len(), 5000 - 4096);
+
+    shell.run("merge part_* joined.bin");
+    assert_eq!(std::fs::read(shell.home().join("joined.bin")).unwrap(), data);
+}
+
+#[test]
+fn du_ends_with_a_grand_total() {
+    let mut shell = Shell::spawn();
+    shell.run("mkdir -p logs/old");
+    std::fs::write(shell.home().join("logs/old/a.log"), vec![b'x'; 10_000]).unwrap();
+
+    let output = shell.run("du logs");
+    assert!(output.contains("logs/old") && output.trim_end().ends_with("total"), "{}", output);
+    let summary = shell.run("du -s logs");
+    assert!(!summary.contains("logs/old"), "{}", summary);
+}
+
+#[test]
+fn exit_ends_the_session() {
+    let shell = Shell::spawn();
+    assert_eq!(shell.exit(), Some(0));
+}
+
+#[test]
+fn tab_completes_paths_and_flags() {
+    let mut shell = Shell::spawn();
+    std::fs::write(shell.home().join("notes.txt"), "remember the milk\n").unwrap();
+
+    shell.send("cat not\t");
+    shell.expect("cat notes.txt \n");
+    assert!(shell.expect(common::PROMPT).contains("remember the milk"));
+
+    // Several flags fit, so they are offered in a menu with what they do
+    shell.type_keys("ls -\t");
+    shell.expect("-l  -- detailed listing");
+    shell.expect("-n  -- numeric owner and group");
+    shell.type_keys("\x03\x03");
+    shell.expect("^C");
+}
+
+#[test]
+fn commands_are_completed_in_groups() {
+    let mut shell = Shell::spawn_configured("autocd = true\n\n[abbr]\ngco = \"git checkout\"\n");
+    std::fs::create_dir(shell.home().join("gallery")).unwrap();
+    // Programs on PATH would fill the menu
+    shell.run("export PATH=/nonexistent");
+
+    shell.type_keys("g\t");
+    let menu = shell.expect("gallery/");
+    let headings: Vec<usize> = ["builtins", "gdiff", "abbreviations", "gco", "-- git checkout", "directories"]
+        .iter()
+        .map(|text| menu.find(text).unwrap_or_else(|| panic!("{:?} in {:?}", text, menu)))
+        .collect();
+    assert!(headings.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", menu);
+    shell.type_keys("\x03\x03");
+    shell.expect("^C");
+}
+
+#[test]
+fn ambiguous_completions_open_a_menu_picked_with_tab() {
+    let mut shell = Shell::spawn();
+    std::fs::write(shell.home().join("alpha1"), "first\n").unwrap();
+    std::fs::write(shell.home().join("alpha2"), "second\n").unwrap();
+    std::fs::create_dir(shell.home().join("alphabet")).unwrap();
+
+    // The first Tab fills in what they share, the next opens the menu
+    shell.type_keys("cat al\t\t");
+    let menu = shell.expect("alphabet/");
+    assert!(menu.contains("alpha1"), "{:?}", menu);
+    // Shift+Tab picks the last, Tab goes round to the first and on, and
+    // Enter keeps the pick
+    shell.type_keys("\x1b[Z\t\t\r\r");
+    let output = shell.expect(common::PROMPT);
+    assert!(output.contains("second"), "{:?}", output);
+
+    // Ctrl+C puts back what was typed
+    shell.type_keys("cat alpha\t\t\x03");
+    shell.send("1\r");
+    assert!(shell.expect(common::PROMPT).contains("first"));
+}
+
+#[test]
+fn programs_on_path_are_indexed() {
+    let mut shell = Shell::spawn_configured("[abbr]\ngco = \"git checkout\"\n");
+    let bin = shell.home().join("bin");
+    std::fs::create_dir(&bin).unwrap();
+    std::fs::write(bin.join("hello-tool"), "#!/bin/sh\necho hello\n").unwrap();
+    std::fs::set_permissions(bin.join("hello-tool"), std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+
+    // A new PATH is indexed as soon as it is looked up
+    shell.run(&format!("export PATH={}", bin.display()));
+    let output = shell.run("which gco cd hello-tool");
+    assert!(output.contains("gco: abbreviation for 'git checkout'"), "{:?}", output);
+    assert!(output.contains("cd: shell builtin"), "{:?}", output);
+    assert!(output.contains(&format!("{}", bin.join("hello-tool").display())), "{:?}", output);
+    assert!(shell.run("which nothing-here").contains("which: no nothing-here in PATH"));
+
+    assert!(shell.run("hello-tol").contains("did you mean 'hello-tool'?"));
+    shell.type_keys("hello-t\t\x03");
+    shell.expect("hello-tool ^C");
+}
+
+#[test]
+fn programs_run_are_hashed() {
+    let mut shell = Shell::spawn();
+    let bin = shell.home().join("bin");
+    std::fs::create_dir(&bin).unwrap();
+    std::fs::write(bin.join("ssh"), "#!/bin/sh\necho connected to $1\n").unwrap();
+    std::fs::set_permissions(bin.join("ssh"), std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+    // The prompt runs git for the branch; only what the user runs counts
+    std::fs::write(bin.join("git"), "#!/bin/sh\nexit 1\n").unwrap();
+    std::fs::set_permissions(bin.join("git"), std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+    shell.run(&format!("export PATH={}", bin.display()));
+    shell.run("pwd");
+    shell.run("pwd");
+    assert!(shell.run("hash").contains("hash: hash table empty"));
+
+    assert!(shell.run("ssh box").contains("connected to box"));
+    shell.run("ssh box");
+    let output = shell.run("hash");
+    assert!(output.contains(&format!("   2  {}", bin.join("ssh").display())), "{:?}", output);
+
+    // A program that has gone is dropped
+    std::fs::remove_file(bin.join("ssh")).unwrap();
+    assert!(!shell.run("hash").contains("ssh"));
+
+    std::fs::write(bin.join("scp"), "#!/bin/sh\n").unwrap();
+    std::fs::set_permissions(bin.join("scp"), std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+    shell.run("rehash");
+    shell.run("hash scp");
+    assert!(shell.run("hash").contains(&format!("   0  {}", bin.join("scp").display())));
+    assert!(shell.run("hash nothing-here").contains("hash: nothing-here not found"));
+    shell.run("hash -d scp");
+    assert!(shell.run("hash").contains("hash: hash table empty"));
+    shell.run("hash scp");
+    shell.run("hash -r");
+    assert!(shell.run("hash").contains("hash: hash table empty"));
+}
+
+#[test]
+fn other_commands_run_programs() {
+    let mut shell = Shell::spawn();
+    let bin = shell.home().join("bin");
+    std::fs::create_dir(&bin).unwrap();
+    std::fs::write(bin.join("greet"), "#!/bin/sh\necho \"hello $1 from $(pwd) as $WHO\"\nexit $2\n").unwrap();
+    std::fs::set_permissions(bin.join("greet"), std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+    shell.run(&format!("export PATH={}:/usr/bin:/bin", bin.display()));
+    shell.run("mkdir work");
+    shell.run("cd work");
+
+    let output = shell.run("WHO=me greet 'the shell' 0");
+    assert!(output.contains(&format!("hello the shell from {} as me", shell.home().join("work").display())), "{:?}", output);
+    // A failure is the program's to explain
+    let output = shell.run("greet again 3");
+    assert!(output.contains("hello again") && !output.contains("Error:"), "{:?}", output);
+    assert!(shell.run("../bin/greet by-path 0").contains("hello by-path"));
+    assert!(shell.run("greet piped 0 | grep -i PIPED").contains("hello piped"));
+    assert!(shell.run("nothing-called-this").contains("Unknown command"));
+}
+
+#[test]
+fn reload_reads_the_settings_again() {
+    let mut shell = Shell::spawn_configured("[abbr]\ngreet = \"echo hello\"\n");
+    let rc = common::rc_path(shell.home());
+
+    // A mistake keeps what was in use
+    std::fs::write(&rc, "autocd = maybe\n").unwrap();
+    assert!(shell.run("reload").contains("Invalid config"));
+    assert!(shell.run("which greet").contains("abbreviation for 'echo hello'"));
+
+    std::fs::write(&rc, "[abbr]\ngreet = \"echo bonjour\"\n").unwrap();
+    assert!(shell.run("reload").contains("Settings reloaded from"));
+    assert!(shell.run("which greet").contains("abbreviation for 'echo bonjour'"));
+}
+
+#[test]
+fn doctor_says_how_to_fix_problems() {
+    let mut shell = Shell::spawn();
+    shell.run("export PATH=/bin:relative:/no/such/dir:/bin");
+    let output = shell.run("doctor");
+    assert!(output.contains("PATH has relative, which is relative"), "{:?}", output);
+    assert!(output.contains("PATH has /no/such/dir, which doesn't exist"), "{:?}", output);
+    assert!(output.contains("PATH has /bin more than once"), "{:?}", output);
+    assert!(output.contains("Fix: export PATH=/bin\n"), "{:?}", output);
+    assert!(output.contains("problems found"), "{:?}", output);
+}
+
+#[test]
+fn history_is_readable_only_by_its_owner() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = |path: &std::path::Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    let home = tempfile::tempdir().unwrap();
+    let history = home.path().join(".local/state/shellbasics/history");
+    let mut shell = Shell::spawn_with_env(&[("HOME", home.path().to_str().unwrap())]);
+    shell.run("echo secret");
+    assert_eq!(mode(&history), 0o600);
+    assert_eq!(shell.exit(), Some(0));
+
+    // A log from before is made private too
+    std::fs::set_permissions(&history, std::fs::Permissions::from_mode(0o644)).unwrap();
+    let mut shell = Shell::spawn_with_env(&[("HOME", home.path().to_str().unwrap())]);
+    shell.run("pwd");
+    assert_eq!(mode(&history), 0o600);
+}
+
+#[test]
+fn files_are_kept_in_the_xdg_directories() {
+    // Settings from an older version are moved into place before they are read
+    let home = tempfile::tempdir().unwrap();
+    std::fs::write(home.path().join(".shellbasicsrc"), "[abbr]\ngreet = \"echo hello\"\n").unwrap();
+    let mut shell = Shell::spawn_with_env(&[("HOME", home.path().to_str().unwrap())]);
+    assert!(shell.run("which greet").contains("echo hello"));
+    assert!(!home.path().join(".shellbasicsrc").exists());
+    assert!(common::rc_path(home.path()).is_file());
+    assert!(home.path().join(".local/state/shellbasics/history").is_file());
+
+    // One left behind is pointed out
+    std::fs::write(home.path().join(".shellbasics_journal"), "").unwrap();
+    let output = shell.run("doctor");
+    assert!(output.contains(".shellbasics_journal is left from an older version"), "{:?}", output);
+    assert!(output.contains(".local/state/shellbasics/journal"), "{:?}", output);
+    assert_eq!(shell.exit(), Some(0));
+
+    // The XDG variables move both directories
+    let xdg = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(xdg.path().join("config/shellbasics")).unwrap();
+    std::fs::write(xdg.path().join("config/shellbasics/config.toml"), "[abbr]\nbye = \"echo later\"\n").unwrap();
+    let (config, state) = (xdg.path().join("config"), xdg.path().join("state"));
+    let mut shell = Shell::spawn_with_env(&[("XDG_CONFIG_HOME", config.to_str().unwrap()), ("XDG_STATE_HOME", state.to_str().unwrap())]);
+    assert!(shell.run("which bye").contains("echo later"));
+    assert!(state.join("shellbasics/history").is_file());
+    assert_eq!(shell.exit(), Some(0));
+}
+
+#[test]
+fn git_helpers_need_git_on_path() {
+    let mut shell = Shell::spawn();
+    shell.run("export PATH=/nonexistent");
+    for helper in ["gst", "glog", "gdiff"] {
+        assert!(shell.run(helper).contains("git was not found on PATH"), "{}", helper);
+    }
+}
+
+#[test]
+fn rm_and_mv_with_git_leave_files_alone_without_a_repository() {
+    let mut shell = Shell::spawn();
+    shell.run("touch notes.txt");
+    assert!(shell.run("rm --git notes.txt").contains("'notes.txt' is not tracked by git; use rm without --git"));
+    assert!(shell.run("mv --git notes.txt kept.txt").contains("'notes.txt' is not tracked by git; use mv without --git"));
+
+    shell.run("export PATH=/nonexistent");
+    assert!(shell.run("rm --git notes.txt").contains("git was not found on PATH"));
+    assert!(shell.run("mv --git notes.txt kept.txt").contains("git was not found on PATH"));
+    assert!(shell.home().join("notes.txt").is_file() && !shell.home().join("kept.txt").exists());
+}
+
+#[test]
+fn hosts_come_from_the_ssh_config() {
+    let mut shell = Shell::spawn();
+    std::fs::create_dir(shell.home().join(".ssh")).unwrap();
+    std::fs::write(shell.home().join(".ssh/config"), "Host build\n  HostName 10.0.0.5\n  User deploy\n").unwrap();
+    std::fs::write(shell.home().join(".ssh/known_hosts"), "github.com ssh-ed25519 AAAA\n").unwrap();
+
+    let output = shell.run("hosts");
+    assert!(output.contains("build  deploy@10.0.0.5"), "{:?}", output);
+    assert!(output.contains("github.com"), "{:?}", output);
+
+    // Completed, then thrown away with Ctrl+C rather than connecting
+    shell.send("ssh bu\t\x03");
+    shell.expect("ssh build ^C");
+}
+
+#[test]
+fn serve_runs_as_a_job_until_killed() {
+    let mut shell = Shell::spawn();
+    std::fs::create_dir(shell.home().join("public")).unwrap();
+    std::fs::write(shell.home().join("public/hello.txt"), "hello over http\n").unwrap();
+
+    let output = shell.run("serve public --port 0");
+    let port = output.split("localhost:").nth(1).and_then(|rest| rest.split('/').next()).expect("port in output");
+
+    let mut stream = std::net::TcpStream::connect(("127.0.0.1", port.parse::<u16>().unwrap())).unwrap();
+    stream.write_all(b"GET /hello.txt HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{:?}", response);
+    assert!(response.ends_with("\r\n\r\nhello over http\n"), "{:?}", response);
+    shell.expect("GET /hello.txt 200");
+
+    // A request logged while a redirected command runs stays off its file
+    shell.run("mkfifo pipe");
+    shell.send("cat pipe > out.txt");
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    let mut stream = std::net::TcpStream::connect(("127.0.0.1", port.parse::<u16>().unwrap())).unwrap();
+    stream.write_all(b"GET /hello.txt HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    stream.read_to_string(&mut String::new()).unwrap();
+    shell.expect("GET /hello.txt 200");
+    std::fs::write(shell.home().join("pipe"), "piped\n").unwrap();
+    shell.expect(common::PROMPT);
+    assert_eq!(std::fs::read_to_string(shell.home().join("out.txt")).unwrap(), "piped\n");
+
+    assert!(shell.run("jobs").contains(&format!("[1]  Running  serve public --port {}", port)));
+    assert!(shell.run("kill %1").contains("[1]  Stopped"));
+    assert!(shell.run("jobs").contains("No background jobs."));
+}
+
+#[test]
+fn download_saves_and_checks_files() {
+    let mut shell = Shell::spawn();
+    std::fs::create_dir(shell.home().join("public")).unwrap();
+    std::fs::write(shell.home().join("public/data.txt"), "downloaded\n").unwrap();
+
+    let output = shell.run("serve public --port 0");
+    let port = output.split("localhost:").nth(1).and_then(|rest| rest.split('/').next()).expect("port in output");
+    let url = format!("http://localhost:{}/data.txt", port);
+
+    assert!(shell.run(&format!("download {}", url)).contains("Saved data.txt (11 B)."));
+    assert_eq!(std::fs::read_to_string(shell.home().join("data.txt")).unwrap(), "downloaded\n");
+
+    let wrong = "0".repeat(64);
+    assert!(shell.run(&format!("download -o copy.txt --sha256 {} {}", wrong, url)).contains("checksum mismatch"));
+    assert!(!shell.home().join("copy.txt").exists() && !shell.home().join("copy.txt.part").exists());
+}
+
+#[test]
+fn finished_jobs_are_announced_with_status_and_duration() {
+    let mut shell = Shell::spawn();
+    std::fs::create_dir(shell.home().join("public")).unwrap();
+    std::fs::write(shell.home().join("public/data.txt"), "downloaded\n").unwrap();
+    let output = shell.run("serve public --port 0");
+    let port = output.split("localhost:").nth(1).and_then(|rest| rest.split('/').next()).expect("port in output");
+
+    let url = format!("http://localhost:{}/data.txt", port);
+    shell.send(&format!("download -b -o job.txt {}", url));
+    shell.expect(&format!("[2]  Done  download {}  (exit status 0, 0s)", url));
+    assert!(shell.home().join("job.txt").exists());
+
+    let missing = format!("http://localhost:{}/missing.txt", port);
+    shell.send(&format!("download -b {}", missing));
+    shell.expect(&format!("[2]  Failed  download {}  (exit status 1, 0s): ", missing));
+}
+
+#[test]
+fn process_substitution_passes_output_as_a_file() {
+    let mut shell = Shell::spawn();
+    shell.run("touch marker.txt");
+
+    assert!(shell.run("cp <(ls) listing.txt").contains("Copied:"));
+    assert!(std::fs::read_to_string(shell.home().join("listing.txt")).unwrap().contains("marker.txt"));
+    let both = shell.run("cat <(echo one) <(echo two)");
+    assert!(both.contains("one") && both.contains("two"), "{}", both);
+    assert!(shell.run("cat <(nosuch)").contains("<(nosuch) failed"));
+}
+
+#[test]
+fn pipelines_feed_each_command_the_output_before() {
+    let mut shell = Shell::spawn();
+    std::fs::write(shell.home().join("log.txt"), "disk error\nall fine\nnet error\n").unwrap();
+
+    let output = shell.run("cat log.txt | grep error | grep -i DISK");
+    assert!(output.contains("Matches in (standard input):") && output.contains("1:disk error"), "{:?}", output);
+    assert!(!output.contains("net error") && !output.contains("==="), "{:?}", output);
+    assert!(shell.run("echo 'a | b' | cat -E").contains("a | b$"));
+
+    // Stages run together, so one that stops reading ends the ones before
+    // it; `run` gives up after the harness's timeout otherwise
+    assert_eq!(shell.run("yes | head -n 1"), "y\n");
+    assert_eq!(shell.run("yes | cat | head -n 1"), "y\n");
+
+    // Earlier failures are reported and the line goes on
+    let output = shell.run("nosuch | echo after");
+    assert!(output.contains("Unknown command") && output.contains("after"), "{:?}", output);
+    assert!(shell.run("echo hi |").contains("syntax error near '|'"));
+}
+
+#[test]
+fn cat_and_grep_read_standard_input() {
+    let mut shell = Shell::spawn();
+    std::fs::write(shell.home().join("a.txt"), "from a\n").unwrap();
+
+    let output = shell.run("echo one | cat");
+    assert!(output.contains("=== (standard input) ===\none\n"), "{:?}", output);
+    assert!(shell.run("echo two | cat -E -").contains("\ntwo$\n"));
+    assert_eq!(shell.run("echo three | cat a.txt - a.txt"), "from a\nthree\nfrom a\n");
+    assert_eq!(shell.run("echo three | cat -H a.txt -"), "==> a.txt <==\nfrom a\n\n==> - <==\nthree\n");
+    std::fs::write(shell.home().join("b.txt"), "four\nfive\n").unwrap();
+    let output = shell.run("cat b.txt | grep five");
+    assert!(output.contains("Matches in (standard input):") && output.contains("2:five"), "{:?}", output);
+    assert!(!output.contains("four"), "{:?}", output);
+}
+
+#[test]
+fn redirections_send_output_and_errors_to_files() {
+    let mut shell = Shell::spawn();
+    shell.run("touch marker.txt");
+
+    shell.run("ls > listing.txt");
+    assert!(std::fs::read_to_string(shell.home().join("listing.txt")).unwrap().contains("marker.txt"));
+    shell.run("echo one > 'log file.txt'");
+    shell.run("echo two >>'log file.txt'");
+    assert_eq!(std::fs::read_to_string(shell.home().join("log file.txt")).unwrap(), "one\ntwo\n");
+
+    // The error goes to the file, not the terminal
+    assert!(!shell.run("cat missing.txt 2> errors.txt").contains("Error:"));
+    assert!(std::fs::read_to_string(shell.home().join("errors.txt")).unwrap().contains("Error:"));
+    shell.run("echo hi | cat > piped.txt");
+    assert_eq!(std::fs::read_to_string(shell.home().join("piped.txt")).unwrap(), "hi\n");
+    assert!(shell.run("echo hi >").contains("syntax error near '>'"));
+}
+
+#[test]
+fn cat_waits_for_a_named_pipe_writer() {
+    let mut shell = Shell::spawn();
+    assert!(shell.run("mkfifo pipe").contains("Named pipe created: pipe"));
+
+    let pipe = shell.home().join("pipe");
+    let writer = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        std::fs::write(pipe, "through the pipe\n").unwrap();
+    });
+    assert!(shell.run("cat pipe").contains("through the pipe"));
+    writer.join().unwrap();
+}
+
+#[test]
+fn progress_and_spinner_show_next_to_the_prompt() {
+    let mut shell = Shell::spawn();
+    shell.send("progress 3 10 Copying");
+    let prompt = shell.expect(common::PROMPT);
+    assert!(prompt.contains("Copying [#########") && prompt.contains("30% 3/10"), "{}", prompt);
+
+    shell.send("progress 10 10");
+    let prompt = shell.expect(common::PROMPT);
+    assert!(!prompt.contains("Copying"), "{}", prompt);
+
+    shell.send("spinner start Building");
+    let prompt = shell.expect(common::PROMPT);
+    assert!(prompt.contains("Building"), "{}", prompt);
+    assert!(shell.run("spinner stop").contains("✔ Building (0s)"));
+    assert!(shell.run("spinner stop").contains("no spinner is running"));
+}
+
+#[test]
+fn background_output_goes_above_the_line_being_typed() {
+    let mut shell = Shell::spawn();
+    std::fs::write(shell.home().join("hello.txt"), "hello\n").unwrap();
+    let output = shell.run("serve . --port 0");
+    let port = output.split("localhost:").nth(1).and_then(|rest| rest.split('/').next()).expect("port in output");
+
+    shell.type_keys("ech");
+    shell.expect("ech");
+    let mut stream = std::net::TcpStream::connect(("127.0.0.1", port.parse::<u16>().unwrap())).unwrap();
+    stream.write_all(b"GET /hello.txt HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    stream.read_to_string(&mut String::new()).unwrap();
+
+    // The log line takes the prompt's place, which comes back with the typed text
+    assert_eq!(shell.expect("GET /hello.txt 200"), "127.0.0.1 ");
+    shell.expect(&format!("{}ech", common::PROMPT));
+    assert!(shell.run("o hi").contains("hi"));
+}
+
+#[test]
+fn nohup_runs_a_process_that_can_be_disowned() {
+    let mut shell = Shell::spawn();
+    std::fs::create_dir(shell.home().join("public")).unwrap();
+
+    let output = shell.run("nohup echo from the job &");
+    assert!(output.contains(&format!("output goes to {}", shell.home().join("nohup.out").display())), "{}", output);
+    // It may end before the prompt is back, and is announced above it
+    let done = "[1]  Done  nohup echo from the job  (exit status 0";
+    if !output.contains(done) {
+        shell.expect(done);
+    }
+    assert!(std::fs::read_to_string(shell.home().join("nohup.out")).unwrap().contains("from the job"));
+
+    shell.run("serve public --port 0");
+    assert!(shell.run("disown %1").contains("runs inside the shell and ends with it"));
+    assert!(shell.run("renice 19 %1").contains("renice: job [1] runs inside the shell"));
+    shell.run("kill %1");
+
+    shell.run("nohup serve public --port 0 &");
+    let pid = shell.run("jobs").split("pid ").nth(1).and_then(|rest| rest.split(',').next()).expect("pid in jobs").to_string();
+    let output = shell.run("renice 19 %1");
+    assert!(output.contains(&format!("{}: priority", pid)) && output.contains("→ 19"), "{:?}", output);
+    assert!(shell.run("disown %1").contains("keeps running after the shell exits"));
+    assert_eq!(shell.exit(), Some(0));
+    assert_eq!(unsafe { libc::kill(pid.parse().unwrap(), 0) }, 0, "the disowned job ended with the shell");
+    unsafe { libc::kill(pid.parse().unwrap(), libc::SIGTERM) };
+}
+
+#[test]
+fn exit_warns_about_running_jobs_and_runs_on_exit() {
+    let elsewhere = tempfile::tempdir().unwrap();
+    let marker = elsewhere.path().join("logged-out");
+    let mut shell = Shell::spawn_configured(&format!("on_exit = [\"touch '{}'\"]", marker.display()));
+    std::fs::create_dir(shell.home().join("public")).unwrap();
+    shell.run("serve public --port 0");
+
+    let warning = shell.run("exit");
+    assert!(warning.contains("There are running jobs:") && warning.contains("[1]  serve public"), "{}", warning);
+    // Anything else in between asks again
+    shell.run("pwd");
+    assert!(shell.run("exit").contains("Type exit or press Ctrl+D again"));
+
+    assert_eq!(shell.exit(), Some(0));
+    assert!(marker.exists());
+}
+
+#[test]
+fn ctrl_d_deletes_mid_line_and_leaves_on_an_empty_one() {
+    let mut shell = Shell::spawn();
+    // Home, four steps right, then Ctrl+D deletes the X
+    shell.type_keys("echoX hi\x01\x1b[C\x1b[C\x1b[C\x1b[C\x04\r");
+    // Only the text after the X is drawn again, and `echo hi` runs
+    shell.expect("echoX hi hi\nhi\n");
+    shell.expect(common::PROMPT);
+
+    shell.type_keys("\x04");
+    shell.expect("Goodbye!");
+    assert_eq!(shell.wait(), Some(0));
+
+    let mut shell = Shell::spawn_configured("ignoreeof = true");
+    shell.type_keys("\x04");
+    shell.expect("Use \"exit\" to leave the shell.");
+    assert_eq!(shell.exit(), Some(0));
+}
+
+#[test]
+fn ctrl_z_suspends_a_program_until_fg() {
+    // An ssh that waits for a line of input
+    let bin = tempfile::tempdir().unwrap();
+    let ssh = bin.path().join("ssh");
+    std::fs::write(&ssh, "#!/bin/sh\necho \"remote $*\"\nread line\necho \"got $line\"\n").unwrap();
+    std::fs::set_permissions(&ssh, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+    let path = format!("{}:{}", bin.path().display(), std::env::var("PATH").unwrap());
+
+    let mut shell = Shell::spawn_with_env(&[("PATH", &path)]);
+    shell.send("ssh box");
+    shell.expect("remote box");
+    shell.type_keys("\x1a");
+    shell.expect("[1]  Stopped  ssh box");
+    shell.expect(common::PROMPT);
+    assert!(shell.run("jobs").contains("[1]  Stopped  ssh box"));
+
+    shell.send("fg");
+    shell.expect("ssh box\n");
+    shell.send("hello");
+    shell.expect("got hello");
+    shell.expect(common::PROMPT);
+    assert!(shell.run("fg").contains("fg: no suspended programs"));
+    assert_eq!(shell.exit(), Some(0));
+}
+
+#[test]
+fn programs_get_typed_ahead_keys_and_leave_the_terminal_as_they_found_it() {
+    // An ssh that reads a line, or turns echo off and exits like a crashed editor
+    let bin = tempfile::tempdir().unwrap();
+    let ssh = bin.path().join("ssh");
+    std::fs::write(&ssh, "#!/bin/sh\ncase $1 in\nbreak) stty raw -echo ;;\ncheck) stty -a ;;\n*) read line; echo \"got $line\" ;;\nesac\n").unwrap();
+    std::fs::set_permissions(&ssh, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+    let path = format!("{}:{}", bin.path().display(), std::env::var("PATH").unwrap());
+
+    let mut shell = Shell::spawn_with_env(&[("PATH", &path)]);
+    // Typed before the program starts, the second line is still its input
+    shell.type_keys("ssh box\rhello\r");
+    shell.expect("got hello");
+    shell.expect(common::PROMPT);
+
+    shell.run("ssh break");
+    let modes = shell.run("ssh check");
+    assert!(modes.contains(" icanon ") && modes.contains(" echo "), "{}", modes);
+    assert_eq!(shell.exit(), Some(0));
+}
+
+#[test]
+fn ctrl_l_clears_the_screen_and_keeps_the_line() {
+    let mut shell = Shell::spawn();
+    shell.type_keys("echo hi\x1b[D\x1b[D");
+    shell.expect("echo hi");
+    shell.type_keys("\x0c");
+    shell.expect(common::PROMPT);
+    shell.expect("echo hi");
+    // The cursor is back before "hi"
+    shell.type_keys("there \r");
+    shell.expect("there hi\n");
+    shell.expect(common::PROMPT);
+
+    assert!(!shell.run("clear").contains("Error"));
+    assert_eq!(shell.exit(), Some(0));
+}
+
+#[test]
+fn resizing_the_window_redraws_the_prompt_for_the_new_width() {
+    let mut shell = Shell::spawn_configured("right_prompt = \"RIGHT\"");
+    shell.type_keys("echo hi");
+    shell.expect("echo hi");
+
+    shell.resize(60, 24);
+    let redrawn = shell.expect(common::PROMPT);
+    let top = redrawn.lines().last().unwrap();
+    assert!(top.ends_with("RIGHT") && top.chars().count() == 59, "{:?}", redrawn);
+    shell.expect("echo hi");
+    shell.type_keys("\r");
+    shell.expect("hi\n");
+    assert_eq!(shell.exit(), Some(0));
+}
+
+#[test]
+fn killed_text_can_be_yanked_back() {
+    let mut shell = Shell::spawn();
+    // Two kills in a row make one entry
+    shell.type_keys("echo one two three\x17\x17\x19\r");
+    shell.expect("\none two three\n");
+    shell.expect(common::PROMPT);
+
+    // Back two words and forward one lands after "echo"; Alt+Y swaps the
+    // yanked "first" for the older kill
+    shell.type_keys("echo first\x17second\x1bb\x1bb\x1bf \x19\x1by\r");
+    shell.expect("\ntwo three second\n");
+    assert_eq!(shell.exit(), Some(0));
+}
+
+#[test]
+fn abbreviations_expand_in_the_line_and_are_saved() {
+    let mut shell = Shell::spawn_configured("# kept as it is\nignoreeof = false\n");
+    let rc = common::rc_path(shell.home());
+    assert!(shell.run("abbr say 'echo said'").contains("say now expands to echo said"));
+    assert_eq!(std::fs::read_to_string(&rc).unwrap(), "# kept as it is\nignoreeof = false\n\n[abbr]\nsay = \"echo said\"\n");
+
+    // Expanded as the command, but not as an argument
+    shell.type_keys("say say\r");
+    shell.expect("\nsaid say\n");
+    shell.expect(common::PROMPT);
+    assert!(shell.run("abbr").contains("abbr say 'echo said'"));
+
+    shell.run("abbr -e say");
+    assert!(!std::fs::read_to_string(&rc).unwrap().contains("say ="));
+    assert!(shell.run("say").contains("Unknown command"));
+    assert_eq!(shell.exit(), Some(0));
+}
+
+#[test]
+fn up_and_down_search_the_history_for_what_is_typed() {
+    let mut shell = Shell::spawn();
+    shell.run("echo alpha");
+    shell.run("echo beta");
+    shell.run("pwd");
+    shell.type_keys("echo\x1b[A\x1b[A\r");
+    shell.expect("\nalpha\n");
+    shell.expect(common::PROMPT);
+
+    // Down past the newest match brings back what was typed
+    shell.type_keys("echo\x1b[A\x1b[B\x1b[B gamma\r");
+    shell.expect("\ngamma\n");
+    assert_eq!(shell.exit(), Some(0));
+
+    let mut shell = Shell::spawn_configured("[keymap]\nup = \"history-substring-search-backward\"\n");
+    shell.run("echo alpha");
+    shell.run("pwd");
+    shell.type_keys("lph\x1b[A\r");
+    shell.expect("\nalpha\n");
+    assert_eq!(shell.exit(), Some(0));
+}
+
+#[test]
+fn shells_share_history_as_they_go_or_when_they_exit() {
+    // Both shells live in a home neither of them owns
+    let home = tempfile::tempdir().unwrap();
+    let env = [("HOME", home.path().to_str().unwrap())];
+    let mut first = Shell::spawn_with_env(&env);
+    let mut second = Shell::spawn_with_env(&env);
+    first.run("echo from-first");
+    // Picked up by the other shell's next prompt
+    second.run("pwd");
+    second.type_keys("\x1b[A\r");
+    second.expect("\nfrom-first\n");
+    assert_eq!(second.exit(), Some(0));
+    assert_eq!(first.exit(), Some(0));
+
+    common::write_rc(home.path(), "history_mode = \"session\"\n");
+    let mut first = Shell::spawn_with_env(&env);
+    let mut second = Shell::spawn_with_env(&env);
+    first.run("echo mine");
+    assert!(first.run("history").contains("echo mine"));
+    assert!(!second.run("history").contains("echo mine"));
+    assert_eq!(first.exit(), Some(0));
+    assert!(second.run("history").contains("echo mine"));
+    assert_eq!(second.exit(), Some(0));
+}
+
+#[test]
+fn history_references_expand_to_the_line_before() {
+    let mut shell = Shell::spawn();
+    shell.run("echo one two");
+    assert_eq!(shell.run("echo !$"), "echo two\ntwo\n");
+    assert_eq!(shell.run("^two^three"), "echo three\nthree\n");
+    assert!(shell.run("^four^five").contains("^four^five: substitution failed"));
+    assert_eq!(shell.run("echo '!!'"), "!!\n");
+    assert_eq!(shell.exit(), Some(0));
+}
+
+#[test]
+fn fc_edits_the_last_line_and_runs_it() {
+    // The "editor" changes the file in place
+    let mut shell = Shell::spawn_with_env(&[("EDITOR", "sed -i s/one/two/")]);
+    shell.run("echo one");
+    assert_eq!(shell.run("fc"), "echo two\ntwo\n");
+    assert_eq!(shell.run("fc -2"), "echo two\ntwo\n");
+    assert!(shell.run("fc 99").contains("fc: no history entry 99"));
+    assert_eq!(shell.exit(), Some(0));
+
+    // Without one it opens in the line editor
+    let mut shell = Shell::spawn_with_env(&[("EDITOR", "")]);
+    shell.run("echo one");
+    shell.send("fc");
+    shell.expect("fc❯ echo one");
+    shell.type_keys("\x17three\r");
+    shell.expect("\necho three\nthree\n");
+    assert_eq!(shell.exit(), Some(0));
+}
+
+#[test]
+fn expanded_lines_wait_for_enter_with_confirm_expansions() {
+    let mut shell = Shell::spawn_configured("confirm_expansions = true\n");
+    std::fs::write(shell.home().join("a.log"), "").unwrap();
+    std::fs::write(shell.home().join("b.log"), "").unwrap();
+    shell.send("echo *.log");
+    shell.expect("\necho a.log b.log\nPress Enter to run it, or any other key to cancel");
+    shell.type_keys("\r");
+    shell.expect("\na.log b.log\n");
+    shell.expect(common::PROMPT);
+
+    // `!$` is `*.log`, which is expanded as well
+    shell.send("echo !$");
+    shell.expect("\necho a.log b.log\nPress Enter");
+    shell.type_keys("n");
+    let skipped = shell.expect(common::PROMPT);
+    assert!(!skipped.contains("a.log"), "{}", skipped);
+    assert_eq!(shell.run("echo plain"), "plain\n");
+    assert_eq!(shell.exit(), Some(0));
+}
+
+#[test]
+fn mistyped_commands_and_cd_paths_are_corrected() {
+    let mut shell = Shell::spawn_configured("correct = \"ask\"\n");
+    shell.send("ehco hi");
+    shell.expect("run 'echo' instead? [y/n] ");
+    shell.send("y");
+    shell.expect("\nhi\n");
+    shell.expect(common::PROMPT);
+    shell.send("ehco hi");
+    shell.expect("run 'echo' instead? [y/n] ");
+    assert!(shell.run("n").contains("Unknown command"));
+    assert_eq!(shell.exit(), Some(0));
+
+    let mut shell = Shell::spawn_configured("correct = \"auto\"\n");
+    std::fs::create_dir_all(shell.home().join("usr/local")).unwrap();
+    shell.run("cd usr/lcoal");
+    assert!(shell.run("pwd").ends_with("/usr/local\n"));
+    assert_eq!(shell.exit(), Some(0));
+}
+
+#[test]
+fn ls_lists_the_paths_given_and_autocd_enters_directories() {
+    let mut shell = Shell::spawn_configured("autocd = true\n");
+    std::fs::create_dir(shell.home().join("src")).unwrap();
+    std::fs::write(shell.home().join("src/main.rs"), "").unwrap();
+    std::fs::write(shell.home().join("notes.txt"), "").unwrap();
+    assert_eq!(shell.run("ls src"), "main.rs\n");
+    assert_eq!(shell.run("ls notes.txt src"), "notes.txt\nsrc:\nmain.rs\n");
+    assert!(shell.run("ls missing").contains("ls: 'missing' does not exist"));
+
+    shell.run("src");
+    assert!(shell.run("pwd").ends_with("/src\n"));
+    assert!(shell.run("main.rs").contains("Unknown command"));
+    shell.run("..");
+    assert_eq!(shell.run("ls notes.txt"), "notes.txt\n");
+    assert_eq!(shell.exit(), Some(0));
+}
+
+#[test]
+fn output_into_a_closed_pipe_ends_the_shell_quietly() {
+    use std::os::unix::process::ExitStatusExt;
+
+    let home = tempfile::TempDir::new().unwrap();
+    std::fs::write(home.path().join("big.txt"), "line\n".repeat(100_000)).unwrap();
+    for args in [&["completions", "bash"][..], &["-c", "cat big.txt"]] {
+        // Nothing reads the pipe, so the first write finds it closed
+        let (reader, writer) = std::io::pipe().unwrap();
+        drop(reader);
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_shell"))
+            .args(args)
+            .current_dir(home.path())
+            .env("HOME", home.path())
+            .env_remove("XDG_CONFIG_HOME")
+            .env_remove("XDG_STATE_HOME")
+            .stdout(writer)
+            .stderr(std::process::Stdio::piped())
+            .output()
+            .unwrap();
+        assert_eq!(output.status.signal(), Some(libc::SIGPIPE), "{:?}: {:?}", args, output);
+        assert!(output.stderr.is_empty(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    }
+}
+
+#[test]
+fn find_delete_needs_yes_without_a_terminal() {
+    let home = tempfile::TempDir::new().unwrap();
+    std::fs::create_dir(home.path().join("logs")).unwrap();
+    std::fs::write(home.path().join("logs/empty.log"), "").unwrap();
+    let find = |line: &str| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_shell"))
+            .args(["--color", "never", "-c", line])
+            .current_dir(home.path())
+            .env("HOME", home.path())
+            .env_remove("XDG_CONFIG_HOME")
+            .env_remove("XDG_STATE_HOME")
+            .stdin(std::process::Stdio::null())
+            .output()
+            .unwrap()
+    };
+
+    let output = find("find logs --empty --delete");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("add --yes"), "{:?}", output);
+    assert!(home.path().join("logs/empty.log").exists());
+
+    assert!(find("find logs --empty --delete --yes").status.success());
+    assert!(!home.path().join("logs/empty.log").exists());
+}